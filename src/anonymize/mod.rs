@@ -0,0 +1,151 @@
+//! bpf-profile anonymize command implementation.
+
+use crate::config::Map;
+use crate::error::{Error, Result};
+use crate::{filebuf, global};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Rewrites the trace file (and the dump file if any) replacing addresses
+/// and names of functions with stable pseudonyms.
+pub fn run(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    output_path: &Path,
+    dump_output_path: Option<&Path>,
+) -> Result<()> {
+    if !crate::trace::contains_standard_header(filebuf::open(trace_path)?)? {
+        return Err(Error::TraceFormat);
+    }
+
+    if global::verbose() {
+        tracing::info!("Anonymizing trace file...")
+    }
+    let reader = filebuf::open(trace_path)?;
+    let mut output = filebuf::open_w(output_path)?;
+    anonymize_trace(reader, &mut output)?;
+    output.commit()?;
+
+    if let Some(dump_path) = dump_path {
+        if global::verbose() {
+            tracing::info!("Anonymizing dump file...")
+        }
+        let dump_output_path = match dump_output_path {
+            Some(path) => path.to_path_buf(),
+            None => output_path.with_extension("dump"),
+        };
+        let reader = filebuf::open(dump_path)?;
+        let mut output = filebuf::open_w(&dump_output_path)?;
+        anonymize_dump(reader, &mut output)?;
+        output.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Represents the stable mapping of original values to pseudonyms.
+#[derive(Default)]
+struct Pseudonyms {
+    mapping: Map<String, String>,
+}
+
+impl Pseudonyms {
+    /// Returns the pseudonym of the value, generating a new one when needed.
+    fn get(&mut self, value: &str, generate: impl Fn(usize) -> String) -> String {
+        let next = self.mapping.len();
+        self.mapping
+            .entry(value.into())
+            .or_insert_with(|| generate(next))
+            .clone()
+    }
+}
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Rewrites the trace: replaces targets of calls with generated addresses
+/// and zeroes register values. Drops lines which are not instructions,
+/// except the standard header.
+pub fn anonymize_trace(mut reader: impl BufRead, mut output: impl Write) -> Result<()> {
+    lazy_static! {
+        static ref LINE: Regex =
+            Regex::new(r"^(\s*\d+\s+)\[(.+)\](\s+\d+:\s+)(.+)").expect("Invalid regex");
+        static ref REGISTER: Regex = Regex::new(r"[[:xdigit:]]+").expect("Invalid regex");
+        static ref CALL: Regex =
+            Regex::new(r"^(call\w*\s+)0x([[:xdigit:]]+)").expect("Invalid regex");
+    }
+
+    let mut addresses = Pseudonyms::default();
+    let mut line = String::with_capacity(512);
+    while filebuf::read_line(&mut reader, &mut line)? != 0 {
+        let caps = match LINE.captures(&line) {
+            Some(caps) => caps,
+            None => {
+                if line.contains(crate::trace::HEADER) {
+                    write!(output, "{}", &line)?;
+                }
+                continue;
+            }
+        };
+
+        let registers =
+            REGISTER.replace_all(&caps[2], |c: &regex::Captures| "0".repeat(c[0].len()));
+        let text = match CALL.captures(&caps[4]) {
+            None => caps[4].to_string(),
+            Some(call) => {
+                let address = addresses.get(&call[2], |i| format!("{:x}", i + 1));
+                format!("{}0x{}", &call[1], address)
+            }
+        };
+        writeln!(output, "{}[{}]{}{}", &caps[1], registers, &caps[3], text)?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Rewrites the dump: replaces names of functions with generated ones
+/// and drops everything before the disassembly (symbol tables, paths).
+pub fn anonymize_dump(mut reader: impl BufRead, mut output: impl Write) -> Result<()> {
+    lazy_static! {
+        static ref FUNC_HEADER: Regex =
+            Regex::new(r"^([[:xdigit:]]+\s+)<(.+)>").expect("Invalid regex");
+        static ref CALL_COMMENT: Regex = Regex::new(r"; <(.+)>\s*$").expect("Invalid regex");
+    }
+
+    let mut names = Pseudonyms::default();
+    let generate = |i| format!("symbol_{}", i);
+    let mut line = String::with_capacity(512);
+    let mut was_disasm = false;
+    while filebuf::read_line(&mut reader, &mut line)? != 0 {
+        if !was_disasm {
+            if line.starts_with(crate::resolver::DISASM_HEADER) {
+                writeln!(output, "{}", crate::resolver::HEADER)?;
+                write!(output, "{}", &line)?;
+                was_disasm = true;
+            }
+            continue;
+        }
+
+        if let Some(caps) = FUNC_HEADER.captures(&line) {
+            let name = if caps[2].starts_with("LBB") {
+                caps[2].to_string()
+            } else {
+                names.get(&caps[2], generate)
+            };
+            writeln!(output, "{}<{}>", &caps[1], name)?;
+        } else if let Some(caps) = CALL_COMMENT.captures(&line) {
+            let name = names.get(&caps[1], generate);
+            let start = caps.get(0).expect("Missing match").start();
+            writeln!(output, "{}; <{}>", &line[..start], name)?;
+        } else {
+            write!(output, "{}", &line)?;
+        }
+    }
+    if !was_disasm {
+        return Err(Error::DumpFormatNoDisasm);
+    }
+
+    output.flush()?;
+    Ok(())
+}
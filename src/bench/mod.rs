@@ -0,0 +1,77 @@
+//! bpf-profile bench command implementation.
+
+use crate::error::{Error, Result};
+use crate::{filebuf, gen, global};
+use std::io::BufRead;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Parses the trace file several times and reports throughput of the converter.
+pub fn run(trace_path: &Path, dump_path: Option<&Path>, iterations: usize) -> Result<()> {
+    if !crate::trace::contains_standard_header(filebuf::open(trace_path)?)? {
+        return Err(Error::TraceFormat);
+    }
+
+    let iterations = iterations.max(1);
+    let bytes = std::fs::metadata(trace_path)?.len();
+    let lines = count_lines(filebuf::open(trace_path)?)?;
+
+    let mut elapsed = Duration::default();
+    for i in 0..iterations {
+        if global::verbose() {
+            tracing::info!("Iteration {} of {}...", i + 1, iterations);
+        }
+        let start = Instant::now();
+        let _ = gen::Profile::create(&[trace_path], dump_path, &gen::Options::default())?;
+        elapsed += start.elapsed();
+    }
+
+    let seconds = elapsed.as_secs_f64();
+    let total_lines = (lines * iterations) as f64;
+    let total_megabytes = (bytes as usize * iterations) as f64 / (1024.0 * 1024.0);
+    println!("Iterations:  {}", iterations);
+    println!("Trace:       {} lines, {} bytes", lines, bytes);
+    println!(
+        "Time:        {:.3} s ({:.3} s per iteration)",
+        seconds,
+        seconds / iterations as f64
+    );
+    if seconds > 0.0 {
+        println!(
+            "Throughput:  {:.0} lines/s, {:.2} MB/s",
+            total_lines / seconds,
+            total_megabytes / seconds
+        );
+    } else {
+        // The trace is parsed faster than the resolution of the clock
+        println!("Throughput:  unknown");
+    }
+    match peak_rss() {
+        Some(kb) => println!("Peak RSS:    {} KiB", kb),
+        None => println!("Peak RSS:    unknown"),
+    }
+
+    Ok(())
+}
+
+/// Counts lines of the input.
+fn count_lines(mut reader: impl BufRead) -> Result<usize> {
+    let mut line = String::with_capacity(512);
+    let mut lines = 0_usize;
+    while filebuf::read_line(&mut reader, &mut line)? != 0 {
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Returns the peak resident set size of the process in kilobytes (Linux only).
+fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find(|l| l.starts_with("VmHWM:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
@@ -7,6 +7,8 @@ use crate::config::{ProgramCounter, PADDING};
 pub struct Instruction {
     pc: ProgramCounter,
     text: String,
+    /// Values of registers before execution of the instruction, if captured.
+    registers: Vec<Option<Address>>,
 }
 
 use crate::config::Address;
@@ -17,25 +19,59 @@ use regex::Regex;
 impl Instruction {
     /// Creates new instance of Instruction.
     pub fn new(pc: ProgramCounter, text: String) -> Self {
-        Instruction { pc, text }
+        Instruction {
+            pc,
+            text,
+            registers: Vec::new(),
+        }
     }
 
     /// Parses the input string and creates corresponding instruction if possible.
+    /// Register values appended to the instruction by some tracers,
+    /// e.g. `r1=0x10 r2=0x20`, are not part of its text.
     pub fn parse(s: &str) -> Result<Self> {
+        Self::parse_with(s, false)
+    }
+
+    /// Parses the input string like `parse`, capturing values of the registers
+    /// of the line: the columns in brackets followed by the appended values, if any.
+    pub fn parse_with_registers(s: &str) -> Result<Self> {
+        Self::parse_with(s, true)
+    }
+
+    /// Parses the input string, capturing the registers if enabled.
+    fn parse_with(s: &str, registers: bool) -> Result<Self> {
         lazy_static! {
             static ref TRACE_INSTRUCTION: Regex =
-                Regex::new(r"\d+\s+\[.+\]\s+(\d+):\s+(.+)").expect("Invalid regex");
+                Regex::new(r"\d+\s+\[(.+?)\]\s+(\d+):\s+(.+)").expect("Invalid regex");
         }
 
-        if let Some(caps) = TRACE_INSTRUCTION.captures(s) {
-            let pc = caps[1]
-                .parse::<ProgramCounter>()
-                .expect("Cannot parse program counter");
-            let text = caps[2].trim().to_string();
-            return Ok(Instruction { pc, text });
+        let Some(caps) = TRACE_INSTRUCTION.captures(s) else {
+            return Err(Error::TraceSkipped);
+        };
+        let pc = str_to_program_counter(&caps[2])?;
+        let (text, appended) = split_appended_registers(caps[3].trim());
+        let mut ix = Instruction::new(pc, text.to_string());
+        if registers {
+            ix.registers = caps[1]
+                .split(',')
+                .map(|r| hex_str_to_address(r.trim()).ok())
+                .collect();
+            for (index, value) in appended {
+                if ix.registers.len() <= index {
+                    ix.registers.resize(index + 1, None);
+                }
+                ix.registers[index] = Some(value);
+            }
         }
+        Ok(ix)
+    }
 
-        Err(Error::TraceSkipped)
+    /// Sets values of registers before execution of the instruction,
+    /// captured by the format of the trace.
+    pub fn with_registers(mut self, registers: Vec<Option<Address>>) -> Self {
+        self.registers = registers;
+        self
     }
 
     /// Returns true if default instruction.
@@ -48,6 +84,12 @@ impl Instruction {
         self.pc
     }
 
+    /// Returns value of the register before execution of the instruction,
+    /// if the registers were captured by `parse_with_registers`.
+    pub fn register(&self, index: usize) -> Option<Address> {
+        self.registers.get(index).copied().flatten()
+    }
+
     /// Returns copy of the textual representation.
     pub fn text(&self) -> String {
         self.text.clone()
@@ -63,6 +105,50 @@ impl Instruction {
         self.text == "exit"
     }
 
+    /// Returns the mnemonic of the instruction's opcode, e.g. "add64".
+    pub fn mnemonic(&self) -> &str {
+        self.text.split_whitespace().next().unwrap_or_default()
+    }
+
+    /// Returns class of the instruction's opcode:
+    /// kind of the operation and size of accessed memory if any.
+    /// Mnemonics unknown to the profiler, e.g. of newer runtimes, are classed "unknown".
+    pub fn opcode_class(&self) -> &'static str {
+        match self.mnemonic() {
+            "lddw" => "load imm64",
+            "ldxb" => "load 1B",
+            "ldxh" => "load 2B",
+            "ldxw" => "load 4B",
+            "ldxdw" => "load 8B",
+            "stb" | "stxb" => "store 1B",
+            "sth" | "stxh" => "store 2B",
+            "stw" | "stxw" => "store 4B",
+            "stdw" | "stxdw" => "store 8B",
+            "call" | "callx" => "call",
+            "exit" => "exit",
+            m if JUMP_OPERATIONS.contains(&m) => "jump",
+            m if is_alu_operation(m, "32") => "alu32",
+            m if is_alu_operation(m, "64") => "alu64",
+            m if is_byte_swap(m) => "alu64",
+            _ => "unknown",
+        }
+    }
+
+    /// Checks if the instruction loads from or stores to memory.
+    pub fn is_memory_access(&self) -> bool {
+        self.is_memory_load() || self.is_memory_store()
+    }
+
+    /// Checks if the instruction loads from memory, e.g. `ldxdw`.
+    pub fn is_memory_load(&self) -> bool {
+        self.mnemonic().starts_with("ldx")
+    }
+
+    /// Checks if the instruction stores to memory, e.g. `stxdw` or `stw`.
+    pub fn is_memory_store(&self) -> bool {
+        self.mnemonic().starts_with("st")
+    }
+
     /// Returns "call" or "callx" or error if instruction is not a call.
     pub fn extract_call_operation(&self, lc: usize) -> Result<String> {
         if !self.is_call() {
@@ -87,11 +173,15 @@ impl Instruction {
         let address = pair
             .next()
             .ok_or_else(|| Error::TraceParsing(self.text(), lc))?;
-        Ok(hex_str_to_address(address))
+        hex_str_to_address(address).map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow => Error::OutOfRange(address.into()),
+            _ => Error::TraceParsing(self.text(), lc),
+        })
     }
 }
 
 use std::fmt;
+use std::num::{IntErrorKind, ParseIntError};
 
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -103,8 +193,197 @@ impl fmt::Display for Instruction {
     }
 }
 
-/// Converts hex number string representation to integer Address.
-fn hex_str_to_address(s: &str) -> Address {
+/// Classes of opcodes returned by `Instruction::opcode_class`.
+pub const OPCODE_CLASSES: &[&str] = &[
+    "load imm64",
+    "load 1B",
+    "load 2B",
+    "load 4B",
+    "load 8B",
+    "store 1B",
+    "store 2B",
+    "store 4B",
+    "store 8B",
+    "call",
+    "exit",
+    "jump",
+    "alu32",
+    "alu64",
+    "unknown",
+];
+
+/// Operations of arithmetic instructions, suffixed by the size of operands.
+const ALU_OPERATIONS: &[&str] = &[
+    "add", "sub", "mul", "div", "or", "and", "lsh", "rsh", "neg", "mod", "xor", "mov", "arsh",
+    "lmul", "uhmul", "shmul", "udiv", "urem", "sdiv", "srem", "hor",
+];
+
+/// Mnemonics of jump instructions.
+const JUMP_OPERATIONS: &[&str] = &[
+    "ja", "jeq", "jgt", "jge", "jlt", "jle", "jset", "jne", "jsgt", "jsge", "jslt", "jsle",
+];
+
+/// Checks if the mnemonic is of an arithmetic operation with operands of the size.
+fn is_alu_operation(mnemonic: &str, size: &str) -> bool {
+    mnemonic
+        .strip_suffix(size)
+        .is_some_and(|operation| ALU_OPERATIONS.contains(&operation))
+}
+
+/// Checks if the mnemonic is of a byte swap, e.g. "le" or "be16".
+fn is_byte_swap(mnemonic: &str) -> bool {
+    ["le", "be"].iter().any(|order| {
+        mnemonic
+            .strip_prefix(order)
+            .is_some_and(|size| matches!(size, "" | "16" | "32" | "64"))
+    })
+}
+
+/// Converts decimal number string representation to ProgramCounter.
+/// Returns error if the number does not fit.
+pub fn str_to_program_counter(s: &str) -> Result<ProgramCounter> {
+    s.parse::<ProgramCounter>()
+        .map_err(|_| Error::OutOfRange(s.into()))
+}
+
+/// Converts hex number string representation to integer Address (full 64 bits).
+fn hex_str_to_address(s: &str) -> std::result::Result<Address, ParseIntError> {
     let a = s.trim_start_matches("0x");
-    Address::from_str_radix(a, 16).expect("Invalid address")
+    Address::from_str_radix(a, 16)
+}
+
+/// Splits register values appended to the instruction, e.g. "add64 r1, r2 ; r1=0x10 r2=5",
+/// into the instruction and indexes and values of the registers.
+fn split_appended_registers(text: &str) -> (&str, Vec<(usize, Address)>) {
+    lazy_static! {
+        static ref APPENDED_REGISTERS: Regex =
+            Regex::new(r"^(.*?)\s*[;|]?\s*((?:\br\d{1,2}=(?:0x[[:xdigit:]]+|\d+)[,\s]*)+)$")
+                .expect("Invalid regex");
+    }
+
+    // Most traces have no appended registers, so the regex is mostly skipped
+    if !text.contains('=') {
+        return (text, Vec::new());
+    }
+    let Some(caps) = APPENDED_REGISTERS.captures(text) else {
+        return (text, Vec::new());
+    };
+    let registers = caps[2]
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|column| {
+            let (name, value) = column.split_once('=')?;
+            let index = name.strip_prefix('r')?.parse::<usize>().ok()?;
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => Address::from_str_radix(hex, 16).ok()?,
+                None => value.parse::<Address>().ok()?,
+            };
+            Some((index, value))
+        })
+        .collect();
+    (caps.get(1).map_or("", |m| m.as_str()), registers)
+}
+
+/// Represents a region of the virtual memory map of SBF programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Region {
+    /// Read-only data and code of the program.
+    Program,
+    /// Stack frames.
+    Stack,
+    /// Heap.
+    Heap,
+    /// Serialized input: accounts followed by the instruction data.
+    Input,
+    /// Outside of the known regions.
+    Unknown,
+}
+
+impl Region {
+    /// Returns the region containing the virtual address.
+    pub fn of(address: Address) -> Self {
+        match address >> 32 {
+            1 => Region::Program,
+            2 => Region::Stack,
+            3 => Region::Heap,
+            4 => Region::Input,
+            _ => Region::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Region::Program => "program",
+            Region::Stack => "stack",
+            Region::Heap => "heap",
+            Region::Input => "input",
+            Region::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns the memory region accessed by the load or store instruction.
+/// The address is computed from the captured registers before execution
+/// of the instruction, e.g. `[r2+0x8]` of "ldxdw r1, [r2+0x8]".
+pub fn memory_access_region(ix: &Instruction) -> Option<Region> {
+    lazy_static! {
+        static ref MEMORY_ACCESS: Regex =
+            Regex::new(r"^(?:ldx|st)\w*\s.*\[r(\d+)(?:([+-])0x([[:xdigit:]]+))?\]")
+                .expect("Invalid regex");
+    }
+
+    let caps = MEMORY_ACCESS.captures(&ix.text)?;
+    let index = caps[1].parse::<usize>().ok()?;
+    let base = ix.register(index)?;
+    let offset = caps
+        .get(3)
+        .map_or(Some(0), |o| hex_str_to_address(o.as_str()).ok())?;
+    let address = if caps.get(2).is_some_and(|s| s.as_str() == "-") {
+        base.wrapping_sub(offset)
+    } else {
+        base.wrapping_add(offset)
+    };
+    Some(Region::of(address))
+}
+
+/// Returns the size of the stack frame implied by the disassembled instruction:
+/// the adjustment of the stack pointer in the prologue of the function,
+/// e.g. "add64 r11, -0x40", or the offset of an access to the frame,
+/// e.g. "stxdw [r10 - 0x8], r1".
+pub fn stack_frame_size(text: &str) -> Option<usize> {
+    lazy_static! {
+        static ref FRAME: Regex = Regex::new(
+            r"(?:\badd64\s+r11,\s*-|\bsub64\s+r11,\s*|\[r10\s*-\s*)(0x[[:xdigit:]]+|\d+)\b"
+        )
+        .expect("Invalid regex");
+    }
+
+    let size = &FRAME.captures(text)?[1];
+    match size.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => size.parse().ok(),
+    }
+}
+
+/// Returns the size of the stack frame implied by the encoded instruction,
+/// like `stack_frame_size` of its disassembly.
+pub fn stack_frame_size_of_bytes(ix: &[u8]) -> Option<usize> {
+    let [opcode, registers, o0, o1, i0, i1, i2, i3] = *ix else {
+        return None;
+    };
+    let (dst, src) = (registers & 0xf, registers >> 4);
+    let offset = i16::from_le_bytes([o0, o1]);
+    let imm = i32::from_le_bytes([i0, i1, i2, i3]);
+    let size = match opcode {
+        // add64 r11, imm and sub64 r11, imm
+        0x07 if dst == 11 => -i64::from(imm),
+        0x17 if dst == 11 => i64::from(imm),
+        // Loads from and stores to the frame pointer
+        _ if opcode & 7 == 1 && src == 10 => -i64::from(offset),
+        _ if matches!(opcode & 7, 2 | 3) && dst == 10 => -i64::from(offset),
+        _ => return None,
+    };
+    usize::try_from(size).ok().filter(|s| *s > 0)
 }
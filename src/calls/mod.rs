@@ -4,8 +4,9 @@ use crate::error::{Error, Result};
 use crate::{filebuf, global};
 use std::path::Path;
 
-/// Reads the trace input file and prints functions in order of calls.
-pub fn run(trace_path: &Path, dump_path: Option<&Path>, tab: usize) -> Result<()> {
+/// Reads the trace input file and prints functions in order of calls,
+/// optionally with the range of trace lines of each call.
+pub fn run(trace_path: &Path, dump_path: Option<&Path>, tab: usize, lines: bool) -> Result<()> {
     if !crate::trace::contains_standard_header(filebuf::open(trace_path)?)? {
         return Err(Error::TraceFormat);
     }
@@ -20,18 +21,35 @@ pub fn run(trace_path: &Path, dump_path: Option<&Path>, tab: usize) -> Result<()
 
     let depth_width = max_depth.to_string().len();
     let reader = filebuf::open(trace_path)?;
-    trace_calls(reader, &resv, depth_width, tab)?;
+    let layout = Layout {
+        depth_width,
+        tab,
+        lines,
+    };
+    trace_calls(reader, io::stdout().lock(), &resv, &layout)?;
 
     Ok(())
 }
 
+/// Represents layout of the printed calls.
+pub struct Layout {
+    /// Width of the printed depth of calls.
+    pub depth_width: usize,
+    /// Indentation size.
+    pub tab: usize,
+    /// Prints the first and the last trace lines of each call.
+    pub lines: bool,
+}
+
 use crate::bpf::Instruction;
 use crate::resolver::Resolver;
-use std::io::BufRead;
+use crate::syscalls;
+use crate::trace::{Legacy, TraceFormat};
+use std::io::{self, BufRead, Write};
 
 /// Parses the trace file line by line updating the resolver.
 /// Returns maximal depth of enclosed function calls.
-fn update_resolver(mut reader: impl BufRead, resv: &mut Resolver) -> Result<usize> {
+pub fn update_resolver(mut reader: impl BufRead, resv: &mut Resolver) -> Result<usize> {
     if global::verbose() {
         tracing::info!("First pass of trace: updating resolver...")
     }
@@ -40,18 +58,21 @@ fn update_resolver(mut reader: impl BufRead, resv: &mut Resolver) -> Result<usiz
     let mut bytes_read = usize::MAX;
     let mut ix: Instruction;
     let mut lc = 0_usize;
+    let mut format: Box<dyn TraceFormat> = Box::new(Legacy);
     let mut depth = 0_usize;
     let mut max_depth = 0_usize;
 
     while bytes_read != 0 {
         if line.is_empty() {
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
+            bytes_read = read_line(&mut reader, &mut line, &mut lc, format.as_mut())?;
         }
 
-        let ixr = Instruction::parse(&line);
+        let ixr = format.parse_line(&line, false);
         if let Err(Error::TraceSkipped) = &ixr {
             /* warn!("Skip '{}'", &line.trim()); */
+            if crate::trace::parse_header(&line).is_some() {
+                format = crate::trace::detect_format(&line);
+            }
             line.clear();
             continue;
         }
@@ -75,9 +96,8 @@ fn update_resolver(mut reader: impl BufRead, resv: &mut Resolver) -> Result<usiz
             depth += 1;
             max_depth = std::cmp::max(depth, max_depth);
             // Read next line — the first instruction of the call
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
-            ix = Instruction::parse(&line)?;
+            bytes_read = read_line(&mut reader, &mut line, &mut lc, format.as_mut())?;
+            ix = format.parse_line(&line, false)?;
             resv.update(address, ix.pc());
         }
         // Keep here the last non-call line to process further
@@ -86,12 +106,17 @@ fn update_resolver(mut reader: impl BufRead, resv: &mut Resolver) -> Result<usiz
     Ok(max_depth)
 }
 
+/// Represents a printed call: depth, name, the first and the last trace lines.
+type CallLines = (usize, String, usize, usize);
+
 /// Parses the trace file line by line printing calls.
-fn trace_calls(
+/// Calls are printed when they start unless their last trace lines are printed,
+/// then all calls are printed at the end of the trace.
+pub fn trace_calls(
     mut reader: impl BufRead,
+    mut output: impl Write,
     resv: &Resolver,
-    depth_width: usize,
-    tab: usize,
+    layout: &Layout,
 ) -> Result<()> {
     if global::verbose() {
         tracing::info!("Second pass of trace: dumping functions...")
@@ -101,17 +126,22 @@ fn trace_calls(
     let mut bytes_read = usize::MAX;
     let mut ix: Instruction;
     let mut lc = 0_usize;
+    let mut format: Box<dyn TraceFormat> = Box::new(Legacy);
     let mut depth = 0_usize;
+    let mut calls = Vec::<CallLines>::new();
+    let mut unfinished = Vec::<usize>::new();
 
     while bytes_read != 0 {
         if line.is_empty() {
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
+            bytes_read = read_line(&mut reader, &mut line, &mut lc, format.as_mut())?;
         }
 
-        let ixr = Instruction::parse(&line);
+        let ixr = format.parse_line(&line, false);
         if let Err(Error::TraceSkipped) = &ixr {
             /* warn!("Skip '{}'", &line.trim()); */
+            if crate::trace::parse_header(&line).is_some() {
+                format = crate::trace::detect_format(&line);
+            }
             line.clear();
             continue;
         }
@@ -120,6 +150,9 @@ fn trace_calls(
         if !ix.is_call() {
             if ix.is_exit() {
                 depth -= 1;
+                if let Some(i) = unfinished.pop() {
+                    calls[i].3 = lc;
+                }
             }
             line.clear();
             continue;
@@ -133,22 +166,62 @@ fn trace_calls(
         while ix.is_call() {
             let address = ix.extract_call_target(lc)?;
             let name = resv.resolve_by_address(address);
-            println!(
-                "[{:width$}] {:indent$}{}",
-                depth,
-                String::default(),
-                &name,
-                width = depth_width,
-                indent = depth * tab
-            );
+            if !layout.lines {
+                write_call(&mut output, layout, &(depth, name, 0, 0))?;
+            } else {
+                // Syscalls finish at the line of the call
+                if !syscalls::is_syscall(address) {
+                    unfinished.push(calls.len());
+                }
+                calls.push((depth, name, lc, lc));
+            }
             depth += 1;
             // Read next line — the first instruction of the call
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
-            ix = Instruction::parse(&line)?;
+            bytes_read = read_line(&mut reader, &mut line, &mut lc, format.as_mut())?;
+            ix = format.parse_line(&line, false)?;
         }
         // Keep here the last non-call line to process further
     }
 
+    // Calls never exited last until the end of the trace
+    for i in unfinished {
+        calls[i].3 = lc - 1;
+    }
+    for call in &calls {
+        write_call(&mut output, layout, call)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the call indented by its depth.
+fn write_call(mut output: impl Write, layout: &Layout, call: &CallLines) -> Result<()> {
+    let (depth, name, first, last) = call;
+    write!(
+        output,
+        "[{:width$}] {:indent$}{}",
+        depth,
+        String::default(),
+        name,
+        width = layout.depth_width,
+        indent = depth * layout.tab
+    )?;
+    if layout.lines {
+        write!(output, " lines={}-{}", first, last)?;
+    }
+    writeln!(output)?;
     Ok(())
 }
+
+/// Reads the next line of the trace in the format, counting lines read.
+/// Returns the number of bytes read, 0 at the end of the trace.
+fn read_line(
+    reader: &mut impl BufRead,
+    line: &mut String,
+    lc: &mut usize,
+    format: &mut dyn TraceFormat,
+) -> Result<usize> {
+    let (bytes_read, lines) = crate::trace::read_line(reader, line, format)?;
+    *lc += lines;
+    Ok(if line.is_empty() { 0 } else { bytes_read })
+}
@@ -1,7 +1,9 @@
 //! bpf-profile command line interface definition.
 
-use crate::config;
+use bpf_profile::config;
+use std::io;
 use std::path::PathBuf;
+use structopt::clap::{ArgGroup, Shell};
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -16,13 +18,91 @@ pub struct Application {
      )]
     pub config: PathBuf,
 
-    #[structopt(short, long, help = "Shows more information")]
-    pub verbose: bool,
+    #[structopt(long, help = "Name of the preset of options from the config file")]
+    pub preset: Option<String>,
+
+    #[structopt(
+        short,
+        long,
+        parse(from_occurrences),
+        help = "Shows more information (-v), debug messages (-vv), every instruction (-vvv)"
+    )]
+    pub verbose: u8,
+
+    #[structopt(short, long, conflicts_with = "verbose", help = "Shows only errors")]
+    pub quiet: bool,
+
+    #[structopt(
+        long,
+        default_value = config::DEFAULT_LOG_FORMAT,
+        possible_values(config::LOG_FORMATS),
+        help = "Format of log messages"
+    )]
+    pub log_format: String,
+
+    #[structopt(
+        long,
+        default_value = config::DEFAULT_NUMBER_FORMAT,
+        possible_values(config::NUMBER_FORMATS),
+        help = "Format of costs in human-readable reports: 12345678, 12,345,678 or 12.3M"
+    )]
+    pub number_format: String,
+
+    #[structopt(
+        long,
+        possible_values(config::PROGRESS_FORMATS),
+        help = "Reports progress of parsing traces to the standard error every second, as text or JSON lines, or as a progress bar [default: bar if the standard error is a terminal]"
+    )]
+    pub progress_format: Option<String>,
+
+    #[structopt(long, help = "Keeps mangled names of Rust and C++ functions")]
+    pub no_demangle: bool,
+
+    #[structopt(
+        long,
+        help = "Template of names of functions not found in the dump file with {index}, {address} and {pc} [default: function_{pc} (0x{address})]"
+    )]
+    pub unresolved_name: Option<String>,
+
+    #[structopt(
+        parse(from_os_str),
+        long,
+        help = "Optional path to the file of syscalls adding names and costs of helpers to the built-in ones"
+    )]
+    pub syscalls: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        help = "Size in bytes of buffers of reading input files [default: 8192]"
+    )]
+    pub read_buffer: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "Size in bytes of buffers of writing output files [default: 8192]"
+    )]
+    pub write_buffer: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "Filters of log messages by modules, e.g. 'bpf_profile::resolver=debug', override RUST_LOG"
+    )]
+    pub log_filter: Option<String>,
+
+    #[cfg(feature = "plugins")]
+    #[structopt(
+        parse(from_os_str),
+        long,
+        help = "Optional path to the directory of plugins providing formats and resolvers"
+    )]
+    pub plugins_dir: Option<PathBuf>,
 
     #[structopt(subcommand)]
     pub cmd: Command,
 }
 
+// Parsed once, so the size of the largest command does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(StructOpt)]
 pub enum Command {
     #[structopt(about = "Prints functions in order of calls")]
@@ -40,12 +120,26 @@ pub enum Command {
 
         #[structopt(short, long, default_value = "2", help = "Indentation size")]
         tab: usize,
+
+        #[structopt(
+            short,
+            long,
+            help = "Prints the first and the last trace lines of each call"
+        )]
+        lines: bool,
     },
 
-    #[structopt(about = "Generates performance profile")]
+    #[structopt(
+        about = "Generates performance profile",
+        group = ArgGroup::with_name("destination")
+    )]
     Generate {
-        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
-        trace: PathBuf,
+        #[structopt(
+            parse(from_os_str),
+            help = "Paths to the input trace files; several traces are written as separate parts \
+                    [default: standard input, also given as '-']"
+        )]
+        traces: Vec<PathBuf>,
 
         #[structopt(
             parse(from_os_str),
@@ -63,26 +157,1067 @@ pub enum Command {
         )]
         dump: Option<PathBuf>,
 
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the manifest of dump files of programs invoked by the trace, by program ids"
+        )]
+        dump_manifest: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the symbol map of functions by first program counters, as lines <program-id>:<address>:<name> or JSON, alone or over the dump file"
+        )]
+        symbols: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            default_value = "0",
+            allow_hyphen_values = true,
+            help = "Offset of program counters of the trace from those of the dump file, e.g. of the program relocated by the loader"
+        )]
+        pc_offset: isize,
+
         #[structopt(
             short,
             long,
-            possible_values(&config::FORMATS),
-            default_value = &config::DEFAULT_FORMAT,
-            help = "Optional format of the generated profile"
+            help = "Optional format of the generated profile [default: callgrind]"
         )]
-        format: String,
+        #[cfg_attr(not(feature = "plugins"), structopt(possible_values(&config::FORMATS)))]
+        format: Option<String>,
 
         #[structopt(
             parse(from_os_str),
             short,
             long,
+            group = "destination",
             help = "Optional path to the generated profile [default: standard output]"
         )]
         output: Option<PathBuf>,
-    },
-}
 
-/// Constructs an instance of the Application.
-pub fn application() -> Application {
-    Application::from_args()
+        #[structopt(
+            long,
+            group = "destination",
+            help = "Names the generated profile after the trace in the current directory, e.g. trace.callgrind.out for trace.log"
+        )]
+        auto_name: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            group = "destination",
+            help = "Optional directory of the generated profiles named after the traces; several traces are converted each into its own profile"
+        )]
+        output_dir: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            requires = "output-dir",
+            conflicts_with_all = &["traces", "watch"],
+            help = "Optional directory of the input trace files, each converted into its own profile in the output directory, with a summary of the conversions"
+        )]
+        input_dir: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            default_value = config::DEFAULT_TRACE_PATTERN,
+            help = "Pattern of names of the trace files in the input directory, with wildcards * and ?"
+        )]
+        pattern: String,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the Prometheus metrics file with summary of the profile"
+        )]
+        metrics: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the badge with the total cost and resolution of functions (.svg or JSON)"
+        )]
+        badge: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the statistics report (cost of functions by opcode classes)"
+        )]
+        stats: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the LCOV coverage report of executed instructions"
+        )]
+        lcov: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the CSV heatmap of costs over the trace and depths of calls"
+        )]
+        heatmap: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            default_value = "1000",
+            help = "Number of instructions in a row of the heatmap"
+        )]
+        heatmap_window: usize,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the stream of executed instructions in the JSON lines format"
+        )]
+        events: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the index of calls of functions by byte offsets in the trace"
+        )]
+        bookmarks: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the intermediate profile saved after parsing, rendered later in any format by the render command"
+        )]
+        save_intermediate: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            conflicts_with_all(&["asm", "metrics", "badge", "stats", "lcov", "heatmap", "events", "counters"]),
+            help = "Records only calls of functions, skipping costs of instructions (fast mode)"
+        )]
+        calls_only: bool,
+
+        #[structopt(
+            long,
+            help = "Optional name of the root frame, e.g. transaction signature [default: GROUND_ZERO]"
+        )]
+        root_name: Option<String>,
+
+        #[structopt(
+            long,
+            help = "Writes the root frame as the function <root> calling the top-level functions"
+        )]
+        explicit_root: bool,
+
+        #[structopt(
+            long,
+            requires = "output",
+            help = "Appends the profile as a new part of the existing output file"
+        )]
+        append: bool,
+
+        #[structopt(
+            long,
+            help = "Optional Solana release which costs of syscalls are taken from, e.g. 1.18"
+        )]
+        cluster_version: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            conflicts_with = "cluster-version",
+            help = "Optional path to the TOML file of weights of instructions by mnemonics and classes, and of syscalls"
+        )]
+        cost_model: Option<PathBuf>,
+
+        #[cfg(feature = "scripting")]
+        #[structopt(
+            parse(from_os_str),
+            long,
+            conflicts_with_all = &["cluster-version", "cost-model"],
+            help = "Optional path to the Rhai script returning costs of instructions"
+        )]
+        cost_script: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Splits calls of syscalls like sol_memcpy_ into nodes by sizes of arguments"
+        )]
+        syscall_sizes: bool,
+
+        #[structopt(
+            long,
+            help = "Attributes costs of programs invoked by syscalls (CPI) to their callers"
+        )]
+        flatten_nested: bool,
+
+        #[structopt(
+            long,
+            help = "Skips malformed lines of damaged or truncated traces instead of failing"
+        )]
+        lenient: bool,
+
+        #[structopt(
+            long,
+            default_value = "1",
+            help = "Number of malformed lines of the trace reported together, with their byte offsets and texts, before failing; 0 reports all"
+        )]
+        error_limit: usize,
+
+        #[structopt(
+            long,
+            requires = "dump",
+            help = "Positions costs at source lines known from the dump (llvm-objdump -l) or ELF file"
+        )]
+        source_lines: bool,
+
+        #[structopt(
+            long,
+            help = "Adds the first and the last trace lines of every call to the JSON profile"
+        )]
+        lines: bool,
+
+        #[structopt(
+            long,
+            help = "Adds callgrind events of memory reads and writes and calls of syscalls besides the cost"
+        )]
+        counters: bool,
+
+        #[structopt(
+            long,
+            help = "Adds the callgrind event of the fixed cost of every invocation of a syscall, unless the file of syscalls gives its cost (enables --counters)"
+        )]
+        syscall_cost: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Aggregates calls costing less than the number of instructions into one record per caller and callee instead of storing every call, saving memory"
+        )]
+        drop_calls_below: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Stores at most the number of calls of every caller and callee separately, aggregating further ones, to bound memory of very hot calls; percentiles of costs of calls become approximate"
+        )]
+        max_call_samples: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Optional name of the function; the trace before the first entry into it is discarded"
+        )]
+        ignore_before: Option<String>,
+
+        #[structopt(
+            long,
+            conflicts_with = "ignore-before",
+            help = "Optional name of the function; only its subtree, the trace inside its calls, is profiled"
+        )]
+        only_function: Option<String>,
+
+        #[structopt(
+            long,
+            default_value = "0",
+            help = "Number of instructions at the start of every trace which are discarded"
+        )]
+        skip_instructions: usize,
+
+        #[structopt(
+            long,
+            help = "Optional maximum number of instructions of every trace profiled after the skipped ones; the rest of the trace is not read"
+        )]
+        max_instructions: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Optional range of numbers of lines of every trace profiled, e.g. 1000:2000, either bound optional"
+        )]
+        range: Option<String>,
+
+        #[structopt(
+            long,
+            help = "Optional regex of names of functions shown; costs of others go to their callers"
+        )]
+        include: Option<String>,
+
+        #[structopt(
+            long,
+            help = "Optional regex of names of functions hidden; their costs go to their callers"
+        )]
+        exclude: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the file of aliases renaming or merging functions"
+        )]
+        aliases: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            requires = "destination",
+            help = "Optional path to the directory caching outputs of identical conversions"
+        )]
+        cache_dir: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Describes how the profile was generated in the callgrind header and JSON outputs: the tool version, the command line, paths and SHA-256 hashes of the inputs, the cost model and the time"
+        )]
+        metadata: bool,
+
+        #[structopt(
+            long,
+            help = "Profiles every invocation of a program in the trace as a separate part"
+        )]
+        split_invocations: bool,
+
+        #[structopt(
+            short,
+            long,
+            help = "Number of threads parsing chunks of invocations of large traces [default: 1]"
+        )]
+        jobs: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Optional percentage of the total cost, functions of lower inclusive costs are merged into <below threshold>"
+        )]
+        threshold: Option<f64>,
+
+        #[structopt(
+            long,
+            help = "Optional maximum size of the callgrind profile in bytes, cheapest functions are merged to fit it"
+        )]
+        max_output_size: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Compresses names and positions in the callgrind profile, e.g. fn=(1) instead of repeated names"
+        )]
+        compress_output: bool,
+
+        #[structopt(
+            long,
+            default_value = config::DEFAULT_SORT_ORDER,
+            possible_values(config::SORT_ORDERS),
+            help = "Order of functions in the callgrind profile; ties are broken by addresses, so profiles of the same trace are the same"
+        )]
+        sort: String,
+
+        #[structopt(
+            long,
+            default_value = config::DEFAULT_GRANULARITY,
+            possible_values(config::GRANULARITIES),
+            help = "Aggregates costs of the callgrind profile by functions or by call paths, e.g. to tell a function called by different callers apart"
+        )]
+        granularity: String,
+
+        #[structopt(
+            long,
+            requires = "destination",
+            help = "Keeps reading the trace while it grows and rewrites the profile (never returns)"
+        )]
+        watch: bool,
+
+        #[structopt(
+            long,
+            default_value = "1000",
+            help = "Interval of polling the watched trace in milliseconds"
+        )]
+        watch_interval: u64,
+
+        #[structopt(
+            long,
+            requires = "destination",
+            help = "Optional URL to upload the generated profile to (http://, https://, s3://)"
+        )]
+        upload: Option<String>,
+    },
+
+    #[structopt(about = "Generates one performance profile of several traces of a program")]
+    Merge {
+        #[structopt(
+            parse(from_os_str),
+            required = true,
+            help = "Paths to the input trace files"
+        )]
+        traces: Vec<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the generated assembly file (enables line-by-line profiling)"
+        )]
+        asm: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the generated profile [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Divides costs and numbers of calls by the number of traces (average run)"
+        )]
+        average: bool,
+
+        #[structopt(
+            long,
+            help = "Describes how the profile was generated in the callgrind header and JSON outputs: the tool version, the command line, paths and SHA-256 hashes of the inputs, the cost model and the time"
+        )]
+        metadata: bool,
+    },
+
+    #[structopt(about = "Renders the intermediate profile saved by generate --save-intermediate")]
+    Render {
+        #[structopt(parse(from_os_str), help = "Path to the intermediate profile")]
+        intermediate: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            help = "Optional format of the rendered profile [default: callgrind]"
+        )]
+        #[cfg_attr(not(feature = "plugins"), structopt(possible_values(&config::FORMATS)))]
+        format: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the rendered profile [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Optional name of the root frame, e.g. transaction signature [default: GROUND_ZERO]"
+        )]
+        root_name: Option<String>,
+
+        #[structopt(
+            long,
+            help = "Writes the root frame as the function <root> calling the top-level functions"
+        )]
+        explicit_root: bool,
+
+        #[structopt(
+            long,
+            help = "Optional percentage of the total cost, functions of lower inclusive costs are merged into <below threshold>"
+        )]
+        threshold: Option<f64>,
+
+        #[structopt(
+            long,
+            help = "Optional maximum size of the callgrind profile in bytes, cheapest functions are merged to fit it"
+        )]
+        max_output_size: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "Compresses names and positions in the callgrind profile, e.g. fn=(1) instead of repeated names"
+        )]
+        compress_output: bool,
+
+        #[structopt(
+            long,
+            default_value = config::DEFAULT_SORT_ORDER,
+            possible_values(config::SORT_ORDERS),
+            help = "Order of functions in the callgrind profile; ties are broken by addresses, so profiles of the same trace are the same"
+        )]
+        sort: String,
+    },
+
+    #[structopt(about = "Generates profile of differences of costs of two traces")]
+    Diff {
+        #[structopt(parse(from_os_str), help = "Path to the old trace file")]
+        old: PathBuf,
+
+        #[structopt(parse(from_os_str), help = "Path to the new trace file")]
+        new: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the dump file of the new trace (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the dump file of the old trace [default: dump of the new trace]"
+        )]
+        old_dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the text table of deltas of self and inclusive costs of functions"
+        )]
+        table: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            requires = "table",
+            help = "Divides costs in the table by numbers of calls of functions"
+        )]
+        per_call: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the generated profile [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Compares sizes of functions of two dump files")]
+    DumpDiff {
+        #[structopt(parse(from_os_str), help = "Path to the old dump file")]
+        old: PathBuf,
+
+        #[structopt(parse(from_os_str), help = "Path to the new dump file")]
+        new: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the table of changes [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Prints the table of the most expensive functions of a trace")]
+    Summary {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            short,
+            long,
+            default_value = "20",
+            help = "Number of functions in the table"
+        )]
+        top: usize,
+
+        #[structopt(
+            long,
+            default_value = "self",
+            possible_values(&["self", "inclusive"]),
+            help = "Cost to sort the functions by"
+        )]
+        by: String,
+
+        #[structopt(long, help = "Writes the table as CSV")]
+        csv: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the table [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Prints numbers of executed instructions by mnemonics")]
+    Instructions {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(long, help = "Breaks the numbers down by functions")]
+        by_function: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the histogram [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(
+        about = "Prints the disassembly of functions with executions and costs of instructions"
+    )]
+    Annotate {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions and listing instructions not executed)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(short, long, help = "Annotates only the function of the name")]
+        function: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the annotated disassembly [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Prints the lines of the trace of a call of a function")]
+    Show {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the bookmarks of the trace written by generate --bookmarks"
+        )]
+        bookmarks: PathBuf,
+
+        #[structopt(help = "Name of the function")]
+        function: String,
+
+        #[structopt(
+            short = "n",
+            long,
+            default_value = "1",
+            help = "Number of the call of the function in order of the trace"
+        )]
+        occurrence: usize,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the lines of the trace [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Runs queries over the profile of a trace")]
+    Query {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            short,
+            long,
+            help = "Query to run, e.g. 'top 10 by self' [default: read queries from standard input]"
+        )]
+        expr: Option<String>,
+    },
+
+    #[structopt(about = "Prints the call tree of a trace with inclusive costs of calls")]
+    Tree {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Optional name of the function to start the tree from, its calls by all paths merged [default: the root]"
+        )]
+        root: Option<String>,
+
+        #[structopt(
+            long,
+            help = "Optional number of levels of calls below the start of the tree [default: all]"
+        )]
+        depth: Option<usize>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the call tree [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[cfg(feature = "run")]
+    #[structopt(
+        about = "Runs the program under the embedded SBF virtual machine and generates the profile of its trace"
+    )]
+    Run {
+        #[structopt(
+            parse(from_os_str),
+            help = "Path to the ELF file of the program (enables resolving names of functions)"
+        )]
+        program: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input of the program, serialized parameters of its entrypoint [default: empty]"
+        )]
+        input: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            default_value = "1400000",
+            help = "Maximum number of instructions the program may execute"
+        )]
+        compute_limit: usize,
+
+        #[structopt(
+            short,
+            long,
+            help = "Optional format of the profile [default: callgrind]"
+        )]
+        #[cfg_attr(not(feature = "plugins"), structopt(possible_values(&config::FORMATS)))]
+        format: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the profile [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Optional name of the root frame, e.g. transaction signature [default: GROUND_ZERO]"
+        )]
+        root_name: Option<String>,
+    },
+
+    #[cfg(feature = "tui")]
+    #[structopt(
+        about = "Opens the profile of a trace in the terminal: a sortable table of functions and a call tree"
+    )]
+    View {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+    },
+
+    #[structopt(
+        about = "Checks the structure of the trace and shows the lines around issues, or costs against the baseline"
+    )]
+    Check {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the JSON profile of the baseline (generate -f json), checks costs instead of the structure"
+        )]
+        baseline: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            default_value = "0%",
+            help = "Maximum growth of the total cost and inclusive costs of functions over the baseline"
+        )]
+        max_regression: String,
+
+        #[structopt(
+            short,
+            long,
+            default_value = "2",
+            help = "Number of trace lines shown before and after each issue"
+        )]
+        context: usize,
+
+        #[structopt(long, help = "Writes the issues or the regressions as JSON")]
+        json: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the report of issues or regressions [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            requires = "baseline",
+            help = "Optional URL of the webhook (e.g. Slack or Discord) to which the regressions are posted as JSON"
+        )]
+        notify_url: Option<String>,
+    },
+
+    #[structopt(
+        about = "Lints the structure of the trace: malformed lines, unbalanced calls, instructions out of sequence and calls beyond the depth limit"
+    )]
+    Lint {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            default_value = "0",
+            help = "Number of trace lines shown before and after each issue"
+        )]
+        context: usize,
+
+        #[structopt(long, help = "Writes the issues as JSON")]
+        json: bool,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the report of issues [default: standard output]"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Checks costs of functions against the budgets")]
+    CheckBudget {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the manifest file of budgets of functions [default: budgets of the preset, or budgets.toml]"
+        )]
+        budgets: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the SARIF log of violations for code scanning tools"
+        )]
+        sarif: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            help = "Optional path to the JUnit XML report of a test case per budgeted function for CI systems"
+        )]
+        junit: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Optional URL of the webhook (e.g. Slack or Discord) to which the violations are posted as JSON"
+        )]
+        notify_url: Option<String>,
+    },
+
+    #[structopt(about = "Watches a directory and converts new trace files")]
+    WatchDir {
+        #[structopt(parse(from_os_str), help = "Path to the watched directory")]
+        input_dir: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            short,
+            long,
+            help = "Optional format of the generated profiles [default: callgrind]"
+        )]
+        #[cfg_attr(not(feature = "plugins"), structopt(possible_values(&config::FORMATS)))]
+        format: Option<String>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the directory of generated profiles"
+        )]
+        output_dir: PathBuf,
+
+        #[structopt(
+            short,
+            long,
+            default_value = "1000",
+            help = "Interval of polling the directory in milliseconds"
+        )]
+        interval: u64,
+    },
+
+    #[cfg(unix)]
+    #[structopt(about = "Aggregates profiles of traces received over a local socket")]
+    Daemon {
+        #[structopt(parse(from_os_str), help = "Path to the listened Unix socket")]
+        socket: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the directory of dump files named <program-id>.dump"
+        )]
+        dump_dir: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Measures throughput of the converter")]
+    Bench {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file (enables resolving names of functions)"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(short = "n", long, default_value = "5", help = "Number of iterations")]
+        iterations: usize,
+    },
+
+    #[structopt(about = "Replaces names and addresses of functions with pseudonyms")]
+    Anonymize {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Optional path to the input dump file to anonymize as well"
+        )]
+        dump: Option<PathBuf>,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the anonymized trace file"
+        )]
+        output: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            long,
+            requires = "dump",
+            help = "Optional path to the anonymized dump file [default: <output>.dump]"
+        )]
+        dump_output: Option<PathBuf>,
+    },
+
+    #[structopt(about = "Converts the trace file into the compact binary format")]
+    Compact {
+        #[structopt(parse(from_os_str), help = "Path to the input trace file")]
+        trace: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the compact trace file"
+        )]
+        output: PathBuf,
+    },
+
+    #[structopt(about = "Converts the compact binary trace file into the text format")]
+    Expand {
+        #[structopt(parse(from_os_str), help = "Path to the compact trace file")]
+        input: PathBuf,
+
+        #[structopt(
+            parse(from_os_str),
+            short,
+            long,
+            help = "Path to the expanded trace file"
+        )]
+        output: PathBuf,
+    },
+
+    #[structopt(about = "Generates shell completion script")]
+    Completions {
+        #[structopt(possible_values(&Shell::variants()), help = "Target shell")]
+        shell: Shell,
+    },
+}
+
+/// Constructs an instance of the Application.
+pub fn application() -> Application {
+    Application::from_args()
+}
+
+/// Writes completion script for the shell to the standard output.
+pub fn write_completions(shell: Shell) {
+    Application::clap().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut io::stdout());
 }
@@ -0,0 +1,251 @@
+//! bpf-profile compact and expand commands implementation.
+//! Implements the compact binary format of trace files.
+//!
+//! The format starts with `MAGIC` and the version byte followed by records.
+//! Instruction lines are stored as: line index (delta to previous one),
+//! registers (XOR with previous ones), program counter and index of the
+//! instruction text in the table of texts (new texts are stored inline).
+//! All other lines are stored verbatim.
+
+use crate::config::Map;
+use crate::error::{Error, Result};
+use crate::{filebuf, global};
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+/// Magic bytes starting compact traces.
+pub const MAGIC: &[u8] = b"BPFTRACE";
+const VERSION: u8 = 1;
+
+const TAG_TEXT: u8 = 1;
+const TAG_INSTRUCTION: u8 = 2;
+
+/// Converts the text trace file into the compact binary format.
+pub fn compact(trace_path: &Path, output_path: &Path) -> Result<()> {
+    if !crate::trace::contains_standard_header(filebuf::open(trace_path)?)? {
+        return Err(Error::TraceFormat);
+    }
+    if global::verbose() {
+        tracing::info!("Compacting trace file...")
+    }
+    let reader = filebuf::open(trace_path)?;
+    let mut output = filebuf::open_w(output_path)?;
+    compact_trace(reader, &mut output)?;
+    output.commit()
+}
+
+/// Converts the compact binary trace file into the text format.
+pub fn expand(input_path: &Path, output_path: &Path) -> Result<()> {
+    if global::verbose() {
+        tracing::info!("Expanding trace file...")
+    }
+    let mut reader = filebuf::open(input_path)?; // expands transparently
+    let mut output = filebuf::open_w(output_path)?;
+    io::copy(&mut reader, &mut output)?;
+    output.commit()
+}
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Writes the text trace in the compact binary format.
+pub fn compact_trace(mut reader: impl BufRead, mut output: impl Write) -> Result<()> {
+    lazy_static! {
+        static ref INSTRUCTION: Regex =
+            Regex::new(r"^\s*(\d+)\s+\[(.*)\]\s+(\d+):\s+(.+)").expect("Invalid regex");
+    }
+
+    output.write_all(MAGIC)?;
+    output.write_all(&[VERSION])?;
+
+    let mut index = 0_u64;
+    let mut registers = Vec::<u64>::new();
+    let mut texts = Map::<String, u64>::new();
+    let mut line = String::with_capacity(512);
+    while filebuf::read_line(&mut reader, &mut line)? != 0 {
+        let caps = INSTRUCTION.captures(&line);
+        let parsed = caps.as_ref().and_then(|caps| {
+            let i = caps[1].parse::<u64>().ok()?;
+            let regs = parse_registers(&caps[2])?;
+            let pc = caps[3].parse::<u64>().ok()?;
+            Some((i, regs, pc, caps[4].trim()))
+        });
+
+        match parsed {
+            None => {
+                output.write_all(&[TAG_TEXT])?;
+                write_varint(&mut output, line.len() as u64)?;
+                output.write_all(line.as_bytes())?;
+            }
+            Some((i, regs, pc, text)) => {
+                output.write_all(&[TAG_INSTRUCTION])?;
+                write_varint(&mut output, zigzag(i.wrapping_sub(index) as i64))?;
+                index = i;
+                write_varint(&mut output, regs.len() as u64)?;
+                registers.resize(regs.len(), 0);
+                for (r, previous) in regs.iter().zip(registers.iter_mut()) {
+                    write_varint(&mut output, r ^ *previous)?;
+                    *previous = *r;
+                }
+                write_varint(&mut output, pc)?;
+                match texts.get(text) {
+                    Some(id) => write_varint(&mut output, *id)?,
+                    None => {
+                        let id = texts.len() as u64;
+                        texts.insert(text.into(), id);
+                        write_varint(&mut output, id)?;
+                        write_varint(&mut output, text.len() as u64)?;
+                        output.write_all(text.as_bytes())?;
+                    }
+                }
+            }
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Parses the comma separated hexadecimal register values.
+fn parse_registers(s: &str) -> Option<Vec<u64>> {
+    s.split(',')
+        .map(|r| u64::from_str_radix(r.trim(), 16).ok())
+        .collect()
+}
+
+/// Represents a reader of the compact binary trace which produces the text trace.
+pub struct Expander<R: BufRead> {
+    input: R,
+    line: Vec<u8>,
+    pos: usize,
+    index: u64,
+    registers: Vec<u64>,
+    texts: Vec<String>,
+}
+
+impl<R: BufRead> Expander<R> {
+    /// Creates new instance of Expander; checks the magic and the version.
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0_u8; MAGIC.len() + 1];
+        input.read_exact(&mut magic)?;
+        if &magic[..MAGIC.len()] != MAGIC || magic[MAGIC.len()] != VERSION {
+            return Err(invalid_data("unsupported compact trace format"));
+        }
+        Ok(Expander {
+            input,
+            line: Vec::with_capacity(512),
+            pos: 0,
+            index: 0,
+            registers: Vec::new(),
+            texts: Vec::new(),
+        })
+    }
+
+    /// Decodes next record into the line buffer. Returns false at the end of input.
+    fn decode(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        self.pos = 0;
+        let mut tag = [0_u8];
+        if self.input.read(&mut tag)? == 0 {
+            return Ok(false);
+        }
+
+        match tag[0] {
+            TAG_TEXT => {
+                let len = read_varint(&mut self.input)?;
+                self.line.resize(len as usize, 0);
+                self.input.read_exact(&mut self.line)?;
+            }
+            TAG_INSTRUCTION => {
+                let delta = unzigzag(read_varint(&mut self.input)?);
+                self.index = self.index.wrapping_add(delta as u64);
+                let count = read_varint(&mut self.input)? as usize;
+                self.registers.resize(count, 0);
+                for r in self.registers.iter_mut() {
+                    *r ^= read_varint(&mut self.input)?;
+                }
+                let pc = read_varint(&mut self.input)?;
+                let id = read_varint(&mut self.input)? as usize;
+                if id == self.texts.len() {
+                    let len = read_varint(&mut self.input)? as usize;
+                    let mut text = vec![0_u8; len];
+                    self.input.read_exact(&mut text)?;
+                    let text = String::from_utf8(text)
+                        .map_err(|_| invalid_data("instruction is not UTF-8"))?;
+                    self.texts.push(text);
+                }
+                let text = self
+                    .texts
+                    .get(id)
+                    .ok_or_else(|| invalid_data("unknown instruction text"))?;
+                let registers = self
+                    .registers
+                    .iter()
+                    .map(|r| format!("{:016X}", r))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    self.line,
+                    "{:>9} [{}] {:>5}: {}",
+                    self.index, registers, pc, text
+                )?;
+            }
+            _ => return Err(invalid_data("unknown record")),
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for Expander<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() && !self.decode()? {
+            return Ok(0);
+        }
+        let n = std::cmp::min(buf.len(), self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes unsigned LEB128 number.
+fn write_varint(output: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return output.write_all(&[byte]);
+        }
+        output.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Reads unsigned LEB128 number.
+fn read_varint(input: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0_u64;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0_u8];
+        input.read_exact(&mut byte)?;
+        if shift >= 64 {
+            return Err(invalid_data("too long number"));
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
@@ -1,26 +1,248 @@
 //! bpf-profile config module.
 
+/// Exit code of failures.
 pub const FAILURE: i32 = 1;
 
+/// Default name of the config file.
 pub const DEFAULT_CONFIG: &str = "bpf-profile.conf";
+/// Default name of the manifest file of budgets.
+pub const DEFAULT_BUDGETS: &str = "budgets.toml";
 
-pub const FORMATS: &[&str] = &["callgrind"];
+/// Supported formats of profiles.
+#[cfg(not(feature = "flamegraph"))]
+pub const FORMATS: &[&str] = &[
+    "callgrind",
+    "folded",
+    "speedscope",
+    "json",
+    "dot",
+    "chrome-trace",
+    "text",
+];
+/// Supported formats of profiles.
+#[cfg(feature = "flamegraph")]
+pub const FORMATS: &[&str] = &[
+    "callgrind",
+    "folded",
+    "flamegraph",
+    "speedscope",
+    "json",
+    "dot",
+    "chrome-trace",
+    "text",
+];
+/// Formats written from costs by call stacks rather than the callgrind profile.
+pub const FLAMEGRAPH_FORMATS: &[&str] = &["folded", "flamegraph", "speedscope"];
+/// Format written from the timeline of calls rather than the callgrind profile.
+pub const TIMELINE_FORMAT: &str = "chrome-trace";
+/// Default format of profiles.
 pub const DEFAULT_FORMAT: &str = "callgrind";
 
+/// Supported orders of functions in callgrind profiles.
+pub const SORT_ORDERS: &[&str] = &["name", "address", "self-cost", "inclusive-cost"];
+/// Default order of functions in callgrind profiles.
+pub const DEFAULT_SORT_ORDER: &str = "address";
+
+/// Supported granularities of costs in callgrind profiles.
+pub const GRANULARITIES: &[&str] = &["function", "path"];
+/// Default granularity of costs in callgrind profiles.
+pub const DEFAULT_GRANULARITY: &str = "function";
+
+/// Supported formats of log messages.
+pub const LOG_FORMATS: &[&str] = &["pretty", "json"];
+/// Default format of log messages.
+pub const DEFAULT_LOG_FORMAT: &str = "pretty";
+
+/// Supported formats of reports of progress of parsing traces.
+#[cfg(not(feature = "progress-bar"))]
+pub const PROGRESS_FORMATS: &[&str] = &["text", "json"];
+/// Supported formats of reports of progress of parsing traces.
+#[cfg(feature = "progress-bar")]
+pub const PROGRESS_FORMATS: &[&str] = &["text", "json", "bar"];
+
+/// Supported formats of numbers in reports.
+pub const NUMBER_FORMATS: &[&str] = &["plain", "separated", "short"];
+/// Default format of numbers in reports.
+pub const DEFAULT_NUMBER_FORMAT: &str = "plain";
+
+/// Value meaning no assembly output.
 pub const DEFAULT_ASM: &str = "<none>";
 
+/// Default regular expression of headers of functions in dump files.
+pub const DEFAULT_DUMP_FUNCTION_HEADER: &str = r"^[[:xdigit:]]+\s+<(?P<name>.+)>";
+/// Default regular expression of instructions in dump files.
+pub const DEFAULT_DUMP_INSTRUCTION: &str = r"^\s+(?P<pc>\d+)(\s+[[:xdigit:]]{2})+\s+(?P<text>.+)";
+
+/// Name of the root frame written explicitly to the callgrind profile.
+pub const EXPLICIT_ROOT_NAME: &str = "<root>";
+
+/// Default template of names of functions not found in the dump file,
+/// stable across runs as derived from the first program counter and the address.
+pub const DEFAULT_UNRESOLVED_NAME: &str = "function_{pc} (0x{address})";
+
+/// Number of the costliest unresolved functions listed after the conversion.
+pub const UNRESOLVED_REPORT_LIMIT: usize = 10;
+
+/// Number of errors of parsing a trace collected before failing by default.
+pub const DEFAULT_ERROR_LIMIT: usize = 1;
+/// Maximum number of characters of the offending line shown in errors of parsing traces.
+pub const MAX_DIAGNOSTIC_TEXT: usize = 120;
+
+/// Position of the bits tagging addresses of functions with the region of their program,
+/// other than the program of the dump file.
+pub const PROGRAM_REGION_SHIFT: u32 = 48;
+
+/// Extension of trace files.
+pub const TRACE_EXTENSION: &str = "trace";
+
+/// Default pattern of names of trace files in input directories.
+pub const DEFAULT_TRACE_PATTERN: &str = "*.trace";
+
+/// Path meaning the standard input.
+pub const STDIN: &str = "-";
+
+/// Indentation of nested lines of outputs.
 pub const PADDING: &str = "        ";
 
+/// Number of the most expensive functions in metrics.
+pub const METRICS_TOP_FUNCTIONS: usize = 10;
+
+/// Maximum number of violations listed in the message of webhook notifications.
+pub const NOTIFY_MESSAGE_LIMIT: usize = 20;
+
+/// Default number of instructions in a window of the heatmap.
+pub const DEFAULT_HEATMAP_WINDOW: usize = 1000;
+
+/// Default size in bytes of buffers of reading and writing files.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 << 10;
+/// Minimum size in bytes of buffers, which hold magic bytes of inputs.
+pub const MIN_BUFFER_SIZE: usize = 64;
+
+/// Minimum size in bytes of a chunk of the trace parsed by a separate thread.
+pub const PARALLEL_CHUNK_SIZE: usize = 16 << 20;
+
+/// Maximum size in bytes of a stack frame of SBF programs.
+pub const STACK_FRAME_LIMIT: usize = 4096;
+
+/// Maximum number of frames on the call stack of SBF programs.
+pub const CALL_DEPTH_LIMIT: usize = 64;
+
+/// Maximum size in bytes of the stack of SBF programs: frames of the maximum call depth.
+pub const STACK_LIMIT: usize = CALL_DEPTH_LIMIT * STACK_FRAME_LIMIT;
+
+/// Percentage of the stack limits above which the stack usage is reported at risk.
+pub const STACK_RISK_PERCENT: usize = 90;
+
+/// Maximum compute units a Solana transaction may consume.
+pub const TRANSACTION_COMPUTE_LIMIT: Cost = 1_400_000;
+
+/// Size of the heap of programs run by the embedded virtual machine, as by the runtime.
+#[cfg(feature = "run")]
+pub const VM_HEAP_SIZE: usize = 32 << 10;
+
+/// Cost of instructions in compute units.
 pub type Cost = usize;
+/// Index of a trace or an instruction.
 pub type Index = usize;
-pub type Address = usize;
+/// Address of a function.
+pub type Address = u64;
+/// Program counter of an instruction.
 pub type ProgramCounter = usize;
 
-pub const GROUND_ZERO: Address = Address::MAX;
-
+/// Map used throughout the converter.
 #[cfg(not(test))]
 pub type Map<K, V> = std::collections::HashMap<K, V>;
 
+/// Map used throughout the converter.
 // Use less performant BTree in tests for deterministic sequences
 #[cfg(test)]
 pub type Map<K, V> = std::collections::BTreeMap<K, V>;
+
+use crate::error::{Error, Result};
+use crate::gen::budget::Budget;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Represents a named set of options stored in the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Format of the profile.
+    pub format: Option<String>,
+    /// Path to the dump file.
+    pub dump: Option<PathBuf>,
+    /// Path to the assembly output.
+    pub asm: Option<PathBuf>,
+    /// Path to the metrics output.
+    pub metrics: Option<PathBuf>,
+    /// Name of the root function.
+    pub root_name: Option<String>,
+    /// Regular expression of names of functions shown in the profile.
+    pub include: Option<String>,
+    /// Regular expression of names of functions hidden from the profile.
+    pub exclude: Option<String>,
+    /// Cluster version of the cost model.
+    pub cluster_version: Option<String>,
+    /// Path to the file of weights of instructions of the cost model.
+    pub cost_model: Option<PathBuf>,
+    /// Path to the file of aliases of functions.
+    pub aliases: Option<PathBuf>,
+    /// Path to the symbol map of functions.
+    pub symbols: Option<PathBuf>,
+    /// Regular expression of headers of functions in dump files
+    /// with the named group `name`.
+    pub dump_function_header: Option<String>,
+    /// Regular expression of instructions in dump files
+    /// with the named groups `pc` and `text`.
+    pub dump_instruction: Option<String>,
+    /// Template of names of functions not found in the dump file
+    /// with the placeholders `{index}`, `{address}` and `{pc}`.
+    pub unresolved_name: Option<String>,
+    /// Path to the file of syscalls.
+    pub syscalls: Option<PathBuf>,
+    /// Number of threads parsing chunks of large traces.
+    pub jobs: Option<usize>,
+    /// Size in bytes of buffers of reading files.
+    pub read_buffer: Option<usize>,
+    /// Size in bytes of buffers of writing files.
+    pub write_buffer: Option<usize>,
+    /// Budgets of functions checked unless the manifest file is given.
+    #[serde(default)]
+    pub budget: Vec<Budget>,
+}
+
+/// Represents contents of the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    preset: BTreeMap<String, Preset>,
+}
+
+/// Reads the config file and returns the preset selected by name.
+/// Returns empty preset if no name is given.
+pub fn read_preset(filepath: &Path, name: Option<&str>) -> Result<Preset> {
+    let name = match name {
+        None => return Ok(Preset::default()),
+        Some(name) => name,
+    };
+
+    let text = fs::read_to_string(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    let mut file: File =
+        toml::from_str(&text).map_err(|e| Error::ConfigParsing(filepath.into(), e.to_string()))?;
+    let preset = file
+        .preset
+        .remove(name)
+        .ok_or_else(|| Error::PresetNotFound(name.into(), filepath.into()))?;
+
+    // Formats of plugins are checked after loading them
+    #[cfg(not(feature = "plugins"))]
+    if let Some(format) = &preset.format {
+        if !FORMATS.contains(&format.as_str()) {
+            return Err(Error::UnknownFormat(format.clone()));
+        }
+    }
+    Ok(preset)
+}
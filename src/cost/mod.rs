@@ -0,0 +1,86 @@
+//! bpf-profile cost module.
+//! Implements models of costs of executed instructions.
+
+#[cfg(feature = "scripting")]
+mod script;
+mod tables;
+mod weights;
+
+use crate::bpf::Instruction;
+use crate::config::{Address, Cost};
+use crate::error::{Error, Result};
+use crate::syscalls;
+#[cfg(feature = "scripting")]
+pub use script::Script;
+use std::fmt;
+pub use weights::Weights;
+
+/// Represents the model which assigns cost to each executed instruction.
+#[derive(Debug, Default)]
+pub enum Model {
+    /// Every instruction costs 1.
+    #[default]
+    Instructions,
+    /// Compute units of the Solana release (index of the built-in cost table).
+    Cluster(usize),
+    /// Costs are weights of instructions read from the file.
+    Weights(Box<Weights>),
+    /// Costs are returned by the user's script.
+    #[cfg(feature = "scripting")]
+    Script(Box<Script>),
+}
+
+impl Model {
+    /// Creates the model of compute units of the Solana release.
+    pub fn cluster(version: &str) -> Result<Self> {
+        let index = tables::find(version).ok_or_else(|| {
+            Error::UnknownClusterVersion(version.into(), tables::versions().join(", "))
+        })?;
+        Ok(Model::Cluster(index))
+    }
+
+    /// Creates the model of compute units of the release matching the runtime version,
+    /// if the runtime is not older than the supported releases.
+    pub fn for_runtime(runtime_version: &str) -> Option<Self> {
+        tables::find_release(runtime_version).map(Model::Cluster)
+    }
+
+    /// Returns cost of the instruction.
+    #[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+    pub fn cost(&mut self, ix: &Instruction) -> Result<Cost> {
+        match self {
+            Model::Instructions | Model::Cluster(_) => Ok(1),
+            Model::Weights(weights) => Ok(weights.cost(ix)),
+            #[cfg(feature = "scripting")]
+            Model::Script(script) => script.cost(ix),
+        }
+    }
+
+    /// Returns cost of the work of a syscall, besides the call instruction.
+    pub fn syscall_cost(&self, address: Address) -> Cost {
+        match self {
+            Model::Cluster(index) => {
+                let name = syscalls::name(address).unwrap_or_default();
+                tables::syscall_cost(*index, name)
+            }
+            Model::Weights(weights) => {
+                weights.syscall_cost(syscalls::name(address).unwrap_or_default())
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Model::Instructions => write!(f, "instructions"),
+            Model::Cluster(index) => {
+                write!(f, "compute units of Solana {}", tables::versions()[*index])
+            }
+            Model::Weights(weights) => write!(f, "weights {}", weights.path().display()),
+            #[cfg(feature = "scripting")]
+            Model::Script(script) => write!(f, "script {}", script.path().display()),
+        }
+    }
+}
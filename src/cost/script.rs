@@ -0,0 +1,79 @@
+//! bpf-profile cost script module.
+//! Implements the cost model defined by a Rhai script.
+//!
+//! The script defines function `weight(opcode, operands, is_syscall)`
+//! which takes the mnemonic of the instruction (e.g. "ldxdw"), its operands
+//! as a string (e.g. "r1, [r2+0x8]") and a flag of a call of a syscall,
+//! and returns the cost of the instruction as a non-negative integer.
+//! Costs are cached by program counters, so the function must depend
+//! on its arguments only.
+
+use crate::bpf::Instruction;
+use crate::config::{Cost, Map, ProgramCounter};
+use crate::error::{Error, Result};
+use crate::syscalls;
+use rhai::{Engine, Scope, AST, INT};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Represents the compiled cost script.
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    costs: Map<ProgramCounter, Cost>,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Script").field("path", &self.path).finish()
+    }
+}
+
+impl Script {
+    /// Reads and compiles the script.
+    pub fn load(path: &Path) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| Error::CostScript(path.into(), e.to_string()))?;
+        Ok(Script {
+            path: path.into(),
+            engine,
+            ast,
+            costs: Map::new(),
+        })
+    }
+
+    /// Returns the path to the script.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns cost of the instruction calling the script's function once per program counter.
+    pub fn cost(&mut self, ix: &Instruction) -> Result<Cost> {
+        if let Some(cost) = self.costs.get(&ix.pc()) {
+            return Ok(*cost);
+        }
+
+        let text = ix.text();
+        let (opcode, operands) = text.split_once(' ').unwrap_or((&text, ""));
+        let is_syscall = ix.is_call() && ix.extract_call_target(0).is_ok_and(syscalls::is_syscall);
+        let error = |message: String| Error::CostScript(self.path.clone(), message);
+
+        let weight = self
+            .engine
+            .call_fn::<INT>(
+                &mut Scope::new(),
+                &self.ast,
+                "weight",
+                (opcode.to_string(), operands.trim().to_string(), is_syscall),
+            )
+            .map_err(|e| error(e.to_string()))?;
+        let cost = Cost::try_from(weight)
+            .map_err(|_| error(format!("negative weight {} of '{}'", weight, text)))?;
+
+        self.costs.insert(ix.pc(), cost);
+        Ok(cost)
+    }
+}
@@ -0,0 +1,124 @@
+//! bpf-profile cost tables module.
+//! Implements built-in costs in compute units by Solana releases.
+//!
+//! Every instruction costs one compute unit, syscalls cost the base costs
+//! of the default compute budget of the release. Costs depending on sizes
+//! of arguments are not modeled, since traces contain no arguments of syscalls.
+
+use crate::config::Cost;
+
+/// Represents changes of costs introduced by a Solana release.
+#[derive(Debug)]
+pub struct Table {
+    /// Release of the cluster, e.g. "1.18".
+    pub version: &'static str,
+    /// Costs of syscalls added or repriced in the release.
+    syscalls: &'static [(&'static str, Cost)],
+}
+
+/// Cost of a syscall unknown to the release.
+const SYSCALL_BASE_COST: Cost = 100;
+
+/// Releases in ascending order; each one inherits costs of the previous ones.
+pub const TABLES: &[Table] = &[
+    Table {
+        version: "1.14",
+        syscalls: &[
+            ("abort", 100),
+            ("sol_panic_", 100),
+            ("sol_log_", 100),
+            ("sol_log_64_", 100),
+            ("sol_log_compute_units_", 100),
+            ("sol_log_pubkey", 100),
+            ("sol_log_data", 100),
+            ("sol_create_program_address", 1500),
+            ("sol_try_find_program_address", 1500),
+            ("sol_sha256", 85),
+            ("sol_keccak256", 85),
+            ("sol_blake3", 85),
+            ("sol_secp256k1_recover", 25000),
+            ("sol_get_clock_sysvar", 100),
+            ("sol_get_epoch_schedule_sysvar", 100),
+            ("sol_get_fees_sysvar", 100),
+            ("sol_get_rent_sysvar", 100),
+            ("sol_memcpy_", 10),
+            ("sol_memmove_", 10),
+            ("sol_memcmp_", 10),
+            ("sol_memset_", 10),
+            ("sol_invoke_signed_c", 1000),
+            ("sol_invoke_signed_rust", 1000),
+            ("sol_set_return_data", 100),
+            ("sol_get_return_data", 100),
+            ("sol_get_processed_sibling_instruction", 100),
+            ("sol_get_stack_height", 100),
+            ("sol_curve_validate_point", 159),
+            ("sol_curve_group_op", 473),
+        ],
+    },
+    Table {
+        version: "1.16",
+        syscalls: &[
+            ("sol_curve_multiscalar_mul", 2273),
+            ("sol_alt_bn128_group_op", 334),
+        ],
+    },
+    Table {
+        version: "1.17",
+        syscalls: &[
+            ("sol_poseidon", 542),
+            ("sol_remaining_compute_units", 100),
+            ("sol_big_mod_exp", 33),
+        ],
+    },
+    Table {
+        version: "1.18",
+        syscalls: &[
+            ("sol_alt_bn128_compression", 30),
+            ("sol_get_epoch_rewards_sysvar", 100),
+            ("sol_get_last_restart_slot_sysvar", 100),
+        ],
+    },
+    Table {
+        version: "2.0",
+        syscalls: &[("sol_get_sysvar", 100)],
+    },
+    Table {
+        version: "2.1",
+        syscalls: &[("sol_get_epoch_stake", 200)],
+    },
+];
+
+/// Returns versions of the supported releases.
+pub fn versions() -> Vec<&'static str> {
+    TABLES.iter().map(|t| t.version).collect()
+}
+
+/// Returns index of the release in the tables.
+pub fn find(version: &str) -> Option<usize> {
+    TABLES.iter().position(|t| t.version == version)
+}
+
+/// Returns index of the latest release not newer than the runtime version, e.g. "1.18.2".
+pub fn find_release(runtime_version: &str) -> Option<usize> {
+    let runtime = major_minor(runtime_version)?;
+    TABLES
+        .iter()
+        .rposition(|t| major_minor(t.version).is_some_and(|v| v <= runtime))
+}
+
+/// Parses the major and minor numbers of the version.
+fn major_minor(version: &str) -> Option<(u32, u32)> {
+    let mut numbers = version.split('.').map(str::parse::<u32>);
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next()?.ok()?;
+    Some((major, minor))
+}
+
+/// Returns cost of the syscall in the release of the index.
+pub fn syscall_cost(index: usize, name: &str) -> Cost {
+    TABLES[..=index]
+        .iter()
+        .rev()
+        .find_map(|t| t.syscalls.iter().find(|(n, _)| *n == name))
+        .map_or(SYSCALL_BASE_COST, |(_, cost)| *cost)
+}
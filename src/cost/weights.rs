@@ -0,0 +1,90 @@
+//! bpf-profile cost weights module.
+//! Implements the cost model defined by weights of instructions in a TOML file:
+//! ```toml
+//! # Weight of instructions not listed below
+//! default = 1
+//!
+//! [classes]
+//! "load 8B" = 2
+//! call = 5
+//!
+//! [mnemonics]
+//! div64 = 4
+//! udiv64 = 4
+//!
+//! [syscalls]
+//! sol_log_ = 100
+//! ```
+//! The weight of a mnemonic takes precedence over the weight of its class.
+//! Classes are those of the opcodes by `Instruction::opcode_class`, e.g. "alu64".
+//! Syscalls weigh the work of the syscall besides the call instruction.
+
+use crate::bpf::{Instruction, OPCODE_CLASSES};
+use crate::config::{Cost, Map};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Represents weights of instructions read from the file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Weights {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default = "default_weight")]
+    default: Cost,
+    #[serde(default)]
+    classes: Map<String, Cost>,
+    #[serde(default)]
+    mnemonics: Map<String, Cost>,
+    #[serde(default)]
+    syscalls: Map<String, Cost>,
+}
+
+/// Returns the weight of instructions not listed in the file.
+fn default_weight() -> Cost {
+    1
+}
+
+impl Weights {
+    /// Reads weights from the file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|e| Error::OpenFile(e, path.into()))?;
+        let mut weights: Weights =
+            toml::from_str(&text).map_err(|e| Error::ConfigParsing(path.into(), e.to_string()))?;
+        if let Some(class) = weights
+            .classes
+            .keys()
+            .find(|class| !OPCODE_CLASSES.contains(&class.as_str()))
+        {
+            let message = format!(
+                "unknown class '{}', expected one of: {}",
+                class,
+                OPCODE_CLASSES.join(", ")
+            );
+            return Err(Error::ConfigParsing(path.into(), message));
+        }
+        weights.path = path.into();
+        Ok(weights)
+    }
+
+    /// Returns the path to the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the weight of the instruction by its mnemonic, or class, or the default.
+    pub fn cost(&self, ix: &Instruction) -> Cost {
+        self.mnemonics
+            .get(ix.mnemonic())
+            .or_else(|| self.classes.get(ix.opcode_class()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Returns the weight of the work of the syscall of the name, 0 if not listed.
+    pub fn syscall_cost(&self, name: &str) -> Cost {
+        self.syscalls.get(name).copied().unwrap_or(0)
+    }
+}
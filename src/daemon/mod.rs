@@ -0,0 +1,114 @@
+//! bpf-profile daemon command implementation.
+//! Accepts successive traces over a local socket and maintains
+//! cumulative profiles of programs in memory.
+//!
+//! Every connection carries one request in its first line:
+//! - `trace <program-id>` followed by the trace lines until the end of input,
+//!   adds the trace to the profile of the program and responds `ok`, or responds
+//!   with the error leaving the profile unchanged if the trace is malformed;
+//! - `report <program-id>` responds with the current profile in the callgrind format;
+//! - `list` responds with ids of known programs and their total costs.
+
+use crate::config::DEFAULT_ASM;
+use crate::error::{Error, Result};
+use crate::{filebuf, gen, global};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+/// Cumulative profiles by program ids.
+pub type Profiles = BTreeMap<String, gen::Profile>;
+
+/// Listens on the socket and serves requests one by one. Never returns on success.
+/// Profiles of programs are created and traces are parsed with the options.
+pub fn run(socket_path: &Path, dump_dir: Option<&Path>, options: &gen::Options) -> Result<()> {
+    if let Some(dump_dir) = dump_dir {
+        if !dump_dir.is_dir() {
+            return Err(Error::NotDirectory(dump_dir.into()));
+        }
+    }
+
+    // Remove the socket left by a previous run
+    if fs::symlink_metadata(socket_path).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    tracing::info!("Listening on {:?}...", socket_path);
+
+    let mut profiles = Profiles::new();
+    for stream in listener.incoming() {
+        let result = stream
+            .map_err(Error::from)
+            .and_then(|stream| serve(stream, &mut profiles, dump_dir, options));
+        if let Err(err) = result {
+            tracing::error!("Cannot serve request: {:#}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves one request of the connection.
+pub fn serve(
+    stream: UnixStream,
+    profiles: &mut Profiles,
+    dump_dir: Option<&Path>,
+    options: &gen::Options,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut output = stream;
+
+    let mut request = String::new();
+    filebuf::read_line(&mut reader, &mut request)?;
+    let words = request.split_whitespace().collect::<Vec<_>>();
+    if global::verbose() {
+        tracing::info!("Request {:?}", &words);
+    }
+
+    match words.as_slice() {
+        ["trace", id] if is_program_id(id) => {
+            let known = profiles.contains_key(*id);
+            let mut profile = match profiles.remove(*id) {
+                Some(profile) => profile,
+                None => {
+                    let dump_path = dump_dir
+                        .map(|d| d.join(format!("{}.dump", id)))
+                        .filter(|p| p.exists());
+                    let resv = gen::read_resolver(dump_path.as_deref(), options)?;
+                    gen::Profile::with_options(resv, options)?
+                }
+            };
+            let result = profile.parse_merged(reader, options);
+            // Programs become known with their first trace parsed successfully
+            if known || result.is_ok() {
+                profiles.insert(id.to_string(), profile);
+            }
+            match result {
+                Ok(()) => writeln!(output, "ok")?,
+                Err(err) => writeln!(output, "error: {}", err)?,
+            }
+        }
+        ["report", id] => match profiles.get(*id) {
+            Some(profile) => profile.write_callgrind(&mut output, DEFAULT_ASM)?,
+            None => writeln!(output, "error: unknown program '{}'", id)?,
+        },
+        ["list"] => {
+            for (id, profile) in profiles.iter() {
+                writeln!(output, "{} {}", id, profile.total_cost())?;
+            }
+        }
+        _ => writeln!(output, "error: unsupported request '{}'", request.trim())?,
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Checks the program id can be used as a file name.
+fn is_program_id(id: &str) -> bool {
+    id.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
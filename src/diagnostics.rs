@@ -0,0 +1,123 @@
+//! bpf-profile diagnostics module.
+//! Implements collecting errors of parsing a trace with their context: the number
+//! of the offending line, its byte offset in the trace and its text, and the format
+//! the trace is parsed in, so a damaged trace is reported at once:
+//! ```text
+//! 2 error(s) in the trace parsed in the legacy format, e.g. `1 [0, 0, ...] 29: exit`:
+//!         line 12, byte 704: Cannot parse trace instruction 'garbage' at line 12
+//!             | garbage
+//! ```
+
+use crate::config::{MAX_DIAGNOSTIC_TEXT, PADDING};
+use crate::error::Error;
+use std::fmt;
+
+/// Represents an error of parsing the line of the trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Number of the line, counting from one.
+    pub line: usize,
+    /// Offset of the start of the line in bytes.
+    pub offset: u64,
+    /// Text of the line, shortened if long.
+    pub text: String,
+    /// Message of the error.
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates the diagnostic of the error at the line of the trace.
+    pub fn new(line: usize, offset: u64, text: &str, error: &Error) -> Self {
+        let text = text.trim_end();
+        let text = match text.char_indices().nth(MAX_DIAGNOSTIC_TEXT) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.into(),
+        };
+        Diagnostic {
+            line,
+            offset,
+            text,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, byte {}: {}",
+            self.line, self.offset, self.message
+        )
+    }
+}
+
+/// Collects errors of parsing the trace up to the limit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    limit: usize,
+    errors: Vec<Diagnostic>,
+    dialect: &'static str,
+}
+
+impl Diagnostics {
+    /// Creates the collection of at most the number of errors, unlimited if zero.
+    pub fn new(limit: usize) -> Self {
+        Diagnostics {
+            limit,
+            ..Diagnostics::default()
+        }
+    }
+
+    /// Adds the error of the trace parsed in the format described by the dialect.
+    /// Returns false if the limit of errors is reached, so parsing stops.
+    pub fn add(&mut self, diagnostic: Diagnostic, dialect: &'static str) -> bool {
+        self.errors.push(diagnostic);
+        self.dialect = dialect;
+        self.limit == 0 || self.errors.len() < self.limit
+    }
+
+    /// Returns the collected errors in the order of the trace.
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    /// Checks if no errors are collected.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Checks if parsing stopped at the limit of errors.
+    pub fn is_limit_reached(&self) -> bool {
+        self.limit != 0 && self.errors.len() >= self.limit
+    }
+
+    /// Moves the collected errors into the error summarizing them, keeping the limit.
+    pub fn take(&mut self) -> Error {
+        let diagnostics = Diagnostics {
+            limit: self.limit,
+            errors: std::mem::take(&mut self.errors),
+            dialect: self.dialect,
+        };
+        Error::TraceErrors(Box::new(diagnostics))
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} error(s) in the trace parsed in {}",
+            self.errors.len(),
+            self.dialect
+        )?;
+        if self.is_limit_reached() && self.limit > 1 {
+            write!(f, ", stopped at the limit")?;
+        }
+        write!(f, ":")?;
+        for error in &self.errors {
+            write!(f, "\n{}{}\n{}    | {}", PADDING, error, PADDING, error.text)?;
+        }
+        Ok(())
+    }
+}
@@ -1,36 +1,192 @@
 //! bpf-profile error module.
 
+use crate::diagnostics::Diagnostics;
 use std::io;
 use std::path::PathBuf;
 
 /// Represents errors of the converter.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    /// Unsupported name of the input file.
     #[error("Unsupported file name '{0}'")]
     Filename(PathBuf),
+    /// Path is not a directory.
+    #[error("Not a directory '{0}'")]
+    NotDirectory(PathBuf),
 
+    /// File cannot be opened.
     #[error("Cannot open file '{1}': {0}")]
     OpenFile(#[source] io::Error, PathBuf),
+    /// File is compressed, but decompression is not built in.
+    #[error("File '{0}' is compressed, build with the 'compression' feature to read it")]
+    Compressed(PathBuf),
+    /// Line of the file cannot be read.
     #[error("Cannot read line '{1}': {0}")]
     ReadLine(#[source] io::Error, String),
+    /// Other input/output error.
     #[error("Input/output error: {0}")]
     Io(#[from] io::Error),
+    /// Remote input cannot be downloaded.
+    #[cfg(feature = "remote")]
+    #[error("Cannot download '{0}': {1}")]
+    Download(String, String),
+    /// Output cannot be uploaded.
+    #[cfg(feature = "remote")]
+    #[error("Cannot upload '{0}': {1}")]
+    Upload(String, String),
+    /// Webhook cannot be notified.
+    #[cfg(feature = "remote")]
+    #[error("Cannot notify '{0}': {1}")]
+    Notify(String, String),
+    /// Remote input is given to a build without the `remote` feature.
+    #[cfg(not(feature = "remote"))]
+    #[error("Support of remote input '{0}' is not enabled in this build")]
+    RemoteDisabled(String),
 
+    /// Config or manifest file cannot be parsed.
+    #[error("Cannot parse config file '{0}': {1}")]
+    ConfigParsing(PathBuf, String),
+    /// Preset is missing in the config file.
+    #[error("Preset '{0}' not found in config file '{1}'")]
+    PresetNotFound(String, PathBuf),
+    /// Unsupported format of the profile.
+    #[error("Unsupported format of profile '{0}'")]
+    UnknownFormat(String),
+    /// Unsupported order of functions of the profile.
+    #[error("Unsupported order of functions '{0}'")]
+    UnknownSort(String),
+    /// Unsupported granularity of costs of the profile.
+    #[error("Unsupported granularity of costs '{0}'")]
+    UnknownGranularity(String),
+    /// Invalid filter of log messages.
+    #[error("Invalid filter of log messages: {0}")]
+    LogFilter(String),
+    /// Plugin failed.
+    #[cfg(feature = "plugins")]
+    #[error("Plugin '{0}' failed: {1}")]
+    Plugin(String, String),
+    /// Unsupported cluster version of the cost model.
+    #[error("Unsupported cluster version '{0}' (supported: {1})")]
+    UnknownClusterVersion(String, String),
+    /// Cost script failed.
+    #[cfg(feature = "scripting")]
+    #[error("Cost script '{0}' failed: {1}")]
+    CostScript(PathBuf, String),
+    /// Flame graph cannot be rendered.
+    #[cfg(feature = "flamegraph")]
+    #[error("Cannot render flame graph: {0}")]
+    Flamegraph(String),
+    /// Profiles of the format cannot be appended.
+    #[error("Cannot append a profile in the format '{0}'")]
+    AppendFormat(String),
+    /// Option is given with several traces.
+    #[error("Option '{0}' supports a single trace only")]
+    SingleTrace(String),
+    /// Conversions of traces of the batch failed.
+    #[error("{0} of {1} traces failed to convert")]
+    BatchFailed(usize, usize),
+    /// No trace files are found in the input directory.
+    #[error("No trace files matching '{1}' in '{0}'")]
+    NoTraces(PathBuf, String),
+    /// Profile cannot be named after the trace, e.g. the standard input.
+    #[error("Cannot name the profile after the trace '{0}'")]
+    OutputName(PathBuf),
+    /// Intermediate profile cannot be written or read.
+    #[error("Intermediate profile '{0}': {1}")]
+    IntermediateProfile(PathBuf, String),
+    /// Option is given with rendering the intermediate profile.
+    #[error("Option '{0}' is not supported by rendering the intermediate profile")]
+    RenderOption(String),
+    /// Option is given with watching the trace.
+    #[error("Option '{0}' is not supported while watching the trace")]
+    WatchOption(String),
+    /// Option is given with costs aggregated by call paths.
+    #[error("Option '{0}' is not supported with costs by call paths")]
+    GranularityOption(String),
+    /// Size of buffers is too small.
+    #[error("Option '{0}' should be at least {1} bytes")]
+    BufferSize(String, usize),
+    /// Call of the function is not found in the bookmarks.
+    #[error("Occurrence {1} of function '{0}' not found in the bookmarks")]
+    BookmarkNotFound(String, usize),
+    /// Function is not found in the profile.
+    #[error("Function '{0}' not found in the profile")]
+    FunctionNotFound(String),
+    /// Costs of functions exceed their budgets.
+    #[error("Costs exceed {0} budget(s)")]
+    BudgetExceeded(usize),
+    /// Costs grew beyond the threshold against the baseline.
+    #[error("Costs regressed against the baseline in {0} case(s)")]
+    CostRegression(usize),
+    /// Percentage cannot be parsed.
+    #[error("Invalid percentage '{0}', expected e.g. '5%'")]
+    Percentage(String),
+    /// Program cannot be run by the embedded virtual machine.
+    #[error("Cannot run the program: {0}")]
+    Program(String),
+    /// Range of lines of the trace cannot be parsed.
+    #[error("Invalid range of lines '{0}', expected e.g. '1000:2000'")]
+    LineRange(String),
+    /// Trace has structural issues.
+    #[error("Found {0} issue(s) in the trace")]
+    TraceIssues(usize),
+    /// Query cannot be parsed.
+    #[error("Invalid query '{0}', type 'help' for the list of queries")]
+    Query(String),
+    /// Existing output cannot be appended to.
+    #[error(
+        "Cannot append to '{0}': not a callgrind file of the same kind produced by bpf-profile"
+    )]
+    IncompatibleOutput(PathBuf),
+
+    /// Invalid regular expression of dump lines.
+    #[error("Invalid pattern of dump lines '{0}': {1}")]
+    DumpPattern(String, String),
+    /// Invalid regular expression of names of functions.
+    #[error("Invalid pattern of function names '{0}': {1}")]
+    FunctionPattern(String, String),
+    /// Dump file lacks the standard header.
     #[error("Unsupported format of dump file: should contain standard header")]
     DumpFormat,
+    /// Dump file lacks disassembly.
     #[error("Dump file without disassembly")]
     DumpFormatNoDisasm,
+    /// Invalid template of names of unresolved functions.
+    #[error("Invalid template of names of unresolved functions '{0}': {1}")]
+    UnresolvedName(String, String),
+    /// Instruction of the dump file cannot be parsed.
     #[error("Cannot parse instruction '{0}' of a function at line '{1}'")]
     DumpParsing(String, usize),
+    /// Instruction of the dump file is at the program counter of another instruction,
+    /// e.g. of overlapping sections.
+    #[error("Instruction {0} at line {1} of the dump overlaps another instruction")]
+    DumpOverlap(usize, usize),
+    /// ELF file cannot be parsed.
+    #[error("Cannot parse ELF file: {0}")]
+    ElfParsing(String),
+
+    /// Parsing of traces is interrupted, the profile gathered so far is written.
+    #[error("Interrupted, the profile is partial")]
+    Interrupted,
 
+    /// Trace file lacks the standard header.
     #[error("Unsupported format of trace file: should contain standard header")]
     TraceFormat,
+    /// Input is skipped.
     #[error("Skipped input")]
     TraceSkipped,
+    /// Instruction of the trace is expected to be a call.
     #[error("Instruction at line {1} is not a call: '{0}'")]
     TraceNotCall(String, usize),
+    /// Instruction of the trace cannot be parsed.
     #[error("Cannot parse trace instruction '{0}' at line {1}")]
     TraceParsing(String, usize),
+    /// Lines of the trace cannot be parsed, collected up to the limit of errors.
+    #[error("{0}")]
+    TraceErrors(Box<Diagnostics>),
+    /// Number is out of range of addresses or program counters.
+    #[error("Number '{0}' is out of range of addresses or program counters")]
+    OutOfRange(String),
 }
 
 /// Represents results.
@@ -1,34 +1,223 @@
 //! bpf-profile file buffered utilities module.
 
+use crate::compact;
+use crate::config::STDIN;
 use crate::error::{Error, Result};
+use crate::global;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-/// Opens a file for buffered reading.
-pub fn open(filepath: &Path) -> Result<impl BufRead> {
-    let file = File::open(&filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
-    Ok(BufReader::new(file))
+/// Opens a file for buffered reading; the path `-` means the standard input.
+/// Trace files in the compact binary format are expanded transparently.
+pub fn open(filepath: &Path) -> Result<Box<dyn BufRead>> {
+    if is_stdin(filepath) {
+        return expand(io::stdin().lock(), filepath);
+    }
+    let file = File::open(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    expand(buffered(file), filepath)
+}
+
+/// Wraps the input into the reader buffered by the size set globally.
+fn buffered<R: io::Read>(inner: R) -> BufReader<R> {
+    BufReader::with_capacity(global::read_buffer(), inner)
+}
+
+/// Returns the size of the file if it is read as is: neither the standard input
+/// nor compressed nor in the compact binary format.
+pub fn plain_size(filepath: &Path) -> Option<u64> {
+    if is_stdin(filepath) {
+        return None;
+    }
+    let mut file = File::open(filepath).ok()?;
+    let mut head = [0; 8];
+    let n = io::Read::read(&mut file, &mut head).ok()?;
+    let head = &head[..n];
+    if head.starts_with(GZIP_MAGIC)
+        || head.starts_with(ZSTD_MAGIC)
+        || head.starts_with(compact::MAGIC)
+    {
+        return None;
+    }
+    file.metadata().ok().map(|m| m.len())
+}
+
+/// Checks if the path means the standard input.
+pub fn is_stdin(filepath: &Path) -> bool {
+    filepath == Path::new(STDIN)
+}
+
+/// Decompresses the input compressed by gzip or zstd, detected by magic bytes,
+/// and expands it if it is in the compact binary format.
+fn expand(mut reader: impl BufRead + 'static, filepath: &Path) -> Result<Box<dyn BufRead>> {
+    let head = reader
+        .fill_buf()
+        .map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    if head.starts_with(GZIP_MAGIC) || head.starts_with(ZSTD_MAGIC) {
+        let gzip = head.starts_with(GZIP_MAGIC);
+        return expand(decompress(reader, gzip, filepath)?, filepath);
+    }
+    let is_compact = head.starts_with(compact::MAGIC);
+    if is_compact {
+        let expander =
+            compact::Expander::new(reader).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+        return Ok(Box::new(buffered(expander)));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Magic bytes starting gzip files.
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+/// Magic bytes starting zstd frames.
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+
+/// Returns the reader of the input decompressed by gzip or zstd.
+#[cfg(feature = "compression")]
+fn decompress(
+    reader: impl BufRead + 'static,
+    gzip: bool,
+    filepath: &Path,
+) -> Result<Box<dyn BufRead>> {
+    if gzip {
+        // Members of concatenated gzip files are read one after another
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        return Ok(Box::new(buffered(decoder)));
+    }
+    let decoder = ruzstd::decoding::StreamingDecoder::new(reader).map_err(|e| {
+        let error = io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+        Error::OpenFile(error, filepath.into())
+    })?;
+    Ok(Box::new(buffered(decoder)))
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_: impl BufRead + 'static, _: bool, filepath: &Path) -> Result<Box<dyn BufRead>> {
+    Err(Error::Compressed(filepath.into()))
 }
 
-/// Opens a file for buffered writing; rewrites existing.
-pub fn open_w(filepath: &Path) -> Result<impl Write> {
-    if filepath.exists() {
-        fs::remove_file(filepath)?;
+/// Represents a file being written atomically.
+/// The contents go to a temporary file in the destination directory,
+/// which replaces the destination file on commit. The temporary file
+/// is removed if the output is dropped without commit.
+pub struct Output {
+    writer: Option<BufWriter<File>>,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl Output {
+    /// Finishes writing and replaces the destination file with the written one.
+    pub fn commit(mut self) -> Result<()> {
+        let writer = self.writer.take().expect("Output already committed");
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&self.temp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        self.writer.as_mut().expect("Output already committed")
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
     }
+}
+
+/// Opens a file for buffered atomic writing; the existing file
+/// is replaced only on commit of the output.
+pub fn open_w(filepath: &Path) -> Result<Output> {
+    let filename = filepath
+        .file_name()
+        .ok_or_else(|| Error::Filename(filepath.into()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(filename);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = filepath.with_file_name(temp_name);
+
     let file = OpenOptions::new()
         .write(true)
         .create(true)
-        .open(&filepath)
+        .truncate(true)
+        .open(&temp_path)
         .map_err(|e| Error::OpenFile(e, filepath.into()))?;
-    Ok(BufWriter::new(file))
+    Ok(Output {
+        writer: Some(BufWriter::with_capacity(global::write_buffer(), file)),
+        temp_path,
+        path: filepath.into(),
+    })
 }
 
-/// Reads all bytes until a newline (the `0xA` byte) is reached,
-/// and puts them to the provided buffer replacing the buffer's contents.
+/// Byte order mark of UTF-8, which some editors put at the start of files.
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Reads all bytes until the end of the line and puts them to the provided buffer
+/// replacing the buffer's contents. Windows (CRLF) and old Mac (lone CR) line endings
+/// are replaced with the newline, and the UTF-8 byte order mark is removed,
+/// so inputs copied between systems are parsed the same way.
+/// Returns the number of bytes read, 0 at the end of the input.
 pub fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize> {
-    line.clear();
-    reader
-        .read_line(line)
-        .map_err(|e| Error::ReadLine(e, line.clone()))
+    let mut bytes = std::mem::take(line).into_bytes();
+    bytes.clear();
+    let bytes_read = read_until_eol(reader, &mut bytes)
+        .map_err(|e| Error::ReadLine(e, String::from_utf8_lossy(&bytes).into()))?;
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+    *line = String::from_utf8(bytes).map_err(|e| {
+        let text = String::from_utf8_lossy(e.as_bytes()).into_owned();
+        let error = io::Error::new(io::ErrorKind::InvalidData, e.utf8_error());
+        Error::ReadLine(error, text)
+    })?;
+    Ok(bytes_read)
+}
+
+/// Reads bytes until LF, CRLF or lone CR, which is appended as LF.
+/// Returns the number of bytes read including the line ending.
+fn read_until_eol(reader: &mut impl BufRead, bytes: &mut Vec<u8>) -> io::Result<usize> {
+    let mut bytes_read = 0;
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            return Ok(bytes_read);
+        }
+        let Some(end) = buf.iter().position(|b| *b == b'\n' || *b == b'\r') else {
+            let len = buf.len();
+            bytes.extend_from_slice(buf);
+            reader.consume(len);
+            bytes_read += len;
+            continue;
+        };
+        let cr = buf[end] == b'\r';
+        bytes.extend_from_slice(&buf[..end]);
+        bytes.push(b'\n');
+        reader.consume(end + 1);
+        bytes_read += end + 1;
+        // LF following CR belongs to the same line ending,
+        // even if it is in the next chunk of the buffer
+        if cr && reader.fill_buf()?.first() == Some(&b'\n') {
+            reader.consume(1);
+            bytes_read += 1;
+        }
+        return Ok(bytes_read);
+    }
 }
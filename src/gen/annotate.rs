@@ -0,0 +1,124 @@
+//! bpf-profile-generate annotate module.
+//! Implements the disassembly of executed functions annotated with numbers of executions
+//! and costs of every instruction, like `perf annotate`. Instructions are taken from
+//! the dump file if available, otherwise from the trace.
+
+use super::profile::{FrameId, Functions};
+use crate::bpf::Instruction;
+use crate::config::{Cost, Map, ProgramCounter, PADDING};
+use crate::error::Result;
+use crate::resolver::{self, Resolver};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Executions of instructions by program counters.
+pub type Executions = Map<ProgramCounter, Execution>;
+
+/// Represents executions of the instruction at a program counter.
+#[derive(Clone, Debug)]
+pub struct Execution {
+    text: String,
+    executed: usize,
+}
+
+/// Counts the execution of the instruction.
+pub fn add(executions: &mut Executions, ix: &Instruction) {
+    executions
+        .entry(ix.pc())
+        .or_insert_with(|| Execution {
+            text: ix.text(),
+            executed: 0,
+        })
+        .executed += 1;
+}
+
+/// Writes the instructions of every executed function, the most costly first,
+/// with their numbers of executions, costs and percentages of the cost of the function.
+/// If the dump is available, not executed instructions of the functions are listed too.
+/// Only the function of the name is written if given.
+pub fn write(
+    mut output: impl Write,
+    executions: &Executions,
+    (functions, total_cost): (&Functions, Cost),
+    resolver: &Resolver,
+    function: Option<&str>,
+) -> Result<()> {
+    let sizes = resolver
+        .dump_functions()
+        .into_iter()
+        .map(|(pc, _, size)| (pc, size))
+        .collect::<Map<_, _>>();
+    let first_pcs = resolver
+        .traced_functions()
+        .into_iter()
+        .collect::<Map<_, _>>();
+
+    let mut annotated = functions
+        .iter()
+        .filter(|(_, f)| !f.costs().is_empty())
+        .filter(|(_, f)| function.is_none_or(|name| resolver::is_named(&f.name(), name)))
+        .map(|(id, f)| {
+            let mut pcs = f.costs().keys().copied().collect::<BTreeSet<_>>();
+            if let FrameId::Addr(address) = id {
+                if let Some(first_pc) = first_pcs.get(address) {
+                    let size = sizes.get(first_pc).copied().unwrap_or(0);
+                    pcs.extend(*first_pc..first_pc + size);
+                }
+            }
+            (f.cost(), f.name(), f.costs(), pcs)
+        })
+        .collect::<Vec<_>>();
+    annotated.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    for (i, (cost, name, costs, pcs)) in annotated.into_iter().enumerate() {
+        if i > 0 {
+            writeln!(output)?;
+        }
+        writeln!(
+            output,
+            "{}: cost {} ({:.2}% of total)",
+            name,
+            cost,
+            percent(cost, total_cost)
+        )?;
+        writeln!(
+            output,
+            "{:>10} {:>10} {:>8}  instruction",
+            "executed", "cost", "%"
+        )?;
+        for pc in pcs {
+            let execution = executions.get(&pc);
+            let executed = execution.map_or(0, |e| e.executed);
+            let pc_cost = costs.get(&pc).copied().unwrap_or(0);
+            let text = match resolver.pretty_instruction(pc) {
+                Some(pretty) => pretty.to_string(),
+                None => format!(
+                    "{}:{}{}",
+                    pc,
+                    PADDING,
+                    execution.map_or("", |e| e.text.as_str())
+                ),
+            };
+            writeln!(
+                output,
+                "{:>10} {:>10} {:>7.2}%  {}",
+                executed,
+                pc_cost,
+                percent(pc_cost, cost),
+                text
+            )?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Returns the percentage of the part of the whole, zero if the whole is zero.
+fn percent(part: Cost, whole: Cost) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        100.0 * part as f64 / whole as f64
+    }
+}
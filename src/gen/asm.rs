@@ -24,6 +24,11 @@ impl Source {
         }
     }
 
+    /// Returns the path of the listing.
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
     /// Adds new instruction to the listing.
     pub fn add_instruction(&mut self, ix: &Instruction) {
         let index = ix.pc() - 1;
@@ -40,18 +45,25 @@ impl Source {
         }
     }
 
+    /// Adds instructions of the other listing, e.g. of another chunk of the trace.
+    pub fn absorb(&mut self, other: Source) {
+        for ix in other.ixs.iter().filter(|ix| !ix.is_empty()) {
+            self.add_instruction(ix);
+        }
+    }
+
     /// Writes all lines of the listing to a file.
     pub fn write(&self, resv: &Resolver) -> Result<()> {
         if global::verbose() {
             tracing::info!("Writing assembly file...")
         }
-        let output = filebuf::open_w(&self.output_path)?;
-        if resv.is_default() {
-            write_assembly_from_trace(output, &self.ixs, resv)?;
+        let mut output = filebuf::open_w(&self.output_path)?;
+        if !resv.has_disassembly() {
+            write_assembly_from_trace(&mut output, &self.ixs, resv)?;
         } else {
-            resv.write_pretty_source(output)?;
+            resv.write_pretty_source(&mut output)?;
         }
-        Ok(())
+        output.commit()
     }
 }
 
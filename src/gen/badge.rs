@@ -0,0 +1,136 @@
+//! bpf-profile-generate badge module.
+//! Implements the small summary of the profile for dashboards and READMEs
+//! of programs: total cost and quality of resolution of functions from the dump,
+//! as JSON or as an SVG badge.
+
+use super::profile::Functions;
+use super::stats;
+use crate::config::{Cost, TRANSACTION_COMPUTE_LIMIT};
+use crate::error::Result;
+use crate::global;
+use crate::resolver::Resolver;
+use serde_json::json;
+use std::io::Write;
+
+/// Share of resolved functions in percent from which the badge is green.
+const GOOD_RESOLUTION: f64 = 90.0;
+/// Share of resolved functions in percent from which the badge is yellow.
+const FAIR_RESOLUTION: f64 = 50.0;
+/// Approximate width of a character of the badge text in pixels.
+const CHAR_WIDTH: usize = 7;
+/// Horizontal padding of each half of the badge in pixels.
+const PADDING: usize = 10;
+
+/// Represents the summary shown by the badge.
+struct Summary {
+    total_cost: Cost,
+    functions: usize,
+    /// Number of functions resolved from the dump, if the dump is given.
+    resolved: Option<usize>,
+}
+
+impl Summary {
+    /// Summarizes the functions of the profile resolved by the resolver.
+    fn new(functions: &Functions, total_cost: Cost, resolver: &Resolver) -> Self {
+        let (resolved, unresolved) = stats::resolution(functions, resolver);
+        Summary {
+            total_cost,
+            functions: resolved + unresolved.len(),
+            resolved: (!resolver.is_default()).then_some(resolved),
+        }
+    }
+
+    /// Returns the share of resolved functions in percent, if the dump is given;
+    /// a trace without functions is fully resolved.
+    fn resolution(&self) -> Option<f64> {
+        self.resolved.map(|resolved| match self.functions {
+            0 => 100.0,
+            n => 100.0 * resolved as f64 / n as f64,
+        })
+    }
+}
+
+/// Writes the summary as JSON.
+pub fn write_json(
+    mut output: impl Write,
+    functions: &Functions,
+    total_cost: Cost,
+    resolver: &Resolver,
+) -> Result<()> {
+    let summary = Summary::new(functions, total_cost, resolver);
+    let badge = json!({
+        "total_cost": summary.total_cost,
+        "exceeds_compute_limit": summary.total_cost > TRANSACTION_COMPUTE_LIMIT,
+        "functions": summary.functions,
+        "resolved": summary.resolved,
+        "resolution": summary.resolution().map(|r| (r * 10.0).round() / 10.0),
+    });
+    serde_json::to_writer_pretty(&mut output, &badge).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the summary as a flat SVG badge: the label on the left, the total cost
+/// and the resolution on the right, colored by the resolution (red beyond the limit).
+pub fn write_svg(
+    mut output: impl Write,
+    functions: &Functions,
+    total_cost: Cost,
+    resolver: &Resolver,
+) -> Result<()> {
+    let summary = Summary::new(functions, total_cost, resolver);
+    let label = "bpf-profile";
+    let mut message = global::short(summary.total_cost);
+    if let Some(resolution) = summary.resolution() {
+        message += &format!(" | {:.0}% resolved", resolution);
+    }
+    let color = match summary.resolution() {
+        _ if summary.total_cost > TRANSACTION_COMPUTE_LIMIT => "#e05d44",
+        Some(r) if r < FAIR_RESOLUTION => "#e05d44",
+        Some(r) if r < GOOD_RESOLUTION => "#dfb317",
+        _ => "#4c1",
+    };
+    let label_width = label.len() * CHAR_WIDTH + PADDING;
+    let message_width = message.len() * CHAR_WIDTH + PADDING;
+    let width = label_width + message_width;
+
+    writeln!(
+        output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"20\" \
+         role=\"img\" aria-label=\"{}: {}\">",
+        width, label, message
+    )?;
+    writeln!(output, "  <title>{}: {}</title>", label, message)?;
+    writeln!(
+        output,
+        "  <rect width=\"{}\" height=\"20\" fill=\"#555\"/>",
+        label_width
+    )?;
+    writeln!(
+        output,
+        "  <rect x=\"{}\" width=\"{}\" height=\"20\" fill=\"{}\"/>",
+        label_width, message_width, color
+    )?;
+    writeln!(
+        output,
+        "  <g fill=\"#fff\" text-anchor=\"middle\" \
+         font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">"
+    )?;
+    writeln!(
+        output,
+        "    <text x=\"{}\" y=\"14\">{}</text>",
+        label_width / 2,
+        label
+    )?;
+    writeln!(
+        output,
+        "    <text x=\"{}\" y=\"14\">{}</text>",
+        label_width + message_width / 2,
+        message
+    )?;
+    writeln!(output, "  </g>")?;
+    writeln!(output, "</svg>")?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! bpf-profile-generate batch module.
+//! Implements the conversion of a batch of traces, each into its own profile,
+//! by several threads if enabled, followed by the summary of the conversions:
+//! ```text
+//! trace           profile                                size      time  status
+//! traces/a.trace  profiles/a.callgrind.out              2148     0.02s  ok
+//! traces/b.trace  profiles/b.callgrind.out                 -     0.00s  Unsupported format of trace file: ...
+//! ```
+//! A failed conversion does not stop the others.
+
+use super::budget;
+use crate::error::{Error, Result};
+use crate::global;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Represents the finished conversion of a trace of the batch.
+struct Conversion<'a> {
+    trace_path: &'a Path,
+    output_path: &'a Path,
+    elapsed: Duration,
+    error: Option<String>,
+}
+
+/// Returns files of the directory with names matching the pattern with wildcards
+/// `*` and `?`, e.g. `*.trace`, in order of their paths.
+pub fn discover(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Err(Error::NotDirectory(dir.into()));
+    }
+    let pattern = budget::wildcard_to_regex(pattern);
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matches = path
+            .file_name()
+            .is_some_and(|name| pattern.is_match(&name.to_string_lossy()));
+        if matches && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Converts every trace by the function into the profile at the path given for it,
+/// by the number of threads, and writes the summary of the conversions in order
+/// of the traces. Returns the number of failed conversions.
+pub fn run(
+    traces: &[(&Path, PathBuf)],
+    jobs: usize,
+    convert: impl Fn(&Path, &Path) -> Result<()> + Sync,
+    mut output: impl Write,
+) -> Result<usize> {
+    if jobs > 1 {
+        tracing::info!("Converting {} traces by {} threads...", traces.len(), jobs);
+    }
+    let mut conversions = thread::scope(|scope| {
+        let (trace_sender, trace_receiver) = mpsc::channel::<usize>();
+        let trace_receiver = Arc::new(Mutex::new(trace_receiver));
+        let (conversion_sender, conversion_receiver) = mpsc::channel::<(usize, Conversion)>();
+        for _ in 0..jobs.clamp(1, traces.len().max(1)) {
+            let trace_receiver = Arc::clone(&trace_receiver);
+            let conversion_sender = conversion_sender.clone();
+            let convert = &convert;
+            scope.spawn(move || loop {
+                let next = trace_receiver.lock().expect("Traces lock poisoned").recv();
+                let Ok(i) = next else {
+                    break;
+                };
+                if global::interrupted() {
+                    break;
+                }
+                let (trace_path, output_path) = &traces[i];
+                let start = Instant::now();
+                let result = convert(trace_path, output_path);
+                if let Err(e) = &result {
+                    tracing::error!("Cannot convert {:?}: {:#}", trace_path, e);
+                }
+                let conversion = Conversion {
+                    trace_path,
+                    output_path,
+                    elapsed: start.elapsed(),
+                    error: result.err().map(|e| e.to_string()),
+                };
+                if conversion_sender.send((i, conversion)).is_err() {
+                    break;
+                }
+            });
+        }
+        for i in 0..traces.len() {
+            trace_sender.send(i).expect("Batch threads finished early");
+        }
+        drop(trace_sender);
+        drop(conversion_sender);
+        conversion_receiver.iter().collect::<Vec<_>>()
+    });
+    conversions.sort_by_key(|(i, _)| *i);
+
+    let width = |column: &str, paths: &mut dyn Iterator<Item = &Path>| {
+        paths
+            .map(|path| path.display().to_string().len())
+            .fold(column.len(), usize::max)
+    };
+    let trace_width = width("trace", &mut conversions.iter().map(|(_, c)| c.trace_path));
+    let output_width = width(
+        "profile",
+        &mut conversions.iter().map(|(_, c)| c.output_path),
+    );
+    writeln!(
+        output,
+        "{:tw$}  {:ow$}  {:>10}  {:>8}  status",
+        "trace",
+        "profile",
+        "size",
+        "time",
+        tw = trace_width,
+        ow = output_width
+    )?;
+    let mut failed = traces.len() - conversions.len();
+    for (_, c) in &conversions {
+        let size = match (&c.error, fs::metadata(c.output_path)) {
+            (None, Ok(meta)) => global::number(meta.len() as usize),
+            _ => "-".into(),
+        };
+        writeln!(
+            output,
+            "{:tw$}  {:ow$}  {:>10}  {:>7.2}s  {}",
+            c.trace_path.display(),
+            c.output_path.display(),
+            size,
+            c.elapsed.as_secs_f64(),
+            c.error.as_deref().unwrap_or("ok"),
+            tw = trace_width,
+            ow = output_width
+        )?;
+        failed += usize::from(c.error.is_some());
+    }
+    output.flush()?;
+    Ok(failed)
+}
@@ -0,0 +1,136 @@
+//! bpf-profile-generate bookmarks module.
+//! Implements the index of calls of functions in the trace as JSON lines, one per call:
+//! `{"function":"function_1 (0x200)","occurrence":1,"start":444,"end":821,"first_line":11,"last_line":18,"cost":8}`.
+//! Byte offsets `start` and `end` delimit the lines of the call in the trace,
+//! from the first instruction of the function to its exit.
+
+use super::profile::FrameId;
+use crate::config::{Cost, Map};
+use crate::error::{Error, Result};
+use crate::filebuf;
+use crate::resolver;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+/// Represents a finished call of a function in the trace.
+#[derive(Deserialize, Serialize)]
+struct Bookmark {
+    function: String,
+    occurrence: usize,
+    start: u64,
+    end: u64,
+    first_line: usize,
+    last_line: usize,
+    cost: Cost,
+}
+
+/// Represents the beginning of an unfinished call.
+#[derive(Debug)]
+struct Open {
+    occurrence: usize,
+    start: u64,
+    first_line: usize,
+}
+
+/// Represents the file of the index of calls.
+/// Errors of writing are reported when the index is committed.
+pub struct Bookmarks {
+    output: filebuf::Output,
+    open: Vec<Open>,
+    occurrences: Map<FrameId, usize>,
+    error: Option<io::Error>,
+}
+
+impl fmt::Debug for Bookmarks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bookmarks")
+            .field("open", &self.open)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Bookmarks {
+    /// Creates the file of the index.
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Bookmarks {
+            output: filebuf::open_w(path)?,
+            open: Vec::new(),
+            occurrences: Map::new(),
+            error: None,
+        })
+    }
+
+    /// Begins the call of the function at the line of its first instruction
+    /// starting at the byte offset. Calls of a function are numbered from one
+    /// in order of their beginnings.
+    pub fn enter(&mut self, id: FrameId, start: u64, first_line: usize) {
+        let occurrence = self.occurrences.entry(id).or_insert(0);
+        *occurrence += 1;
+        self.open.push(Open {
+            occurrence: *occurrence,
+            start,
+            first_line,
+        });
+    }
+
+    /// Finishes the innermost call, of the function of the name, at the line
+    /// of its exit ending at the byte offset, and writes it to the index.
+    pub fn exit(&mut self, function: &str, end: u64, last_line: usize, cost: Cost) {
+        let open = self.open.pop().expect("Exit without call in bookmarks");
+        let bookmark = Bookmark {
+            function: function.into(),
+            occurrence: open.occurrence,
+            start: open.start,
+            end,
+            first_line: open.first_line,
+            last_line,
+            cost,
+        };
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.output, &bookmark)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(self.output));
+        self.error = written.err();
+    }
+
+    /// Finishes writing the index.
+    pub fn commit(self) -> Result<()> {
+        if let Some(e) = self.error {
+            return Err(e.into());
+        }
+        self.output.commit()
+    }
+}
+
+/// Finds the call of the function by its occurrence in the index and writes
+/// the lines of the call from the trace.
+pub fn show(
+    mut trace: impl BufRead,
+    bookmarks: impl BufRead,
+    function: &str,
+    occurrence: usize,
+    mut output: impl Write,
+) -> Result<()> {
+    let mut found = None;
+    for line in bookmarks.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let bookmark = serde_json::from_str::<Bookmark>(&line).map_err(io::Error::from)?;
+        if resolver::is_named(&bookmark.function, function) && bookmark.occurrence == occurrence {
+            found = Some(bookmark);
+            break;
+        }
+    }
+    let bookmark = found.ok_or_else(|| Error::BookmarkNotFound(function.into(), occurrence))?;
+
+    io::copy(&mut trace.by_ref().take(bookmark.start), &mut io::sink())?;
+    io::copy(&mut trace.take(bookmark.end - bookmark.start), &mut output)?;
+    output.flush()?;
+    Ok(())
+}
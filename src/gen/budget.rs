@@ -0,0 +1,255 @@
+//! bpf-profile-generate budget module.
+//! Implements checking costs of functions against the budgets of the manifest file:
+//! ```toml
+//! [[budget]]
+//! function = "process_instruction*"
+//! self = 10000
+//! inclusive = 200000
+//! ```
+//! Patterns of function names may contain wildcards `*` and `?`.
+//! Violations are reported as text, as SARIF for code scanning tools or as a JUnit
+//! XML report of a test case per budgeted function for CI systems.
+
+use super::junit;
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map, ProgramCounter};
+use crate::error::{Error, Result};
+use crate::global;
+use crate::resolver::Resolver;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Represents maximum allowed costs of functions matching the pattern.
+#[derive(Debug, Deserialize)]
+pub struct Budget {
+    /// Pattern of names of functions.
+    pub function: String,
+    /// Maximum immediate cost of each function.
+    #[serde(rename = "self")]
+    pub self_cost: Option<Cost>,
+    /// Maximum inclusive cost of each function.
+    pub inclusive: Option<Cost>,
+}
+
+/// Represents the manifest file.
+#[derive(Debug, Deserialize)]
+struct File {
+    #[serde(default)]
+    budget: Vec<Budget>,
+}
+
+/// Reads budgets from the manifest file.
+pub fn read(path: &Path) -> Result<Vec<Budget>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::OpenFile(e, path.into()))?;
+    let file: File =
+        toml::from_str(&text).map_err(|e| Error::ConfigParsing(path.into(), e.to_string()))?;
+    Ok(file.budget)
+}
+
+/// Represents a cost of a function exceeding its budget.
+#[derive(Debug, Serialize)]
+pub struct Violation<'a> {
+    /// Name of the function.
+    pub function: String,
+    /// Kind of the cost: "self" or "inclusive".
+    pub kind: &'static str,
+    /// Cost of the function.
+    pub actual: Cost,
+    /// Budget of the cost.
+    pub allowed: Cost,
+    /// Pattern of the budget.
+    pub pattern: &'a str,
+    /// First executed program counter of the function, if any.
+    pub pc: Option<ProgramCounter>,
+}
+
+impl Violation<'_> {
+    /// Returns the message describing the violation.
+    pub fn message(&self) -> String {
+        format!(
+            "{}: {} cost {} exceeds budget {} of '{}'",
+            self.function,
+            self.kind,
+            global::number(self.actual),
+            global::number(self.allowed),
+            self.pattern
+        )
+    }
+}
+
+/// Evaluates every budget against the functions.
+pub fn violations<'a>(
+    functions: &Functions,
+    total_cost: Cost,
+    budgets: &'a [Budget],
+) -> Vec<Violation<'a>> {
+    let inclusive_costs = inclusive_costs(functions, total_cost);
+    let mut names = functions
+        .iter()
+        .map(|(id, f)| {
+            let pc = f.costs().keys().next().copied();
+            (f.name(), f.cost(), inclusive_costs[id], pc)
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut violations = Vec::new();
+    for budget in budgets {
+        let pattern = wildcard_to_regex(&budget.function);
+        for (name, self_cost, inclusive_cost, pc) in names.iter().filter(|n| pattern.is_match(&n.0))
+        {
+            let limits = [
+                ("self", *self_cost, budget.self_cost),
+                ("inclusive", *inclusive_cost, budget.inclusive),
+            ];
+            for (kind, actual, allowed) in limits {
+                if let Some(allowed) = allowed.filter(|a| actual > *a) {
+                    violations.push(Violation {
+                        function: name.clone(),
+                        kind,
+                        actual,
+                        allowed,
+                        pattern: &budget.function,
+                        pc: *pc,
+                    });
+                }
+            }
+        }
+    }
+    violations
+}
+
+/// Writes each violation as a line of text.
+pub fn write(mut output: impl Write, violations: &[Violation]) -> Result<()> {
+    for violation in violations {
+        writeln!(output, "{}", violation.message())?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the violations as a SARIF log. Violations are attached to source lines
+/// of the functions if the dump file contains source locations (`llvm-objdump -l`).
+pub fn write_sarif(
+    mut output: impl Write,
+    violations: &[Violation],
+    resolver: &Resolver,
+) -> Result<()> {
+    let results = violations
+        .iter()
+        .map(|v| {
+            let mut result = json!({
+                "ruleId": format!("budget/{}", v.kind),
+                "level": "error",
+                "message": { "text": v.message() },
+                "locations": [{
+                    "logicalLocations": [{ "name": v.function, "kind": "function" }],
+                }],
+            });
+            if let Some((file, line)) = v.pc.and_then(|pc| resolver.source_location(pc)) {
+                result["locations"][0]["physicalLocation"] = json!({
+                    "artifactLocation": { "uri": file },
+                    "region": { "startLine": line },
+                });
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    let rule = |kind: &str, text: &str| {
+        json!({
+            "id": format!("budget/{}", kind),
+            "shortDescription": { "text": text },
+        })
+    };
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "bpf-profile",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        rule("self", "Immediate cost of the function exceeds its budget"),
+                        rule("inclusive", "Inclusive cost of the function exceeds its budget"),
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut output, &log).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the checks as a JUnit XML report: a test case for every function matching
+/// any budget, failed by each of its violations.
+pub fn write_junit(
+    output: impl Write,
+    functions: &Functions,
+    budgets: &[Budget],
+    violations: &[Violation],
+) -> Result<()> {
+    let patterns = budgets
+        .iter()
+        .map(|b| wildcard_to_regex(&b.function))
+        .collect::<Vec<_>>();
+    let names = functions
+        .values()
+        .map(|f| f.name())
+        .filter(|name| patterns.iter().any(|p| p.is_match(name)))
+        .collect::<BTreeSet<_>>();
+    let cases = names
+        .into_iter()
+        .map(|name| junit::TestCase {
+            failures: violations
+                .iter()
+                .filter(|v| v.function == name)
+                .map(|v| junit::Failure {
+                    kind: format!("budget/{}", v.kind),
+                    message: v.message(),
+                })
+                .collect(),
+            name,
+        })
+        .collect::<Vec<_>>();
+    junit::write(output, "budgets", &cases)
+}
+
+/// Computes inclusive costs of the functions as the sum of costs of their calls.
+/// Recursive calls, direct or mutual, are included in the enclosing calls
+/// of the same function, so they are not counted twice.
+pub(super) fn inclusive_costs(functions: &Functions, total_cost: Cost) -> Map<FrameId, Cost> {
+    let mut costs = functions
+        .keys()
+        .map(|id| (*id, 0))
+        .collect::<Map<FrameId, Cost>>();
+    for call in functions.values().flat_map(|f| f.calls()) {
+        if !call.is_recursive() {
+            *costs.entry(call.id()).or_insert(0) += call.cost();
+        }
+    }
+    costs.insert(FrameId::Root, total_cost);
+    costs
+}
+
+/// Converts the pattern with wildcards `*` and `?` to the regular expression.
+pub(crate) fn wildcard_to_regex(pattern: &str) -> Regex {
+    let parts = pattern
+        .split('*')
+        .map(|part| {
+            part.split('?')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect::<Vec<_>>();
+    Regex::new(&format!("^{}$", parts.join(".*"))).expect("Invalid regex")
+}
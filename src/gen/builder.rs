@@ -0,0 +1,230 @@
+//! bpf-profile-generate builder module.
+//! Implements building the profile from readers rather than files on disk,
+//! for programs embedding the profiler:
+//! ```no_run
+//! # fn main() -> bpf_profile::error::Result<()> {
+//! use bpf_profile::gen::ProfileBuilder;
+//! use bpf_profile::resolver;
+//! use std::io::Cursor;
+//!
+//! let trace = std::fs::read("program.trace")?;
+//! let dump = std::fs::read("program.dump")?;
+//! let profile = ProfileBuilder::new()
+//!     .trace_reader(Cursor::new(trace))
+//!     .resolver(resolver::read_from(Cursor::new(dump))?)
+//!     .build()?;
+//! profile.write_callgrind(std::io::stdout(), "<none>")?;
+//! # Ok(())
+//! # }
+//! ```
+//! Virtual machines may report executed instructions, calls and returns from their
+//! hooks instead, without writing the trace at all:
+//! ```
+//! # fn main() -> bpf_profile::error::Result<()> {
+//! let mut builder = bpf_profile::gen::ProfileBuilder::new();
+//! builder.cost(1, 1)?;            // instruction at pc 1
+//! builder.cost(2, 1)?;            // call instruction at pc 2
+//! builder.call(0x1000, 10)?;      // the callee starts at pc 10
+//! builder.cost(10, 1)?;
+//! builder.cost(11, 1)?;           // exit
+//! builder.ret()?;
+//! let profile = builder.build()?;
+//! assert_eq!(profile.total_cost(), 4);
+//! # Ok(())
+//! # }
+//! ```
+//! Simulators which meter costs per call rather than per instruction record entries
+//! into and exits from functions with the costs in between:
+//! ```
+//! # fn main() -> bpf_profile::error::Result<()> {
+//! let mut recorder = bpf_profile::gen::ProfileBuilder::new().recorder();
+//! recorder.tick(2)?;
+//! recorder.enter(0x1000, 10)?;    // the callee starts at pc 10
+//! recorder.tick(5)?;
+//! recorder.exit()?;
+//! let profile = recorder.build()?;
+//! assert_eq!(profile.total_cost(), 7);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{apply_aliases, parse, Options, Profile};
+use crate::config::{Address, Cost, ProgramCounter};
+use crate::error::Result;
+use crate::resolver::Resolver;
+use crate::syscalls;
+use std::fmt;
+use std::io::BufRead;
+
+/// Represents the builder of the profile of traces read from readers
+/// or of events reported by the embedder.
+#[derive(Default)]
+pub struct ProfileBuilder<'a> {
+    readers: Vec<Box<dyn BufRead + 'a>>,
+    resolver: Option<Resolver>,
+    options: Options,
+    recorded: Option<Profile>,
+    last_pc: ProgramCounter,
+}
+
+impl fmt::Debug for ProfileBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProfileBuilder")
+            .field("readers", &self.readers.len())
+            .field("resolver", &self.resolver)
+            .field("options", &self.options)
+            .field("recorded", &self.recorded.is_some())
+            .finish()
+    }
+}
+
+impl<'a> ProfileBuilder<'a> {
+    /// Creates the builder without traces, with the default resolver and options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the trace in the text format; functions of several traces
+    /// of the same program are merged by addresses.
+    pub fn trace_reader(mut self, reader: impl BufRead + 'a) -> Self {
+        self.readers.push(Box::new(reader));
+        self
+    }
+
+    /// Sets the resolver of names of functions, e.g. from `resolver::read_from`.
+    pub fn resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Sets the options of the profile. Outputs of the options are ignored
+    /// besides the assembly and the events files; `metadata` is ignored as well,
+    /// since there are no input files to describe.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Adds the cost of the instruction executed at the program counter
+    /// to the current function. The resolver and the options are taken
+    /// by the first event, so they must be set before.
+    pub fn cost(&mut self, pc: ProgramCounter, cost: Cost) -> Result<()> {
+        self.recorded()?.record_cost(pc, cost);
+        self.last_pc = pc;
+        Ok(())
+    }
+
+    /// Calls the function at the address from the last instruction given to `cost`;
+    /// the callee starts at the program counter. Calls of syscalls, recognized
+    /// by their addresses, return at once, with the costs of the cost model.
+    pub fn call(&mut self, address: Address, pc: ProgramCounter) -> Result<()> {
+        let caller_pc = self.last_pc;
+        self.recorded()?.record_call(address, caller_pc, pc);
+        Ok(())
+    }
+
+    /// Returns from the current call, after its exit instruction given to `cost`.
+    pub fn ret(&mut self) -> Result<()> {
+        self.recorded()?.record_return();
+        Ok(())
+    }
+
+    /// Turns the builder into the recorder of entries into functions and costs,
+    /// with the resolver and the options of the builder. Traces are parsed
+    /// following the recorded events.
+    pub fn recorder(self) -> ProfileRecorder<'a> {
+        ProfileRecorder {
+            builder: self,
+            positions: Vec::new(),
+        }
+    }
+
+    /// Returns the profile of the events, created at the first one.
+    fn recorded(&mut self) -> Result<&mut Profile> {
+        if self.recorded.is_none() {
+            self.recorded = Some(self.profile()?);
+        }
+        Ok(self
+            .recorded
+            .as_mut()
+            .expect("Profile of events not created"))
+    }
+
+    /// Creates the profile with the resolver and the options.
+    fn profile(&mut self) -> Result<Profile> {
+        let mut resolver = self.resolver.take().unwrap_or_default();
+        apply_aliases(&mut resolver, &self.options)?;
+        Profile::with_options(resolver, &self.options)
+    }
+
+    /// Parses the traces, following the events if any, and returns the profile.
+    /// Calls not returned from by the events are finished.
+    pub fn build(mut self) -> Result<Profile> {
+        let mut prof = match self.recorded.take() {
+            Some(mut prof) => {
+                prof.finish_records();
+                prof
+            }
+            None => self.profile()?,
+        };
+        for reader in self.readers {
+            parse(reader, &mut prof)?;
+        }
+        prof.finish()?;
+        Ok(prof)
+    }
+}
+
+/// Represents the recorder of the profile of calls reported by an embedder
+/// which meters costs of functions rather than of every instruction.
+/// Costs are positioned at the first program counters of the functions,
+/// and calls at the first program counters of their callers.
+#[derive(Debug, Default)]
+pub struct ProfileRecorder<'a> {
+    builder: ProfileBuilder<'a>,
+    /// First program counters of the functions entered and not exited.
+    positions: Vec<ProgramCounter>,
+}
+
+impl<'a> ProfileRecorder<'a> {
+    /// Creates the recorder with the default resolver and options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enters the function at the address starting at the program counter,
+    /// called from the current function. Syscalls, recognized by their addresses,
+    /// return at once with the costs of the cost model, so they are not exited.
+    pub fn enter(&mut self, address: Address, pc: ProgramCounter) -> Result<()> {
+        self.builder.last_pc = self.position();
+        self.builder.call(address, pc)?;
+        if !syscalls::is_syscall(address) {
+            self.positions.push(pc);
+        }
+        Ok(())
+    }
+
+    /// Exits from the current function to its caller.
+    pub fn exit(&mut self) -> Result<()> {
+        self.builder.ret()?;
+        self.positions.pop();
+        Ok(())
+    }
+
+    /// Adds the cost to the current function.
+    pub fn tick(&mut self, cost: Cost) -> Result<()> {
+        let pc = self.position();
+        self.builder.cost(pc, cost)
+    }
+
+    /// Returns the profile of the recorded events.
+    /// Functions not exited from are finished.
+    pub fn build(self) -> Result<Profile> {
+        self.builder.build()
+    }
+
+    /// Returns the first program counter of the current function, 0 at the top level.
+    fn position(&self) -> ProgramCounter {
+        self.positions.last().copied().unwrap_or_default()
+    }
+}
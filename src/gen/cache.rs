@@ -0,0 +1,155 @@
+//! bpf-profile-generate cache module.
+//! Implements the cache of finished conversions: outputs are stored in a directory
+//! named by the hash of the input files and the options, so identical re-runs copy
+//! the outputs instead of parsing the trace again.
+
+use super::metadata::sha256;
+use super::Options;
+use crate::error::{Error, Result};
+use crate::filebuf;
+use crate::resolver;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns the directory of the cache entry of the conversion,
+/// or none if the inputs cannot be hashed (e.g. the standard input).
+pub fn entry(
+    cache_dir: &Path,
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    options: &Options,
+) -> Result<Option<PathBuf>> {
+    let Some(trace_hash) = sha256(trace_path)? else {
+        return Ok(None);
+    };
+    let hash_opt = |path: Option<&Path>| path.map(sha256).transpose().map(Option::flatten);
+
+    // Paths of outputs do not change the contents, except the assembly file
+    // referred by the callgrind profile
+    let mut key = format!(
+        "bpf-profile {}\ntrace {}\ndump {:?}\npc offset {}\naliases {:?}\n",
+        env!("CARGO_PKG_VERSION"),
+        trace_hash,
+        hash_opt(dump_path)?,
+        options.pc_offset,
+        hash_opt(options.aliases.as_deref())?,
+    );
+    if let Some(manifest_path) = &options.dump_manifest {
+        for (program_id, path) in resolver::read_manifest(manifest_path)? {
+            key.push_str(&format!("program {} {:?}\n", program_id, sha256(&path)?));
+        }
+    }
+    key.push_str(&format!(
+        "symbols {:?}\n",
+        hash_opt(options.symbols.as_deref())?
+    ));
+    key.push_str(&format!(
+        "cost model {:?}\n",
+        hash_opt(options.cost_model.as_deref())?
+    ));
+    #[cfg(feature = "scripting")]
+    key.push_str(&format!(
+        "cost script {:?}\n",
+        hash_opt(options.cost_script.as_deref())?
+    ));
+    key.push_str(&format!(
+        "format {}\nasm {:?}\nreports {:?}\nheatmap window {}\ncalls only {}\nroot name {:?}\n\
+         explicit root {}\ncluster version {:?}\nsyscall sizes {}\nflatten nested {}\n\
+         lenient {}\nsource lines {}\ncall lines {}\n\
+         counters {}\nsyscall cost {:?}\ndrop calls below {:?}\nmax call samples {:?}\nignore before {:?}\nonly function {:?}\n\
+         skip instructions {}\nmax instructions {:?}\nrange {:?}\ninclude {:?}\nexclude {:?}\n\
+         metadata {}\nsplit invocations {}\n\
+         threshold {:?}\nmax output size {:?}\ncompress output {}\nsort {}\ngranularity {}\naverage {}\n",
+        options.format,
+        options.asm,
+        outputs(options)
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>(),
+        options.heatmap_window,
+        options.calls_only,
+        options.root_name,
+        options.explicit_root,
+        options.cluster_version,
+        options.syscall_sizes,
+        options.flatten_nested,
+        options.lenient,
+        options.source_lines,
+        options.call_lines,
+        options.counters,
+        options.syscall_cost,
+        options.drop_calls_below,
+        options.max_call_samples,
+        options.ignore_before,
+        options.only_function,
+        options.skip_instructions,
+        options.max_instructions,
+        options.range,
+        options.include,
+        options.exclude,
+        options.metadata,
+        options.split_invocations,
+        options.threshold,
+        options.max_output_size,
+        options.compress_output,
+        options.sort,
+        options.granularity,
+        options.average,
+    ));
+
+    let hash = Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    Ok(Some(cache_dir.join(hash)))
+}
+
+/// Returns names and paths of the files written by the conversion.
+pub fn outputs(options: &Options) -> Vec<(&'static str, &Path)> {
+    [
+        ("profile", &options.output),
+        ("asm", &options.asm),
+        ("metrics", &options.metrics),
+        ("badge", &options.badge),
+        ("stats", &options.stats),
+        ("lcov", &options.lcov),
+        ("heatmap", &options.heatmap),
+        ("events", &options.events),
+        ("bookmarks", &options.bookmarks),
+        ("intermediate", &options.save_intermediate),
+    ]
+    .into_iter()
+    .filter_map(|(name, path)| path.as_deref().map(|p| (name, p)))
+    .collect()
+}
+
+/// Copies the outputs from the cache entry if all of them are cached.
+/// Returns false if the conversion is not cached.
+pub fn restore(entry: &Path, outputs: &[(&str, &Path)]) -> Result<bool> {
+    if !outputs.iter().all(|(name, _)| entry.join(name).is_file()) {
+        return Ok(false);
+    }
+    for (name, path) in outputs {
+        copy(&entry.join(name), path)?;
+    }
+    Ok(true)
+}
+
+/// Stores the outputs of the finished conversion in the cache entry.
+pub fn store(entry: &Path, outputs: &[(&str, &Path)]) -> Result<()> {
+    fs::create_dir_all(entry)?;
+    for (name, path) in outputs {
+        copy(path, &entry.join(name))?;
+    }
+    Ok(())
+}
+
+/// Copies the file atomically, so interrupted copies are never taken as complete.
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    let mut input = File::open(from).map_err(|e| Error::OpenFile(e, from.into()))?;
+    let mut output = filebuf::open_w(to)?;
+    io::copy(&mut input, &mut output)?;
+    output.commit()
+}
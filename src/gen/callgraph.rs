@@ -0,0 +1,225 @@
+//! bpf-profile-generate callgraph module.
+//! Implements the call graph of functions with statistics of every caller→callee edge:
+//! number of calls, total inclusive cost and distribution of costs per call,
+//! written as JSON for post-processing or as a Graphviz DOT graph.
+
+use super::budget;
+use super::profile::{self, CallStats, FrameId, Functions};
+use crate::config::Cost;
+use crate::error::Result;
+use crate::resolver::Resolver;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Font sizes of nodes of the cheapest and of the most expensive functions.
+const MIN_FONT_SIZE: f64 = 10.0;
+const MAX_FONT_SIZE: f64 = 24.0;
+
+/// Widths of edges of the cheapest and of the most expensive calls.
+const MIN_PEN_WIDTH: f64 = 1.0;
+const MAX_PEN_WIDTH: f64 = 5.0;
+
+/// Statistics of calls by callers and callees.
+pub type Edges = BTreeMap<(FrameId, FrameId), CallStats>;
+
+/// Collects statistics of calls of all functions by callers and callees,
+/// regardless of program counters of the calls.
+pub fn edges(functions: &Functions) -> Edges {
+    let mut edges = Edges::new();
+    for (id, f) in functions {
+        for ((_, callee), stats) in profile::call_statistics(f, false) {
+            edges.insert((*id, callee), stats);
+        }
+    }
+    edges
+}
+
+/// Version of the structure of the JSON profile, increased on incompatible changes.
+const JSON_VERSION: u32 = 1;
+
+/// Represents the profile written as JSON.
+#[derive(Serialize)]
+struct JsonProfile<'a> {
+    version: u32,
+    /// Identifiers of the executions marked in the trace.
+    labels: &'a [String],
+    total_cost: Cost,
+    functions: Vec<JsonFunction>,
+    edges: Vec<JsonEdge>,
+    /// Description of how the profile was generated, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<serde_json::Value>,
+}
+
+/// Represents a function of the JSON profile with its costs and numbers of calls.
+#[derive(Serialize)]
+struct JsonFunction {
+    id: String,
+    name: String,
+    /// Origin of the name: "resolved", "unresolved", "syscall" or "synthetic".
+    resolution: &'static str,
+    #[serde(rename = "self")]
+    self_cost: Cost,
+    inclusive: Cost,
+    called: usize,
+    calls: usize,
+}
+
+/// Represents a caller→callee edge of the JSON profile with statistics of calls.
+#[derive(Serialize)]
+struct JsonEdge {
+    caller: String,
+    callee: String,
+    calls: usize,
+    cost: Cost,
+    min: Cost,
+    avg: Cost,
+    /// Median and 95th percentile of costs of calls.
+    p50: Cost,
+    p95: Cost,
+    max: Cost,
+    /// First and last trace lines of the records of calls, if recorded.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    lines: Vec<JsonLines>,
+}
+
+/// Represents the first and the last trace lines of a record of calls
+/// with its inclusive cost.
+#[derive(Serialize)]
+struct JsonLines {
+    first: usize,
+    last: usize,
+    cost: Cost,
+}
+
+/// Returns the origin of the name of the frame.
+fn resolution(id: FrameId, resolver: &Resolver) -> &'static str {
+    match id {
+        _ if id.is_syscall() => "syscall",
+        FrameId::Addr(address) if resolver.is_resolved(address) => "resolved",
+        FrameId::Addr(_) => "unresolved",
+        FrameId::Merged(first_pc) if resolver.is_resolved_pc(first_pc) => "resolved",
+        FrameId::Merged(_) => "unresolved",
+        _ => "synthetic",
+    }
+}
+
+/// Writes the profile as JSON of a stable structure: labels of executions,
+/// the total cost, functions with their costs, numbers of calls in both directions
+/// and origins of names, edges with statistics of calls, trace lines of calls
+/// and the metadata, if any.
+pub fn write_json(
+    mut output: impl Write,
+    functions: &Functions,
+    total_cost: Cost,
+    resolver: &Resolver,
+    labels: &[String],
+    metadata: Option<serde_json::Value>,
+) -> Result<()> {
+    let edges = edges(functions);
+    let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+    let count_calls = |side: fn(&(FrameId, FrameId)) -> FrameId, id: FrameId| {
+        edges
+            .iter()
+            .filter(|(key, _)| side(key) == id)
+            .map(|(_, stats)| stats.calls)
+            .sum::<usize>()
+    };
+    let mut ids = functions.keys().copied().collect::<Vec<_>>();
+    ids.sort();
+    let profile = JsonProfile {
+        version: JSON_VERSION,
+        labels,
+        total_cost,
+        functions: ids
+            .iter()
+            .map(|id| JsonFunction {
+                id: id.to_string(),
+                name: functions[id].name(),
+                resolution: resolution(*id, resolver),
+                self_cost: functions[id].cost(),
+                inclusive: inclusive_costs[id],
+                called: count_calls(|key| key.1, *id),
+                calls: count_calls(|key| key.0, *id),
+            })
+            .collect(),
+        edges: edges
+            .iter()
+            .map(|((caller, callee), stats)| JsonEdge {
+                caller: functions[caller].name(),
+                callee: functions[callee].name(),
+                calls: stats.calls,
+                cost: stats.cost,
+                min: stats.min,
+                avg: stats.average(),
+                p50: stats.percentile(50.0),
+                p95: stats.percentile(95.0),
+                max: stats.max,
+                lines: stats
+                    .lines
+                    .iter()
+                    .map(|(first, last, cost)| JsonLines {
+                        first: *first,
+                        last: *last,
+                        cost: *cost,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        metadata,
+    };
+    serde_json::to_writer_pretty(&mut output, &profile).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the call graph in the DOT language of Graphviz;
+/// nodes are sized by shares of their inclusive costs in the total cost,
+/// edges are labeled by statistics of calls and weighted by their costs.
+pub fn write_dot(mut output: impl Write, functions: &Functions, total_cost: Cost) -> Result<()> {
+    let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+    let mut ids = functions.keys().copied().collect::<Vec<_>>();
+    ids.sort();
+    // Synthetic frames of syscalls share addresses, so nodes are numbered
+    let node = |id: &FrameId| ids.binary_search(id).expect("Unknown frame");
+    let share = |cost: Cost| cost as f64 / total_cost.max(1) as f64;
+
+    writeln!(output, "digraph callgraph {{")?;
+    writeln!(output, "    node [shape=box];")?;
+    for (i, id) in ids.iter().enumerate() {
+        let inclusive = inclusive_costs[id];
+        writeln!(
+            output,
+            "    n{} [label=\"{}\\nself={}\\ninclusive={} ({:.2}%)\", fontsize={:.1}];",
+            i,
+            escape(&functions[id].name()),
+            functions[id].cost(),
+            inclusive,
+            100.0 * share(inclusive),
+            MIN_FONT_SIZE + (MAX_FONT_SIZE - MIN_FONT_SIZE) * share(inclusive)
+        )?;
+    }
+    for ((caller, callee), stats) in edges(functions) {
+        writeln!(
+            output,
+            "    n{} -> n{} [label=\"calls={}\\ncost={}\\nmin={} max={}\", penwidth={:.1}];",
+            node(&caller),
+            node(&callee),
+            stats.calls,
+            stats.cost,
+            stats.min,
+            stats.max,
+            MIN_PEN_WIDTH + (MAX_PEN_WIDTH - MIN_PEN_WIDTH) * share(stats.cost)
+        )?;
+    }
+    writeln!(output, "}}")?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Escapes the text for a quoted string of the DOT language.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
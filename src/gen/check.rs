@@ -0,0 +1,139 @@
+//! bpf-profile-generate check module.
+//! Implements structural issues found in the trace: malformed lines, calls dropped
+//! at the end of the trace, unbalanced calls and exits, and the missing header.
+//! Each issue is shown with the surrounding lines of the trace, as text or as JSON.
+//! Issues losing parts of the trace are errors, the ones the profile accounts
+//! for (e.g. calls left unfinished) are warnings.
+
+use crate::error::Result;
+use crate::filebuf;
+use serde::Serialize;
+use serde_json::json;
+use std::io::{BufRead, Write};
+
+/// Represents a structural issue of the trace.
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    line: usize,
+    severity: &'static str,
+    kind: &'static str,
+    message: String,
+    context: Vec<ContextLine>,
+}
+
+/// Represents a line of the trace around the issue.
+#[derive(Debug, Serialize)]
+struct ContextLine {
+    line: usize,
+    text: String,
+}
+
+impl Finding {
+    /// Creates the issue at the line of the trace, without surrounding lines.
+    pub fn new(line: usize, kind: &'static str, message: String) -> Self {
+        Finding {
+            line,
+            severity: match kind {
+                "unbalanced" | "trace-start" => "warning",
+                _ => "error",
+            },
+            kind,
+            message,
+            context: Vec::new(),
+        }
+    }
+
+    /// Returns the number of the line of the issue.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Checks if the issue is an error rather than a warning.
+    pub fn is_error(&self) -> bool {
+        self.severity == "error"
+    }
+}
+
+/// Attaches the lines of the trace within the distance from each issue,
+/// reading the trace once. The issues should be sorted by lines.
+pub fn add_context(findings: &mut [Finding], mut reader: impl BufRead, lines: usize) -> Result<()> {
+    let last = match findings.last() {
+        Some(f) => f.line + lines,
+        None => return Ok(()),
+    };
+    let mut text = String::new();
+    let mut first = 0;
+    for lc in 1..=last {
+        text.clear();
+        if filebuf::read_line(&mut reader, &mut text)? == 0 {
+            break;
+        }
+        while first < findings.len() && findings[first].line + lines < lc {
+            first += 1;
+        }
+        for f in findings[first..]
+            .iter_mut()
+            .take_while(|f| f.line.saturating_sub(lines) <= lc)
+        {
+            f.context.push(ContextLine {
+                line: lc,
+                text: text.trim_end().into(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Writes the issues as text, marking the line of each issue among its context.
+pub fn write_text(mut output: impl Write, findings: &[Finding]) -> Result<()> {
+    for f in findings {
+        writeln!(output, "line {}: {}: {}", f.line, f.kind, f.message)?;
+        write_context(&mut output, f)?;
+    }
+    writeln!(output, "{} issue(s) found", findings.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the issues as text with their severities, followed by the numbers
+/// of errors and warnings.
+pub fn write_lint(mut output: impl Write, findings: &[Finding]) -> Result<()> {
+    for f in findings {
+        writeln!(
+            output,
+            "line {}: {} [{}]: {}",
+            f.line, f.severity, f.kind, f.message
+        )?;
+        write_context(&mut output, f)?;
+    }
+    let errors = findings.iter().filter(|f| f.is_error()).count();
+    writeln!(
+        output,
+        "{} error(s), {} warning(s)",
+        errors,
+        findings.len() - errors
+    )?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the lines of the trace around the issue, marking the line of the issue.
+fn write_context(mut output: impl Write, finding: &Finding) -> Result<()> {
+    for c in &finding.context {
+        let marker = if c.line == finding.line { '>' } else { ' ' };
+        writeln!(output, "{} {:>8} | {}", marker, c.line, c.text)?;
+    }
+    if !finding.context.is_empty() {
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// Writes the issues as JSON.
+pub fn write_json(mut output: impl Write, findings: &[Finding]) -> Result<()> {
+    let report = json!({ "findings": findings });
+    serde_json::to_writer_pretty(&mut output, &report).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
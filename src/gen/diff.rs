@@ -0,0 +1,324 @@
+//! bpf-profile-generate diff module.
+//! Implements the callgrind profile of differences of two profiles:
+//! costs are new minus old, so growth is positive and improvement is negative.
+//! Functions and calls are matched by names, since addresses change between builds.
+//! Also implements the text table of per-function deltas of self and inclusive costs,
+//! and the table of changes of sizes of functions of two dump files.
+
+use super::budget;
+use super::profile::{FrameId, Function, Functions};
+use crate::config::{Cost, ProgramCounter};
+use crate::error::Result;
+use crate::global;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Represents costs of a function in both profiles.
+#[derive(Default)]
+struct Entry {
+    pc: Option<ProgramCounter>,
+    old: Cost,
+    new: Cost,
+    calls: BTreeMap<String, CallEntry>,
+}
+
+/// Represents calls of a callee from a function in both profiles.
+struct CallEntry {
+    target: ProgramCounter,
+    pc: ProgramCounter,
+    old_calls: usize,
+    new_calls: usize,
+    old_cost: Cost,
+    new_cost: Cost,
+}
+
+/// Writes the differences of the new profile against the old one in the callgrind format.
+pub fn write(
+    mut output: impl Write,
+    old: (&Functions, Cost),
+    new: (&Functions, Cost),
+    asm_fl: &str,
+) -> Result<()> {
+    let mut entries = BTreeMap::<String, Entry>::new();
+    for (functions, is_new) in [(old.0, false), (new.0, true)] {
+        for (id, f) in functions {
+            if *id != FrameId::Root {
+                add(&mut entries, f, functions, is_new);
+            }
+        }
+    }
+
+    // The summary and the totals are the sum of the cost lines, without the immediate
+    // costs of the root
+    let root_cost = |functions: &Functions| functions.get(&FrameId::Root).map_or(0, |f| f.cost());
+    let totals = signed(new.1 - root_cost(new.0), old.1 - root_cost(old.0));
+    writeln!(output, "# callgrind format")?;
+    writeln!(output, "version: 1")?;
+    writeln!(output, "creator: bpf-profile")?;
+    writeln!(output, "positions: line")?;
+    writeln!(output, "events: Instructions")?;
+    writeln!(output, "summary: {}", totals)?;
+    writeln!(output, "totals: {}", totals)?;
+    writeln!(output, "fl={}", asm_fl)?;
+
+    for (name, entry) in &entries {
+        writeln!(output, "\nfn={}", name)?;
+        if let Some(pc) = entry.pc {
+            writeln!(output, "{} {}", pc, signed(entry.new, entry.old))?;
+        }
+        for (callee, call) in &entry.calls {
+            let calls = if call.new_calls > 0 {
+                call.new_calls
+            } else {
+                call.old_calls
+            };
+            writeln!(output, "cfn={}", callee)?;
+            writeln!(output, "calls={} {}", calls, call.target)?;
+            writeln!(
+                output,
+                "{} {}",
+                call.pc,
+                signed(call.new_cost, call.old_cost)
+            )?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the table of self and inclusive costs of functions in both profiles
+/// and their deltas, the largest change of the inclusive cost first.
+/// Costs are divided by numbers of calls of functions if normalized per call,
+/// so changes of costs of each call are told apart from changes of numbers of calls.
+/// Functions with unchanged costs and numbers of calls are skipped.
+pub fn write_table(
+    mut output: impl Write,
+    old: (&Functions, Cost),
+    new: (&Functions, Cost),
+    per_call: bool,
+) -> Result<()> {
+    // Name => [old self, new self, old inclusive, new inclusive, old calls, new calls]
+    let mut rows = BTreeMap::<String, [Cost; 6]>::new();
+    for ((functions, total_cost), column) in [(old, 0), (new, 1)] {
+        let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+        for (id, f) in functions {
+            let row = rows.entry(f.name()).or_default();
+            row[column] += f.cost();
+            row[column + 2] += inclusive_costs[id];
+            for c in f.calls() {
+                rows.entry(functions[&c.id()].name()).or_default()[column + 4] += c.count();
+            }
+        }
+    }
+    let mut rows = rows
+        .into_iter()
+        .filter(|(_, c)| c[0] != c[1] || c[2] != c[3] || c[4] != c[5])
+        .collect::<Vec<_>>();
+
+    if per_call {
+        // The root and functions where the trace starts are entered once
+        let per_call = |c: &[Cost; 6], i: usize| c[i] as f64 / c[4 + i % 2].max(1) as f64;
+        let delta = |c: &[Cost; 6], i: usize| per_call(c, i + 1) - per_call(c, i);
+        rows.sort_by(|a, b| {
+            delta(&b.1, 2)
+                .abs()
+                .total_cmp(&delta(&a.1, 2).abs())
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        writeln!(
+            output,
+            "{:>10} {:>10} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14}  function",
+            "calls old",
+            "calls new",
+            "self/call old",
+            "self/call new",
+            "self delta",
+            "incl/call old",
+            "incl/call new",
+            "incl delta"
+        )?;
+        for (name, c) in rows {
+            writeln!(
+                output,
+                "{:>10} {:>10} {:>14.1} {:>14.1} {:>14} {:>14.1} {:>14.1} {:>14}  {}",
+                global::number(c[4]),
+                global::number(c[5]),
+                per_call(&c, 0),
+                per_call(&c, 1),
+                fractional_delta(delta(&c, 0)),
+                per_call(&c, 2),
+                per_call(&c, 3),
+                fractional_delta(delta(&c, 2)),
+                name
+            )?;
+        }
+    } else {
+        rows.sort_by(|a, b| {
+            let delta = |c: &[Cost; 6]| signed(c[3], c[2]).unsigned_abs();
+            delta(&b.1).cmp(&delta(&a.1)).then_with(|| a.0.cmp(&b.0))
+        });
+        writeln!(
+            output,
+            "{:>12} {:>12} {:>12} {:>12} {:>12} {:>12}  function",
+            "self old", "self new", "self delta", "incl old", "incl new", "incl delta"
+        )?;
+        for (name, c) in rows {
+            writeln!(
+                output,
+                "{:>12} {:>12} {:>12} {:>12} {:>12} {:>12}  {}",
+                global::number(c[0]),
+                global::number(c[1]),
+                delta(c[1], c[0]),
+                global::number(c[2]),
+                global::number(c[3]),
+                delta(c[3], c[2]),
+                name
+            )?;
+        }
+    }
+    writeln!(
+        output,
+        "Total: {} -> {} ({})",
+        global::number(old.1),
+        global::number(new.1),
+        delta(new.1, old.1)
+    )?;
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the table of sizes in instructions of functions of two dump files
+/// and their deltas, the largest change first, to tell regressions caused
+/// by code generation from changes of the executed paths.
+/// Functions are matched by names; functions with unchanged sizes are skipped.
+pub fn write_dump_table(
+    mut output: impl Write,
+    old: &[(ProgramCounter, String, usize)],
+    new: &[(ProgramCounter, String, usize)],
+) -> Result<()> {
+    // Name => [old size, new size], none if the dump lacks the function
+    let mut rows = BTreeMap::<&str, [Option<usize>; 2]>::new();
+    for (functions, column) in [(old, 0), (new, 1)] {
+        for (_, name, size) in functions {
+            *rows.entry(name).or_default()[column].get_or_insert(0) += size;
+        }
+    }
+    let mut rows = rows
+        .into_iter()
+        .filter(|(_, sizes)| sizes[0] != sizes[1])
+        .collect::<Vec<_>>();
+    let size = |s: Option<usize>| s.unwrap_or(0);
+    rows.sort_by(|a, b| {
+        let delta = |s: &[Option<usize>; 2]| signed(size(s[1]), size(s[0])).unsigned_abs();
+        delta(&b.1).cmp(&delta(&a.1)).then_with(|| a.0.cmp(b.0))
+    });
+
+    writeln!(
+        output,
+        "{:>12} {:>12} {:>12}  {:<8}  function",
+        "size old", "size new", "size delta", "change"
+    )?;
+    let mut changes = [0; 3];
+    for (name, sizes) in rows {
+        let (change, i) = match sizes {
+            [None, _] => ("added", 0),
+            [_, None] => ("removed", 1),
+            _ => ("changed", 2),
+        };
+        changes[i] += 1;
+        let column = |s: Option<usize>| s.map_or_else(|| "-".into(), global::number);
+        writeln!(
+            output,
+            "{:>12} {:>12} {:>12}  {:<8}  {}",
+            column(sizes[0]),
+            column(sizes[1]),
+            delta(size(sizes[1]), size(sizes[0])),
+            change,
+            name
+        )?;
+    }
+    let total = |functions: &[(ProgramCounter, String, usize)]| {
+        functions.iter().map(|(_, _, size)| size).sum::<usize>()
+    };
+    writeln!(
+        output,
+        "Total: {} -> {} ({}) instructions, {} functions added, {} removed, {} changed",
+        global::number(total(old)),
+        global::number(total(new)),
+        delta(total(new), total(old)),
+        changes[0],
+        changes[1],
+        changes[2]
+    )?;
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Adds costs and calls of the function of one of the profiles.
+fn add(entries: &mut BTreeMap<String, Entry>, f: &Function, functions: &Functions, is_new: bool) {
+    let entry = entries.entry(f.name()).or_default();
+    if let Some(first_pc) = f.costs().keys().next() {
+        // Positions of the new profile are preferred
+        if is_new || entry.pc.is_none() {
+            entry.pc = Some(*first_pc);
+        }
+    }
+    *select(&mut entry.old, &mut entry.new, is_new) += f.cost();
+
+    for c in f.calls() {
+        let call = entry
+            .calls
+            .entry(functions[&c.id()].name())
+            .or_insert_with(|| CallEntry {
+                target: functions[&c.id()].first_pc(),
+                pc: c.caller_pc(),
+                old_calls: 0,
+                new_calls: 0,
+                old_cost: 0,
+                new_cost: 0,
+            });
+        if is_new && call.new_calls == 0 {
+            call.target = functions[&c.id()].first_pc();
+            call.pc = c.caller_pc();
+        }
+        *select(&mut call.old_calls, &mut call.new_calls, is_new) += c.count();
+        *select(&mut call.old_cost, &mut call.new_cost, is_new) += c.cost();
+    }
+}
+
+/// Returns the value of the new or the old profile.
+fn select<'a, T>(old: &'a mut T, new: &'a mut T, is_new: bool) -> &'a mut T {
+    if is_new {
+        new
+    } else {
+        old
+    }
+}
+
+/// Returns the difference of costs.
+fn signed(new: Cost, old: Cost) -> i64 {
+    new as i64 - old as i64
+}
+
+/// Formats the difference of costs with the sign.
+fn delta(new: Cost, old: Cost) -> String {
+    if new == old {
+        "0".into()
+    } else if new > old {
+        format!("+{}", global::number(new - old))
+    } else {
+        format!("-{}", global::number(old - new))
+    }
+}
+
+/// Formats the difference of costs per call with the sign and one decimal.
+fn fractional_delta(delta: f64) -> String {
+    if delta.abs() < 0.05 {
+        "0".into()
+    } else {
+        format!("{:+.1}", delta)
+    }
+}
@@ -0,0 +1,67 @@
+//! bpf-profile-generate events module.
+//! Implements the stream of executed instructions as JSON lines, one per instruction:
+//! `{"pc":42,"class":"alu","depth":1,"function":"function_0 (0x100)","cost":1}`.
+
+use crate::config::{Cost, ProgramCounter};
+use crate::error::Result;
+use crate::filebuf;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Represents an executed instruction.
+#[derive(Serialize)]
+struct Event<'a> {
+    pc: ProgramCounter,
+    class: &'a str,
+    depth: usize,
+    function: &'a str,
+    cost: Cost,
+}
+
+/// Represents the file of the stream of executed instructions.
+pub struct Events {
+    output: filebuf::Output,
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events").finish_non_exhaustive()
+    }
+}
+
+impl Events {
+    /// Creates the file of the stream.
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Events {
+            output: filebuf::open_w(path)?,
+        })
+    }
+
+    /// Writes the instruction executed in the function at the depth of calls.
+    pub fn add(
+        &mut self,
+        pc: ProgramCounter,
+        class: &str,
+        depth: usize,
+        function: &str,
+        cost: Cost,
+    ) -> Result<()> {
+        let event = Event {
+            pc,
+            class,
+            depth,
+            function,
+            cost,
+        };
+        serde_json::to_writer(&mut self.output, &event).map_err(io::Error::from)?;
+        writeln!(self.output)?;
+        Ok(())
+    }
+
+    /// Finishes writing the stream.
+    pub fn commit(self) -> Result<()> {
+        self.output.commit()
+    }
+}
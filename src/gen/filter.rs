@@ -0,0 +1,39 @@
+//! bpf-profile-generate filter module.
+//! Implements hiding functions from the profile by regular expressions of their names.
+//! Calls of hidden functions are not recorded, their costs are attributed to the callers.
+
+use crate::error::{Error, Result};
+use regex::Regex;
+
+/// Represents the filter of functions by names.
+#[derive(Debug)]
+pub struct Filter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl Filter {
+    /// Creates the filter showing functions matching the include pattern, if any,
+    /// besides functions matching the exclude pattern.
+    /// Returns None if no pattern is given.
+    pub fn new(include: Option<&str>, exclude: Option<&str>) -> Result<Option<Self>> {
+        if include.is_none() && exclude.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Filter {
+            include: include.map(compile).transpose()?,
+            exclude: exclude.map(compile).transpose()?,
+        }))
+    }
+
+    /// Checks if the function of the name is shown in the profile.
+    pub fn shows(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|r| r.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|r| r.is_match(name))
+    }
+}
+
+/// Compiles the pattern of names of functions.
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::FunctionPattern(pattern.into(), e.to_string()))
+}
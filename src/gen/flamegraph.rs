@@ -0,0 +1,119 @@
+//! bpf-profile-generate flamegraph module.
+//! Implements the collapsed (folded) stacks output: a line per call stack
+//! with the cost of its innermost frame, e.g. `GROUND_ZERO;process;helper 42`,
+//! the SVG flame graph rendered from it and the sampled profile of speedscope,
+//! where every call stack is a sample weighted by its cost.
+
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::Result;
+use serde_json::json;
+use std::io::Write;
+
+/// Immediate costs of the innermost frames by call stacks (without the root).
+pub type Stacks = Map<Vec<FrameId>, Cost>;
+
+/// Writes the call stacks in the collapsed format of flamegraph tools.
+pub fn write_folded(mut output: impl Write, stacks: &Stacks, functions: &Functions) -> Result<()> {
+    for line in folded_lines(stacks, functions) {
+        writeln!(output, "{}", line)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the flame graph of the call stacks as an SVG image.
+#[cfg(feature = "flamegraph")]
+pub fn write_svg(output: impl Write, stacks: &Stacks, functions: &Functions) -> Result<()> {
+    let lines = folded_lines(stacks, functions);
+    let mut options = inferno::flamegraph::Options::default();
+    options.count_name = "instructions".into();
+    inferno::flamegraph::from_lines(&mut options, lines.iter().map(String::as_str), output)
+        .map_err(|e| crate::error::Error::Flamegraph(e.to_string()))
+}
+
+/// Writes the call stacks as a sampled profile in the file format of speedscope.
+pub fn write_speedscope(
+    mut output: impl Write,
+    stacks: &Stacks,
+    functions: &Functions,
+    metadata: Option<serde_json::Value>,
+) -> Result<()> {
+    let mut samples = stacks
+        .iter()
+        .filter(|(_, cost)| **cost > 0)
+        .map(|(stack, cost)| {
+            let mut frames = vec![FrameId::Root];
+            frames.extend(stack);
+            let names = frames
+                .iter()
+                .map(|id| functions[id].name())
+                .collect::<Vec<_>>();
+            (names, frames, *cost)
+        })
+        .collect::<Vec<_>>();
+    samples.sort();
+
+    let mut frames = Vec::<FrameId>::new();
+    let mut indices = Map::<FrameId, usize>::new();
+    let stacks = samples
+        .iter()
+        .map(|(_, stack, _)| {
+            stack
+                .iter()
+                .map(|id| {
+                    *indices.entry(*id).or_insert_with(|| {
+                        frames.push(*id);
+                        frames.len() - 1
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let weights = samples.iter().map(|(_, _, cost)| *cost).collect::<Vec<_>>();
+    let name = functions[&FrameId::Root].name();
+    let mut profile = json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "shared": {
+            "frames": frames
+                .iter()
+                .map(|id| json!({ "name": functions[id].name() }))
+                .collect::<Vec<_>>(),
+        },
+        "profiles": [{
+            "type": "sampled",
+            "name": name,
+            "unit": "none",
+            "startValue": 0,
+            "endValue": weights.iter().sum::<Cost>(),
+            "samples": stacks,
+            "weights": weights,
+        }],
+        "name": name,
+        "activeProfileIndex": 0,
+        "exporter": format!("bpf-profile {}", env!("CARGO_PKG_VERSION")),
+    });
+    if let Some(metadata) = metadata {
+        profile["metadata"] = metadata;
+    }
+    serde_json::to_writer(&mut output, &profile).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Returns the sorted lines of the collapsed format; stacks without cost are skipped.
+fn folded_lines(stacks: &Stacks, functions: &Functions) -> Vec<String> {
+    let name = |id: &FrameId| functions[id].name().replace(';', ":");
+    let mut lines = stacks
+        .iter()
+        .filter(|(_, cost)| **cost > 0)
+        .map(|(stack, cost)| {
+            let mut frames = vec![name(&FrameId::Root)];
+            frames.extend(stack.iter().map(name));
+            format!("{} {}", frames.join(";"), cost)
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
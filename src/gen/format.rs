@@ -0,0 +1,78 @@
+//! bpf-profile-generate format module.
+//! Implements the registry of formats of profiles written by the generate command.
+//! Besides the built-in formats, crates using bpf-profile as a library register
+//! their own ones before the conversion, e.g. an exporter to a dashboard:
+//! ```
+//! use bpf_profile::gen::{format, Options, Profile};
+//! use std::io::Write;
+//!
+//! format::register("total", |profile: &Profile, output: &mut dyn Write| {
+//!     writeln!(output, "{}", profile.total_cost())?;
+//!     Ok(())
+//! });
+//! let options = Options {
+//!     format: "total".into(),
+//!     ..Options::default()
+//! };
+//! ```
+
+use super::Profile;
+use crate::config::Map;
+use crate::error::Result;
+use lazy_static::lazy_static;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+/// Represents a format of profiles.
+pub trait OutputFormat: Send + Sync {
+    /// Writes the profile in the format.
+    fn write(&self, profile: &Profile, output: &mut dyn Write) -> Result<()>;
+}
+
+impl<F> OutputFormat for F
+where
+    F: Fn(&Profile, &mut dyn Write) -> Result<()> + Send + Sync,
+{
+    fn write(&self, profile: &Profile, output: &mut dyn Write) -> Result<()> {
+        self(profile, output)
+    }
+}
+
+lazy_static! {
+    static ref FORMATS: RwLock<Map<String, Arc<dyn OutputFormat>>> = RwLock::new(builtin());
+}
+
+/// Returns the built-in formats by their names.
+fn builtin() -> Map<String, Arc<dyn OutputFormat>> {
+    let mut formats = Map::<String, Arc<dyn OutputFormat>>::new();
+    let mut add = |name: &str, format: fn(&Profile, &mut dyn Write) -> Result<()>| {
+        formats.insert(name.into(), Arc::new(format));
+    };
+    add("callgrind", |p, o| p.write_callgrind(o, &p.asm_filename()));
+    add("folded", |p, o| p.write_folded(o));
+    #[cfg(feature = "flamegraph")]
+    add("flamegraph", |p, o| p.write_flamegraph(o));
+    add("speedscope", |p, o| p.write_speedscope(o));
+    add("json", |p, o| p.write_callgraph_json(o));
+    add("dot", |p, o| p.write_callgraph_dot(o));
+    add("chrome-trace", |p, o| p.write_chrome_trace(o));
+    add("text", |p, o| p.write_text(o));
+    formats
+}
+
+/// Registers the format of the name, replacing the format registered before, if any.
+pub fn register(name: &str, format: impl OutputFormat + 'static) {
+    FORMATS
+        .write()
+        .expect("Formats lock poisoned")
+        .insert(name.into(), Arc::new(format));
+}
+
+/// Returns the format registered with the name.
+pub fn find(name: &str) -> Option<Arc<dyn OutputFormat>> {
+    FORMATS
+        .read()
+        .expect("Formats lock poisoned")
+        .get(name)
+        .cloned()
+}
@@ -0,0 +1,61 @@
+//! bpf-profile-generate heatmap module.
+//! Implements the CSV heatmap of costs over windows of the trace and depths of calls.
+
+use crate::config::Cost;
+use crate::error::Result;
+use std::io::Write;
+
+/// Represents costs by windows of consecutive instructions and depths of calls.
+#[derive(Debug)]
+pub struct Heatmap {
+    window: usize,
+    instructions: usize,
+    rows: Vec<Vec<Cost>>,
+}
+
+impl Heatmap {
+    /// Creates the heatmap with windows of the number of instructions.
+    pub fn new(window: usize) -> Self {
+        Heatmap {
+            window: window.max(1),
+            instructions: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds cost of the next instruction executed at the depth of calls.
+    pub fn add(&mut self, depth: usize, cost: Cost) {
+        let row = self.instructions / self.window;
+        self.instructions += 1;
+        if row >= self.rows.len() {
+            self.rows.resize(row + 1, Vec::new());
+        }
+        let row = &mut self.rows[row];
+        if depth >= row.len() {
+            row.resize(depth + 1, 0);
+        }
+        row[depth] += cost;
+    }
+
+    /// Writes the heatmap as CSV: a row per window starting at the instruction,
+    /// a column per depth of calls.
+    pub fn write(&self, mut output: impl Write) -> Result<()> {
+        let depths = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        write!(output, "instruction")?;
+        for depth in 0..depths {
+            write!(output, ",depth_{}", depth)?;
+        }
+        writeln!(output)?;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            write!(output, "{}", i * self.window)?;
+            for depth in 0..depths {
+                write!(output, ",{}", row.get(depth).copied().unwrap_or(0))?;
+            }
+            writeln!(output)?;
+        }
+
+        output.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! bpf-profile-generate instructions module.
+//! Implements the histogram of executed instructions by mnemonics of their opcodes,
+//! for the whole trace or broken down by functions.
+
+use super::profile::{FrameId, Functions};
+use crate::bpf::Instruction;
+use crate::config::Map;
+use crate::error::Result;
+use crate::global;
+use std::io::Write;
+
+/// Numbers of executed instructions by functions and mnemonics.
+pub type Mnemonics = Map<FrameId, Map<String, Count>>;
+
+/// Represents executions of instructions of a mnemonic.
+#[derive(Clone, Copy, Debug)]
+pub struct Count {
+    class: &'static str,
+    executed: usize,
+}
+
+/// Counts the execution of the instruction by the function.
+pub fn add(mnemonics: &mut Mnemonics, id: FrameId, ix: &Instruction) {
+    let counts = mnemonics.entry(id).or_default();
+    match counts.get_mut(ix.mnemonic()) {
+        Some(count) => count.executed += 1,
+        None => {
+            let count = Count {
+                class: ix.opcode_class(),
+                executed: 1,
+            };
+            counts.insert(ix.mnemonic().into(), count);
+        }
+    }
+}
+
+/// Writes numbers and percentages of executed instructions by mnemonics,
+/// the most executed first, followed by the same table of every function if enabled.
+pub fn write(
+    mut output: impl Write,
+    mnemonics: &Mnemonics,
+    functions: &Functions,
+    by_function: bool,
+) -> Result<()> {
+    writeln!(output, "Executed instructions by mnemonic:")?;
+    let mut total = Map::<String, Count>::new();
+    for (mnemonic, count) in mnemonics.values().flatten() {
+        total
+            .entry(mnemonic.clone())
+            .or_insert(Count {
+                class: count.class,
+                executed: 0,
+            })
+            .executed += count.executed;
+    }
+    write_table(&mut output, &total)?;
+
+    if by_function {
+        let mut by_function = mnemonics
+            .iter()
+            .map(|(id, counts)| {
+                let executed = counts.values().map(|c| c.executed).sum::<usize>();
+                (executed, functions[id].name(), counts)
+            })
+            .collect::<Vec<_>>();
+        by_function.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        for (executed, name, counts) in by_function {
+            writeln!(output, "\n{} {}", name, global::number(executed))?;
+            write_table(&mut output, counts)?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the rows of the mnemonics with their classes, numbers and percentages
+/// of executions, followed by the total.
+fn write_table(mut output: impl Write, counts: &Map<String, Count>) -> Result<()> {
+    let total = counts.values().map(|c| c.executed).sum::<usize>();
+    let mut rows = counts.iter().collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.1.executed.cmp(&a.1.executed).then_with(|| a.0.cmp(b.0)));
+    for (mnemonic, count) in rows {
+        writeln!(
+            output,
+            "    {:<10}{:<12}{:>7.2}% {}",
+            mnemonic,
+            count.class,
+            100.0 * count.executed as f64 / total.max(1) as f64,
+            global::number(count.executed)
+        )?;
+    }
+    writeln!(output, "    Total: {}", global::number(total))?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! bpf-profile-generate intermediate module.
+//! Implements the intermediate profile saved after parsing traces, so the profile
+//! is rendered in other formats by the render command without parsing again.
+//!
+//! The file starts with `MAGIC` and the version byte followed by the accounting
+//! of the profile encoded by bincode: costs and calls of functions and call stacks.
+//! Statistics, the timeline and the resolver are not saved.
+
+use super::flamegraph::Stacks;
+use super::profile::{Counters, Functions};
+use crate::config::Cost;
+use crate::error::{Error, Result};
+use crate::filebuf;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Magic bytes starting intermediate profiles.
+pub const MAGIC: &[u8] = b"BPFPROF";
+const VERSION: u8 = 2;
+
+/// Represents the accounting of the profile which outputs are rendered from,
+/// borrowed from the profile when saved.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Intermediate<'a> {
+    pub total_cost: Cost,
+    pub total_counters: Option<Counters>,
+    pub syscall_cost: Option<Cost>,
+    pub functions: Cow<'a, Functions>,
+    pub stacks: Option<Cow<'a, Stacks>>,
+    pub runtime_version: Option<String>,
+    pub labels: Vec<String>,
+}
+
+/// Writes the intermediate profile to the file.
+pub(super) fn save(intermediate: &Intermediate, path: &Path) -> Result<()> {
+    let mut output = filebuf::open_w(path)?;
+    output.write_all(MAGIC)?;
+    output.write_all(&[VERSION])?;
+    bincode::serialize_into(&mut output, intermediate)
+        .map_err(|e| Error::IntermediateProfile(path.into(), e.to_string()))?;
+    output.commit()
+}
+
+/// Reads the intermediate profile from the file.
+pub(super) fn load(path: &Path) -> Result<Intermediate<'static>> {
+    let mut input = filebuf::open(path)?;
+    let mut header = [0; MAGIC.len() + 1];
+    let invalid = |message: &str| Error::IntermediateProfile(path.into(), message.into());
+    input
+        .read_exact(&mut header)
+        .map_err(|_| invalid("not an intermediate profile"))?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not an intermediate profile"));
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(invalid("unsupported version"));
+    }
+    bincode::deserialize_from(input).map_err(|e| invalid(&e.to_string()))
+}
@@ -0,0 +1,79 @@
+//! bpf-profile JUnit XML reports module.
+//!
+//! Checks are reported as a test suite with a test case for every checked item,
+//! failed by its failures, so CI systems show them without custom scripts.
+
+use crate::error::Result;
+use std::io::Write;
+
+/// Represents a test case of a JUnit report, passed unless it has failures.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub failures: Vec<Failure>,
+}
+
+/// Represents a failure of a test case with its type and message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub kind: String,
+    pub message: String,
+}
+
+/// Writes the test cases as a JUnit XML report of the single test suite.
+pub fn write(mut output: impl Write, suite: &str, cases: &[TestCase]) -> Result<()> {
+    let failed = cases.iter().filter(|c| !c.failures.is_empty()).count();
+    writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        output,
+        r#"<testsuites name="bpf-profile" tests="{}" failures="{}">"#,
+        cases.len(),
+        failed
+    )?;
+    writeln!(
+        output,
+        r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+        escape(suite),
+        cases.len(),
+        failed
+    )?;
+    for case in cases {
+        if case.failures.is_empty() {
+            writeln!(
+                output,
+                r#"    <testcase classname="{}" name="{}"/>"#,
+                escape(suite),
+                escape(&case.name)
+            )?;
+            continue;
+        }
+        writeln!(
+            output,
+            r#"    <testcase classname="{}" name="{}">"#,
+            escape(suite),
+            escape(&case.name)
+        )?;
+        for failure in &case.failures {
+            writeln!(
+                output,
+                r#"      <failure type="{}" message="{}"/>"#,
+                escape(&failure.kind),
+                escape(&failure.message)
+            )?;
+        }
+        writeln!(output, "    </testcase>")?;
+    }
+    writeln!(output, "  </testsuite>")?;
+    writeln!(output, "</testsuites>")?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Escapes the text for XML attributes.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,73 @@
+//! bpf-profile-generate lcov module.
+//! Implements the LCOV coverage report of executed instructions.
+//! Lines of the report are program counters, which are line numbers
+//! of the generated assembly file.
+
+use super::profile::Functions;
+use crate::config::{Map, ProgramCounter};
+use crate::error::Result;
+use crate::resolver::Resolver;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Writes the coverage report in the LCOV tracefile format.
+/// If the dump is available, all instructions and functions of the program are listed,
+/// otherwise only those executed.
+pub fn write(
+    mut output: impl Write,
+    source_filename: &str,
+    functions: &Functions,
+    resolver: &Resolver,
+) -> Result<()> {
+    // Number of executions of each program counter
+    let mut hits = BTreeMap::<ProgramCounter, usize>::new();
+    for f in functions.values() {
+        for (pc, cost) in f.costs() {
+            *hits.entry(*pc).or_insert(0) += cost;
+        }
+    }
+
+    let mut lines = hits.clone();
+    let mut entries = Map::<ProgramCounter, String>::new();
+    if !resolver.has_disassembly() {
+        for f in functions.values() {
+            if let Some(first_pc) = f.costs().keys().next() {
+                entries.insert(*first_pc, f.name());
+            }
+        }
+    } else {
+        for pc in resolver.instruction_pcs() {
+            lines.entry(pc).or_insert(0);
+        }
+        for (pc, name, _) in resolver.dump_functions() {
+            entries.insert(pc, name);
+        }
+    }
+    let mut entries = entries.into_iter().collect::<Vec<_>>();
+    entries.sort();
+
+    writeln!(output, "TN:")?;
+    writeln!(output, "SF:{}", source_filename)?;
+    for (pc, name) in &entries {
+        writeln!(output, "FN:{},{}", pc, name)?;
+    }
+    let mut functions_hit = 0_usize;
+    for (pc, name) in &entries {
+        let count = hits.get(pc).copied().unwrap_or(0);
+        if count > 0 {
+            functions_hit += 1;
+        }
+        writeln!(output, "FNDA:{},{}", count, name)?;
+    }
+    writeln!(output, "FNF:{}", entries.len())?;
+    writeln!(output, "FNH:{}", functions_hit)?;
+    for (pc, count) in &lines {
+        writeln!(output, "DA:{},{}", pc, count)?;
+    }
+    writeln!(output, "LF:{}", lines.len())?;
+    writeln!(output, "LH:{}", hits.len())?;
+    writeln!(output, "end_of_record")?;
+
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! bpf-profile-generate metadata module.
+//! Implements the description of how the profile was generated: the tool version,
+//! the command line, paths and SHA-256 hashes of the input files and the time,
+//! so archived profiles remain self-describing and comparable. Written on request
+//! only, as the time and the paths make outputs differ between runs.
+
+use crate::error::{Error, Result};
+use crate::filebuf;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Represents the description of the generated profile.
+#[derive(Debug)]
+pub struct Metadata {
+    command_line: String,
+    timestamp: u64,
+    traces: Vec<(PathBuf, Option<String>)>,
+    dump: Option<(PathBuf, Option<String>)>,
+}
+
+impl Metadata {
+    /// Describes the profile of the traces and the dump file being generated now.
+    pub fn new(trace_paths: &[&Path], dump_path: Option<&Path>) -> Result<Self> {
+        let traces = trace_paths
+            .iter()
+            .map(|p| Ok((p.to_path_buf(), sha256(p)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let dump = match dump_path {
+            Some(p) => Some((p.to_path_buf(), sha256(p)?)),
+            None => None,
+        };
+        Ok(Metadata {
+            command_line: std::env::args().collect::<Vec<_>>().join(" "),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            traces,
+            dump,
+        })
+    }
+
+    /// Writes the description as header lines of a callgrind file.
+    pub fn write_callgrind(&self, mut output: impl Write, cost_model: &str) -> Result<()> {
+        writeln!(output, "cmd: {}", self.command_line)?;
+        writeln!(
+            output,
+            "desc: Generator: bpf-profile {}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        for (path, hash) in &self.traces {
+            write_input(&mut output, "Trace", path, hash.as_deref())?;
+        }
+        if let Some((path, hash)) = &self.dump {
+            write_input(&mut output, "Dump", path, hash.as_deref())?;
+        }
+        writeln!(output, "desc: Cost model: {}", cost_model)?;
+        writeln!(output, "desc: Timestamp: {}", self.timestamp)?;
+        Ok(())
+    }
+
+    /// Returns the description as an object embedded in JSON outputs.
+    pub fn to_json(&self, cost_model: &str) -> Value {
+        let input = |(path, hash): &(PathBuf, Option<String>)| match hash {
+            Some(hash) => json!({ "path": path.display().to_string(), "sha256": hash }),
+            None => json!({ "path": "standard input", "sha256": null }),
+        };
+        json!({
+            "generator": format!("bpf-profile {}", env!("CARGO_PKG_VERSION")),
+            "command_line": self.command_line,
+            "traces": self.traces.iter().map(input).collect::<Vec<_>>(),
+            "dump": self.dump.as_ref().map(input),
+            "cost_model": cost_model,
+            "timestamp": self.timestamp,
+        })
+    }
+}
+
+/// Writes the path to the input file and its hash, if known.
+fn write_input(mut output: impl Write, kind: &str, path: &Path, hash: Option<&str>) -> Result<()> {
+    match hash {
+        Some(hash) => writeln!(output, "desc: {}: {} sha256:{}", kind, path.display(), hash)?,
+        None => writeln!(output, "desc: {}: standard input", kind)?,
+    }
+    Ok(())
+}
+
+/// Computes the SHA-256 hash of the file as a hex string.
+/// The standard input cannot be read twice, so it is not hashed.
+pub fn sha256(path: &Path) -> Result<Option<String>> {
+    if filebuf::is_stdin(path) {
+        return Ok(None);
+    }
+    let mut file = File::open(path).map_err(|e| Error::OpenFile(e, path.into()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    ))
+}
@@ -0,0 +1,74 @@
+//! bpf-profile-generate metrics module.
+//! Implements the Prometheus text exposition of the profile summary.
+
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, METRICS_TOP_FUNCTIONS, TRANSACTION_COMPUTE_LIMIT};
+use crate::error::Result;
+use std::io::Write;
+
+/// Writes total cost, whether it exceeds the transaction compute limit,
+/// costs of the most expensive functions and the number of unresolved functions
+/// as Prometheus metrics.
+pub fn write(
+    mut output: impl Write,
+    total_cost: Cost,
+    functions: &Functions,
+    unresolved: usize,
+) -> Result<()> {
+    writeln!(
+        output,
+        "# HELP bpf_profile_total_cost Total cost of the traced program."
+    )?;
+    writeln!(output, "# TYPE bpf_profile_total_cost gauge")?;
+    writeln!(output, "bpf_profile_total_cost {}", total_cost)?;
+
+    writeln!(
+        output,
+        "# HELP bpf_profile_exceeds_compute_limit Total cost exceeds the transaction compute limit."
+    )?;
+    writeln!(output, "# TYPE bpf_profile_exceeds_compute_limit gauge")?;
+    writeln!(
+        output,
+        "bpf_profile_exceeds_compute_limit {}",
+        u8::from(total_cost > TRANSACTION_COMPUTE_LIMIT)
+    )?;
+
+    let mut top = functions
+        .iter()
+        .filter(|(id, _)| **id != FrameId::Root)
+        .map(|(_, f)| (f.cost(), f.name()))
+        .collect::<Vec<_>>();
+    top.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    top.truncate(METRICS_TOP_FUNCTIONS);
+
+    writeln!(
+        output,
+        "# HELP bpf_profile_function_cost Self cost of the most expensive functions."
+    )?;
+    writeln!(output, "# TYPE bpf_profile_function_cost gauge")?;
+    for (cost, name) in &top {
+        writeln!(
+            output,
+            "bpf_profile_function_cost{{function=\"{}\"}} {}",
+            escape_label_value(name),
+            cost
+        )?;
+    }
+
+    writeln!(
+        output,
+        "# HELP bpf_profile_unresolved_functions Number of functions not found in the dump."
+    )?;
+    writeln!(output, "# TYPE bpf_profile_unresolved_functions gauge")?;
+    writeln!(output, "bpf_profile_unresolved_functions {}", unresolved)?;
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Escapes a string to be used as a label value.
+fn escape_label_value(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
@@ -1,45 +1,1100 @@
 //! bpf-profile generate command implementation.
 
+mod annotate;
 mod asm;
+mod badge;
+pub mod batch;
+mod bookmarks;
+pub mod budget;
+mod builder;
+mod cache;
+mod callgraph;
+mod check;
+mod diff;
+mod events;
+mod filter;
+mod flamegraph;
+pub mod format;
+mod heatmap;
+mod instructions;
+mod intermediate;
+mod junit;
+mod lcov;
+mod metadata;
+mod metrics;
+mod notify;
+mod parallel;
 mod profile;
+mod progress;
+pub mod query;
+mod regression;
+mod stats;
+mod summary;
+mod tail;
+mod text;
+mod timeline;
 mod trace;
+mod tree;
+#[cfg(feature = "tui")]
+mod view;
+mod window;
 
 #[cfg(test)]
 mod tests;
 
-use crate::config::DEFAULT_ASM;
+use crate::config::{
+    Cost, DEFAULT_ASM, DEFAULT_ERROR_LIMIT, DEFAULT_FORMAT, DEFAULT_GRANULARITY,
+    DEFAULT_HEATMAP_WINDOW, DEFAULT_SORT_ORDER, TIMELINE_FORMAT, TRANSACTION_COMPUTE_LIMIT,
+};
 use crate::error::{Error, Result};
-use crate::filebuf;
-use std::io;
-use std::path::Path;
-use trace::Profile;
+use crate::resolver::{self, Resolver};
+use crate::{filebuf, global};
+pub use builder::{ProfileBuilder, ProfileRecorder};
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+pub use trace::{parse, Profile};
+
+/// Represents options of the conversion besides the input files.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Format of the generated profile, one of the formats registered in `format`.
+    pub format: String,
+    /// Path to the generated profile; standard output if none.
+    pub output: Option<PathBuf>,
+    /// Path to the generated assembly file; enables line-by-line profiling.
+    pub asm: Option<PathBuf>,
+    /// Path to the Prometheus metrics file.
+    pub metrics: Option<PathBuf>,
+    /// Path to the badge summarizing the profile, SVG if the extension is `svg`,
+    /// JSON otherwise.
+    pub badge: Option<PathBuf>,
+    /// Path to the statistics report.
+    pub stats: Option<PathBuf>,
+    /// Path to the LCOV coverage report.
+    pub lcov: Option<PathBuf>,
+    /// Path to the CSV heatmap of costs over the trace and depths of calls.
+    pub heatmap: Option<PathBuf>,
+    /// Number of instructions in a window (row) of the heatmap.
+    pub heatmap_window: usize,
+    /// Path to the stream of executed instructions in the JSON lines format.
+    pub events: Option<PathBuf>,
+    /// Path to the index of calls of functions by byte offsets in the trace
+    /// in the JSON lines format.
+    pub bookmarks: Option<PathBuf>,
+    /// Path to the intermediate profile saved after parsing, rendered later
+    /// in any format by `render`.
+    pub save_intermediate: Option<PathBuf>,
+    /// Records only calls of functions, skipping costs of instructions.
+    pub calls_only: bool,
+    /// Name of the root frame of the profile instead of GROUND_ZERO.
+    pub root_name: Option<String>,
+    /// Writes the root frame as the function `<root>` unless it is named.
+    pub explicit_root: bool,
+    /// Appends the profile as a new part of the existing output file.
+    pub append: bool,
+    /// Release of Solana which costs of syscalls are taken from.
+    pub cluster_version: Option<String>,
+    /// Path to the TOML file of weights of instructions by mnemonics and classes.
+    pub cost_model: Option<PathBuf>,
+    /// Path to the Rhai script which returns costs of instructions.
+    #[cfg(feature = "scripting")]
+    pub cost_script: Option<PathBuf>,
+    /// Splits calls of syscalls into nodes by buckets of their size arguments.
+    pub syscall_sizes: bool,
+    /// Attributes costs of programs invoked by syscalls to the callers of the syscalls
+    /// instead of the syscalls.
+    pub flatten_nested: bool,
+    /// Skips malformed lines of the trace instead of failing.
+    pub lenient: bool,
+    /// Number of malformed lines of the trace reported together before failing,
+    /// unlimited if zero.
+    pub error_limit: usize,
+    /// Positions costs at source lines of instructions known from the dump file.
+    pub source_lines: bool,
+    /// Records the first and the last trace lines of every call in the JSON profile.
+    pub call_lines: bool,
+    /// Adds callgrind events of memory reads and writes and calls of syscalls besides the cost.
+    pub counters: bool,
+    /// Fixed cost of every invocation of a syscall counted as a separate event (if any).
+    pub syscall_cost: Option<Cost>,
+    /// Cost below which finished calls are aggregated instead of stored (if any).
+    pub drop_calls_below: Option<Cost>,
+    /// Number of calls of every caller→callee edge stored separately, beyond which
+    /// calls are aggregated (if any).
+    pub max_call_samples: Option<usize>,
+    /// Name of the function before the first entry into which the trace is discarded.
+    pub ignore_before: Option<String>,
+    /// Name of the function outside of calls of which the trace is discarded.
+    pub only_function: Option<String>,
+    /// Number of instructions at the start of every trace which are discarded.
+    pub skip_instructions: usize,
+    /// Maximum number of instructions of every trace profiled after the skipped ones (if any).
+    pub max_instructions: Option<usize>,
+    /// Range of numbers of lines of every trace profiled, `start:end` (if any).
+    pub range: Option<String>,
+    /// Regular expression of names of functions shown in the profile;
+    /// costs of other functions are attributed to their callers.
+    pub include: Option<String>,
+    /// Regular expression of names of functions hidden from the profile;
+    /// their costs are attributed to their callers.
+    pub exclude: Option<String>,
+    /// Path to the file of aliases renaming or merging functions.
+    pub aliases: Option<PathBuf>,
+    /// Path to the manifest of dump files of programs invoked by the trace.
+    pub dump_manifest: Option<PathBuf>,
+    /// Path to the symbol map naming functions by their first program counters,
+    /// instead of the dump file or over it.
+    pub symbols: Option<PathBuf>,
+    /// Offset of program counters of the trace from those of the dump file,
+    /// e.g. of the program relocated by the loader.
+    pub pc_offset: isize,
+    /// Embeds the tool version, the command line, hashes of the input files,
+    /// the cost model and the time into the callgrind profile.
+    pub metadata: bool,
+    /// Path to the directory caching outputs of conversions.
+    pub cache_dir: Option<PathBuf>,
+    /// Profiles every invocation of a program in the trace as a separate part
+    /// instead of aggregating them.
+    pub split_invocations: bool,
+    /// Number of threads parsing chunks of every trace; one parses the trace sequentially.
+    pub jobs: usize,
+    /// Percentage of the total cost below which inclusive costs of functions
+    /// are insignificant; such functions are merged into `<below threshold>`.
+    pub threshold: Option<f64>,
+    /// Maximum size in bytes of the callgrind profile; the cheapest functions
+    /// are merged into `<truncated>` to fit it.
+    pub max_output_size: Option<usize>,
+    /// Compresses names of functions and files and positions of costs
+    /// in the callgrind profile.
+    pub compress_output: bool,
+    /// Order of functions in the callgrind profile: "name", "address", "self-cost"
+    /// or "inclusive-cost".
+    pub sort: String,
+    /// Granularity of costs in the callgrind profile: "function", or "path"
+    /// for costs of every call path from the root.
+    pub granularity: String,
+    /// Divides costs and numbers of calls of merged traces by the number of traces.
+    pub average: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: DEFAULT_FORMAT.into(),
+            output: None,
+            asm: None,
+            metrics: None,
+            badge: None,
+            stats: None,
+            lcov: None,
+            heatmap: None,
+            heatmap_window: DEFAULT_HEATMAP_WINDOW,
+            events: None,
+            bookmarks: None,
+            save_intermediate: None,
+            calls_only: false,
+            root_name: None,
+            explicit_root: false,
+            append: false,
+            cluster_version: None,
+            cost_model: None,
+            #[cfg(feature = "scripting")]
+            cost_script: None,
+            syscall_sizes: false,
+            flatten_nested: false,
+            lenient: false,
+            error_limit: DEFAULT_ERROR_LIMIT,
+            source_lines: false,
+            call_lines: false,
+            counters: false,
+            syscall_cost: None,
+            drop_calls_below: None,
+            max_call_samples: None,
+            ignore_before: None,
+            only_function: None,
+            skip_instructions: 0,
+            max_instructions: None,
+            range: None,
+            include: None,
+            exclude: None,
+            aliases: None,
+            dump_manifest: None,
+            symbols: None,
+            pc_offset: 0,
+            metadata: false,
+            cache_dir: None,
+            split_invocations: false,
+            jobs: 1,
+            threshold: None,
+            max_output_size: None,
+            compress_output: false,
+            sort: DEFAULT_SORT_ORDER.into(),
+            granularity: DEFAULT_GRANULARITY.into(),
+            average: false,
+        }
+    }
+}
 
 /// Runs the conversion from BPF trace to a profiler output.
-pub fn run(
+/// Outputs are copied from the cache directory, if the conversion is cached.
+pub fn run(trace_path: &Path, dump_path: Option<&Path>, options: &Options) -> Result<()> {
+    let convert = || {
+        if options.split_invocations {
+            run_parts(&[trace_path], dump_path, options)
+        } else {
+            merge(&[trace_path], dump_path, options)
+        }
+    };
+    let entry = match &options.cache_dir {
+        // Files of labeled parts are known after the conversion only
+        Some(cache_dir)
+            if !options.append && !options.output.as_deref().is_some_and(is_labeled_path) =>
+        {
+            cache::entry(cache_dir, trace_path, dump_path, options)?
+        }
+        _ => None,
+    };
+    let Some(entry) = entry else {
+        return convert();
+    };
+
+    let outputs = cache::outputs(options);
+    if cache::restore(&entry, &outputs)? {
+        tracing::info!("Copied outputs of {:?} from cache {:?}", trace_path, entry);
+        return Ok(());
+    }
+    convert()?;
+    // The profile of the interrupted conversion is partial
+    if global::interrupted() {
+        return Ok(());
+    }
+    cache::store(&entry, &outputs)
+}
+
+/// Watches the trace file while it grows, parsing the appended lines and rewriting
+/// the callgrind profile at most once per interval in milliseconds if it changed.
+/// Calls still running are accounted when they return. Never returns on success.
+pub fn watch(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    options: &Options,
+    interval: u64,
+) -> Result<()> {
+    let unsupported = [
+        ("--metrics", options.metrics.is_some()),
+        ("--badge", options.badge.is_some()),
+        ("--stats", options.stats.is_some()),
+        ("--lcov", options.lcov.is_some()),
+        ("--heatmap", options.heatmap.is_some()),
+        ("--events", options.events.is_some()),
+        ("--bookmarks", options.bookmarks.is_some()),
+        ("--save-intermediate", options.save_intermediate.is_some()),
+        ("--skip-instructions", options.skip_instructions > 0),
+        ("--max-instructions", options.max_instructions.is_some()),
+        ("--range", options.range.is_some()),
+        ("--format", options.format != DEFAULT_FORMAT),
+        ("--append", options.append),
+        ("--split-invocations", options.split_invocations),
+        ("--threshold", options.threshold.is_some()),
+        ("--max-output-size", options.max_output_size.is_some()),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, enabled)| *enabled) {
+        return Err(Error::WatchOption(option.to_string()));
+    }
+    let output_path = options
+        .output
+        .as_deref()
+        .ok_or_else(|| Error::WatchOption("standard output".into()))?;
+    let source_filename = &source_filename(options);
+    let resv = read_resolver(dump_path, options)?;
+    let interval = std::time::Duration::from_millis(interval);
+    tracing::info!("Watching {:?} for new lines...", trace_path);
+
+    let mut tail = tail::Tail::new(trace_path);
+    let mut profile = Profile::with_options(resv.clone(), options)?;
+    let mut parser = trace::TraceParser::new(&mut profile);
+    loop {
+        if tail.restarted()? {
+            tracing::warn!("Trace {:?} got shorter, profiling it over", trace_path);
+            profile = Profile::with_options(resv.clone(), options)?;
+            parser = trace::TraceParser::new(&mut profile);
+        }
+        let lines = tail.read()?;
+        if !lines.is_empty() {
+            parser.feed(lines.as_bytes(), &mut profile)?;
+            let mut output = filebuf::open_w(output_path)?;
+            profile.write_callgrind(&mut output, source_filename)?;
+            output.commit()?;
+            if global::verbose() {
+                tracing::info!("Updated {:?}", output_path);
+            }
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+/// Runs the conversion of several BPF traces into one callgrind profile,
+/// each trace as a separate part and thread of the profile, in order of the traces.
+/// Every invocation of a program becomes a separate part if split_invocations is set.
+/// Reports besides the profile are not supported.
+pub fn run_parts(trace_paths: &[&Path], dump_path: Option<&Path>, options: &Options) -> Result<()> {
+    let format = ("--format", options.format != DEFAULT_FORMAT);
+    check_single_trace(reports(options).iter().chain([&format]))?;
+
+    // The dump is read once, its contents are shared by all profiles
+    let resv = read_resolver(dump_path, options)?;
+    let mut profiles = Vec::with_capacity(trace_paths.len());
+    for trace_path in trace_paths {
+        if global::interrupted() {
+            break;
+        }
+        if options.split_invocations {
+            profiles.extend(invocation_profiles(trace_path, &resv, dump_path, options)?);
+        } else {
+            let resv = resv.clone();
+            profiles.push(Profile::create_with_resolver(
+                &[trace_path],
+                resv,
+                dump_path,
+                options,
+            )?);
+        }
+    }
+    let source_filename = &source_filename(options);
+    let count = profiles.len();
+    for (i, profile) in profiles.iter_mut().enumerate() {
+        warn_compute_limit(profile);
+        profile.set_thread(i + 1);
+        if let Some(threshold) = options.threshold {
+            profile.prune_below(threshold);
+        }
+        // Every part gets an equal share of the maximum size
+        if let Some(max_output_size) = options.max_output_size {
+            profile.truncate_callgrind(max_output_size / count, source_filename)?;
+        }
+    }
+
+    let Some(output_path) = &options.output else {
+        return write_parts(io::stdout(), &profiles, source_filename, None);
+    };
+    if !is_labeled_path(output_path) {
+        return write_parts_to(output_path, &profiles, source_filename, options.append);
+    }
+    for (i, profile) in profiles.iter().enumerate() {
+        let part_path = labeled_path(output_path, profile.labels(), i + 1);
+        let part = std::slice::from_ref(profile);
+        write_parts_to(&part_path, part, source_filename, options.append)?;
+    }
+    Ok(())
+}
+
+/// Runs the conversion of every trace into its own profile at the path given
+/// for the trace, e.g. of a batch of traces into a directory, by the number of threads
+/// of the options, and prints the summary of the conversions. A failed conversion
+/// does not stop the others. Reports besides the profiles are not supported.
+pub fn run_batch(
+    traces: &[(&Path, PathBuf)],
+    dump_path: Option<&Path>,
+    options: &Options,
+) -> Result<()> {
+    check_single_trace(reports(options).iter())?;
+    let convert = |trace_path: &Path, output_path: &Path| {
+        let options = Options {
+            output: Some(output_path.into()),
+            jobs: 1,
+            ..options.clone()
+        };
+        run(trace_path, dump_path, &options)
+    };
+    let failed = batch::run(traces, options.jobs, convert, io::stdout())?;
+    if failed > 0 && !global::interrupted() {
+        return Err(Error::BatchFailed(failed, traces.len()));
+    }
+    Ok(())
+}
+
+/// Returns the options of reports besides the profile, each with true if given.
+fn reports(options: &Options) -> [(&'static str, bool); 8] {
+    [
+        ("--metrics", options.metrics.is_some()),
+        ("--badge", options.badge.is_some()),
+        ("--stats", options.stats.is_some()),
+        ("--lcov", options.lcov.is_some()),
+        ("--heatmap", options.heatmap.is_some()),
+        ("--events", options.events.is_some()),
+        ("--bookmarks", options.bookmarks.is_some()),
+        ("--save-intermediate", options.save_intermediate.is_some()),
+    ]
+}
+
+/// Fails with the first of the options given, which support a single trace only.
+fn check_single_trace<'a>(
+    mut options: impl Iterator<Item = &'a (&'static str, bool)>,
+) -> Result<()> {
+    match options.find(|(_, enabled)| *enabled) {
+        Some((option, _)) => Err(Error::SingleTrace(option.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Extensions of compressed traces, which are dropped from names of profiles.
+const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "zst"];
+
+/// Returns the file name of the profile of the trace in the format: the name
+/// of the trace without its extension (and the extension of compression),
+/// followed by the extension of the format, e.g. `trace.callgrind.out` for `trace.log`.
+/// Names which are not UTF-8 are kept as they are.
+pub fn output_name(trace_path: &Path, format: &str) -> Result<PathBuf> {
+    let mut name = trace_path
+        .file_name()
+        .filter(|_| !filebuf::is_stdin(trace_path))
+        .map(Path::new)
+        .ok_or_else(|| Error::OutputName(trace_path.into()))?;
+    let compressed = name
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| COMPRESSED_EXTENSIONS.contains(&e));
+    if compressed {
+        name = Path::new(name.file_stem().unwrap_or_default());
+    }
+    let mut output = name.file_stem().unwrap_or_default().to_os_string();
+    output.push(".");
+    output.push(match format {
+        "callgrind" => "callgrind.out",
+        "flamegraph" => "svg",
+        "speedscope" => "speedscope.json",
+        "chrome-trace" => "trace.json",
+        "text" => "txt",
+        // Formats of plugins and libraries are named after themselves
+        format => format,
+    });
+    Ok(output.into())
+}
+
+/// Placeholder of the path of the profile replaced by labels of every part.
+const LABEL_PLACEHOLDER: &str = "{label}";
+
+/// Checks if every part of the profile is written to its own file.
+fn is_labeled_path(path: &Path) -> bool {
+    path.components()
+        .any(|c| c.as_os_str().to_string_lossy().contains(LABEL_PLACEHOLDER))
+}
+
+/// Returns the path of the file of the part: the placeholder is replaced
+/// by identifiers of its executions, or by the number of the part if unlabeled.
+fn labeled_path(path: &Path, labels: &[String], part: usize) -> PathBuf {
+    let label = match labels {
+        [] => part.to_string(),
+        labels => labels.join("+"),
+    };
+    let label = label.replace(
+        |c: char| !(c.is_ascii_alphanumeric() || "+-._".contains(c)),
+        "_",
+    );
+    // Components without the placeholder are kept as they are, e.g. not UTF-8
+    path.components()
+        .map(|c| {
+            let name = c.as_os_str().to_string_lossy();
+            if name.contains(LABEL_PLACEHOLDER) {
+                name.replace(LABEL_PLACEHOLDER, &label).into()
+            } else {
+                c.as_os_str().to_os_string()
+            }
+        })
+        .collect()
+}
+
+/// Writes the profiles as parts of the callgrind file, following the parts
+/// of the existing file if appended.
+fn write_parts_to(
+    output_path: &Path,
+    profiles: &[Profile],
+    source_filename: &str,
+    append: bool,
+) -> Result<()> {
+    if append && output_path.exists() {
+        let previous = fs::read_to_string(output_path)?;
+        let part = trace::next_callgrind_part(&previous, &profiles[0].callgrind_events())
+            .ok_or_else(|| Error::IncompatibleOutput(output_path.into()))?;
+        let mut output = filebuf::open_w(output_path)?;
+        output.write_all(previous.as_bytes())?;
+        write_parts(&mut output, profiles, source_filename, Some(part))?;
+        return output.commit();
+    }
+    let mut output = filebuf::open_w(output_path)?;
+    write_parts(&mut output, profiles, source_filename, None)?;
+    output.commit()
+}
+
+/// Creates a profile of every invocation of a program in the trace.
+fn invocation_profiles(
+    trace_path: &Path,
+    resv: &Resolver,
+    dump_path: Option<&Path>,
+    options: &Options,
+) -> Result<Vec<Profile>> {
+    let mut profiles = Vec::new();
+    for invocation in trace::invocations(filebuf::open(trace_path)?) {
+        let invocation = invocation?;
+        let mut profile = Profile::with_options(resv.clone(), options)?;
+        if options.metadata {
+            profile.set_metadata(metadata::Metadata::new(&[trace_path], dump_path)?);
+        }
+        parse(invocation.as_bytes(), &mut profile)?;
+        profile.finish()?;
+        profiles.push(profile);
+    }
+    tracing::info!("Found {} invocations of programs", profiles.len());
+    Ok(profiles)
+}
+
+/// Reads the dump file, if any, names functions by the symbol map, if any,
+/// and renames them by the aliases.
+pub(crate) fn read_resolver(dump_path: Option<&Path>, options: &Options) -> Result<Resolver> {
+    let mut resv = resolver::read(dump_path)?;
+    resv.set_pc_offset(options.pc_offset);
+    if let Some(manifest_path) = &options.dump_manifest {
+        for (program_id, path) in resolver::read_manifest(manifest_path)? {
+            resv.add_program(&program_id, resolver::read(Some(&path))?);
+        }
+    }
+    if let Some(symbols_path) = &options.symbols {
+        resv.add_symbols(&resolver::read_symbols(symbols_path)?);
+    }
+    apply_aliases(&mut resv, options)?;
+    Ok(resv)
+}
+
+/// Renames functions of the resolver by the aliases of the options, if any.
+/// Aliases are applied before the resolver is shared, so the dump is not copied.
+fn apply_aliases(resv: &mut Resolver, options: &Options) -> Result<()> {
+    if let Some(aliases_path) = &options.aliases {
+        resv.set_aliases(resolver::read_aliases(aliases_path)?);
+    }
+    Ok(())
+}
+
+/// Writes the profiles as consecutive parts of a callgrind file,
+/// numbered from the first part if the file is appended.
+fn write_parts(
+    mut output: impl Write,
+    profiles: &[Profile],
+    source_filename: &str,
+    first_part: Option<usize>,
+) -> Result<()> {
+    for (i, profile) in profiles.iter().enumerate() {
+        let part = match first_part {
+            Some(first_part) => Some(first_part + i),
+            None => (i > 0).then_some(i + 1),
+        };
+        if part.is_some() {
+            writeln!(output)?;
+        }
+        profile.write_callgrind_part(&mut output, source_filename, part)?;
+    }
+    Ok(())
+}
+
+/// Checks costs of functions of the trace against the budgets
+/// and reports each violation, optionally as a SARIF log too.
+/// Fails if any budget is exceeded.
+pub fn check_budget(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    budgets: &[budget::Budget],
+    sarif_path: Option<&Path>,
+    junit_path: Option<&Path>,
+    notify_url: Option<&str>,
+) -> Result<()> {
+    let profile = Profile::create(&[trace_path], dump_path, &Options::default())?;
+    if let Some(sarif_path) = sarif_path {
+        let mut output = filebuf::open_w(sarif_path)?;
+        profile.check_budgets_sarif(&mut output, budgets)?;
+        output.commit()?;
+    }
+    if let Some(junit_path) = junit_path {
+        let mut output = filebuf::open_w(junit_path)?;
+        profile.check_budgets_junit(&mut output, budgets)?;
+        output.commit()?;
+    }
+    let violations = profile.check_budgets(io::stdout(), budgets)?;
+    if violations > 0 {
+        if let Some(url) = notify_url {
+            let violations = profile.budget_violations(budgets);
+            let messages = violations.iter().map(|v| v.message()).collect::<Vec<_>>();
+            let summary = format!(
+                "{} budget violation(s) in {:?}",
+                violations.len(),
+                trace_path
+            );
+            notify::post(url, &summary, &messages, &violations);
+        }
+        return Err(Error::BudgetExceeded(violations));
+    }
+    Ok(())
+}
+
+/// Loads the profile of the trace and writes the table of the top functions
+/// by the kind of cost.
+pub fn summary(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    top: usize,
+    by: query::By,
+    csv: bool,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let profile = Profile::create(&[trace_path], dump_path, &Options::default())?;
+    write_output(output_path, |output| {
+        profile.write_summary(output, top, by, csv)
+    })
+}
+
+/// Loads the trace and writes numbers of executed instructions by mnemonics,
+/// optionally broken down by functions.
+pub fn instructions(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    by_function: bool,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let mut prof = Profile::new(read_resolver(dump_path, &Options::default())?, None)?;
+    prof.set_mnemonics(true);
+    parse(filebuf::open(trace_path)?, &mut prof)?;
+    prof.finish()?;
+    write_output(output_path, |output| {
+        prof.write_instructions(output, by_function)
+    })
+}
+
+/// Loads the trace and writes the disassembly of executed functions annotated with
+/// numbers of executions and costs of instructions, only of the function if given.
+pub fn annotate(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    function: Option<&str>,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let mut prof = Profile::new(read_resolver(dump_path, &Options::default())?, None)?;
+    prof.set_executions(true);
+    parse(filebuf::open(trace_path)?, &mut prof)?;
+    prof.finish()?;
+    write_output(output_path, |output| prof.write_annotated(output, function))
+}
+
+/// Writes the lines of the trace of the call of the function found by its occurrence
+/// in the bookmarks written by the generate command for the same trace.
+pub fn show(
+    trace_path: &Path,
+    bookmarks_path: &Path,
+    function: &str,
+    occurrence: usize,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let trace = filebuf::open(trace_path)?;
+    let bookmarks = filebuf::open(bookmarks_path)?;
+    write_output(output_path, |output| {
+        bookmarks::show(trace, bookmarks, function, occurrence, output)
+    })
+}
+
+/// Checks the structure of the trace and reports the issues found,
+/// each with the number of surrounding lines of the trace.
+pub fn check(
+    trace_path: &Path,
+    context: usize,
+    json: bool,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let findings = findings(trace_path, context)?;
+    write_output(output_path, |output| {
+        if json {
+            check::write_json(output, &findings)
+        } else {
+            check::write_text(output, &findings)
+        }
+    })?;
+    if !findings.is_empty() {
+        return Err(Error::TraceIssues(findings.len()));
+    }
+    Ok(())
+}
+
+/// Parses the trace collecting its structural issues in order of lines,
+/// each with the lines of the trace within the distance around it.
+fn findings(trace_path: &Path, context: usize) -> Result<Vec<check::Finding>> {
+    let mut prof = Profile::new(Resolver::default(), None)?;
+    prof.set_findings(true);
+    match parse(filebuf::open(trace_path)?, &mut prof) {
+        Ok(()) | Err(Error::TraceFormat) => {}
+        Err(e) => return Err(e),
+    }
+    let mut findings = prof.take_findings();
+    findings.sort_by_key(|f| f.line());
+    // The standard input cannot be read twice
+    if context > 0 && !filebuf::is_stdin(trace_path) {
+        check::add_context(&mut findings, filebuf::open(trace_path)?, context)?;
+    }
+    Ok(findings)
+}
+
+/// Lints the trace: writes its structural issues with their severities,
+/// including instructions out of sequence and calls beyond the depth limit.
+/// Fails if any issue is an error.
+pub fn lint(
+    trace_path: &Path,
+    context: usize,
+    json: bool,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let findings = findings(trace_path, context)?;
+    write_output(output_path, |output| {
+        if json {
+            check::write_json(output, &findings)
+        } else {
+            check::write_lint(output, &findings)
+        }
+    })?;
+    let errors = findings.iter().filter(|f| f.is_error()).count();
+    if errors > 0 {
+        return Err(Error::TraceIssues(errors));
+    }
+    Ok(())
+}
+
+/// Compares costs of the trace with the baseline JSON profile and reports
+/// the costs which grew by more than the percentage, e.g. "5%".
+/// Fails if any cost regressed.
+pub fn check_regression(
     trace_path: &Path,
-    asm_path: Option<&Path>,
     dump_path: Option<&Path>,
-    _: &str, // always 'callgrind' currently
+    baseline_path: &Path,
+    max_regression: &str,
+    json: bool,
     output_path: Option<&Path>,
+    notify_url: Option<&str>,
 ) -> Result<()> {
-    if !crate::trace::contains_standard_header(filebuf::open(trace_path)?)? {
-        return Err(Error::TraceFormat);
+    let max_percent = regression::parse_percent(max_regression)?;
+    let baseline = regression::read(baseline_path)?;
+    let profile = Profile::create(&[trace_path], dump_path, &Options::default())?;
+    let mut regressions = 0;
+    write_output(output_path, |output| {
+        regressions = profile.check_regressions(output, &baseline, max_percent, json)?;
+        Ok(())
+    })?;
+    if regressions > 0 {
+        if let Some(url) = notify_url {
+            let regressions = profile.regressions(&baseline, max_percent);
+            let messages = regressions.iter().map(|r| r.message()).collect::<Vec<_>>();
+            let summary = format!(
+                "{} cost regression(s) in {:?}",
+                regressions.len(),
+                trace_path
+            );
+            notify::post(url, &summary, &messages, &regressions);
+        }
+        return Err(Error::CostRegression(regressions));
+    }
+    Ok(())
+}
+
+/// Loads the profile of the trace and runs the query over it,
+/// or reads queries from the standard input one per line if no query given.
+pub fn query(trace_path: &Path, dump_path: Option<&Path>, expr: Option<&str>) -> Result<()> {
+    let profile = Profile::create(&[trace_path], dump_path, &Options::default())?;
+    if let Some(expr) = expr {
+        return profile.query(io::stdout(), &query::parse(expr)?);
+    }
+
+    let stdin = io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            break;
+        }
+        match line.trim() {
+            "" => continue,
+            "quit" | "exit" => break,
+            expr => match query::parse(expr) {
+                Ok(query) => profile.query(io::stdout(), &query)?,
+                Err(e) => eprintln!("{}", e),
+            },
+        }
     }
+    Ok(())
+}
 
-    let profile = Profile::create(trace_path, dump_path, asm_path)?;
+/// Loads the profile of the trace with its call stacks and writes the call tree
+/// from the root or from calls of the function, down to the depth if given.
+pub fn tree(
+    trace_path: &Path,
+    dump_path: Option<&Path>,
+    root: Option<&str>,
+    depth: Option<usize>,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let options = Options::default();
+    let mut profile = Profile::with_options(read_resolver(dump_path, &options)?, &options)?;
+    profile.set_stacks(true);
+    parse(filebuf::open(trace_path)?, &mut profile)?;
+    profile.finish()?;
+    write_output(output_path, |output| {
+        profile.write_tree(output, root, depth)
+    })
+}
 
-    let source_filename = match asm_path {
-        None => DEFAULT_ASM,
-        Some(asm_path) => asm_path
-            .to_str()
-            .ok_or_else(|| Error::Filename(asm_path.into()))?,
+/// Runs the program of the ELF file with the input under the embedded virtual machine,
+/// executing at most the number of instructions, and writes the profile of its trace
+/// in the format of the options. Functions are named by symbols of the ELF file.
+/// The profile of a failed program is written up to the failure.
+#[cfg(feature = "run")]
+pub fn run_program(
+    program_path: &Path,
+    input_path: Option<&Path>,
+    compute_limit: Cost,
+    options: &Options,
+) -> Result<()> {
+    let elf = fs::read(program_path)?;
+    let input = match input_path {
+        Some(input_path) => fs::read(input_path)?,
+        None => Vec::new(),
     };
+    let execution = crate::vm::execute(&elf, &input, compute_limit)?;
+    if let Err(e) = execution.result() {
+        tracing::warn!(
+            "Program failed after {} instructions: {}",
+            global::number(execution.instructions()),
+            e
+        );
+    }
+
+    let mut profile = Profile::with_options(read_resolver(Some(program_path), options)?, options)?;
+    parse(io::BufReader::new(execution.trace()?), &mut profile)?;
+    profile.finish()?;
+    warn_compute_limit(&profile);
+    write_profile(profile, options, &source_filename(options))
+}
+
+/// Loads the profile of the trace and opens it in the interactive terminal viewer.
+#[cfg(feature = "tui")]
+pub fn view(trace_path: &Path, dump_path: Option<&Path>) -> Result<()> {
+    let profile = Profile::create(&[trace_path], dump_path, &Options::default())?;
+    profile.viewer().run()
+}
+
+/// Writes the callgrind profile of differences of costs of the new trace
+/// against the old one (new minus old) and, optionally, the text table
+/// of deltas of self and inclusive costs of functions, normalized per call if asked.
+pub fn diff(
+    old_trace_path: &Path,
+    old_dump_path: Option<&Path>,
+    new_trace_path: &Path,
+    new_dump_path: Option<&Path>,
+    output_path: Option<&Path>,
+    table_path: Option<&Path>,
+    per_call: bool,
+) -> Result<()> {
+    let options = Options::default();
+    let old = Profile::create(&[old_trace_path], old_dump_path, &options)?;
+    let new = Profile::create(&[new_trace_path], new_dump_path, &options)?;
+
+    if let Some(table_path) = table_path {
+        let mut output = filebuf::open_w(table_path)?;
+        new.write_diff_table(&mut output, &old, per_call)?;
+        output.commit()?;
+    }
 
     match output_path {
+        None => new.write_callgrind_diff(io::stdout(), &old, DEFAULT_ASM),
+        Some(output_path) => {
+            let mut output = filebuf::open_w(output_path)?;
+            new.write_callgrind_diff(&mut output, &old, DEFAULT_ASM)?;
+            output.commit()
+        }
+    }
+}
+
+/// Writes the table of changes of sizes of functions of the new dump file
+/// against the old one.
+pub fn dump_diff(
+    old_dump_path: &Path,
+    new_dump_path: &Path,
+    output_path: Option<&Path>,
+) -> Result<()> {
+    let old = resolver::read(Some(old_dump_path))?;
+    let new = resolver::read(Some(new_dump_path))?;
+    write_output(output_path, |output| {
+        diff::write_dump_table(output, &old.dump_functions(), &new.dump_functions())
+    })
+}
+
+/// Runs the conversion of several BPF traces of the same program
+/// into one profiler output.
+pub fn merge(trace_paths: &[&Path], dump_path: Option<&Path>, options: &Options) -> Result<()> {
+    #[cfg(feature = "plugins")]
+    if format::find(&options.format).is_none() && !crate::plugin::has_format(&options.format) {
+        return Err(Error::UnknownFormat(options.format.clone()));
+    }
+
+    let mut profile = Profile::create(trace_paths, dump_path, options)?;
+    if options.average {
+        profile.average(trace_paths.len());
+    }
+    warn_compute_limit(&profile);
+    let source_filename = &source_filename(options);
+
+    if let Some(intermediate_path) = &options.save_intermediate {
+        intermediate::save(&profile.intermediate(), intermediate_path)?;
+    }
+
+    if let Some(metrics_path) = &options.metrics {
+        let mut output = filebuf::open_w(metrics_path)?;
+        profile.write_metrics(&mut output)?;
+        output.commit()?;
+    }
+
+    if let Some(badge_path) = &options.badge {
+        let mut output = filebuf::open_w(badge_path)?;
+        if badge_path.extension().is_some_and(|e| e == "svg") {
+            profile.write_badge_svg(&mut output)?;
+        } else {
+            profile.write_badge_json(&mut output)?;
+        }
+        output.commit()?;
+    }
+
+    if let Some(stats_path) = &options.stats {
+        let mut output = filebuf::open_w(stats_path)?;
+        profile.write_stats(&mut output)?;
+        output.commit()?;
+    }
+
+    if let Some(heatmap_path) = &options.heatmap {
+        let mut output = filebuf::open_w(heatmap_path)?;
+        profile.write_heatmap(&mut output)?;
+        output.commit()?;
+    }
+
+    if let Some(lcov_path) = &options.lcov {
+        let mut output = filebuf::open_w(lcov_path)?;
+        profile.write_lcov(&mut output, source_filename)?;
+        output.commit()?;
+    }
+
+    write_profile(profile, options, source_filename)
+}
+
+/// Renders the intermediate profile saved by `generate` in the format of the options.
+/// Options of parsing and reports besides the profile are not supported,
+/// as well as formats of data which is not saved.
+pub fn render(intermediate_path: &Path, options: &Options) -> Result<()> {
+    let unsupported = [
+        ("--asm", options.asm.is_some()),
+        ("--source-lines", options.source_lines),
+        ("--append", options.append),
+        ("--granularity", options.granularity != DEFAULT_GRANULARITY),
+        ("--format", options.format == TIMELINE_FORMAT),
+    ];
+    if let Some((option, _)) = unsupported
+        .iter()
+        .chain(reports(options).iter())
+        .find(|(_, enabled)| *enabled)
+    {
+        return Err(Error::RenderOption(option.to_string()));
+    }
+    #[cfg(feature = "plugins")]
+    if format::find(&options.format).is_none() && !crate::plugin::has_format(&options.format) {
+        return Err(Error::UnknownFormat(options.format.clone()));
+    }
+
+    let mut profile = Profile::with_options(Resolver::default(), options)?;
+    profile.restore(intermediate::load(intermediate_path)?)?;
+    write_profile(profile, options, &source_filename(options))
+}
+
+/// Writes the profile in the format of the options, pruned and truncated
+/// if the callgrind profile.
+fn write_profile(mut profile: Profile, options: &Options, source_filename: &str) -> Result<()> {
+    #[cfg(feature = "plugins")]
+    if format::find(&options.format).is_none() {
+        let mut callgrind = Vec::new();
+        profile.write_callgrind(&mut callgrind, source_filename)?;
+        return crate::plugin::write_format(&options.format, callgrind, options.output.as_deref());
+    }
+
+    if options.format != DEFAULT_FORMAT {
+        if options.append {
+            return Err(Error::AppendFormat(options.format.clone()));
+        }
+        let format = format::find(&options.format)
+            .ok_or_else(|| Error::UnknownFormat(options.format.clone()))?;
+        return write_output(options.output.as_deref(), |output| {
+            format.write(&profile, output)
+        });
+    }
+
+    if let Some(threshold) = options.threshold {
+        profile.prune_below(threshold);
+    }
+    if let Some(max_output_size) = options.max_output_size {
+        profile.truncate_callgrind(max_output_size, source_filename)?;
+    }
+    match &options.output {
         None => profile.write_callgrind(io::stdout(), source_filename),
+        Some(output_path) if options.append && output_path.exists() => {
+            let previous = fs::read_to_string(output_path)?;
+            let part = trace::next_callgrind_part(&previous, &profile.callgrind_events())
+                .ok_or_else(|| Error::IncompatibleOutput(output_path.into()))?;
+            let mut output = filebuf::open_w(output_path)?;
+            output.write_all(previous.as_bytes())?;
+            writeln!(output)?;
+            profile.write_callgrind_part(&mut output, source_filename, Some(part))?;
+            output.commit()
+        }
+        Some(output_path) => {
+            let mut output = filebuf::open_w(output_path)?;
+            profile.write_callgrind(&mut output, source_filename)?;
+            output.commit()
+        }
+    }
+}
+
+/// Warns if the total cost of the profile exceeds the transaction compute limit.
+fn warn_compute_limit(profile: &Profile) {
+    if profile.total_cost() > TRANSACTION_COMPUTE_LIMIT {
+        tracing::warn!(
+            "Total cost {} exceeds the transaction compute limit of {} units",
+            global::number(profile.total_cost()),
+            global::number(TRANSACTION_COMPUTE_LIMIT)
+        );
+    }
+}
+
+/// Returns the name of the source file referred by the callgrind profile,
+/// with characters of a path which is not UTF-8 replaced; the file keeps its path.
+fn source_filename(options: &Options) -> Cow<'_, str> {
+    match options.asm.as_deref() {
+        None => DEFAULT_ASM.into(),
+        Some(asm_path) => asm_path.to_string_lossy(),
+    }
+}
+
+/// Writes the output to the file, or to the standard output if no path given.
+fn write_output(
+    output_path: Option<&Path>,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    match output_path {
+        None => write(&mut io::stdout()),
         Some(output_path) => {
-            let output = filebuf::open_w(output_path)?;
-            profile.write_callgrind(output, source_filename)
+            let mut output = filebuf::open_w(output_path)?;
+            write(&mut output)?;
+            output.commit()
         }
     }
 }
@@ -0,0 +1,35 @@
+//! bpf-profile-generate notification module.
+//! Composes notifications of issues found by checks, e.g. violations of budgets,
+//! and posts them to webhooks of chats like Slack or Discord.
+
+use crate::config::NOTIFY_MESSAGE_LIMIT;
+use crate::remote;
+use serde::Serialize;
+
+/// Returns the text of the notification: the summary followed by the messages
+/// of the first issues and the number of the rest.
+pub fn text(summary: &str, messages: &[String]) -> String {
+    let mut text = format!("bpf-profile: {}", summary);
+    for message in messages.iter().take(NOTIFY_MESSAGE_LIMIT) {
+        text += &format!("\n{}", message);
+    }
+    if messages.len() > NOTIFY_MESSAGE_LIMIT {
+        text += &format!("\n... and {} more", messages.len() - NOTIFY_MESSAGE_LIMIT);
+    }
+    text
+}
+
+/// Posts the issues found by a check to the webhook: the text of the notification
+/// as `text` of Slack and `content` of Discord, and all the issues as `issues`.
+/// Failures are logged, not to hide the issues.
+pub fn post<T: Serialize>(url: &str, summary: &str, messages: &[String], issues: &[T]) {
+    let text = text(summary, messages);
+    let payload = serde_json::json!({
+        "text": text,
+        "content": text,
+        "issues": issues,
+    });
+    if let Err(err) = remote::notify(url, &payload) {
+        tracing::error!("{:#}", err);
+    }
+}
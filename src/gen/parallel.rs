@@ -0,0 +1,145 @@
+//! bpf-profile-generate parallel module.
+//! Implements parsing of large traces by several threads. The trace is split
+//! into chunks of whole invocations of programs, which are parsed independently
+//! and merged in order of the trace, so the profile is the same as of sequential parsing.
+//! A trace of a single invocation is parsed by one thread.
+
+use super::trace::{self, Part, Profile};
+use super::Options;
+use crate::config::PARALLEL_CHUNK_SIZE;
+use crate::error::Result;
+use crate::global;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// Parses the trace by the number of threads, building the Profile instance.
+/// Falls back to sequential parsing if the options depend on the order
+/// of the whole trace.
+pub fn parse(reader: impl BufRead, prof: &mut Profile, options: &Options) -> Result<()> {
+    let sequential = [
+        ("--events", options.events.is_some()),
+        ("--bookmarks", options.bookmarks.is_some()),
+        ("--lines", options.call_lines),
+        ("--heatmap", options.heatmap.is_some()),
+        ("--ignore-before", options.ignore_before.is_some()),
+        ("--only-function", options.only_function.is_some()),
+        ("--skip-instructions", options.skip_instructions > 0),
+        ("--max-instructions", options.max_instructions.is_some()),
+        ("--range", options.range.is_some()),
+        ("--include", options.include.is_some()),
+        ("--exclude", options.exclude.is_some()),
+    ];
+    if let Some((option, _)) = sequential.iter().find(|(_, enabled)| *enabled) {
+        tracing::warn!("Trace is parsed by one thread because of {}", option);
+        return trace::parse(reader, prof);
+    }
+
+    parse_chunks(reader, prof, options, PARALLEL_CHUNK_SIZE)
+}
+
+/// Parses the trace by the number of threads in chunks of at least the size in bytes.
+pub(super) fn parse_chunks(
+    reader: impl BufRead,
+    prof: &mut Profile,
+    options: &Options,
+    chunk_size: usize,
+) -> Result<()> {
+    tracing::info!("Parsing trace file by {} threads...", options.jobs);
+    let resv = prof.resolver().clone();
+    let mut progress = prof.take_progress();
+    let result = thread::scope(|scope| {
+        let (chunk_sender, chunk_receiver) = mpsc::sync_channel::<(usize, String)>(options.jobs);
+        let chunk_receiver = Arc::new(Mutex::new(chunk_receiver));
+        let (part_sender, part_receiver) = mpsc::channel::<(usize, Result<Part>)>();
+        for _ in 0..options.jobs {
+            let chunk_receiver = Arc::clone(&chunk_receiver);
+            let part_sender = part_sender.clone();
+            let resv = &resv;
+            scope.spawn(move || loop {
+                let next = chunk_receiver.lock().expect("Chunks lock poisoned").recv();
+                let Ok((i, chunk)) = next else {
+                    break;
+                };
+                let part = trace::parse_part(&chunk, resv.clone(), options);
+                if part_sender.send((i, part)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(chunk_receiver);
+        drop(part_sender);
+
+        let mut merger = Merger::default();
+        let mut invocations = trace::invocations(reader);
+        let mut chunk = String::new();
+        let mut chunks = 0;
+        let mut pending = false;
+        loop {
+            if global::interrupted() {
+                break;
+            }
+            if !pending {
+                // Costs follow the runtime of the first header before the chunk,
+                // as in sequential parsing
+                chunk = invocations.runtime_header().unwrap_or_default().into();
+            }
+            let Some(invocation) = invocations.next() else {
+                break;
+            };
+            chunk += &invocation?;
+            if let Some(progress) = &mut progress {
+                progress.update(invocations.bytes_read(), invocations.lines(), None, None);
+            }
+            pending = true;
+            if chunk.len() < chunk_size {
+                continue;
+            }
+            if chunk_sender
+                .send((chunks, std::mem::take(&mut chunk)))
+                .is_err()
+            {
+                break;
+            }
+            pending = false;
+            chunks += 1;
+            for (i, part) in part_receiver.try_iter() {
+                merger.add(prof, i, part)?;
+            }
+        }
+        if pending || chunks == 0 {
+            let _ = chunk_sender.send((chunks, chunk));
+        }
+        drop(chunk_sender);
+        for (i, part) in part_receiver {
+            merger.add(prof, i, part)?;
+        }
+        if let Some(progress) = progress.take() {
+            progress.finish(invocations.bytes_read(), invocations.lines(), None, None);
+        }
+        Ok(())
+    });
+    trace::report_parsed(prof);
+    result
+}
+
+/// Merges parts of the trace into the profile in order of their chunks.
+#[derive(Default)]
+struct Merger {
+    next: usize,
+    pending: BTreeMap<usize, Result<Part>>,
+}
+
+impl Merger {
+    /// Adds the part of the chunk of the number, merging it and the pending parts
+    /// following it unless a previous chunk is still parsed.
+    fn add(&mut self, prof: &mut Profile, i: usize, part: Result<Part>) -> Result<()> {
+        self.pending.insert(i, part);
+        while let Some(part) = self.pending.remove(&self.next) {
+            prof.merge(part?);
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
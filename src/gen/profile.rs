@@ -1,37 +1,161 @@
 //! bpf-profile-generate profile module.
 
 use crate::config::{Address, Cost, Map, ProgramCounter};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 
-pub type Functions = Map<Address, Function>;
+pub type Functions = Map<FrameId, Function>;
 type Costs = BTreeMap<ProgramCounter, Cost>; // sort by pc
 
+/// Name of the callgrind object of functions of unknown programs.
+const NO_PROGRAM: &str = "<none>";
+
+/// Names of the callgrind events counted besides the cost, if enabled.
+/// The fixed costs of invocations of syscalls are counted only if given.
+pub const COUNTER_EVENTS: [&str; 4] = ["Reads", "Writes", "Syscalls", "SyscallCost"];
+/// Index of the number of executed loads from memory in counters.
+pub const READS: usize = 0;
+/// Index of the number of executed stores to memory in counters.
+pub const WRITES: usize = 1;
+/// Index of the number of calls of syscalls in counters.
+pub const SYSCALLS: usize = 2;
+/// Index of the fixed costs of invocations of syscalls in counters.
+pub const SYSCALL_COST: usize = 3;
+/// Numbers of the events counted besides the cost.
+pub type Counters = [Cost; COUNTER_EVENTS.len()];
+
+/// Adds the counters to the accumulated ones.
+pub fn add_counters(total: &mut Counters, counters: &Counters) {
+    for (t, c) in total.iter_mut().zip(counters) {
+        *t += c;
+    }
+}
+
+/// Identifies a frame of the profile: a function of the program
+/// or a synthetic frame which can not collide with any real address.
+/// Synthetic frames go after the functions in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FrameId {
+    /// Function of the program starting at the address.
+    Addr(Address),
+    /// Calls of the syscall at the address with the size argument in the bucket.
+    SyscallSize(Address, usize),
+    /// Function of a merged trace at the address taken by another function of the profile,
+    /// identified by its first program counter.
+    Merged(ProgramCounter),
+    /// Synthetic frame absorbing costs of the trace started in the middle of a function.
+    TraceStart,
+    /// Synthetic frame absorbing functions below the threshold of significance.
+    BelowThreshold,
+    /// Synthetic frame absorbing functions dropped to limit the size of the output.
+    Truncated,
+    /// Synthetic root frame of the profile ("ground zero").
+    Root,
+}
+
+impl FrameId {
+    /// Checks if the frame is a call of a syscall.
+    pub fn is_syscall(&self) -> bool {
+        match self {
+            FrameId::Addr(address) => syscalls::is_syscall(*address),
+            FrameId::SyscallSize(..) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for FrameId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameId::Addr(address) | FrameId::SyscallSize(address, _) => {
+                write!(f, "0x{:x}", address)
+            }
+            FrameId::Merged(first_pc) => write!(f, "merged-{}", first_pc),
+            FrameId::TraceStart => write!(f, "trace-start"),
+            FrameId::BelowThreshold => write!(f, "below-threshold"),
+            FrameId::Truncated => write!(f, "truncated"),
+            FrameId::Root => write!(f, "root"),
+        }
+    }
+}
+
 /// Represents a function which will be dumped into a profile.
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Function {
-    address: Address,
+    id: FrameId,
     name: String,
     costs: Costs,
+    counters: BTreeMap<ProgramCounter, Counters>,
     calls: Vec<Call>,
+    /// Identifier of the program executing the function, if reported by the trace.
+    program: Option<String>,
+    /// Positions in calls of the records of aggregated cheap calls.
+    #[serde(skip)]
+    aggregated: Map<(ProgramCounter, FrameId, bool), usize>,
 }
 
-use crate::config::GROUND_ZERO;
 use crate::resolver::Resolver;
+use crate::syscalls;
 
 impl Function {
     /// Creates initial function object which stores total cost of entire program.
     pub fn ground_zero() -> Self {
         Function {
-            address: GROUND_ZERO,
+            id: FrameId::Root,
             name: "GROUND_ZERO".into(),
             costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
+        }
+    }
+
+    /// Creates synthetic function object which absorbs costs of the trace
+    /// started in the middle of a function.
+    pub fn trace_start() -> Self {
+        Function {
+            id: FrameId::TraceStart,
+            name: "<trace-start>".into(),
+            costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
+        }
+    }
+
+    /// Creates synthetic function object which absorbs functions
+    /// below the threshold of significance.
+    pub fn below_threshold() -> Self {
+        Function {
+            id: FrameId::BelowThreshold,
+            name: "<below threshold>".into(),
+            costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
+        }
+    }
+
+    /// Creates synthetic function object which absorbs functions dropped
+    /// to limit the size of the output.
+    pub fn truncated() -> Self {
+        Function {
+            id: FrameId::Truncated,
+            name: "<truncated>".into(),
+            costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
             calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
         }
     }
 
     /// Creates new function object.
     pub fn new(address: Address, first_pc: ProgramCounter, resolver: &mut Resolver) -> Self {
-        assert_ne!(address, GROUND_ZERO);
         let name = resolver.update(address, first_pc);
         tracing::debug!(
             "New function {} with 0x{:x} and first pc {}",
@@ -40,45 +164,188 @@ impl Function {
             first_pc
         );
         Function {
-            address,
+            id: FrameId::Addr(address),
             name,
             costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
             calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
         }
     }
 
+    /// Creates new function object for a syscall.
+    pub fn syscall(address: Address) -> Self {
+        let name = match syscalls::name(address) {
+            Some(name) => name.into(),
+            None => format!("syscall_0x{:x}", address),
+        };
+        tracing::debug!("New syscall {} with 0x{:x}", &name, address);
+        Function {
+            id: FrameId::Addr(address),
+            name,
+            costs: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            calls: Vec::new(),
+            program: None,
+            aggregated: Map::new(),
+        }
+    }
+
+    /// Creates new function object for calls of a syscall with sizes in the bucket.
+    pub fn syscall_size(address: Address, bucket: usize) -> Self {
+        let mut function = Function::syscall(address);
+        function.id = FrameId::SyscallSize(address, bucket);
+        function.name += &syscalls::size_bucket_label(bucket);
+        function
+    }
+
     /// Returns copy of the function's name.
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
-    /// Increments the immediate cost of the function.
-    pub fn increment_cost(&mut self, pc: ProgramCounter) {
-        tracing::debug!("Function(0x{:x}).increment_cost", self.address);
-        let c = *self.costs.entry(pc).or_insert(0);
-        self.costs.insert(pc, c + 1);
+    /// Renames the function.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.into();
+    }
+
+    /// Returns the identifier of the program executing the function, if known.
+    pub fn program(&self) -> Option<&str> {
+        self.program.as_deref()
+    }
+
+    /// Sets the program executing the function.
+    pub fn set_program(&mut self, program: &str) {
+        self.program = Some(program.into());
+    }
+
+    /// Returns the immediate costs of the function by program counters.
+    pub fn costs(&self) -> &BTreeMap<ProgramCounter, Cost> {
+        &self.costs
+    }
+
+    /// Returns the first program counter of the immediate costs of the function,
+    /// the target position of its calls in the callgrind profile, or 0 if it has none,
+    /// e.g. of syscalls.
+    pub fn first_pc(&self) -> ProgramCounter {
+        self.costs.keys().next().copied().unwrap_or(0)
+    }
+
+    /// Returns the total immediate cost of the function.
+    pub fn cost(&self) -> Cost {
+        self.costs.values().sum()
+    }
+
+    /// Returns the total immediate counters of the function.
+    pub fn total_counters(&self) -> Counters {
+        let mut total = Counters::default();
+        for c in self.counters.values() {
+            add_counters(&mut total, c);
+        }
+        total
+    }
+
+    /// Increments the immediate cost of the function by the cost of the instruction.
+    pub fn increment_cost(&mut self, pc: ProgramCounter, cost: Cost) {
+        tracing::trace!("Function({}).increment_cost", self.id);
+        *self.costs.entry(pc).or_insert(0) += cost;
+    }
+
+    /// Increments the immediate counters of the function by the counters of the instruction.
+    pub fn increment_counters(&mut self, pc: ProgramCounter, counters: &Counters) {
+        add_counters(self.counters.entry(pc).or_default(), counters);
+    }
+
+    /// Moves immediate costs and finished calls of another function into this one.
+    pub fn absorb(&mut self, other: &mut Function) {
+        tracing::debug!("Function({}).absorb {}", self.id, other.id);
+        for (pc, cost) in std::mem::take(&mut other.costs) {
+            *self.costs.entry(pc).or_insert(0) += cost;
+        }
+        for (pc, counters) in std::mem::take(&mut other.counters) {
+            self.increment_counters(pc, &counters);
+        }
+        for mut call in other.calls.drain(..) {
+            call.caller = self.id;
+            self.calls.push(call);
+        }
+        // The same function of another part of the trace may know its program
+        if self.id == other.id && self.program.is_none() {
+            self.program = other.program.take();
+        }
+    }
+
+    /// Replaces identifiers of the function and of its callees, e.g. by the ones
+    /// of the same functions in another trace.
+    pub fn remap(&mut self, map: impl Fn(FrameId) -> FrameId) {
+        self.id = map(self.id);
+        for call in &mut self.calls {
+            call.id = map(call.id);
+            call.caller = self.id;
+        }
+        self.aggregated.clear();
+    }
+
+    /// Marks calls of the function by itself as recursive.
+    pub fn set_recursive_calls(&mut self) {
+        for call in self.calls.iter_mut().filter(|c| c.id == self.id) {
+            call.recursive = true;
+        }
+    }
+
+    /// Returns finished enclosed calls of the function.
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
     }
 
     /// Adds finished enclosed call for this function.
     pub fn add_call(&mut self, call: Call) {
-        tracing::debug!(
-            "Function(0x{:x}).add_call 0x{:x}",
-            self.address,
-            call.address
-        );
+        tracing::debug!("Function({}).add_call {}", self.id, call.id);
         self.calls.push(call);
     }
+
+    /// Adds finished enclosed call for this function to the record of calls
+    /// of the same callee from the same position, instead of storing it separately.
+    pub fn aggregate_call(&mut self, call: Call) {
+        tracing::debug!("Function({}).aggregate_call {}", self.id, call.id);
+        let key = (call.caller_pc, call.id, call.recursive);
+        // Records may be moved or renamed after parsing, e.g. by truncation
+        let record = self
+            .aggregated
+            .get(&key)
+            .copied()
+            .filter(|i| self.calls.get(*i).is_some_and(|c| c.key() == key));
+        match record {
+            Some(i) => {
+                let record = &mut self.calls[i];
+                record.cost += call.cost;
+                add_counters(&mut record.counters, &call.counters);
+                record.count += call.count;
+                if let (Some((first, last)), Some(lines)) = (&mut record.lines, call.lines) {
+                    *first = (*first).min(lines.0);
+                    *last = (*last).max(lines.1);
+                }
+            }
+            None => {
+                self.aggregated.insert(key, self.calls.len());
+                self.calls.push(call);
+            }
+        }
+    }
 }
 
 /// Represents a function call.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Call {
-    address: Address,
-    caller: Address,
+    id: FrameId,
+    caller: FrameId,
     caller_pc: ProgramCounter,
     cost: Cost,
-    callee: Box<Option<Call>>,
-    depth: usize,
+    counters: Counters,
+    recursive: bool,
+    count: usize,
+    lines: Option<(usize, usize)>,
 }
 
 use crate::bpf::Instruction;
@@ -86,14 +353,16 @@ use crate::error::{Error, Result};
 
 impl Call {
     /// Creates new call object.
-    pub fn new(address: Address, caller_pc: ProgramCounter) -> Self {
+    pub fn new(id: FrameId, caller_pc: ProgramCounter) -> Self {
         Call {
-            address,
-            caller: Address::default(), // will be found later
+            id,
+            caller: FrameId::Root, // will be found later
             caller_pc,
             cost: 0,
-            callee: Box::new(None),
-            depth: 0,
+            counters: Counters::default(),
+            recursive: false,
+            count: 1,
+            lines: None,
         }
     }
 
@@ -104,144 +373,750 @@ impl Call {
             return Err(Error::TraceNotCall(text, lc));
         }
         let address = ix.extract_call_target(lc)?;
-        Ok(Call::new(address, ix.pc()))
+        Ok(Call::new(FrameId::Addr(address), ix.pc()))
     }
 
-    /// Returns address of the call.
-    pub fn address(&self) -> Address {
-        self.address
+    /// Returns identifier of the called frame.
+    pub fn id(&self) -> FrameId {
+        self.id
     }
 
     /// Checks if the call is the root ("ground zero").
     pub fn is_ground(&self) -> bool {
-        self.address == GROUND_ZERO
+        self.id == FrameId::Root
     }
 
-    /// Returns address of the caller.
-    pub fn caller(&self) -> Address {
+    /// Returns program counter of the call instruction.
+    pub fn caller_pc(&self) -> ProgramCounter {
+        self.caller_pc
+    }
+
+    /// Returns identifier of the caller.
+    pub fn caller(&self) -> FrameId {
         self.caller
     }
 
-    /// Returns depth of enclosed callees.
-    pub fn depth(&self) -> usize {
-        self.depth
+    /// Returns inclusive cost of the call.
+    pub fn cost(&self) -> Cost {
+        self.cost
     }
 
-    /// Increments the cost of this call.
-    pub fn increment_cost(&mut self, pc: ProgramCounter, functions: &mut Functions) {
-        tracing::debug!("Call(0x{:x}).increment_cost", self.address);
-        match *self.callee {
-            Some(ref mut callee) => {
-                callee.increment_cost(pc, functions);
-            }
-            None => {
-                self.cost += 1;
-                let f = functions
-                    .get_mut(&self.address)
-                    .expect("Call address not found in the registry of functions");
-                f.increment_cost(pc);
-            }
+    /// Adds cost of the work done outside of the trace, e.g. by a syscall.
+    pub fn add_cost(&mut self, cost: Cost) {
+        self.cost += cost;
+    }
+
+    /// Adds counters of the work done outside of the trace, e.g. by a syscall.
+    pub fn add_counters(&mut self, counters: &Counters) {
+        add_counters(&mut self.counters, counters);
+    }
+
+    /// Checks if the call is made within an unfinished call of the same function,
+    /// directly or through other functions, so its cost is included in that call.
+    pub fn is_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    /// Marks the call as made within an unfinished call of the same function.
+    pub fn set_recursive(&mut self) {
+        self.recursive = true;
+    }
+
+    /// Returns the number of calls the record stands for, more than one
+    /// if cheap calls are aggregated.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Records the trace line at which the call starts, also ending it until the exit.
+    pub fn enter_line(&mut self, line: usize) {
+        self.lines = Some((line, line));
+    }
+
+    /// Records the trace line of the exit from the call, if its start is recorded.
+    pub fn exit_line(&mut self, line: usize) {
+        if let Some((_, last)) = &mut self.lines {
+            *last = line;
         }
     }
 
-    /// Adds next call to the call stack.
+    /// Returns the first and the last trace lines of the call, if recorded;
+    /// aggregated calls span the lines of all of them.
+    pub fn lines(&self) -> Option<(usize, usize)> {
+        self.lines
+    }
+
+    /// Returns the position, the callee and the recursion of the call,
+    /// which identify calls aggregated into one record.
+    fn key(&self) -> (ProgramCounter, FrameId, bool) {
+        (self.caller_pc, self.id, self.recursive)
+    }
+}
+
+/// Represents the stack of unfinished calls, the root ("ground zero") at the bottom.
+#[derive(Clone, Debug)]
+pub struct CallStack {
+    calls: Vec<Call>,
+}
+
+impl Default for CallStack {
+    fn default() -> Self {
+        CallStack {
+            calls: vec![Call::new(FrameId::Root, 0)],
+        }
+    }
+}
+
+impl CallStack {
+    /// Creates the stack of the root call only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of calls above the root.
+    pub fn depth(&self) -> usize {
+        self.calls.len() - 1
+    }
+
+    /// Returns the innermost call.
+    fn top(&mut self) -> &mut Call {
+        self.calls.last_mut().expect("Root call not found")
+    }
+
+    /// Increments the cost of the innermost call by the cost of the instruction.
+    pub fn increment_cost(&mut self, pc: ProgramCounter, cost: Cost, functions: &mut Functions) {
+        let call = self.top();
+        tracing::trace!("Call({}).increment_cost", call.id);
+        call.cost += cost;
+        functions
+            .get_mut(&call.id)
+            .expect("Call not found in the registry of functions")
+            .increment_cost(pc, cost);
+    }
+
+    /// Increments the counters of the innermost call by the counters of the instruction.
+    pub fn increment_counters(
+        &mut self,
+        pc: ProgramCounter,
+        counters: &Counters,
+        functions: &mut Functions,
+    ) {
+        let call = self.top();
+        add_counters(&mut call.counters, counters);
+        functions
+            .get_mut(&call.id)
+            .expect("Call not found in the registry of functions")
+            .increment_counters(pc, counters);
+    }
+
+    /// Adds finished call of a syscall to the innermost call.
+    pub fn add_syscall(&mut self, mut call: Call, functions: &mut Functions) {
+        let top = self.top();
+        tracing::debug!("Call({}).add_syscall {}", top.id, call.id);
+        call.caller = top.id;
+        top.cost += call.cost;
+        add_counters(&mut top.counters, &call.counters);
+        functions
+            .get_mut(&top.id)
+            .expect("Call not found in the registry of functions")
+            .add_call(call);
+    }
+
+    /// Adds next call to the top of the stack.
     pub fn push_call(&mut self, mut call: Call) {
-        tracing::debug!(
-            "Call(0x{:x}).push_call 0x{:x} depth={}",
-            self.address,
-            call.address,
-            self.depth
-        );
-        self.depth += 1;
-        match *self.callee {
-            Some(ref mut callee) => {
-                callee.push_call(call);
+        let depth = self.depth();
+        let top = self.top();
+        tracing::debug!("Call({}).push_call {} depth={}", top.id, call.id, depth);
+        call.caller = top.id;
+        self.calls.push(call);
+    }
+
+    /// Removes the innermost call from the stack, adding its costs to the caller.
+    pub fn pop_call(&mut self) -> Call {
+        tracing::debug!("CallStack.pop_call depth={}", self.depth());
+        if self.depth() == 0 {
+            panic!("Exit without call");
+        }
+        let call = self.calls.pop().expect("Missing callee");
+        let caller = self.top();
+        caller.cost += call.cost;
+        add_counters(&mut caller.counters, &call.counters);
+        call
+    }
+}
+
+/// Moves the functions into the synthetic function `<truncated>` with their
+/// immediate costs and calls; calls of them become calls of `<truncated>`.
+/// Calls between the truncated functions are recursive calls of `<truncated>`.
+pub fn truncate(functions: &mut Functions, ids: &BTreeSet<FrameId>) {
+    fold(functions, ids, FrameId::Truncated, Function::truncated);
+}
+
+/// Moves the functions into the synthetic function `<below threshold>`
+/// the same way as truncated ones into `<truncated>`.
+pub fn prune(functions: &mut Functions, ids: &BTreeSet<FrameId>) {
+    fold(
+        functions,
+        ids,
+        FrameId::BelowThreshold,
+        Function::below_threshold,
+    );
+}
+
+/// Moves the functions into the synthetic function of the id, created if missing.
+fn fold(
+    functions: &mut Functions,
+    ids: &BTreeSet<FrameId>,
+    into: FrameId,
+    synthetic: impl FnOnce() -> Function,
+) {
+    let mut folded = functions.remove(&into).unwrap_or_else(synthetic);
+    for id in ids {
+        if let Some(mut f) = functions.remove(id) {
+            folded.absorb(&mut f);
+        }
+    }
+    for f in functions.values_mut() {
+        for call in f.calls.iter_mut().filter(|c| ids.contains(&c.id)) {
+            call.id = into;
+        }
+    }
+    for call in folded
+        .calls
+        .iter_mut()
+        .filter(|c| c.id == into || ids.contains(&c.id))
+    {
+        call.id = into;
+        call.recursive = true;
+    }
+    functions.insert(into, folded);
+}
+
+/// Divides the cost by the number of runs, rounding to the nearest.
+pub fn divide(cost: Cost, runs: usize) -> Cost {
+    (cost + runs / 2) / runs
+}
+
+/// Divides the counters by the number of runs, rounding to the nearest.
+fn divide_counters(counters: &mut Counters, runs: usize) {
+    for c in counters.iter_mut() {
+        *c = divide(*c, runs);
+    }
+}
+
+/// Divides immediate costs of the functions and numbers and costs of their calls
+/// by the number of runs, so the functions are of an average run. Calls from
+/// the same position are replaced by their average number of calls, at least one,
+/// sharing their average cost.
+pub fn average(functions: &mut Functions, runs: usize) {
+    for f in functions.values_mut() {
+        for cost in f.costs.values_mut() {
+            *cost = divide(*cost, runs);
+        }
+        for counters in f.counters.values_mut() {
+            divide_counters(counters, runs);
+        }
+
+        let mut groups = Vec::<(Call, usize)>::new();
+        for call in f.calls.drain(..) {
+            let group = groups.iter_mut().find(|(c, _)| c.key() == call.key());
+            match group {
+                Some((c, n)) => {
+                    c.cost += call.cost;
+                    add_counters(&mut c.counters, &call.counters);
+                    *n += call.count;
+                }
+                None => {
+                    let n = call.count;
+                    groups.push((call, n));
+                }
             }
-            None => {
-                call.caller = self.address;
-                let old = std::mem::replace(&mut *self.callee, Some(call));
-                assert!(old.is_none());
+        }
+        for (mut total, n) in groups {
+            let calls = divide(n, runs).max(1);
+            let cost = divide(total.cost, runs);
+            divide_counters(&mut total.counters, runs);
+            total.count = 1;
+            for i in 0..calls {
+                let mut call = total.clone();
+                // The first call takes the remainder of the cost
+                call.cost = cost / calls + if i == 0 { cost % calls } else { 0 };
+                if i > 0 {
+                    call.counters = Counters::default();
+                }
+                f.calls.push(call);
             }
         }
     }
+}
 
-    /// Removes current call from the call stack.
-    pub fn pop_call(&mut self) -> Call {
-        tracing::debug!("Call(0x{:x}).pop_call depth={}", self.address, self.depth);
-        if self.depth == 0 {
-            panic!("Exit without call");
+/// Represents statistics of calls from a caller to a callee.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallStats {
+    /// Number of calls.
+    pub calls: usize,
+    /// Total inclusive cost of the calls.
+    pub cost: Cost,
+    /// Minimum inclusive cost of a call.
+    pub min: Cost,
+    /// Maximum inclusive cost of a call.
+    pub max: Cost,
+    /// Total inclusive counters of the calls.
+    pub counters: Counters,
+    /// Inclusive costs of single calls with the numbers of calls in their records.
+    samples: Vec<(Cost, usize)>,
+    /// First and last trace lines of the records of calls with their inclusive costs,
+    /// if recorded.
+    pub lines: Vec<(usize, usize, Cost)>,
+}
+
+impl CallStats {
+    /// Adds the finished call to the statistics.
+    /// Aggregated calls count with their average cost as the minimum and maximum,
+    /// and as the cost of every aggregated call in percentiles.
+    pub fn add(&mut self, call: &Call) {
+        let cost = call.cost / call.count;
+        self.samples.push((cost, call.count));
+        if let Some((first, last)) = call.lines {
+            self.lines.push((first, last, call.cost));
         }
-        self.depth -= 1;
-        let callee = self.callee.as_mut().as_mut().expect("Missing callee");
-        if callee.callee.is_some() {
-            callee.pop_call()
+        self.min = if self.calls == 0 {
+            cost
         } else {
-            let call = self.callee.take().expect("Missing callee");
-            self.cost += call.cost;
-            call
+            self.min.min(cost)
+        };
+        self.max = self.max.max(cost);
+        self.calls += call.count;
+        self.cost += call.cost;
+        add_counters(&mut self.counters, &call.counters);
+    }
+
+    /// Returns the average inclusive cost of a call.
+    pub fn average(&self) -> Cost {
+        self.cost / self.calls.max(1)
+    }
+
+    /// Returns the inclusive cost of a call at the percentile (nearest rank),
+    /// e.g. 50 for the median.
+    pub fn percentile(&self, percentile: f64) -> Cost {
+        let mut samples = self.samples.clone();
+        samples.sort_unstable();
+        let rank = ((percentile / 100.0 * self.calls as f64).ceil() as usize).max(1);
+        let mut calls = 0;
+        for (cost, count) in samples {
+            calls += count;
+            if calls >= rank {
+                return cost;
+            }
         }
+        self.max
     }
 }
 
+/// Collects statistics of calls of the function by program counters of the calls
+/// and callees, ordered by them so outputs are reproducible. Unless by_pc, calls
+/// of a callee from different program counters are collapsed into the first of them.
+pub fn call_statistics(
+    f: &Function,
+    by_pc: bool,
+) -> BTreeMap<(ProgramCounter, FrameId), CallStats> {
+    let mut ids = Map::new();
+    let mut statistics = BTreeMap::<_, CallStats>::new();
+    for c in &f.calls {
+        let pc = if by_pc {
+            c.caller_pc
+        } else {
+            *ids.entry(c.id).or_insert(c.caller_pc)
+        };
+        statistics.entry((pc, c.id)).or_default().add(c);
+    }
+    statistics
+}
+
+use super::flamegraph::Stacks;
 use crate::global;
 use std::io::Write;
 
-/// Writes information about calls of functions and their costs.
+/// Represents the compression of the callgrind file, if enabled: names of functions
+/// and files are numbered on the first use, `fn=(1) name`, and referred to by
+/// the numbers later, `fn=(1)`; positions of costs are relative to the previous
+/// ones, `+2` or `-1`, besides the first position of every function.
+#[derive(Debug, Default)]
+pub struct Compression {
+    enabled: bool,
+    functions: Map<String, usize>,
+    files: Map<String, usize>,
+    objects: Map<String, usize>,
+    position: Option<usize>,
+}
+
+impl Compression {
+    /// Creates the compression of one part of the callgrind file.
+    pub fn new(enabled: bool) -> Self {
+        Compression {
+            enabled,
+            ..Compression::default()
+        }
+    }
+
+    /// Returns the name of the function, compressed if enabled.
+    fn function(&mut self, name: &str) -> String {
+        compress_name(self.enabled, &mut self.functions, name)
+    }
+
+    /// Returns the name of the file, compressed if enabled.
+    fn file(&mut self, name: &str) -> String {
+        compress_name(self.enabled, &mut self.files, name)
+    }
+
+    /// Returns the name of the object, compressed if enabled.
+    fn object(&mut self, name: &str) -> String {
+        compress_name(self.enabled, &mut self.objects, name)
+    }
+
+    /// Starts the records of the function, which begin with the absolute position.
+    fn start_function(&mut self) {
+        self.position = None;
+    }
+
+    /// Writes the line of costs at the position, followed by the enabled counters.
+    fn write_costs(
+        &mut self,
+        mut output: impl Write,
+        position: usize,
+        cost: Cost,
+        counters: &[Cost],
+    ) -> Result<()> {
+        match self.position.filter(|_| self.enabled) {
+            Some(previous) if previous == position => write!(output, "* {}", cost)?,
+            Some(previous) if previous < position => {
+                write!(output, "+{} {}", position - previous, cost)?
+            }
+            Some(previous) => write!(output, "-{} {}", previous - position, cost)?,
+            None => write!(output, "{} {}", position, cost)?,
+        }
+        self.position = Some(position);
+        for c in counters {
+            write!(output, " {}", c)?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+}
+
+/// Returns the callgrind object of the function, the program executing it.
+fn object(f: &Function) -> &str {
+    f.program().unwrap_or(NO_PROGRAM)
+}
+
+/// Checks if the functions are executed by more than one program, e.g. invoked
+/// by cross-program invocations, so the callgrind profile groups them into objects
+/// by programs: `ob=` before functions and `cob=` before calls of other programs.
+fn has_programs(functions: &Functions) -> bool {
+    let mut programs = functions.values().filter_map(|f| f.program());
+    programs
+        .next()
+        .is_some_and(|first| programs.any(|program| program != first))
+}
+
+/// Returns the name numbered on its first use, or its number if used already.
+fn compress_name(enabled: bool, numbers: &mut Map<String, usize>, name: &str) -> String {
+    if !enabled {
+        return name.into();
+    }
+    if let Some(number) = numbers.get(name) {
+        return format!("({})", number);
+    }
+    let number = numbers.len() + 1;
+    numbers.insert(name.into(), number);
+    format!("({}) {}", number, name)
+}
+
+/// Represents the order of functions in the callgrind profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// By names of functions.
+    Name,
+    /// By addresses of functions, followed by synthetic frames.
+    #[default]
+    Address,
+    /// By immediate costs, the most costly first.
+    SelfCost,
+    /// By inclusive costs, the most costly first.
+    InclusiveCost,
+}
+
+impl Order {
+    /// Returns the order by its name: "name", "address", "self-cost" or "inclusive-cost".
+    pub fn from_name(name: &str) -> Option<Order> {
+        match name {
+            "name" => Some(Order::Name),
+            "address" => Some(Order::Address),
+            "self-cost" => Some(Order::SelfCost),
+            "inclusive-cost" => Some(Order::InclusiveCost),
+            _ => None,
+        }
+    }
+}
+
+/// Represents the unit of aggregation of costs in the callgrind profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Granularity {
+    /// Costs of every function, whatever the callers.
+    #[default]
+    Function,
+    /// Costs of every call path from the root, e.g. of the same function
+    /// called by different callers separately.
+    Path,
+}
+
+impl Granularity {
+    /// Returns the granularity by its name: "function" or "path".
+    pub fn from_name(name: &str) -> Option<Granularity> {
+        match name {
+            "function" => Some(Granularity::Function),
+            "path" => Some(Granularity::Path),
+            _ => None,
+        }
+    }
+}
+
+/// Numbers of calls by call stacks (without the root) of the callees.
+pub type PathCalls = Map<Vec<FrameId>, usize>;
+
+/// Returns identifiers of the functions in the order, ties broken by identifiers,
+/// so the profile of the same trace is written the same way.
+/// Inclusive costs are used by the order of inclusive costs only.
+pub fn sort(
+    functions: &Functions,
+    order: Order,
+    inclusive_costs: &Map<FrameId, Cost>,
+) -> Vec<FrameId> {
+    let inclusive = |id: &FrameId| inclusive_costs.get(id).copied().unwrap_or(0);
+    let mut ids = functions.keys().copied().collect::<Vec<_>>();
+    match order {
+        Order::Name => ids.sort_by(|a, b| functions[a].name.cmp(&functions[b].name).then(a.cmp(b))),
+        Order::Address => ids.sort(),
+        Order::SelfCost => {
+            ids.sort_by(|a, b| functions[b].cost().cmp(&functions[a].cost()).then(a.cmp(b)))
+        }
+        Order::InclusiveCost => ids.sort_by(|a, b| inclusive(b).cmp(&inclusive(a)).then(a.cmp(b))),
+    }
+    ids
+}
+
+/// Writes information about calls of functions and their costs in the order
+/// of the identifiers, followed by their counters if enabled.
 pub fn write_callgrind_functions(
     mut output: impl Write,
-    functions: &Functions,
+    (functions, order): (&Functions, &[FrameId]),
     line_by_line_profile_enabled: bool,
+    root_enabled: bool,
+    counter_events: usize,
+    compress: bool,
 ) -> Result<()> {
     if global::verbose() {
         tracing::info!("Writing callgrind profile...")
     }
 
-    // Collapse possible calls of functions from different pcs
-    // in case line_by_line_profile_enabled == false
-    let mut addresses = Map::new();
-
-    // Collect (caller-pc, function-address) => (number-of-calls, inclusive-cost)
-    let mut statistics = Map::new();
+    let mut compression = Compression::new(compress);
+    let objects = has_programs(functions);
+    let mut current_object = None;
 
-    for (a, f) in functions {
-        if *a == GROUND_ZERO {
+    for id in order {
+        let f = &functions[id];
+        if *id == FrameId::Root && !root_enabled {
             continue;
         }
 
         // Dump costs of current function
-        writeln!(output, "\nfn={}", f.name())?;
+        writeln!(output)?;
+        if objects && current_object != Some(object(f)) {
+            writeln!(output, "ob={}", compression.object(object(f)))?;
+            current_object = Some(object(f));
+        }
+        writeln!(output, "fn={}", compression.function(&f.name))?;
+        compression.start_function();
         if line_by_line_profile_enabled {
             for (pc, cost) in &f.costs {
-                writeln!(output, "{} {}", pc, cost)?;
+                let pc_counters = f.counters.get(pc).copied().unwrap_or_default();
+                compression.write_costs(&mut output, *pc, *cost, &pc_counters[..counter_events])?;
             }
-        } else {
-            let first_pc = f.costs.iter().next().expect("Empty function").0;
-            let total_cost = f.costs.values().sum::<Cost>();
-            writeln!(output, "{} {}", first_pc, total_cost)?;
-        }
-
-        // Collect statistics of callees
-        addresses.clear();
-        statistics.clear();
-        for c in &f.calls {
-            let key = if line_by_line_profile_enabled {
-                (c.caller_pc, c.address)
-            } else {
-                let pc = addresses.entry(c.address).or_insert(c.caller_pc);
-                let unified_caller_pc = *pc;
-                (unified_caller_pc, c.address)
-            };
-            let stat = statistics.entry(key).or_insert((0_usize, 0_usize));
-            let number_of_calls = stat.0 + 1;
-            let inclusive_cost = stat.1 + c.cost;
-            statistics.insert(key, (number_of_calls, inclusive_cost));
-        }
-
-        // Finally dump the statistics
-        for ((pc, address), (number_of_calls, inclusive_cost)) in &statistics {
-            writeln!(output, "cfn={}", functions[address].name)?;
-            writeln!(output, "calls={} 0x{:x}", number_of_calls, address)?;
-            writeln!(output, "{} {}", pc, inclusive_cost)?;
+        } else if let Some((first_pc, _)) = f.costs.iter().next() {
+            // Syscalls have no immediate costs
+            let total = f.total_counters();
+            compression.write_costs(&mut output, *first_pc, f.cost(), &total[..counter_events])?;
+        }
+
+        // Dump statistics of callees at the call instructions, every call site separately
+        for ((pc, id), stats) in &call_statistics(f, true) {
+            if objects && object(&functions[id]) != object(f) {
+                writeln!(output, "cob={}", compression.object(object(&functions[id])))?;
+            }
+            writeln!(output, "cfn={}", compression.function(&functions[id].name))?;
+            writeln!(output, "calls={} {}", stats.calls, functions[id].first_pc())?;
+            compression.write_costs(
+                &mut output,
+                *pc,
+                stats.cost,
+                &stats.counters[..counter_events],
+            )?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes information about calls of functions and their costs by positions
+/// in source files: every function refers to the file of its first instruction,
+/// instructions and calls positioned in other files (e.g. inlined) switch the file
+/// with `fi=`. Functions without instructions refer to the default file.
+/// Functions are written in the order of the identifiers.
+pub fn write_callgrind_source_lines<'a>(
+    mut output: impl Write,
+    (functions, order): (&Functions, &[FrameId]),
+    root_enabled: bool,
+    counter_events: usize,
+    default_file: &'a str,
+    position: impl Fn(ProgramCounter) -> (&'a str, usize),
+    compress: bool,
+) -> Result<()> {
+    if global::verbose() {
+        tracing::info!("Writing callgrind profile by source lines...")
+    }
+
+    let file_of = |f: &Function| {
+        f.costs
+            .keys()
+            .next()
+            .map_or(default_file, |pc| position(*pc).0)
+    };
+    let mut compression = Compression::new(compress);
+    let objects = has_programs(functions);
+    let mut current_object = None;
+
+    for id in order {
+        let f = &functions[id];
+        if *id == FrameId::Root && !root_enabled {
+            continue;
+        }
+
+        let fl = file_of(f);
+        writeln!(output)?;
+        if objects && current_object != Some(object(f)) {
+            writeln!(output, "ob={}", compression.object(object(f)))?;
+            current_object = Some(object(f));
+        }
+        writeln!(output, "fl={}", compression.file(fl))?;
+        writeln!(output, "fn={}", compression.function(&f.name))?;
+        compression.start_function();
+        let mut file = fl;
+        let mut switch_file = |output: &mut dyn Write,
+                               compression: &mut Compression,
+                               pc_file: &'a str|
+         -> Result<()> {
+            if pc_file != file {
+                writeln!(output, "fi={}", compression.file(pc_file))?;
+                file = pc_file;
+            }
+            Ok(())
+        };
+
+        for (pc, cost) in &f.costs {
+            let (pc_file, line) = position(*pc);
+            switch_file(&mut output, &mut compression, pc_file)?;
+            let pc_counters = f.counters.get(pc).copied().unwrap_or_default();
+            compression.write_costs(&mut output, line, *cost, &pc_counters[..counter_events])?;
+        }
+
+        for ((pc, id), stats) in &call_statistics(f, true) {
+            let (pc_file, line) = position(*pc);
+            switch_file(&mut output, &mut compression, pc_file)?;
+            if objects && object(&functions[id]) != object(f) {
+                writeln!(output, "cob={}", compression.object(object(&functions[id])))?;
+            }
+            writeln!(output, "cfl={}", compression.file(file_of(&functions[id])))?;
+            writeln!(output, "cfn={}", compression.function(&functions[id].name))?;
+            // The target is the line of the first instruction of the callee with costs
+            let target = functions[id]
+                .costs
+                .keys()
+                .next()
+                .map_or(0, |pc| position(*pc).1);
+            writeln!(output, "calls={} {}", stats.calls, target)?;
+            compression.write_costs(
+                &mut output,
+                line,
+                stats.cost,
+                &stats.counters[..counter_events],
+            )?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes information about calls and costs of call paths instead of functions:
+/// every path from the root is a function named like functions separated by callers
+/// in callgrind, the innermost frame first, e.g. `helper'process'GROUND_ZERO`.
+/// Costs are positioned at the line zero; paths are written in the order of their frames.
+pub fn write_callgrind_paths(
+    mut output: impl Write,
+    (stacks, calls): (&Stacks, &PathCalls),
+    functions: &Functions,
+    root_enabled: bool,
+    compress: bool,
+) -> Result<()> {
+    if global::verbose() {
+        tracing::info!("Writing callgrind profile of call paths...")
+    }
+
+    // Every prefix of a call path is a call path too
+    let mut self_costs = BTreeMap::<&[FrameId], Cost>::new();
+    let mut inclusive_costs = Map::<&[FrameId], Cost>::new();
+    for (stack, cost) in stacks {
+        *self_costs.entry(stack).or_insert(0) += cost;
+        for depth in 0..=stack.len() {
+            self_costs.entry(&stack[..depth]).or_insert(0);
+            *inclusive_costs.entry(&stack[..depth]).or_insert(0) += cost;
+        }
+    }
+    let mut callees = Map::<&[FrameId], Vec<&[FrameId]>>::new();
+    for path in self_costs.keys().filter(|path| !path.is_empty()) {
+        callees
+            .entry(&path[..path.len() - 1])
+            .or_default()
+            .push(path);
+    }
+    let name = |path: &[FrameId]| {
+        let mut names = path
+            .iter()
+            .rev()
+            .map(|id| functions[id].name())
+            .collect::<Vec<_>>();
+        names.push(functions[&FrameId::Root].name());
+        names.join("'")
+    };
+
+    let mut compression = Compression::new(compress);
+    for (path, cost) in &self_costs {
+        if path.is_empty() && !root_enabled {
+            continue;
+        }
+        writeln!(output, "\nfn={}", compression.function(&name(path)))?;
+        compression.start_function();
+        if *cost > 0 {
+            compression.write_costs(&mut output, 0, *cost, &[])?;
+        }
+        for callee in callees.get(path).into_iter().flatten() {
+            // Frames entered before the start of the trace are called once
+            let count = calls.get(*callee).copied().unwrap_or(1);
+            writeln!(output, "cfn={}", compression.function(&name(callee)))?;
+            // Costs of call paths are at position 0, which is the target of calls too
+            writeln!(output, "calls={} 0", count)?;
+            compression.write_costs(&mut output, 0, inclusive_costs[callee], &[])?;
         }
     }
 
@@ -0,0 +1,202 @@
+//! bpf-profile-generate progress module.
+//! Implements periodic reports of progress of parsing a trace to the standard error,
+//! as text, as a progress bar for terminals or as JSON lines for GUIs and CI wrappers:
+//! `{"event":"progress","trace":"trace.log","bytes_read":1048576,"total_bytes":8388608,"lines":20480,"calls_open":7,"functions":12,"elapsed_secs":1.0,"eta_secs":7.0}`.
+//! The last record of a trace has the event `done`. The remaining time is estimated
+//! from the rate of reading if the size of the trace is known, i.e. it is a plain file.
+//! Calls open and functions found are unknown while the trace is parsed by several threads.
+
+use crate::filebuf;
+use crate::global;
+#[cfg(feature = "progress-bar")]
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Minimum time between reports.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of lines between checks of the time of the next report.
+pub const CHECK_LINES: usize = 1024;
+
+/// Represents a report of progress.
+#[derive(Serialize)]
+struct Record<'a> {
+    event: &'a str,
+    trace: &'a str,
+    bytes_read: u64,
+    total_bytes: Option<u64>,
+    lines: usize,
+    calls_open: Option<usize>,
+    functions: Option<usize>,
+    elapsed_secs: f64,
+    eta_secs: Option<f64>,
+}
+
+/// Represents the progress of parsing a trace.
+#[derive(Debug)]
+pub struct Progress {
+    trace: String,
+    total_bytes: Option<u64>,
+    json: bool,
+    start: Instant,
+    last: Instant,
+    #[cfg(feature = "progress-bar")]
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    /// Starts reporting progress of parsing the trace if enabled globally.
+    pub fn new(trace_path: &Path) -> Option<Self> {
+        let format = global::progress_format()?;
+        let now = Instant::now();
+        let trace = trace_path.display().to_string();
+        let total_bytes = filebuf::plain_size(trace_path);
+        Some(Progress {
+            #[cfg(feature = "progress-bar")]
+            bar: (format == "bar").then(|| bar(total_bytes)),
+            trace,
+            total_bytes,
+            json: format == "json",
+            start: now,
+            last: now,
+        })
+    }
+
+    /// Reports the progress unless reported less than the interval ago.
+    /// The progress bar is redrawn at its own rate.
+    pub fn update(
+        &mut self,
+        bytes_read: u64,
+        lines: usize,
+        calls_open: Option<usize>,
+        functions: Option<usize>,
+    ) {
+        #[cfg(feature = "progress-bar")]
+        if let Some(bar) = &self.bar {
+            bar.set_position(bytes_read);
+            bar.set_message(counts(lines, calls_open, functions));
+            return;
+        }
+        let now = Instant::now();
+        if now.duration_since(self.last) < INTERVAL {
+            return;
+        }
+        self.last = now;
+        self.report("progress", bytes_read, lines, calls_open, functions);
+    }
+
+    /// Reports the end of parsing. The progress bar is cleared.
+    pub fn finish(
+        self,
+        bytes_read: u64,
+        lines: usize,
+        calls_open: Option<usize>,
+        functions: Option<usize>,
+    ) {
+        #[cfg(feature = "progress-bar")]
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+            return;
+        }
+        self.report("done", bytes_read, lines, calls_open, functions);
+    }
+
+    /// Writes the report to the standard error, ignoring errors of writing.
+    fn report(
+        &self,
+        event: &str,
+        bytes_read: u64,
+        lines: usize,
+        calls_open: Option<usize>,
+        functions: Option<usize>,
+    ) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let eta = match (event, self.total_bytes) {
+            ("done", _) => Some(0.0),
+            (_, Some(total)) if bytes_read > 0 => {
+                Some(elapsed * total.saturating_sub(bytes_read) as f64 / bytes_read as f64)
+            }
+            _ => None,
+        };
+        let record = Record {
+            event,
+            trace: &self.trace,
+            bytes_read,
+            total_bytes: self.total_bytes,
+            lines,
+            calls_open,
+            functions,
+            elapsed_secs: elapsed,
+            eta_secs: eta,
+        };
+        let mut output = io::stderr().lock();
+        let _ = if self.json {
+            serde_json::to_writer(&mut output, &record)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(output))
+        } else {
+            writeln!(output, "{}", text(&record))
+        };
+    }
+}
+
+/// Formats the report as a line of text.
+fn text(record: &Record) -> String {
+    let mut text = format!(
+        "{}: {} bytes",
+        record.trace,
+        global::number(record.bytes_read as usize)
+    );
+    if let Some(total) = record.total_bytes.filter(|t| *t > 0) {
+        text += &format!(
+            " of {} ({:.0}%)",
+            global::number(total as usize),
+            100.0 * record.bytes_read as f64 / total as f64
+        );
+    }
+    text += &format!(
+        ", {}",
+        counts(record.lines, record.calls_open, record.functions)
+    );
+    match (record.event, record.eta_secs) {
+        ("done", _) => text += &format!(", done in {:.1}s", record.elapsed_secs),
+        (_, Some(eta)) => text += &format!(", ETA {:.0}s", eta),
+        _ => {}
+    }
+    text
+}
+
+/// Formats numbers of lines parsed, calls open and functions found, if known.
+fn counts(lines: usize, calls_open: Option<usize>, functions: Option<usize>) -> String {
+    let mut text = format!("{} lines", global::number(lines));
+    if let Some(calls_open) = calls_open {
+        text += &format!(", {} calls open", calls_open);
+    }
+    if let Some(functions) = functions {
+        text += &format!(", {} functions", functions);
+    }
+    text
+}
+
+/// Creates the progress bar of bytes of the trace read, a spinner if the size
+/// of the trace is unknown. The bar is short to fit terminals of 80 columns.
+#[cfg(feature = "progress-bar")]
+fn bar(total_bytes: Option<u64>) -> ProgressBar {
+    let (bar, template) = match total_bytes.filter(|t| *t > 0) {
+        Some(total) => (
+            ProgressBar::new(total),
+            "{bar:24} {percent:>3}% ETA {eta}, {msg}",
+        ),
+        None => {
+            let spinner = ProgressBar::new_spinner();
+            spinner.enable_steady_tick(Duration::from_millis(100));
+            (spinner, "{spinner} {bytes}, {msg}")
+        }
+    };
+    let style = ProgressStyle::with_template(template).expect("Invalid progress bar template");
+    bar.set_style(style);
+    bar
+}
@@ -0,0 +1,180 @@
+//! bpf-profile-generate query module.
+//! Implements queries over functions of a loaded profile:
+//! ```text
+//! top 10 by self
+//! top 5 by inclusive
+//! callers of process_instruction
+//! callees of process_instruction
+//! sum cost where name ~ 'borsh'
+//! ```
+//! Names of functions in `callers of` and `callees of` match by substring.
+
+use super::budget;
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::{Error, Result};
+use crate::global;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Text of the help on queries.
+pub const HELP: &str = "Queries:
+    top <number> [by self|inclusive]    most expensive functions
+    callers of <name>                   calls of functions matching the name by callers
+    callees of <name>                   calls from functions matching the name by callees
+    sum cost where name ~ '<regex>'     total immediate cost of matching functions
+    help                                this help
+    quit                                end the session";
+
+/// Represents kind of cost of functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum By {
+    /// Immediate cost of the function.
+    SelfCost,
+    /// Cost of the function including its callees.
+    Inclusive,
+}
+
+impl By {
+    /// Returns the kind of cost by its name: "self" or "inclusive".
+    pub fn from_name(name: &str) -> Option<By> {
+        match name {
+            "self" => Some(By::SelfCost),
+            "inclusive" => Some(By::Inclusive),
+            _ => None,
+        }
+    }
+}
+
+/// Represents a parsed query.
+#[derive(Debug)]
+pub enum Query {
+    /// The most expensive functions.
+    Top(usize, By),
+    /// Calls of functions matching the name grouped by callers.
+    Callers(String),
+    /// Calls from functions matching the name grouped by callees.
+    Callees(String),
+    /// Total immediate cost of functions with names matching the regex.
+    Sum(Regex),
+    /// Help on queries.
+    Help,
+}
+
+/// Parses the text of a query.
+pub fn parse(expr: &str) -> Result<Query> {
+    let invalid = || Error::Query(expr.into());
+    let words = expr.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["help"] => Ok(Query::Help),
+        ["top", n] => Ok(Query::Top(n.parse().map_err(|_| invalid())?, By::SelfCost)),
+        ["top", n, "by", by] => {
+            let by = By::from_name(by).ok_or_else(invalid)?;
+            Ok(Query::Top(n.parse().map_err(|_| invalid())?, by))
+        }
+        ["callers", "of", _, ..] => Ok(Query::Callers(rest(expr, 2))),
+        ["callees", "of", _, ..] => Ok(Query::Callees(rest(expr, 2))),
+        ["sum", "cost", "where", "name", "~", _, ..] => {
+            let pattern = rest(expr, 5);
+            let regex = Regex::new(&pattern).map_err(|_| invalid())?;
+            Ok(Query::Sum(regex))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Returns the text after the number of words, without enclosing quotes.
+fn rest(expr: &str, words: usize) -> String {
+    let mut rest = expr.trim_start();
+    for _ in 0..words {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+    let rest = rest.trim_end();
+    for quote in ['\'', '"'] {
+        if let Some(unquoted) = rest.strip_prefix(quote).and_then(|r| r.strip_suffix(quote)) {
+            return unquoted.into();
+        }
+    }
+    rest.into()
+}
+
+/// Writes the result of the query over the functions.
+pub fn run(
+    mut output: impl Write,
+    query: &Query,
+    functions: &Functions,
+    total_cost: Cost,
+) -> Result<()> {
+    match query {
+        Query::Help => writeln!(output, "{}", HELP)?,
+        Query::Top(n, by) => {
+            let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+            let mut costs = functions
+                .iter()
+                .filter(|(id, _)| **id != FrameId::Root)
+                .map(|(id, f)| {
+                    let cost = match by {
+                        By::SelfCost => f.cost(),
+                        By::Inclusive => inclusive_costs[id],
+                    };
+                    (cost, f.name())
+                })
+                .collect::<Vec<_>>();
+            costs.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            for (cost, name) in costs.into_iter().take(*n) {
+                writeln!(output, "{:>12}  {}", global::number(cost), name)?;
+            }
+        }
+        Query::Callers(name) | Query::Callees(name) => {
+            let callers = matches!(query, Query::Callers(_));
+            // Name of the matching function => other side => (calls, inclusive cost)
+            let mut found = BTreeMap::<String, Map<String, (usize, Cost)>>::new();
+            for f in functions.values() {
+                for c in f.calls() {
+                    let callee = &functions[&c.id()];
+                    let (matching, other) = if callers { (callee, f) } else { (f, callee) };
+                    if matching.name().contains(name.as_str()) {
+                        let entry = found
+                            .entry(matching.name())
+                            .or_default()
+                            .entry(other.name())
+                            .or_insert((0, 0));
+                        entry.0 += c.count();
+                        entry.1 += c.cost();
+                    }
+                }
+            }
+            for (matching, others) in found {
+                writeln!(output, "{}:", matching)?;
+                let mut others = others.into_iter().collect::<Vec<_>>();
+                others.sort_by(|a, b| b.1 .1.cmp(&a.1 .1).then_with(|| a.0.cmp(&b.0)));
+                for (other, (calls, cost)) in others {
+                    writeln!(
+                        output,
+                        "    {:>12}  calls={}  {}",
+                        global::number(cost),
+                        calls,
+                        other
+                    )?;
+                }
+            }
+        }
+        Query::Sum(regex) => {
+            let matching = functions
+                .values()
+                .filter(|f| regex.is_match(&f.name()))
+                .collect::<Vec<_>>();
+            let cost = matching.iter().map(|f| f.cost()).sum::<Cost>();
+            writeln!(
+                output,
+                "{} in {} function(s)",
+                global::number(cost),
+                matching.len()
+            )?;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! bpf-profile-generate regression module.
+//! Implements the guard of costs against the baseline: the JSON profile of an earlier
+//! run (`generate -f json`). The total cost and the inclusive cost of every function
+//! found in the baseline may not grow by more than the given percentage.
+
+use super::budget;
+use super::profile::Functions;
+use crate::config::{Cost, Map};
+use crate::error::{Error, Result};
+use crate::global;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Represents the JSON profile of the baseline, of which only costs are compared.
+#[derive(Debug, Deserialize)]
+pub struct Baseline {
+    total_cost: Cost,
+    #[serde(default)]
+    functions: Vec<BaselineFunction>,
+}
+
+/// Represents a function of the baseline.
+#[derive(Debug, Deserialize)]
+struct BaselineFunction {
+    name: String,
+    inclusive: Cost,
+}
+
+/// Reads the baseline from the JSON profile.
+pub fn read(path: &Path) -> Result<Baseline> {
+    let text = fs::read_to_string(path).map_err(|e| Error::OpenFile(e, path.into()))?;
+    serde_json::from_str(&text).map_err(|e| Error::ConfigParsing(path.into(), e.to_string()))
+}
+
+/// Parses the maximum growth of costs in percent, e.g. "5%" or "2.5".
+pub fn parse_percent(text: &str) -> Result<f64> {
+    text.trim()
+        .trim_end_matches('%')
+        .trim_end()
+        .parse::<f64>()
+        .ok()
+        .filter(|p| p.is_finite() && *p >= 0.0)
+        .ok_or_else(|| Error::Percentage(text.into()))
+}
+
+/// Represents a cost which grew beyond the threshold.
+#[derive(Debug, Serialize)]
+pub struct Regression {
+    /// Name of the function, none for the total cost.
+    pub function: Option<String>,
+    /// Cost of the baseline.
+    pub baseline: Cost,
+    /// Cost of the trace.
+    pub actual: Cost,
+    /// Growth of the cost in percent.
+    pub growth: f64,
+}
+
+impl Regression {
+    /// Returns the message describing the regression.
+    pub fn message(&self) -> String {
+        format!(
+            "{}: cost {} exceeds baseline {} by {:.1}%",
+            self.function.as_deref().unwrap_or("total"),
+            global::number(self.actual),
+            global::number(self.baseline),
+            self.growth
+        )
+    }
+}
+
+/// Compares the total cost and inclusive costs of functions with the baseline.
+/// Functions missing from the baseline are new, their costs count in the total only.
+pub fn regressions(
+    baseline: &Baseline,
+    functions: &Functions,
+    total_cost: Cost,
+    max_percent: f64,
+) -> Vec<Regression> {
+    let growth = |baseline: Cost, actual: Cost| match baseline {
+        0 => f64::INFINITY,
+        _ => 100.0 * (actual as f64 - baseline as f64) / baseline as f64,
+    };
+    let exceeds =
+        |baseline: Cost, actual: Cost| actual > baseline && growth(baseline, actual) > max_percent;
+
+    let mut regressions = Vec::new();
+    if exceeds(baseline.total_cost, total_cost) {
+        regressions.push(Regression {
+            function: None,
+            baseline: baseline.total_cost,
+            actual: total_cost,
+            growth: growth(baseline.total_cost, total_cost),
+        });
+    }
+
+    // Functions of the same name, e.g. copies at several addresses, are summed up
+    let sum_by_name = |costs: &mut Map<String, Cost>, name: String, cost: Cost| {
+        *costs.entry(name).or_insert(0) += cost;
+    };
+    let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+    let mut actual_costs = Map::new();
+    for (id, f) in functions {
+        sum_by_name(&mut actual_costs, f.name(), inclusive_costs[id]);
+    }
+    let mut baseline_costs = Map::new();
+    for f in &baseline.functions {
+        sum_by_name(&mut baseline_costs, f.name.clone(), f.inclusive);
+    }
+    let mut names = baseline_costs.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let (baseline, actual) = (baseline_costs[name], actual_costs.get(name).copied());
+        if let Some(actual) = actual.filter(|a| exceeds(baseline, *a)) {
+            regressions.push(Regression {
+                function: Some(name.clone()),
+                baseline,
+                actual,
+                growth: growth(baseline, actual),
+            });
+        }
+    }
+    regressions
+}
+
+/// Writes the comparison of the total cost with the baseline,
+/// followed by each regression as a line of text.
+pub fn write_text(
+    mut output: impl Write,
+    baseline: &Baseline,
+    total_cost: Cost,
+    regressions: &[Regression],
+) -> Result<()> {
+    writeln!(
+        output,
+        "Total cost {} (baseline {})",
+        global::number(total_cost),
+        global::number(baseline.total_cost)
+    )?;
+    for regression in regressions {
+        writeln!(output, "{}", regression.message())?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the regressions as a JSON array.
+pub fn write_json(mut output: impl Write, regressions: &[Regression]) -> Result<()> {
+    serde_json::to_writer_pretty(&mut output, regressions).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,544 @@
+//! bpf-profile-generate stats module.
+//! Implements the human-readable statistics report of the profile.
+
+use super::profile::{FrameId, Functions};
+use crate::bpf::Region;
+use crate::config::{
+    Address, Cost, Map, ProgramCounter, CALL_DEPTH_LIMIT, STACK_FRAME_LIMIT, STACK_LIMIT,
+    STACK_RISK_PERCENT,
+};
+use crate::error::Result;
+use crate::global;
+use crate::resolver::Resolver;
+use std::io::Write;
+
+pub type OpcodeClasses = Map<ProgramCounter, &'static str>;
+
+/// Numbers of executions of unknown opcodes by mnemonics.
+pub type UnknownOpcodes = Map<String, usize>;
+
+/// Represents recursive calls of a function.
+#[derive(Debug, Default)]
+pub struct Recursion {
+    /// Number of calls of the function by itself.
+    pub direct: usize,
+    /// Number of calls of the function by other functions called by it.
+    pub mutual: usize,
+    /// Maximum number of frames of the function on the call stack less one.
+    pub max_depth: usize,
+}
+
+impl Recursion {
+    /// Adds recursive calls of another part of the trace or of another function.
+    pub fn add(&mut self, other: &Recursion) {
+        self.direct += other.direct;
+        self.mutual += other.mutual;
+        self.max_depth = self.max_depth.max(other.max_depth);
+    }
+}
+
+/// Recursive calls by functions.
+pub type Recursions = Map<FrameId, Recursion>;
+
+/// Represents depths of the call stack at entries into a function.
+#[derive(Debug, Default)]
+pub struct Depth {
+    /// Number of frames of the function entered.
+    pub frames: usize,
+    /// Sum of depths at the entries.
+    pub total: usize,
+    /// Maximum depth at an entry.
+    pub max: usize,
+}
+
+impl Depth {
+    /// Adds entries of another part of the trace or of another function.
+    pub fn add(&mut self, other: &Depth) {
+        self.frames += other.frames;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Depths of the call stack by functions.
+pub type Depths = Map<FrameId, Depth>;
+
+/// Numbers of memory accesses by instructions and accessed regions.
+pub type MemoryRegions = Map<(ProgramCounter, Region), usize>;
+
+const TOP_CALLERS: usize = 3;
+
+/// Writes the statistics report: cost of each function broken down by opcode classes,
+/// executions of unknown opcodes, memory accesses of each function by regions,
+/// depths of the call stack at entries into functions, recursion of functions,
+/// usage of syscalls, functions of the dump never executed,
+/// functions of the trace not found in the dump and sizes of stack frames.
+pub fn write(
+    mut output: impl Write,
+    functions: &Functions,
+    classes: &OpcodeClasses,
+    unknown: &UnknownOpcodes,
+    regions: Option<&MemoryRegions>,
+    (depths, recursions): (&Depths, &Recursions),
+    resolver: &Resolver,
+) -> Result<()> {
+    writeln!(output, "# bpf-profile statistics")?;
+    write_opcode_classes(&mut output, functions, classes)?;
+    write_unknown_opcodes(&mut output, unknown)?;
+    if let Some(regions) = regions {
+        write_memory_regions(&mut output, functions, regions)?;
+    }
+    write_depths(&mut output, functions, depths)?;
+    write_recursions(&mut output, functions, recursions)?;
+    write_syscalls(&mut output, functions)?;
+    if !resolver.is_default() {
+        write_dead_code(&mut output, classes, resolver)?;
+        write_resolution(&mut output, functions, resolver)?;
+        write_stack_frames(&mut output, functions, resolver)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes cost of each function broken down by opcode classes.
+fn write_opcode_classes(
+    mut output: impl Write,
+    functions: &Functions,
+    classes: &OpcodeClasses,
+) -> Result<()> {
+    writeln!(output, "\nCost by opcode class:")?;
+    let mut functions = functions
+        .values()
+        .map(|f| (f.cost(), f))
+        .filter(|(cost, _)| *cost > 0)
+        .collect::<Vec<_>>();
+    functions.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name().cmp(&b.1.name())));
+
+    for (cost, f) in functions {
+        writeln!(output, "\n{} {}", f.name(), global::number(cost))?;
+
+        let mut by_class = Map::<&str, Cost>::new();
+        for (pc, c) in f.costs() {
+            let class = classes.get(pc).copied().unwrap_or("other");
+            *by_class.entry(class).or_insert(0) += c;
+        }
+        let mut by_class = by_class.into_iter().collect::<Vec<_>>();
+        by_class.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        for (class, c) in by_class {
+            writeln!(
+                output,
+                "    {:<12}{:>7.2}% {}",
+                class,
+                100.0 * c as f64 / cost as f64,
+                global::number(c)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes numbers of executions of unknown opcodes by mnemonics, most executed first.
+fn write_unknown_opcodes(mut output: impl Write, unknown: &UnknownOpcodes) -> Result<()> {
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(output, "\nUnknown opcodes:")?;
+    let mut unknown = unknown.iter().collect::<Vec<_>>();
+    unknown.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    for (mnemonic, n) in unknown {
+        writeln!(output, "    {:<12}{}", mnemonic, global::number(*n))?;
+    }
+    Ok(())
+}
+
+/// Writes numbers of memory accesses of each function broken down by regions.
+fn write_memory_regions(
+    mut output: impl Write,
+    functions: &Functions,
+    regions: &MemoryRegions,
+) -> Result<()> {
+    let owners = functions
+        .iter()
+        .flat_map(|(id, f)| f.costs().keys().map(move |pc| (*pc, *id)))
+        .collect::<Map<_, _>>();
+    let mut by_function = Map::<FrameId, Map<Region, usize>>::new();
+    for ((pc, region), n) in regions {
+        if let Some(id) = owners.get(pc) {
+            *by_function
+                .entry(*id)
+                .or_default()
+                .entry(*region)
+                .or_insert(0) += n;
+        }
+    }
+    if by_function.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(output, "\nMemory accesses by region:")?;
+    let mut by_function = by_function
+        .into_iter()
+        .map(|(id, r)| (r.values().sum::<usize>(), functions[&id].name(), r))
+        .collect::<Vec<_>>();
+    by_function.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    for (total, name, by_region) in by_function {
+        writeln!(output, "\n{} {}", name, global::number(total))?;
+        let mut by_region = by_region.into_iter().collect::<Vec<_>>();
+        by_region.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (region, n) in by_region {
+            writeln!(
+                output,
+                "    {:<12}{}",
+                region.to_string(),
+                global::number(n)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the maximum depth of the call stack against the limit, followed by
+/// the number of frames and the maximum and average depths at entries of each function.
+fn write_depths(mut output: impl Write, functions: &Functions, depths: &Depths) -> Result<()> {
+    let Some(max) = depths.values().map(|d| d.max).max() else {
+        return Ok(());
+    };
+    let risk = if is_stack_at_risk(max, CALL_DEPTH_LIMIT) {
+        " at risk"
+    } else {
+        ""
+    };
+
+    writeln!(
+        output,
+        "\nCall depth: max {} (limit {} frames){}",
+        max, CALL_DEPTH_LIMIT, risk
+    )?;
+    let mut depths = depths
+        .iter()
+        .map(|(id, d)| (functions[id].name(), d))
+        .collect::<Vec<_>>();
+    depths.sort_by(|a, b| b.1.max.cmp(&a.1.max).then_with(|| a.0.cmp(&b.0)));
+
+    for (name, d) in depths {
+        writeln!(
+            output,
+            "    {} frames={} max_depth={} avg_depth={:.2}",
+            name,
+            d.frames,
+            d.max,
+            d.total as f64 / d.frames as f64
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes numbers of recursive calls and maximum depth of recursion of each function.
+fn write_recursions(
+    mut output: impl Write,
+    functions: &Functions,
+    recursions: &Recursions,
+) -> Result<()> {
+    if recursions.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(output, "\nRecursion:")?;
+    let mut recursions = recursions
+        .iter()
+        .map(|(id, r)| (functions[id].name(), r))
+        .collect::<Vec<_>>();
+    recursions.sort_by(|a, b| {
+        b.1.max_depth
+            .cmp(&a.1.max_depth)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+
+    for (name, r) in recursions {
+        writeln!(
+            output,
+            "    {} direct={} mutual={} max_depth={}",
+            name, r.direct, r.mutual, r.max_depth
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Represents usage of a syscall.
+#[derive(Default)]
+struct Usage {
+    calls: usize,
+    cost: Cost,
+    callers: Map<FrameId, usize>,
+}
+
+/// Writes every invoked syscall with number of calls, attributed cost and top callers.
+fn write_syscalls(mut output: impl Write, functions: &Functions) -> Result<()> {
+    let mut usages = Map::<FrameId, Usage>::new();
+    for (caller, f) in functions {
+        for call in f.calls() {
+            if !call.id().is_syscall() {
+                continue;
+            }
+            let usage = usages.entry(call.id()).or_default();
+            usage.calls += call.count();
+            usage.cost += call.cost();
+            *usage.callers.entry(*caller).or_insert(0) += call.count();
+        }
+    }
+    if usages.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(output, "\nSyscalls:")?;
+    let mut usages = usages
+        .into_iter()
+        .map(|(id, u)| (functions[&id].name(), u))
+        .collect::<Vec<_>>();
+    usages.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then_with(|| a.0.cmp(&b.0)));
+
+    for (name, usage) in usages {
+        writeln!(
+            output,
+            "\n{} calls={} cost={}",
+            name,
+            global::number(usage.calls),
+            global::number(usage.cost)
+        )?;
+        let mut callers = usage
+            .callers
+            .into_iter()
+            .map(|(id, n)| (functions[&id].name(), n))
+            .collect::<Vec<_>>();
+        callers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (caller, n) in callers.into_iter().take(TOP_CALLERS) {
+            writeln!(output, "    {} {}", caller, n)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes functions of the dump which contain no executed instructions.
+fn write_dead_code(
+    mut output: impl Write,
+    classes: &OpcodeClasses,
+    resolver: &Resolver,
+) -> Result<()> {
+    let mut executed = classes.keys().copied().collect::<Vec<_>>();
+    executed.sort_unstable();
+
+    let functions = resolver.dump_functions();
+    let total = functions.len();
+    let mut dead = functions
+        .into_iter()
+        .filter(|(pc, _, size)| {
+            // Search the first executed pc after the function's beginning
+            let i = executed.partition_point(|e| e < pc);
+            executed.get(i).is_none_or(|e| *e >= pc + size)
+        })
+        .map(|(_, name, size)| (size, name))
+        .collect::<Vec<_>>();
+    dead.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    writeln!(
+        output,
+        "\nNever executed functions: {} of {}, {} instructions",
+        dead.len(),
+        total,
+        dead.iter().map(|(size, _)| size).sum::<usize>()
+    )?;
+    for (size, name) in dead {
+        writeln!(output, "    {} {}", name, size)?;
+    }
+
+    Ok(())
+}
+
+/// Represents a function of the trace not found in the dump, which got a generated name.
+#[derive(Debug)]
+pub struct Unresolved {
+    /// Address of the function.
+    pub address: Address,
+    /// First program counter of the function.
+    pub first_pc: ProgramCounter,
+    /// Generated name of the function.
+    pub name: String,
+    /// Immediate cost of the function.
+    pub cost: Cost,
+}
+
+/// Returns the number of functions of the trace resolved by the resolver
+/// and the unresolved ones (by decreasing cost).
+pub fn resolution(functions: &Functions, resolver: &Resolver) -> (usize, Vec<Unresolved>) {
+    let mut resolved = 0;
+    let mut unresolved = Vec::new();
+    for (id, f) in functions {
+        if let FrameId::Addr(address) = id {
+            if id.is_syscall() {
+                continue;
+            }
+            if resolver.is_resolved(*address) {
+                resolved += 1;
+            } else {
+                unresolved.push(Unresolved {
+                    address: *address,
+                    first_pc: resolver.first_pc(*address).unwrap_or_default(),
+                    name: f.name(),
+                    cost: f.cost(),
+                });
+            }
+        }
+    }
+    unresolved.sort_by(|a, b| b.cost.cmp(&a.cost).then_with(|| a.address.cmp(&b.address)));
+    (resolved, unresolved)
+}
+
+/// Writes the numbers of functions of the trace resolved from the dump and got
+/// generated names, and lists the unresolved ones with their first program counters,
+/// costs and shares of the total cost.
+fn write_resolution(
+    mut output: impl Write,
+    functions: &Functions,
+    resolver: &Resolver,
+) -> Result<()> {
+    let (resolved, unresolved) = resolution(functions, resolver);
+    let total_cost = functions.values().map(|f| f.cost()).sum::<Cost>();
+    let unresolved_cost = unresolved.iter().map(|u| u.cost).sum::<Cost>();
+    let share = |cost: Cost| 100.0 * cost as f64 / total_cost.max(1) as f64;
+    writeln!(
+        output,
+        "\nResolved functions: {} of {}, unresolved cost={} share={:.2}%",
+        resolved,
+        resolved + unresolved.len(),
+        global::number(unresolved_cost),
+        share(unresolved_cost)
+    )?;
+    for u in unresolved {
+        writeln!(
+            output,
+            "    0x{:x} {} first_pc={} cost={} share={:.2}%",
+            u.address,
+            u.name,
+            u.first_pc,
+            global::number(u.cost),
+            share(u.cost)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns names and sizes of stack frames of the functions of the trace
+/// found in the dump (by decreasing size); functions without frames are skipped.
+pub fn stack_frames(functions: &Functions, resolver: &Resolver) -> Vec<(String, usize)> {
+    let mut frames = functions
+        .iter()
+        .filter(|(id, _)| !id.is_syscall())
+        .filter_map(|(id, f)| match id {
+            FrameId::Addr(address) => Some((f.name(), resolver.frame_size(*address)?)),
+            _ => None,
+        })
+        .filter(|(_, size)| *size > 0)
+        .collect::<Vec<_>>();
+    frames.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    frames
+}
+
+/// Returns names and sizes of stack frames (if known) of the functions
+/// of the hottest path: from the root, the callee of the largest inclusive cost
+/// of every function, until a leaf or a recursive call.
+pub fn hottest_path(functions: &Functions, resolver: &Resolver) -> Vec<(String, Option<usize>)> {
+    let mut path = vec![FrameId::Root];
+    loop {
+        let caller = path[path.len() - 1];
+        let mut costs = Map::<FrameId, Cost>::new();
+        for call in functions[&caller].calls() {
+            if !call.id().is_syscall() {
+                *costs.entry(call.id()).or_insert(0) += call.cost();
+            }
+        }
+        let hottest = costs
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)));
+        match hottest {
+            Some((callee, _)) if !path.contains(&callee) => path.push(callee),
+            _ => break,
+        }
+    }
+    path.into_iter()
+        .skip(1)
+        .map(|id| {
+            let size = match id {
+                FrameId::Addr(address) => resolver.frame_size(address),
+                _ => None,
+            };
+            (functions[&id].name(), size)
+        })
+        .collect()
+}
+
+/// Checks if the stack usage is close to the limit.
+pub fn is_stack_at_risk(size: usize, limit: usize) -> bool {
+    size * 100 >= limit * STACK_RISK_PERCENT
+}
+
+/// Writes sizes of stack frames of the functions and the stack usage
+/// of the hottest path, marking the ones close to the limits.
+fn write_stack_frames(
+    mut output: impl Write,
+    functions: &Functions,
+    resolver: &Resolver,
+) -> Result<()> {
+    let frames = stack_frames(functions, resolver);
+    if frames.is_empty() {
+        return Ok(());
+    }
+    let risk = |size, limit| {
+        if is_stack_at_risk(size, limit) {
+            " at risk"
+        } else {
+            ""
+        }
+    };
+
+    writeln!(
+        output,
+        "\nStack frames (limit {} bytes):",
+        STACK_FRAME_LIMIT
+    )?;
+    for (name, size) in frames {
+        writeln!(
+            output,
+            "    {} {}{}",
+            name,
+            size,
+            risk(size, STACK_FRAME_LIMIT)
+        )?;
+    }
+
+    let path = hottest_path(functions, resolver);
+    let total = path.iter().filter_map(|(_, size)| *size).sum::<usize>();
+    writeln!(
+        output,
+        "\nStack of the hottest path: {} bytes (limit {}){}",
+        total,
+        STACK_LIMIT,
+        risk(total, STACK_LIMIT)
+    )?;
+    for (name, size) in path {
+        match size {
+            Some(size) => writeln!(output, "    {} {}", name, size)?,
+            None => writeln!(output, "    {} ?", name)?,
+        }
+    }
+
+    Ok(())
+}
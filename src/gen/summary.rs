@@ -0,0 +1,103 @@
+//! bpf-profile-generate summary module.
+//! Implements the table of the most expensive functions with their shares
+//! of the total cost, numbers of calls and average costs per call,
+//! as plain text or as CSV.
+
+use super::budget;
+use super::profile::{FrameId, Functions};
+use super::query::By;
+use crate::config::{Cost, Map};
+use crate::error::Result;
+use crate::global;
+use std::io::Write;
+
+/// Represents a row of the summary.
+struct Row {
+    name: String,
+    self_cost: Cost,
+    inclusive: Cost,
+    calls: usize,
+}
+
+impl Row {
+    /// Returns the average inclusive cost per call.
+    /// The root and functions where the trace starts are entered once.
+    fn per_call(&self) -> f64 {
+        self.inclusive as f64 / self.calls.max(1) as f64
+    }
+}
+
+/// Writes the top functions by the kind of cost.
+pub fn write(
+    mut output: impl Write,
+    functions: &Functions,
+    total_cost: Cost,
+    top: usize,
+    by: By,
+    csv: bool,
+) -> Result<()> {
+    let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+    let mut calls = Map::<FrameId, usize>::new();
+    for c in functions.values().flat_map(|f| f.calls()) {
+        *calls.entry(c.id()).or_insert(0) += c.count();
+    }
+    let mut rows = functions
+        .iter()
+        .filter(|(id, _)| **id != FrameId::Root)
+        .map(|(id, f)| Row {
+            name: f.name(),
+            self_cost: f.cost(),
+            inclusive: inclusive_costs[id],
+            calls: calls.get(id).copied().unwrap_or(0),
+        })
+        .collect::<Vec<_>>();
+    let key = |row: &Row| match by {
+        By::SelfCost => row.self_cost,
+        By::Inclusive => row.inclusive,
+    };
+    rows.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.name.cmp(&b.name)));
+    rows.truncate(top);
+    let percent = |cost: Cost| 100.0 * cost as f64 / total_cost.max(1) as f64;
+
+    if csv {
+        writeln!(
+            output,
+            "function,self,self %,inclusive,inclusive %,calls,per call"
+        )?;
+        for row in rows {
+            writeln!(
+                output,
+                "\"{}\",{},{:.2},{},{:.2},{},{:.1}",
+                row.name.replace('"', "\"\""),
+                row.self_cost,
+                percent(row.self_cost),
+                row.inclusive,
+                percent(row.inclusive),
+                row.calls,
+                row.per_call()
+            )?;
+        }
+    } else {
+        writeln!(
+            output,
+            "{:>12} {:>7} {:>12} {:>7} {:>10} {:>12}  function",
+            "self", "self %", "inclusive", "incl %", "calls", "per call"
+        )?;
+        for row in rows {
+            writeln!(
+                output,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>10} {:>12.1}  {}",
+                global::number(row.self_cost),
+                percent(row.self_cost),
+                global::number(row.inclusive),
+                percent(row.inclusive),
+                global::number(row.calls),
+                row.per_call(),
+                row.name
+            )?;
+        }
+        writeln!(output, "Total: {}", global::number(total_cost))?;
+    }
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! bpf-profile-generate tail module.
+//! Implements reading the trace file while it grows, e.g. while the validator
+//! is still writing it, by whole lines appended since the last read.
+
+use crate::error::{Error, Result};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Represents the growing trace file.
+#[derive(Debug)]
+pub(super) struct Tail {
+    path: PathBuf,
+    offset: u64,
+    pending: Vec<u8>,
+}
+
+impl Tail {
+    /// Starts reading the trace file from its beginning.
+    pub(super) fn new(path: &Path) -> Self {
+        Tail {
+            path: path.into(),
+            offset: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Checks if the file got shorter than the part read already,
+    /// e.g. it was truncated or replaced, and starts reading it over then.
+    pub(super) fn restarted(&mut self) -> Result<bool> {
+        let len = fs::metadata(&self.path)
+            .map_err(|e| Error::OpenFile(e, self.path.clone()))?
+            .len();
+        if len >= self.offset {
+            return Ok(false);
+        }
+        self.offset = 0;
+        self.pending.clear();
+        Ok(true)
+    }
+
+    /// Returns whole lines appended to the file since the last read.
+    /// A trailing call is kept until the next line, the first instruction of the callee,
+    /// is appended, so calls are not taken for calls cut off at the end of the trace.
+    pub(super) fn read(&mut self) -> Result<String> {
+        let mut file = File::open(&self.path).map_err(|e| Error::OpenFile(e, self.path.clone()))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let read = file.read_to_end(&mut self.pending)?;
+        self.offset += read as u64;
+
+        let mut end = match self.pending.iter().rposition(|b| *b == b'\n') {
+            Some(last) => last + 1,
+            None => return Ok(String::new()),
+        };
+        // States of registers and frames may separate the call from the callee
+        while end > 0 {
+            let last_line_start = self.pending[..end - 1]
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |i| i + 1);
+            let last_line = String::from_utf8_lossy(&self.pending[last_line_start..end]);
+            if crate::trace::is_state_line(&last_line) {
+                end = last_line_start;
+                continue;
+            }
+            if crate::trace::parse_instruction(&last_line).is_some_and(|ix| ix.is_call()) {
+                end = last_line_start;
+            }
+            break;
+        }
+        let lines = self.pending.drain(..end).collect::<Vec<_>>();
+        Ok(String::from_utf8_lossy(&lines).into_owned())
+    }
+}
@@ -32,30 +32,130 @@ pub const SIMPLE_INPUT: &[u8] = b"
 25 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 11: ddd
 ";
 
+pub const REGISTER_LINES_INPUT: &[u8] = b"
+# SIMPLE_INPUT in the format of newer validators: registers and frames on separate lines.
+[Z TRACE bpf] BPF Program Instruction Trace (format 2):
+frame 0 enter
+    r1=0x400000000 r10=0x200001000
+1 1: aaa
+2 2: bbb
+3 3: call 0x100
+frame 1 enter 0x100
+    r1=0x400000004
+4 4: xxx
+5 5: call 0x200
+frame 2 enter 0x200
+    r1=0x400000014
+6 20: yyy
+7 21: call 0x300
+frame 3 enter 0x300
+    r1=0x40000001e
+8 30: zzz
+9 31: exit
+frame 3 exit
+10 22: call 0x300
+frame 3 enter 0x300
+    r1=0x40000001e
+11 30: zzz
+12 31: exit
+frame 3 exit
+13 23: exit
+frame 2 exit
+14 6: call 0x300
+frame 2 enter 0x300
+    r1=0x40000001e
+15 30: zzz
+16 31: exit
+frame 2 exit
+17 7: call 0x300
+frame 2 enter 0x300
+    r1=0x40000001e
+18 30: zzz
+19 31: exit
+frame 2 exit
+20 8: call 0x300
+frame 2 enter 0x300
+    r1=0x40000001e
+21 30: zzz
+22 31: exit
+frame 2 exit
+23 9: exit
+frame 1 exit
+24 10: ccc
+25 11: ddd
+";
+
 pub const SIMPLE_CALLGRIND_INTEGRAL: &[u8] = b"# callgrind format
 version: 1
 creator: bpf-profile
 positions: line
 events: Instructions
-totals: 25
+summary: 20
+totals: 20
 fl=<none>
 
-fn=function_0 (0x100)
+fn=function_4 (0x100)
 4 6
-cfn=function_1 (0x200)
-calls=1 0x200
+cfn=function_20 (0x200)
+calls=1 20
 5 8
-cfn=function_2 (0x300)
-calls=3 0x300
-6 6
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fn=function_20 (0x200)
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+
+fn=function_30 (0x300)
+30 10
+";
+
+pub const SIMPLE_CALLGRIND_COMPRESSED: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fn=(1) function_4 (0x100)
+4 6
+cfn=(2) function_20 (0x200)
+calls=1 20
++1 8
+cfn=(3) function_30 (0x300)
+calls=1 30
++1 2
+cfn=(3)
+calls=1 30
++1 2
+cfn=(3)
+calls=1 30
++1 2
 
-fn=function_1 (0x200)
+fn=(2)
 20 4
-cfn=function_2 (0x300)
-calls=2 0x300
-21 4
+cfn=(3)
+calls=1 30
++1 2
+cfn=(3)
+calls=1 30
++1 2
 
-fn=function_2 (0x300)
+fn=(3)
 30 10
 ";
 
@@ -64,54 +164,55 @@ version: 1
 creator: bpf-profile
 positions: line
 events: Instructions
-totals: 25
+summary: 20
+totals: 20
 fl=/tmp/generate_line_by_line.asm
 
-fn=function_0 (0x100)
+fn=function_4 (0x100)
 4 1
 5 1
 6 1
 7 1
 8 1
 9 1
-cfn=function_1 (0x200)
-calls=1 0x200
+cfn=function_20 (0x200)
+calls=1 20
 5 8
-cfn=function_2 (0x300)
-calls=1 0x300
+cfn=function_30 (0x300)
+calls=1 30
 6 2
-cfn=function_2 (0x300)
-calls=1 0x300
+cfn=function_30 (0x300)
+calls=1 30
 7 2
-cfn=function_2 (0x300)
-calls=1 0x300
+cfn=function_30 (0x300)
+calls=1 30
 8 2
 
-fn=function_1 (0x200)
+fn=function_20 (0x200)
 20 1
 21 1
 22 1
 23 1
-cfn=function_2 (0x300)
-calls=1 0x300
+cfn=function_30 (0x300)
+calls=1 30
 21 2
-cfn=function_2 (0x300)
-calls=1 0x300
+cfn=function_30 (0x300)
+calls=1 30
 22 2
 
-fn=function_2 (0x300)
+fn=function_30 (0x300)
 30 5
 31 5
 ";
 
 pub const SIMPLE_GENERATED_ASM: &str = r"1:        aaa
 2:        bbb
-3:        call function_0 (0x100)
-4:        xxx        ; function_0 (0x100)
-5:        call function_1 (0x200)
-6:        call function_2 (0x300)
-7:        call function_2 (0x300)
-8:        call function_2 (0x300)
+3:        call function_4 (0x100)
+4:        xxx        ; function_4 (0x100)
+5:        call function_20 (0x200)
+6:        call function_30 (0x300)
+7:        call function_30 (0x300)
+8:        call function_30 (0x300)
 9:        exit
 10:        ccc
 11:        ddd
@@ -123,9 +224,9 @@ pub const SIMPLE_GENERATED_ASM: &str = r"1:        aaa
 
 
 
-20:        yyy        ; function_1 (0x200)
-21:        call function_2 (0x300)
-22:        call function_2 (0x300)
+20:        yyy        ; function_20 (0x200)
+21:        call function_30 (0x300)
+22:        call function_30 (0x300)
 23:        exit
 
 
@@ -133,6 +234,1159 @@ pub const SIMPLE_GENERATED_ASM: &str = r"1:        aaa
 
 
 
-30:        zzz        ; function_2 (0x300)
+30:        zzz        ; function_30 (0x300)
 31:        exit
 ";
+
+pub const SIMPLE_METRICS: &[u8] =
+    br#"# HELP bpf_profile_total_cost Total cost of the traced program.
+# TYPE bpf_profile_total_cost gauge
+bpf_profile_total_cost 25
+# HELP bpf_profile_exceeds_compute_limit Total cost exceeds the transaction compute limit.
+# TYPE bpf_profile_exceeds_compute_limit gauge
+bpf_profile_exceeds_compute_limit 0
+# HELP bpf_profile_function_cost Self cost of the most expensive functions.
+# TYPE bpf_profile_function_cost gauge
+bpf_profile_function_cost{function="function_30 (0x300)"} 10
+bpf_profile_function_cost{function="function_4 (0x100)"} 6
+bpf_profile_function_cost{function="function_20 (0x200)"} 4
+# HELP bpf_profile_unresolved_functions Number of functions not found in the dump.
+# TYPE bpf_profile_unresolved_functions gauge
+bpf_profile_unresolved_functions 3
+"#;
+
+pub const SIMPLE_BUDGET_VIOLATIONS: &str =
+    "function_30 (0x300): self cost 10 exceeds budget 5 of 'function_?*'
+function_4 (0x100): self cost 6 exceeds budget 5 of 'function_?*'
+function_4 (0x100): inclusive cost 20 exceeds budget 19 of 'function_4 (0x100)'
+";
+
+pub const SIMPLE_BUDGET_JUNIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="bpf-profile" tests="3" failures="2">
+  <testsuite name="budgets" tests="3" failures="2">
+    <testcase classname="budgets" name="function_20 (0x200)"/>
+    <testcase classname="budgets" name="function_30 (0x300)">
+      <failure type="budget/self" message="function_30 (0x300): self cost 10 exceeds budget 5 of &apos;function_?*&apos;"/>
+    </testcase>
+    <testcase classname="budgets" name="function_4 (0x100)">
+      <failure type="budget/self" message="function_4 (0x100): self cost 6 exceeds budget 5 of &apos;function_?*&apos;"/>
+      <failure type="budget/inclusive" message="function_4 (0x100): inclusive cost 20 exceeds budget 19 of &apos;function_4 (0x100)&apos;"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+
+pub const SIMPLE_HEATMAP: &[u8] = b"instruction,depth_0,depth_1,depth_2,depth_3
+0,3,2,3,2
+10,0,3,5,2
+20,2,1,2,0
+";
+
+pub const SIMPLE_STATS: &[u8] = b"# bpf-profile statistics
+
+Cost by opcode class:
+
+function_30 (0x300) 10
+    exit          50.00% 5
+    unknown       50.00% 5
+
+function_4 (0x100) 6
+    call          66.67% 4
+    exit          16.67% 1
+    unknown       16.67% 1
+
+GROUND_ZERO 5
+    unknown       80.00% 4
+    call          20.00% 1
+
+function_20 (0x200) 4
+    call          50.00% 2
+    exit          25.00% 1
+    unknown       25.00% 1
+
+Unknown opcodes:
+    zzz         5
+    aaa         1
+    bbb         1
+    ccc         1
+    ddd         1
+    xxx         1
+    yyy         1
+
+Call depth: max 4 (limit 64 frames)
+    function_30 (0x300) frames=5 max_depth=4 avg_depth=3.40
+    function_20 (0x200) frames=1 max_depth=3 avg_depth=3.00
+    function_4 (0x100) frames=1 max_depth=2 avg_depth=2.00
+";
+
+pub const SIMPLE_INSTRUCTIONS: &[u8] = b"Executed instructions by mnemonic:
+    call      call          28.00% 7
+    exit      exit          28.00% 7
+    zzz       unknown       20.00% 5
+    aaa       unknown        4.00% 1
+    bbb       unknown        4.00% 1
+    ccc       unknown        4.00% 1
+    ddd       unknown        4.00% 1
+    xxx       unknown        4.00% 1
+    yyy       unknown        4.00% 1
+    Total: 25
+
+function_30 (0x300) 10
+    exit      exit          50.00% 5
+    zzz       unknown       50.00% 5
+    Total: 10
+
+function_4 (0x100) 6
+    call      call          66.67% 4
+    exit      exit          16.67% 1
+    xxx       unknown       16.67% 1
+    Total: 6
+
+GROUND_ZERO 5
+    aaa       unknown       20.00% 1
+    bbb       unknown       20.00% 1
+    call      call          20.00% 1
+    ccc       unknown       20.00% 1
+    ddd       unknown       20.00% 1
+    Total: 5
+
+function_20 (0x200) 4
+    call      call          50.00% 2
+    exit      exit          25.00% 1
+    yyy       unknown       25.00% 1
+    Total: 4
+";
+
+pub const SYSCALL_INPUT: &[u8] = b"
+# Function 0x100 calls syscall sol_log_ (0x207559bd) twice.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: call 0x100
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: call 0x207559bd
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 11: call 0x207559bd
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 12: exit
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: exit
+";
+
+pub const SYSCALL_STATS: &str = "
+Syscalls:
+
+sol_log_ calls=2 cost=0
+    function_10 (0x100) 2
+";
+
+pub const NESTED_INPUT: &[u8] = b"
+# Function 0x100 invokes another program by sol_invoke_signed_rust (0xd7449092),
+# its entrypoint at 100 calls 0x500 once.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: aaa
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: call 0x100
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: bbb
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 11: call 0xd7449092
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 100: ccc
+ 6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 101: call 0x500
+ 7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 200: ddd
+ 8 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 201: exit
+ 9 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 102: exit
+Program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin success
+10 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 12: eee
+11 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+12 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 3: fff
+13 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 4: exit
+";
+
+pub const NESTED_CALLGRIND: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 9
+totals: 9
+fl=<none>
+
+fn=function_10 (0x100)
+10 4
+cfn=sol_invoke_signed_rust
+calls=1 100
+11 5
+
+fn=function_200 (0x500)
+200 2
+
+fn=sol_invoke_signed_rust
+100 3
+cfn=function_200 (0x500)
+calls=1 200
+101 2
+";
+
+pub const NESTED_CALLGRIND_PROGRAMS: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 13
+totals: 13
+fl=<none>
+
+ob=TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+fn=function_10 (0x100)
+10 4
+cfn=sol_invoke_signed_rust
+calls=1 100
+11 5
+
+ob=9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin
+fn=function_200 (0x500)
+200 2
+
+ob=TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+fn=sol_invoke_signed_rust
+100 3
+cob=9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin
+cfn=function_200 (0x500)
+calls=1 200
+101 2
+
+fn=entrypoint
+1 4
+cfn=function_10 (0x100)
+calls=1 10
+2 9
+";
+
+pub const NESTED_TREE: &[u8] = b"          13  100.00%  GROUND_ZERO
+           9   69.23%    function_10 (0x100)
+           5   38.46%      sol_invoke_signed_rust
+           2   15.38%        function_200 (0x500)
+";
+
+pub const NESTED_TREE_LIMITED: &[u8] = b"           9   69.23%  function_10 (0x100)
+           5   38.46%    sol_invoke_signed_rust
+";
+
+pub const NESTED_CALLGRIND_FLATTENED: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 9
+totals: 9
+fl=<none>
+
+fn=function_10 (0x100)
+10 7
+cfn=sol_invoke_signed_rust
+calls=1 0
+11 0
+cfn=function_200 (0x500)
+calls=1 200
+101 2
+
+fn=function_200 (0x500)
+200 2
+
+fn=sol_invoke_signed_rust
+";
+
+pub const STACK_STATS: &str = "
+Stack frames (limit 4096 bytes):
+    process 3840 at risk
+    helper 64
+
+Stack of the hottest path: 3904 bytes (limit 262144)
+    function_4 (0x100) ?
+    process 3840
+    helper 64
+";
+
+pub const SIMPLE_LCOV: &[u8] = b"TN:
+SF:<none>
+FN:1,GROUND_ZERO
+FN:4,function_4 (0x100)
+FN:20,function_20 (0x200)
+FN:30,function_30 (0x300)
+FNDA:1,GROUND_ZERO
+FNDA:1,function_4 (0x100)
+FNDA:1,function_20 (0x200)
+FNDA:5,function_30 (0x300)
+FNF:4
+FNH:4
+DA:1,1
+DA:2,1
+DA:3,1
+DA:4,1
+DA:5,1
+DA:6,1
+DA:7,1
+DA:8,1
+DA:9,1
+DA:10,1
+DA:11,1
+DA:20,1
+DA:21,1
+DA:22,1
+DA:23,1
+DA:30,5
+DA:31,5
+LF:17
+LH:17
+end_of_record
+";
+
+pub const SIMPLE_CALLGRIND_CALLS_ONLY: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 0
+totals: 0
+fl=<none>
+
+fn=function_4 (0x100)
+cfn=function_20 (0x200)
+calls=1 0
+5 0
+cfn=function_30 (0x300)
+calls=1 0
+6 0
+cfn=function_30 (0x300)
+calls=1 0
+7 0
+cfn=function_30 (0x300)
+calls=1 0
+8 0
+
+fn=function_20 (0x200)
+cfn=function_30 (0x300)
+calls=1 0
+21 0
+cfn=function_30 (0x300)
+calls=1 0
+22 0
+
+fn=function_30 (0x300)
+";
+
+pub const MIDDLE_INPUT: &[u8] = b"
+# The trace starts in the middle of a function which calls 0x300
+# and returns to the entrypoint, which calls 0x300 as well.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: yyy
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 21: call 0x300
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 30: zzz
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 31: exit
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 22: exit
+ 6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 6: call 0x300
+ 7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 30: zzz
+ 8 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 31: exit
+ 9 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 7: exit
+";
+
+pub const MIDDLE_CALLGRIND: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 7
+totals: 7
+fl=/tmp/generate_trace_start.asm
+
+fn=function_30 (0x300)
+30 2
+31 2
+
+fn=<trace-start>
+20 1
+21 1
+22 1
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+";
+
+pub const SECOND_INPUT: &[u8] = b"
+# Another entrypoint of the program of SIMPLE_INPUT:
+# function 0x200 is called once and calls new function 0x400.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: aaa
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 12: call 0x200
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: yyy
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 24: call 0x400
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 40: www
+ 6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 41: exit
+ 7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 25: exit
+ 8 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+";
+
+pub const MERGED_CALLGRIND: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 25
+totals: 25
+fl=/tmp/generate_merged.asm
+
+fn=function_4 (0x100)
+4 1
+5 1
+6 1
+7 1
+8 1
+9 1
+cfn=function_20 (0x200)
+calls=1 20
+5 8
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fn=function_20 (0x200)
+20 2
+21 1
+22 1
+23 1
+24 1
+25 1
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+cfn=function_40 (0x400)
+calls=1 40
+24 2
+
+fn=function_30 (0x300)
+30 5
+31 5
+
+fn=function_40 (0x400)
+40 1
+41 1
+";
+
+pub const SIMPLE_CALLGRIND_ROOT_NAME: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 25
+totals: 25
+fl=<none>
+
+fn=function_4 (0x100)
+4 6
+cfn=function_20 (0x200)
+calls=1 20
+5 8
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fn=function_20 (0x200)
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+
+fn=function_30 (0x300)
+30 10
+
+fn=test_case
+1 5
+cfn=function_4 (0x100)
+calls=1 4
+3 20
+";
+
+pub const MAX_ADDRESS_INPUT: &[u8] = b"
+# The function has the largest possible address.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: aaa
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: call 0xffffffffffffffff
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 40: www
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 41: exit
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 3: exit
+";
+
+pub const MEMORY_INPUT: &[u8] = b"
+# Function 0x100 reads the input twice and writes the stack once,
+# the ground reads the heap once.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 400000000, 0, 0, 0, 0, 0, 0, 0, 0, 200001000] 1: call 0x100
+ 2 [0, 400000000, 0, 0, 0, 0, 0, 0, 0, 0, 200002000] 10: ldxdw r2, [r1+0x8]
+ 3 [0, 400000000, 5, 0, 0, 0, 0, 0, 0, 0, 200002000] 11: stxdw [r10-0x8], r2
+ 4 [0, 400000000, 5, 0, 0, 0, 0, 0, 0, 0, 200002000] 12: ldxb r3, [r1]
+ 5 [0, 400000000, 5, 1, 0, 0, 0, 0, 0, 0, 200002000] 13: exit
+ 6 [0, 400000000, 5, 1, 300000010, 0, 0, 0, 0, 0, 200001000] 2: ldxw r0, [r4-0x10]
+ 7 [0, 400000000, 5, 1, 300000010, 0, 0, 0, 0, 0, 200001000] 3: exit
+";
+
+pub const MEMORY_CALLGRIND_COUNTERS: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions Reads Writes Syscalls
+summary: 4 2 1 0
+totals: 4 2 1 0
+fl=<none>
+
+fn=function_10 (0x100)
+10 4 2 1 0
+";
+
+pub const MEMORY_STATS: &[u8] = b"# bpf-profile statistics
+
+Cost by opcode class:
+
+function_10 (0x100) 4
+    exit          25.00% 1
+    load 1B       25.00% 1
+    load 8B       25.00% 1
+    store 8B      25.00% 1
+
+GROUND_ZERO 3
+    call          33.33% 1
+    exit          33.33% 1
+    load 4B       33.33% 1
+
+Memory accesses by region:
+
+function_10 (0x100) 3
+    input       2
+    stack       1
+
+GROUND_ZERO 1
+    heap        1
+
+Call depth: max 2 (limit 64 frames)
+    function_10 (0x100) frames=1 max_depth=2 avg_depth=2.00
+";
+
+pub const SYSCALL_SIZES_INPUT: &[u8] = b"
+# Function 0x100 calls syscall sol_log_ (0x207559bd) with lengths 16, 64 and 8192.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: call 0x100
+ 2 [0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0] 10: call 0x207559bd
+ 3 [0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 0] 11: call 0x207559bd
+ 4 [0, 0, 2000, 0, 0, 0, 0, 0, 0, 0, 0] 12: call 0x207559bd
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+ 6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: exit
+";
+
+pub const SIMPLE_DIFF_CALLGRIND: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: -3
+totals: -3
+fl=<none>
+
+fn=function_20 (0x200)
+20 0
+cfn=function_30 (0x300)
+calls=2 30
+21 0
+
+fn=function_30 (0x300)
+30 -2
+
+fn=function_4 (0x100)
+4 -1
+cfn=function_20 (0x200)
+calls=1 20
+5 0
+cfn=function_30 (0x300)
+calls=2 30
+7 -2
+";
+
+pub const SIMPLE_DUMP: &[u8] = b"
+ELF Header:
+  Class: ELF64
+
+Disassembly of section .text:
+
+0000000000000100 <process>:
+      20       07 00 00 00 00 00 00 00 yyy
+      21       85 10 00 00 00 00 00 00 call 0x300
+      22       85 10 00 00 00 00 00 00 call 0x300
+      23       95 00 00 00 00 00 00 00 exit
+";
+
+pub const SIMPLE_CALLGRIND_WINDOW: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 8
+totals: 8
+fl=<none>
+
+fn=function_20 (0x200)
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+
+fn=function_30 (0x300)
+30 4
+";
+
+pub const SIMPLE_CALLGRIND_IGNORE_BEFORE: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 19
+totals: 19
+fl=<none>
+
+fn=process
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+
+fn=function_30 (0x300)
+30 10
+
+fn=<trace-start>
+5 5
+cfn=process
+calls=1 20
+5 8
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+";
+
+pub const RECURSION_INPUT: &[u8] = b"
+# Function 0x100 calls itself, then function 0x200 which calls 0x100 again.
+[Z TRACE bpf] BPF Program Instruction Trace:
+ 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: call 0x100
+ 2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: jne r1, 0, +1
+ 3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 11: call 0x100
+ 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: jne r1, 0, +1
+ 5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 12: call 0x200
+ 6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: call 0x100
+ 7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: jne r1, 0, +1
+ 8 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+ 9 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 21: exit
+10 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+11 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 13: exit
+12 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: exit
+";
+
+pub const SIMPLE_CALLGRIND_ONLY_FUNCTION: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 8
+totals: 8
+fl=<none>
+
+fn=process
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+
+fn=function_30 (0x300)
+30 4
+";
+
+pub const SIMPLE_FOLDED: &[u8] = b"GROUND_ZERO 5
+GROUND_ZERO;function_4 (0x100) 6
+GROUND_ZERO;function_4 (0x100);function_20 (0x200) 4
+GROUND_ZERO;function_4 (0x100);function_20 (0x200);function_30 (0x300) 4
+GROUND_ZERO;function_4 (0x100);function_30 (0x300) 6
+";
+
+pub const SIMPLE_DIFF_TABLE: &[u8] =
+    b"    self old     self new   self delta     incl old     incl new   incl delta  function
+           5            5            0           25           22           -3  GROUND_ZERO
+           6            5           -1           20           17           -3  function_4 (0x100)
+          10            8           -2           10            8           -2  function_30 (0x300)
+Total: 25 -> 22 (-3)
+";
+
+pub const SIMPLE_DIFF_TABLE_PER_CALL: &[u8] = b" calls old  calls new  self/call old  self/call new     self delta  incl/call old  incl/call new     incl delta  function
+         0          0            5.0            5.0              0           25.0           22.0           -3.0  GROUND_ZERO
+         1          1            6.0            5.0           -1.0           20.0           17.0           -3.0  function_4 (0x100)
+         5          4            2.0            2.0              0            2.0            2.0              0  function_30 (0x300)
+Total: 25 -> 22 (-3)
+";
+
+pub const SIMPLE_EVENTS: &[u8] =
+    br#"{"pc":1,"class":"unknown","depth":0,"function":"GROUND_ZERO","cost":1}
+{"pc":2,"class":"unknown","depth":0,"function":"GROUND_ZERO","cost":1}
+{"pc":3,"class":"call","depth":0,"function":"GROUND_ZERO","cost":1}
+{"pc":4,"class":"unknown","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":5,"class":"call","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":20,"class":"unknown","depth":2,"function":"function_20 (0x200)","cost":1}
+{"pc":21,"class":"call","depth":2,"function":"function_20 (0x200)","cost":1}
+{"pc":30,"class":"unknown","depth":3,"function":"function_30 (0x300)","cost":1}
+{"pc":31,"class":"exit","depth":3,"function":"function_30 (0x300)","cost":1}
+{"pc":22,"class":"call","depth":2,"function":"function_20 (0x200)","cost":1}
+{"pc":30,"class":"unknown","depth":3,"function":"function_30 (0x300)","cost":1}
+{"pc":31,"class":"exit","depth":3,"function":"function_30 (0x300)","cost":1}
+{"pc":23,"class":"exit","depth":2,"function":"function_20 (0x200)","cost":1}
+{"pc":6,"class":"call","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":30,"class":"unknown","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":31,"class":"exit","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":7,"class":"call","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":30,"class":"unknown","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":31,"class":"exit","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":8,"class":"call","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":30,"class":"unknown","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":31,"class":"exit","depth":2,"function":"function_30 (0x300)","cost":1}
+{"pc":9,"class":"exit","depth":1,"function":"function_4 (0x100)","cost":1}
+{"pc":10,"class":"unknown","depth":0,"function":"GROUND_ZERO","cost":1}
+{"pc":11,"class":"unknown","depth":0,"function":"GROUND_ZERO","cost":1}
+"#;
+
+pub const SIMPLE_QUERIES: &[u8] = b"          20  function_4 (0x100)
+          10  function_30 (0x300)
+          10  function_30 (0x300)
+function_30 (0x300):
+               6  calls=3  function_4 (0x100)
+               4  calls=2  function_20 (0x200)
+function_20 (0x200):
+               4  calls=2  function_30 (0x300)
+14 in 2 function(s)
+";
+
+pub const SIMPLE_CALLGRAPH_JSON: &[u8] = b"{
+  \"version\": 1,
+  \"labels\": [],
+  \"total_cost\": 25,
+  \"functions\": [
+    {
+      \"id\": \"0x100\",
+      \"name\": \"function_4 (0x100)\",
+      \"resolution\": \"unresolved\",
+      \"self\": 6,
+      \"inclusive\": 20,
+      \"called\": 1,
+      \"calls\": 4
+    },
+    {
+      \"id\": \"0x200\",
+      \"name\": \"function_20 (0x200)\",
+      \"resolution\": \"unresolved\",
+      \"self\": 4,
+      \"inclusive\": 8,
+      \"called\": 1,
+      \"calls\": 2
+    },
+    {
+      \"id\": \"0x300\",
+      \"name\": \"function_30 (0x300)\",
+      \"resolution\": \"unresolved\",
+      \"self\": 10,
+      \"inclusive\": 10,
+      \"called\": 5,
+      \"calls\": 0
+    },
+    {
+      \"id\": \"root\",
+      \"name\": \"GROUND_ZERO\",
+      \"resolution\": \"synthetic\",
+      \"self\": 5,
+      \"inclusive\": 25,
+      \"called\": 0,
+      \"calls\": 1
+    }
+  ],
+  \"edges\": [
+    {
+      \"caller\": \"function_4 (0x100)\",
+      \"callee\": \"function_20 (0x200)\",
+      \"calls\": 1,
+      \"cost\": 8,
+      \"min\": 8,
+      \"avg\": 8,
+      \"p50\": 8,
+      \"p95\": 8,
+      \"max\": 8
+    },
+    {
+      \"caller\": \"function_4 (0x100)\",
+      \"callee\": \"function_30 (0x300)\",
+      \"calls\": 3,
+      \"cost\": 6,
+      \"min\": 2,
+      \"avg\": 2,
+      \"p50\": 2,
+      \"p95\": 2,
+      \"max\": 2
+    },
+    {
+      \"caller\": \"function_20 (0x200)\",
+      \"callee\": \"function_30 (0x300)\",
+      \"calls\": 2,
+      \"cost\": 4,
+      \"min\": 2,
+      \"avg\": 2,
+      \"p50\": 2,
+      \"p95\": 2,
+      \"max\": 2
+    },
+    {
+      \"caller\": \"GROUND_ZERO\",
+      \"callee\": \"function_4 (0x100)\",
+      \"calls\": 1,
+      \"cost\": 20,
+      \"min\": 20,
+      \"avg\": 20,
+      \"p50\": 20,
+      \"p95\": 20,
+      \"max\": 20
+    }
+  ]
+}
+";
+
+pub const SIMPLE_CALLGRAPH_DOT: &[u8] = b"digraph callgraph {
+    node [shape=box];
+    n0 [label=\"function_4 (0x100)\\nself=6\\ninclusive=20 (80.00%)\", fontsize=21.2];
+    n1 [label=\"function_20 (0x200)\\nself=4\\ninclusive=8 (32.00%)\", fontsize=14.5];
+    n2 [label=\"function_30 (0x300)\\nself=10\\ninclusive=10 (40.00%)\", fontsize=15.6];
+    n3 [label=\"GROUND_ZERO\\nself=5\\ninclusive=25 (100.00%)\", fontsize=24.0];
+    n0 -> n1 [label=\"calls=1\\ncost=8\\nmin=8 max=8\", penwidth=2.3];
+    n0 -> n2 [label=\"calls=3\\ncost=6\\nmin=2 max=2\", penwidth=2.0];
+    n1 -> n2 [label=\"calls=2\\ncost=4\\nmin=2 max=2\", penwidth=1.6];
+    n3 -> n0 [label=\"calls=1\\ncost=20\\nmin=20 max=20\", penwidth=4.2];
+}
+";
+
+pub const SIMPLE_CALLGRIND_SOURCE_LINES: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fl=<none>
+fn=function_4 (0x100)
+4 1
+5 1
+6 1
+7 1
+8 1
+9 1
+cfl=src/lib.rs
+cfn=process
+calls=1 42
+5 8
+cfl=<none>
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfl=<none>
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfl=<none>
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fl=src/lib.rs
+fn=process
+42 1
+fi=src/util.rs
+7 1
+7 1
+7 1
+cfl=<none>
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfl=<none>
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+
+fl=<none>
+fn=function_30 (0x300)
+30 5
+31 5
+";
+
+pub const DUMP_DIFF_TABLE: &[u8] = b"    size old     size new   size delta  change    function
+           -            1           +1  added     helper
+           1            -           -1  removed   old_helper
+           4            5           +1  changed   process
+Total: 5 -> 6 (+1) instructions, 1 functions added, 1 removed, 1 changed
+";
+
+pub const CHECK_FINDINGS: &[u8] = b"line 10: malformed: Number '99999999999999999999999' is out of range of addresses or program counters
+         9 |  4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 4: xxx
+>       10 |  4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 99999999999999999999999: xxx
+        11 |  5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 5: call 0x200
+
+line 13: dropped-call: call without its first instruction
+        12 |  6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: yyy
+>       13 |  7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 21: call 0x300
+
+line 14: unbalanced: 2 unfinished calls at the end of the invocation
+        13 |  7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 21: call 0x300
+
+3 issue(s) found
+";
+
+pub const SUMMARY_TABLE: &[u8] =
+    b"        self  self %    inclusive  incl %      calls     per call  function
+          10  40.00%           10  40.00%          5          2.0  function_30 (0x300)
+           6  24.00%           20  80.00%          1         20.0  function_4 (0x100)
+Total: 25
+function,self,self %,inclusive,inclusive %,calls,per call
+\"function_4 (0x100)\",6,24.00,20,80.00,1,20.0
+\"function_30 (0x300)\",10,40.00,10,40.00,5,2.0
+\"function_20 (0x200)\",4,16.00,8,32.00,1,8.0
+";
+
+pub const SIMPLE_CHROME_TRACE: &[u8] = b"[
+    {\"name\":\"function_4 (0x100)\",\"ph\":\"B\",\"ts\":3,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_20 (0x200)\",\"ph\":\"B\",\"ts\":5,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"B\",\"ts\":7,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"E\",\"ts\":9,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"B\",\"ts\":10,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"E\",\"ts\":12,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_20 (0x200)\",\"ph\":\"E\",\"ts\":13,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"B\",\"ts\":14,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"E\",\"ts\":16,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"B\",\"ts\":17,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"E\",\"ts\":19,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"B\",\"ts\":20,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_30 (0x300)\",\"ph\":\"E\",\"ts\":22,\"pid\":1,\"tid\":1},
+    {\"name\":\"function_4 (0x100)\",\"ph\":\"E\",\"ts\":23,\"pid\":1,\"tid\":1}
+]
+";
+
+pub const SIMPLE_CALLGRIND_PATHS: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fn=function_4 (0x100)'GROUND_ZERO
+0 6
+cfn=function_20 (0x200)'function_4 (0x100)'GROUND_ZERO
+calls=1 0
+0 8
+cfn=function_30 (0x300)'function_4 (0x100)'GROUND_ZERO
+calls=3 0
+0 6
+
+fn=function_20 (0x200)'function_4 (0x100)'GROUND_ZERO
+0 4
+cfn=function_30 (0x300)'function_20 (0x200)'function_4 (0x100)'GROUND_ZERO
+calls=2 0
+0 4
+
+fn=function_30 (0x300)'function_20 (0x200)'function_4 (0x100)'GROUND_ZERO
+0 4
+
+fn=function_30 (0x300)'function_4 (0x100)'GROUND_ZERO
+0 6
+";
+
+pub const SIMPLE_TEXT: &[u8] =
+    b"--------------------------------------------------------------------------------
+Events shown: Instructions
+Total:        25
+--------------------------------------------------------------------------------
+             inclusive                   self  function
+--------------------------------------------------------------------------------
+          25 (100.00%)             5 (20.00%)  GROUND_ZERO
+           20 (80.00%)             6 (24.00%)  function_4 (0x100)
+           10 (40.00%)            10 (40.00%)  function_30 (0x300)
+            8 (32.00%)             4 (16.00%)  function_20 (0x200)
+
+--------------------------------------------------------------------------------
+-- Callers (<) and callees (>) of functions, by inclusive costs of calls
+--------------------------------------------------------------------------------
+
+*         25 (100.00%)  GROUND_ZERO
+           20 (80.00%)  > function_4 (0x100) (1x)
+
+           20 (80.00%)  < GROUND_ZERO (1x)
+*          20 (80.00%)  function_4 (0x100)
+            8 (32.00%)  > function_20 (0x200) (1x)
+            6 (24.00%)  > function_30 (0x300) (3x: min 2 avg 2 p50 2 p95 2 max 2)
+
+            6 (24.00%)  < function_4 (0x100) (3x: min 2 avg 2 p50 2 p95 2 max 2)
+            4 (16.00%)  < function_20 (0x200) (2x: min 2 avg 2 p50 2 p95 2 max 2)
+*          10 (40.00%)  function_30 (0x300)
+
+            8 (32.00%)  < function_4 (0x100) (1x)
+*           8 (32.00%)  function_20 (0x200)
+            4 (16.00%)  > function_30 (0x300) (2x: min 2 avg 2 p50 2 p95 2 max 2)
+";
+
+pub const SIMPLE_CALLGRIND_PRUNED: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fn=function_4 (0x100)
+4 6
+cfn=<below threshold>
+calls=1 20
+5 8
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fn=function_30 (0x300)
+30 10
+
+fn=<below threshold>
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+";
+
+pub const SIMPLE_CALLGRIND_TRUNCATED: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fn=function_4 (0x100)
+4 6
+cfn=<truncated>
+calls=1 20
+5 8
+cfn=function_30 (0x300)
+calls=1 30
+6 2
+cfn=function_30 (0x300)
+calls=1 30
+7 2
+cfn=function_30 (0x300)
+calls=1 30
+8 2
+
+fn=function_30 (0x300)
+30 10
+
+fn=<truncated>
+20 4
+cfn=function_30 (0x300)
+calls=1 30
+21 2
+cfn=function_30 (0x300)
+calls=1 30
+22 2
+";
+pub const SIMPLE_CALLGRIND_FILTERED: &[u8] = b"# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 20
+totals: 20
+fl=<none>
+
+fn=function_4 (0x100)
+4 12
+cfn=function_20 (0x200)
+calls=1 20
+5 8
+
+fn=function_20 (0x200)
+20 8
+";
+pub const SIMPLE_BOOKMARK: &str = "\
+{\"function\":\"function_30 (0x300)\",\"occurrence\":1,\"start\":541,\"end\":632,\"first_line\":13,\"last_line\":14,\"cost\":2}
+";
+
+pub const SIMPLE_ANNOTATED: &[u8] = b"function_30 (0x300): cost 10 (40.00% of total)
+  executed       cost        %  instruction
+         5          5   50.00%  30:        zzz
+         5          5   50.00%  31:        exit
+
+function_4 (0x100): cost 6 (24.00% of total)
+  executed       cost        %  instruction
+         1          1   16.67%  4:        xxx
+         1          1   16.67%  5:        call 0x200
+         1          1   16.67%  6:        call 0x300
+         1          1   16.67%  7:        call 0x300
+         1          1   16.67%  8:        call 0x300
+         1          1   16.67%  9:        exit
+
+GROUND_ZERO: cost 5 (20.00% of total)
+  executed       cost        %  instruction
+         1          1   20.00%  1:        aaa
+         1          1   20.00%  2:        bbb
+         1          1   20.00%  3:        call 0x100
+         1          1   20.00%  10:        ccc
+         1          1   20.00%  11:        ddd
+
+process: cost 4 (16.00% of total)
+  executed       cost        %  instruction
+         1          1   25.00%  20:        yyy
+         1          1   25.00%  21:        call 0x300
+         1          1   25.00%  22:        call 0x300
+         1          1   25.00%  23:        exit
+";
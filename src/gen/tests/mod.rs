@@ -2,7 +2,12 @@
 
 mod mock;
 
-use crate::{config, gen::trace, resolver};
+use crate::diagnostics::Diagnostic;
+use crate::gen::{
+    batch, bookmarks, budget, cache, check, diff, events, filter, format, metadata, parallel,
+    profile, query, regression, tail, trace, window, Options, ProfileBuilder, ProfileRecorder,
+};
+use crate::{compact, config, resolver};
 use std::fs;
 use std::io::Cursor;
 use std::path::Path;
@@ -26,7 +31,7 @@ fn generate_integral() {
     //println!("{}", std::str::from_utf8(&output).unwrap());
     //=======================================================
 
-    assert_eq!(output.len(), 317);
+    assert_eq!(output.len(), 443);
     assert_eq!(output, mock::SIMPLE_CALLGRIND_INTEGRAL);
 }
 
@@ -36,7 +41,7 @@ fn generate_line_by_line() {
     let reader = Cursor::new(mock::SIMPLE_INPUT);
     let asm_name = "/tmp/generate_line_by_line.asm".to_owned();
     let asm = Path::new(&asm_name);
-    let prof = trace::Profile::new(resv, Some(&asm));
+    let prof = trace::Profile::new(resv, Some(asm));
     assert!(prof.is_ok());
 
     let mut prof = prof.unwrap();
@@ -51,20 +56,2779 @@ fn generate_line_by_line() {
     //println!("{}", std::str::from_utf8(&output).unwrap());
     //=======================================================
 
-    assert_eq!(output.len(), 504);
+    assert_eq!(output.len(), 506);
     assert_eq!(output, mock::SIMPLE_CALLGRIND_LINE_BY_LINE);
 
-    let asm = fs::read(&asm).unwrap();
+    let asm = fs::read(asm).unwrap();
     let asm = std::str::from_utf8(&asm).unwrap();
 
     //==== do not delete ====================================
     //println!("{}", &asm);
     //=======================================================
 
-    assert_eq!(asm.len(), 487);
+    assert_eq!(asm.len(), 495);
     assert_eq!(asm, mock::SIMPLE_GENERATED_ASM);
 }
 
+#[test]
+fn generate_calls_only() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_calls_only(true);
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_CALLS_ONLY);
+}
+
+#[test]
+fn generate_trace_start() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::MIDDLE_INPUT);
+    let asm_name = "/tmp/generate_trace_start.asm".to_owned();
+    let mut prof = trace::Profile::new(resv, Some(Path::new(&asm_name))).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, &asm_name);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::MIDDLE_CALLGRIND);
+}
+
+#[test]
+fn generate_merged() {
+    let resv = resolver::Resolver::default();
+    let asm_name = "/tmp/generate_merged.asm".to_owned();
+    let mut prof = trace::Profile::new(resv, Some(Path::new(&asm_name))).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let r = trace::parse(Cursor::new(mock::SECOND_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, &asm_name);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::MERGED_CALLGRIND);
+}
+
+#[test]
+fn generate_merged_relinked() {
+    // Functions 0x200 and 0x300 swap their addresses in the relinked program
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let relinked = simple
+        .replace("call 0x200", "call 0x999")
+        .replace("call 0x300", "call 0x200")
+        .replace("call 0x999", "call 0x300");
+    // Function 0x300 of the rebuilt program starts at other instructions
+    let rebuilt = simple
+        .replace("30: zzz", "40: zzz")
+        .replace("31: exit", "41: exit");
+    let dir = std::env::temp_dir().join(format!("bpf-profile-relinked-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let simple_path = dir.join("simple.trace");
+    let relinked_path = dir.join("relinked.trace");
+    let rebuilt_path = dir.join("rebuilt.trace");
+    fs::write(&simple_path, simple).unwrap();
+    fs::write(&relinked_path, relinked).unwrap();
+    fs::write(&rebuilt_path, rebuilt).unwrap();
+
+    let write = |trace_paths: &[&Path]| {
+        let prof = trace::Profile::create(trace_paths, None, &Options::default()).unwrap();
+        let mut output = Vec::<u8>::new();
+        prof.write_callgrind(&mut output, config::DEFAULT_ASM)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    let output = write(&[&simple_path, &relinked_path]);
+    assert!(!output.contains("(0x200)\ncalls=3"));
+    assert_eq!(output, write(&[&simple_path, &simple_path]));
+    // Both functions at 0x300 are kept
+    let output = write(&[&simple_path, &rebuilt_path]);
+    let functions = output
+        .lines()
+        .filter(|line| line.starts_with("fn=") && line.ends_with("(0x300)"))
+        .count();
+    assert_eq!(functions, 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generate_averaged() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    for _ in 0..2 {
+        let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+        assert!(r.is_ok());
+    }
+    assert_eq!(prof.total_cost(), 50);
+    prof.average(2);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_INTEGRAL);
+}
+
+#[test]
+fn generate_root_name() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_root_name("test_case");
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_ROOT_NAME);
+}
+
+#[test]
+fn generate_explicit_root() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_explicit_root(true);
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    let expected = std::str::from_utf8(mock::SIMPLE_CALLGRIND_ROOT_NAME).unwrap();
+    let expected = expected.replace("fn=test_case", "fn=<root>");
+    assert_eq!(output, expected.as_bytes());
+}
+
+#[test]
+fn generate_max_address() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::MAX_ADDRESS_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_root_name("root");
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\nfn=function_40 (0xffffffffffffffff)\n40 2\n"));
+    assert!(output.contains("\nfn=root\n1 3\ncfn=function_40 (0xffffffffffffffff)\n"));
+}
+
+#[test]
+fn generate_appended_part() {
+    let first = std::str::from_utf8(mock::SIMPLE_CALLGRIND_INTEGRAL).unwrap();
+    assert_eq!(trace::next_callgrind_part(first, "Instructions"), Some(2));
+    assert_eq!(
+        trace::next_callgrind_part("events: Instructions\n", "Instructions"),
+        None
+    );
+    let other = first.replace("creator: bpf-profile", "creator: callgrind-3.22");
+    assert_eq!(trace::next_callgrind_part(&other, "Instructions"), None);
+
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind_part(&mut output, config::DEFAULT_ASM, Some(2));
+    assert!(r.is_ok());
+    let second = std::str::from_utf8(&output).unwrap();
+    assert!(second
+        .starts_with("part: 2\npositions: line\nevents: Instructions\nsummary: 20\ntotals: 20\n"));
+    assert!(second.ends_with(first.split_once("fl=").unwrap().1));
+
+    let both = format!("{}\n{}", first, second);
+    assert_eq!(trace::next_callgrind_part(&both, "Instructions"), Some(3));
+}
+
+#[test]
+fn generate_cost_model() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_cost_model.toml");
+    fs::write(
+        &path,
+        "default = 2\n[classes]\nexit = 3\n[mnemonics]\nzzz = 10\n",
+    )
+    .unwrap();
+
+    let options = Options {
+        cost_model: Some(path.clone()),
+        ..Options::default()
+    };
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::with_options(resv, &options).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    // 5 executions of 'zzz' cost 10 each, 7 exits cost 3 each, others cost 2 each
+    assert_eq!(prof.total_cost(), 5 * 10 + 7 * 3 + (25 - 5 - 7) * 2);
+
+    fs::write(&path, "[classes]\ndivision = 4\n").unwrap();
+    assert!(crate::cost::Weights::read(&path).is_err());
+    fs::write(&path, "[mnemonic]\ndiv64 = 4\n").unwrap();
+    assert!(crate::cost::Weights::read(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn generate_cost_script() {
+    let script_path = std::env::temp_dir().join("generate_cost_script.rhai");
+    fs::write(
+        &script_path,
+        r#"fn weight(opcode, operands, is_syscall) { if opcode == "zzz" { 10 } else { 1 } }"#,
+    )
+    .unwrap();
+
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let script = crate::cost::Script::load(&script_path).unwrap();
+    prof.set_cost_model(crate::cost::Model::Script(Box::new(script)));
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    // 5 executions of 'zzz' cost 10 each
+    assert_eq!(prof.total_cost(), 25 + 5 * 9);
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\nfn=function_30 (0x300)\n30 55\n"));
+}
+
+#[test]
+fn generate_metrics() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_metrics(&mut output);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::SIMPLE_METRICS);
+}
+
+#[test]
+fn generate_stats() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_STATS);
+}
+
+#[test]
+fn generate_memory_regions() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::MEMORY_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_memory_regions(true);
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::MEMORY_STATS);
+}
+
+#[test]
+fn generate_stack_frames() {
+    let dump = std::str::from_utf8(mock::SIMPLE_DUMP).unwrap();
+    let dump = dump.replace("yyy", "stxdw [r10 - 0xf00], r1")
+        + "\n0000000000000120 <helper>:\n      \
+           30       07 0b 00 00 c0 ff ff ff add64 r11, -64\n      \
+           31       95 00 00 00 00 00 00 00 exit\n";
+    let resv = resolver::read_from(Cursor::new(dump)).unwrap();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+    let output = String::from_utf8(output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.ends_with(mock::STACK_STATS));
+}
+
+#[test]
+fn check_budgets() {
+    let path = std::env::temp_dir().join("bpf-profile-check_budgets.toml");
+    std::fs::write(
+        &path,
+        "[[budget]]\nfunction = \"function_?*\"\nself = 5\n\n\
+         [[budget]]\nfunction = \"function_4 (0x100)\"\ninclusive = 19\n",
+    )
+    .unwrap();
+    let budgets = budget::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let violations = prof.check_budgets(&mut output, &budgets).unwrap();
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(violations, 3);
+    assert_eq!(output, mock::SIMPLE_BUDGET_VIOLATIONS);
+}
+
+#[test]
+fn check_budgets_junit() {
+    let path = std::env::temp_dir().join("bpf-profile-check_budgets_junit.toml");
+    fs::write(
+        &path,
+        "[[budget]]\nfunction = \"function_?*\"\nself = 5\n\n\
+         [[budget]]\nfunction = \"function_4 (0x100)\"\ninclusive = 19\n",
+    )
+    .unwrap();
+    let budgets = budget::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let violations = prof.check_budgets_junit(&mut output, &budgets).unwrap();
+    assert_eq!(violations, 3);
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        mock::SIMPLE_BUDGET_JUNIT
+    );
+}
+
+#[cfg(feature = "remote")]
+#[test]
+fn check_budgets_notify() {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    // Webhook answering a single request with its body
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let webhook = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            match line.to_lowercase().strip_prefix("content-length:") {
+                Some(value) => length = value.trim().parse().unwrap(),
+                None if line.trim().is_empty() => break,
+                None => {}
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let mut stream = reader.into_inner();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        body
+    });
+
+    let trace = std::env::temp_dir().join("bpf-profile-check_budgets_notify.trace");
+    fs::write(&trace, mock::SIMPLE_INPUT).unwrap();
+    let budgets_path = std::env::temp_dir().join("bpf-profile-check_budgets_notify.toml");
+    fs::write(
+        &budgets_path,
+        "[[budget]]\nfunction = \"function_?*\"\nself = 5\n",
+    )
+    .unwrap();
+    let budgets = budget::read(&budgets_path).unwrap();
+    fs::remove_file(&budgets_path).unwrap();
+    let r = crate::gen::check_budget(&trace, None, &budgets, None, None, Some(&url));
+    fs::remove_file(&trace).unwrap();
+    assert!(matches!(r, Err(crate::error::Error::BudgetExceeded(2))));
+
+    let payload: serde_json::Value = serde_json::from_slice(&webhook.join().unwrap()).unwrap();
+    let text = payload["text"].as_str().unwrap();
+    assert!(text.starts_with("bpf-profile: 2 budget violation(s) in "));
+    assert!(text.ends_with("\nfunction_4 (0x100): self cost 6 exceeds budget 5 of 'function_?*'"));
+    assert_eq!(payload["content"], payload["text"]);
+    assert_eq!(payload["issues"][0]["function"], "function_30 (0x300)");
+    assert_eq!(payload["issues"][0]["actual"], 10);
+}
+
+#[test]
+fn check_regressions() {
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    // The profile does not regress against itself
+    let path = std::env::temp_dir().join("bpf-profile-check_regressions.json");
+    let mut json = Vec::<u8>::new();
+    prof.write_callgraph_json(&mut json).unwrap();
+    fs::write(&path, &json).unwrap();
+    let baseline = regression::read(&path).unwrap();
+    let mut output = Vec::<u8>::new();
+    assert_eq!(
+        prof.check_regressions(&mut output, &baseline, 0.0, false)
+            .unwrap(),
+        0
+    );
+    assert_eq!(output, b"Total cost 25 (baseline 25)\n");
+
+    fs::write(
+        &path,
+        r#"{"total_cost": 22, "functions": [
+            {"name": "function_20 (0x200)", "inclusive": 8},
+            {"name": "function_30 (0x300)", "inclusive": 8}
+        ]}"#,
+    )
+    .unwrap();
+    let baseline = regression::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let mut output = Vec::<u8>::new();
+    let max_percent = regression::parse_percent("5%").unwrap();
+    assert_eq!(
+        prof.check_regressions(&mut output, &baseline, max_percent, false)
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        "Total cost 25 (baseline 22)\n\
+         total: cost 25 exceeds baseline 22 by 13.6%\n\
+         function_30 (0x300): cost 10 exceeds baseline 8 by 25.0%\n"
+    );
+    let mut output = Vec::<u8>::new();
+    assert_eq!(
+        prof.check_regressions(&mut output, &baseline, 20.0, true)
+            .unwrap(),
+        1
+    );
+
+    assert_eq!(regression::parse_percent(" 2.5 % ").unwrap(), 2.5);
+    assert!(regression::parse_percent("five").is_err());
+    assert!(regression::parse_percent("-1%").is_err());
+}
+
+#[test]
+fn check_budgets_sarif() {
+    let path = std::env::temp_dir().join("bpf-profile-check_budgets_sarif.dump");
+    let dump = std::str::from_utf8(mock::SIMPLE_DUMP).unwrap();
+    let dump = dump.replace("<process>:\n", "<process>:\n; src/lib.rs:42\n");
+    fs::write(&path, dump).unwrap();
+    let resv = resolver::read(Some(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let path = std::env::temp_dir().join("bpf-profile-check_budgets_sarif.toml");
+    fs::write(
+        &path,
+        "[[budget]]\nfunction = \"process\"\nself = 3\n\n\
+         [[budget]]\nfunction = \"function_?*\"\ninclusive = 19\n",
+    )
+    .unwrap();
+    let budgets = budget::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let violations = prof.check_budgets_sarif(&mut output, &budgets).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(violations, 2);
+    let log: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+    let results = &log["runs"][0]["results"];
+    assert_eq!(results[0]["ruleId"], "budget/self");
+    assert_eq!(
+        results[0]["message"]["text"],
+        "process: self cost 4 exceeds budget 3 of 'process'"
+    );
+    let location = &results[0]["locations"][0];
+    assert_eq!(location["logicalLocations"][0]["name"], "process");
+    assert_eq!(
+        location["physicalLocation"]["artifactLocation"]["uri"],
+        "src/lib.rs"
+    );
+    assert_eq!(location["physicalLocation"]["region"]["startLine"], 42);
+    // Functions not found in the dump have no source locations
+    assert_eq!(results[1]["ruleId"], "budget/inclusive");
+    assert!(results[1]["locations"][0]["physicalLocation"].is_null());
+}
+
+#[test]
+fn generate_aliases() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_aliases.toml");
+    fs::write(
+        &path,
+        "[[alias]]\npattern = '^function_\\d+ \\(0x[23]00\\)$'\nname = 'callee'\n",
+    )
+    .unwrap();
+    let aliases = resolver::read_aliases(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut resv = resolver::Resolver::default();
+    resv.set_aliases(aliases);
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+    assert!(prof.finish().is_ok());
+    assert_eq!(prof.total_cost(), 25);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    // Both functions are renamed to one, so reports list it once
+    assert_eq!(output.matches("\nfn=callee\n").count(), 1);
+    assert!(output.contains("\nfn=function_4 (0x100)\n"));
+    assert!(output.contains("\nfn=callee\n20 14\n"));
+    let mut text = Vec::<u8>::new();
+    prof.write_text(&mut text).unwrap();
+    let text = std::str::from_utf8(&text).unwrap();
+    // Calls of the merged function by itself are not counted twice
+    assert!(text.contains("14 (56.00%)            14 (56.00%)  callee\n"));
+}
+
+#[test]
+fn generate_syscall_sizes() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SYSCALL_SIZES_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_syscall_sizes(true);
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.contains("\ncfn=sol_log_<=64\ncalls=1 0\n10 0\n"));
+    assert!(output.contains("\ncfn=sol_log_<=64\ncalls=1 0\n11 0\n"));
+    assert!(output.contains("\ncfn=sol_log_<=16384\ncalls=1 0\n"));
+}
+
+#[test]
+fn generate_diff() {
+    let mut old = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut old);
+    assert!(r.is_ok());
+    // The new trace skips the last call of 0x300 from 0x100
+    let input = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let input = input
+        .lines()
+        .filter(|l| !l.starts_with("14 ") && !l.starts_with("15 ") && !l.starts_with("16 "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut new = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(input), &mut new);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = new.write_callgrind_diff(&mut output, &old, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_DIFF_CALLGRIND);
+}
+
+#[test]
+fn dump_diff_table() {
+    let simple = std::str::from_utf8(mock::SIMPLE_DUMP).unwrap();
+    let old = simple.to_string()
+        + "\n0000000000000120 <old_helper>:\n      \
+           24       95 00 00 00 00 00 00 00 exit\n";
+    let new = simple.replace(
+        "      23       95",
+        "      23       07 00 00 00 00 00 00 00 xxx\n      \
+         24       95 00 00 00 00 00 00 00 exit\n\n\
+         0000000000000128 <helper>:\n      \
+         25       95",
+    );
+    let old = resolver::read_from(Cursor::new(old)).unwrap();
+    let new = resolver::read_from(Cursor::new(new)).unwrap();
+
+    let mut output = Vec::<u8>::new();
+    let r = diff::write_dump_table(&mut output, &old.dump_functions(), &new.dump_functions());
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::DUMP_DIFF_TABLE);
+}
+
+#[test]
+fn generate_diff_table() {
+    let mut old = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut old);
+    assert!(r.is_ok());
+    let input = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let input = input
+        .lines()
+        .filter(|l| !l.starts_with("14 ") && !l.starts_with("15 ") && !l.starts_with("16 "))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut new = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(input), &mut new);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = new.write_diff_table(&mut output, &old, false);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_DIFF_TABLE);
+
+    let mut output = Vec::<u8>::new();
+    let r = new.write_diff_table(&mut output, &old, true);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_DIFF_TABLE_PER_CALL);
+}
+
+#[test]
+fn generate_ignore_before() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_ignore_before.dump");
+    fs::write(&path, mock::SIMPLE_DUMP).unwrap();
+    let resv = resolver::read(Some(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_ignore_before("process");
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_CALLGRIND_IGNORE_BEFORE);
+}
+
+#[test]
+fn generate_window() {
+    // Instructions from the call of 0x200 to its exit are at lines 10 to 18
+    let write = |options: &Options| {
+        let resv = resolver::Resolver::default();
+        let mut prof = trace::Profile::with_options(resv, options).unwrap();
+        let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+        assert!(r.is_ok());
+        assert_eq!(prof.total_cost(), 9);
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        output
+    };
+    let output = write(&Options {
+        skip_instructions: 4,
+        max_instructions: Some(9),
+        ..Options::default()
+    });
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_WINDOW);
+    let lines = write(&Options {
+        range: Some("10:18".into()),
+        ..Options::default()
+    });
+    assert_eq!(lines, output);
+
+    assert_eq!(window::Window::new(0, None, None).unwrap(), None);
+    assert!(window::Window::new(0, None, Some("10:")).unwrap().is_some());
+    for range in ["10", "0:5", "18:10", "a:b"] {
+        assert!(window::Window::new(0, None, Some(range)).is_err());
+    }
+}
+
+#[test]
+fn build_from_readers() {
+    let resv = resolver::read_from(Cursor::new(mock::SIMPLE_DUMP)).unwrap();
+    let options = Options {
+        ignore_before: Some("process".into()),
+        ..Options::default()
+    };
+    let prof = ProfileBuilder::new()
+        .trace_reader(Cursor::new(mock::SIMPLE_INPUT))
+        .resolver(resv)
+        .options(options)
+        .build()
+        .unwrap();
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_IGNORE_BEFORE);
+}
+
+#[test]
+fn build_from_events() {
+    // Events of the instructions of SIMPLE_INPUT: call targets follow call instructions
+    let events = [
+        (1, None),
+        (2, None),
+        (3, Some(0x100)),
+        (4, None),
+        (5, Some(0x200)),
+        (20, None),
+        (21, Some(0x300)),
+        (30, None),
+        (31, None),
+        (22, Some(0x300)),
+        (30, None),
+        (31, None),
+        (23, None),
+        (6, Some(0x300)),
+        (30, None),
+        (31, None),
+        (7, Some(0x300)),
+        (30, None),
+        (31, None),
+        (8, Some(0x300)),
+        (30, None),
+        (31, None),
+        (9, None),
+        (10, None),
+        (11, None),
+    ];
+    let exits = [31, 23, 9];
+    let first_pcs = config::Map::from([(0x100, 4), (0x200, 20), (0x300, 30)]);
+
+    let mut builder = ProfileBuilder::new();
+    for (pc, target) in events {
+        assert!(builder.cost(pc, 1).is_ok());
+        if let Some(address) = target {
+            assert!(builder.call(address, first_pcs[&address]).is_ok());
+        }
+        if exits.contains(&pc) {
+            assert!(builder.ret().is_ok());
+        }
+    }
+    let prof = builder.build().unwrap();
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_INTEGRAL);
+}
+
+#[test]
+fn build_deep_calls() {
+    // Calls nested deeper than the native stack would allow for recursion
+    const DEPTH: usize = 200_000;
+    let mut builder = ProfileBuilder::new();
+    for _ in 0..DEPTH {
+        assert!(builder.cost(10, 1).is_ok());
+        assert!(builder.call(0x100, 10).is_ok());
+    }
+    for _ in 0..DEPTH {
+        assert!(builder.cost(11, 1).is_ok());
+        assert!(builder.ret().is_ok());
+    }
+    let prof = builder.build().unwrap();
+    assert_eq!(prof.total_cost(), 2 * DEPTH);
+}
+
+#[test]
+fn build_from_recorder() {
+    // Calls of SIMPLE_INPUT with the costs of the functions between them
+    let mut recorder = ProfileRecorder::new();
+    assert!(recorder.tick(3).is_ok());
+    assert!(recorder.enter(0x100, 4).is_ok());
+    assert!(recorder.tick(2).is_ok());
+    assert!(recorder.enter(0x200, 20).is_ok());
+    for _ in 0..2 {
+        assert!(recorder.tick(1).is_ok());
+        assert!(recorder.enter(0x300, 30).is_ok());
+        assert!(recorder.tick(2).is_ok());
+        assert!(recorder.exit().is_ok());
+    }
+    assert!(recorder.tick(2).is_ok());
+    assert!(recorder.exit().is_ok());
+    for _ in 0..3 {
+        assert!(recorder.tick(1).is_ok());
+        assert!(recorder.enter(0x300, 30).is_ok());
+        assert!(recorder.tick(2).is_ok());
+        assert!(recorder.exit().is_ok());
+    }
+    assert!(recorder.tick(1).is_ok());
+    assert!(recorder.exit().is_ok());
+    assert!(recorder.tick(2).is_ok());
+    let prof = recorder.build().unwrap();
+    assert_eq!(prof.total_cost(), 25);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = String::from_utf8(output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(
+        output.contains("\nfn=function_4 (0x100)\n4 6\ncfn=function_20 (0x200)\ncalls=1 20\n4 8\n")
+    );
+    assert!(output
+        .contains("\nfn=function_20 (0x200)\n20 4\ncfn=function_30 (0x300)\ncalls=2 30\n20 4\n"));
+    assert!(output.ends_with("\nfn=function_30 (0x300)\n30 10\n"));
+}
+
+#[test]
+fn generate_callgraph() {
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgraph_json(&mut output);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRAPH_JSON);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgraph_dot(&mut output);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRAPH_DOT);
+}
+
+#[test]
+fn generate_callgraph_lines() {
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_call_lines(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgraph_json(&mut output);
+    assert!(r.is_ok());
+    let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    // The call of 0x200 spans from its first instruction to its exit
+    let edge = &json["edges"][0];
+    assert_eq!(edge["callee"], "function_20 (0x200)");
+    assert_eq!(
+        edge["lines"],
+        serde_json::json!([{"first": 11, "last": 18, "cost": 8}])
+    );
+    assert_eq!(json["edges"][1]["lines"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn generate_invocations() {
+    let second = std::str::from_utf8(mock::SECOND_INPUT).unwrap();
+    let input = format!(
+        "{}[Z DEBUG log] Program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin success\n{}",
+        second,
+        second.replace("[Z TRACE bpf] BPF Program Instruction Trace:\n", "")
+    );
+
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(input.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 16);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(!output.contains("trace-start"));
+    assert!(output.contains("\ncalls=2 40\n"));
+
+    let mut expected = Vec::<u8>::new();
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    trace::parse(mock::SECOND_INPUT, &mut prof).unwrap();
+    prof.write_callgrind(&mut expected, config::DEFAULT_ASM)
+        .unwrap();
+
+    let invocations = trace::invocations(input.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(invocations.len(), 2);
+    for invocation in invocations {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        let r = trace::parse(invocation.as_bytes(), &mut prof);
+        assert!(r.is_ok());
+
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn generate_labels() {
+    assert_eq!(
+        crate::trace::parse_label("[Z DEBUG] Transaction signature: 5VERv8NMvzbJ\n"),
+        Some("5VERv8NMvzbJ")
+    );
+    assert_eq!(
+        crate::trace::parse_label("execution id = test_swap"),
+        Some("test_swap")
+    );
+    assert_eq!(crate::trace::parse_label("Program log: transaction"), None);
+
+    let second = std::str::from_utf8(mock::SECOND_INPUT).unwrap();
+    let input = format!("Transaction signature: tx1\n{second}Transaction signature: tx2\n{second}");
+    let invocations = trace::invocations(input.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(invocations.len(), 2);
+    for (invocation, label) in invocations.iter().zip(["tx1", "tx2"]) {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        trace::parse(invocation.as_bytes(), &mut prof).unwrap();
+        assert_eq!(prof.labels(), [label]);
+    }
+
+    let dir = std::env::temp_dir().join(format!("bpf-profile-labels-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let trace_path = dir.join("trace.log");
+    std::fs::write(&trace_path, &input).unwrap();
+    let options = Options {
+        output: Some(dir.join("profile-{label}.out")),
+        split_invocations: true,
+        ..Options::default()
+    };
+    let r = super::run(&trace_path, None, &options);
+    assert!(r.is_ok());
+    for label in ["tx1", "tx2"] {
+        let output = std::fs::read_to_string(dir.join(format!("profile-{label}.out"))).unwrap();
+        assert!(output.contains(&format!("\ndesc: Execution: {label}\n")));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generate_parallel() {
+    let second = std::str::from_utf8(mock::SECOND_INPUT).unwrap();
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let input = format!("{}{}{}", second, simple, second);
+    let options = Options {
+        stats: Some("/tmp/generate_parallel.stats".into()),
+        jobs: 3,
+        ..Options::default()
+    };
+
+    let resv = resolver::Resolver::default();
+    let mut expected = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    trace::parse(input.as_bytes(), &mut expected).unwrap();
+    let mut prof = trace::Profile::with_options(resv, &options).unwrap();
+    let r = parallel::parse_chunks(input.as_bytes(), &mut prof, &options, 1);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), expected.total_cost());
+
+    let write = |prof: &trace::Profile| {
+        let mut output = Vec::<u8>::new();
+        prof.write_callgrind(&mut output, config::DEFAULT_ASM)
+            .unwrap();
+        prof.write_stats(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    let output = write(&prof);
+    assert!(output.contains("fn=function_30 (0x300)"));
+    assert_eq!(output, write(&expected));
+
+    let r = parallel::parse_chunks(&b"aaa"[..], &mut prof, &options, 1);
+    assert!(r.is_err());
+}
+
+#[test]
+fn generate_register_lines() {
+    let options = Options {
+        jobs: 2,
+        ..Options::default()
+    };
+    let write = |prof: &trace::Profile| {
+        let mut output = Vec::<u8>::new();
+        prof.write_callgrind(&mut output, config::DEFAULT_ASM)
+            .unwrap();
+        output
+    };
+
+    let mut prof = trace::Profile::with_options(resolver::Resolver::default(), &options).unwrap();
+    let r = trace::parse(mock::REGISTER_LINES_INPUT, &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), mock::SIMPLE_CALLGRIND_INTEGRAL);
+
+    let mut prof = trace::Profile::with_options(resolver::Resolver::default(), &options).unwrap();
+    let r = parallel::parse_chunks(mock::REGISTER_LINES_INPUT, &mut prof, &options, 1);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), mock::SIMPLE_CALLGRIND_INTEGRAL);
+
+    // Without the format in the header, lines of instructions are not recognized
+    let input = std::str::from_utf8(mock::REGISTER_LINES_INPUT)
+        .unwrap()
+        .replace(" (format 2)", "");
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(input.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 0);
+}
+
+#[test]
+fn generate_source_lines() {
+    let dump = std::str::from_utf8(mock::SIMPLE_DUMP).unwrap();
+    let dump = dump
+        .replace("<process>:\n", "<process>:\n; src/lib.rs:42\n")
+        .replace("yyy\n", "yyy\n; src/util.rs:7\n");
+    let resv = resolver::read_from(Cursor::new(dump)).unwrap();
+
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_source_lines(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_SOURCE_LINES);
+}
+
+#[test]
+fn generate_lenient() {
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    // Malformed program counter, the trace is cut off right after a call
+    let truncated = simple[..simple.find(" 8 [").unwrap()].replace(
+        " 5 [",
+        " 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 99999999999999999999999: xxx\n 5 [",
+    );
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(truncated.as_bytes(), &mut prof);
+    assert!(r.is_err());
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_lenient(true);
+    let r = trace::parse(truncated.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 7);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\nfn=function_20 (0x200)\n20 2\n"));
+    assert!(!output.contains("0x300"));
+}
+
+#[test]
+fn generate_error_limit() {
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let malformed = " 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 99999999999999999999999: xxx\n";
+    let damaged = simple
+        .replace(" 5 [", &format!("{} 5 [", malformed))
+        .replace(" 9 [", &format!("{} 9 [", malformed));
+    let errors = |limit: usize| {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        prof.set_error_limit(limit);
+        match trace::parse(damaged.as_bytes(), &mut prof) {
+            Err(crate::error::Error::TraceErrors(diagnostics)) => diagnostics,
+            r => panic!("Unexpected result {:?}", r),
+        }
+    };
+
+    // The first error stops parsing by default
+    let diagnostics = errors(config::DEFAULT_ERROR_LIMIT);
+    let line = damaged.lines().position(|l| l.contains("999")).unwrap() + 1;
+    let offset = damaged.find(malformed).unwrap() as u64;
+    assert_eq!(
+        diagnostics.errors(),
+        [Diagnostic::new(
+            line,
+            offset,
+            malformed,
+            &crate::error::Error::OutOfRange("99999999999999999999999".into())
+        )]
+    );
+    let message = diagnostics.to_string();
+
+    //==== do not delete ====================================
+    //println!("{}", message);
+    //=======================================================
+
+    assert!(message.starts_with("1 error(s) in the trace parsed in the legacy format"));
+
+    // Errors are collected up to the limit, the rest of the trace is parsed
+    let diagnostics = errors(0);
+    assert_eq!(diagnostics.errors().len(), 2);
+    assert!(!diagnostics.is_limit_reached());
+    let diagnostics = errors(2);
+    assert_eq!(diagnostics.errors().len(), 2);
+    assert!(diagnostics.to_string().contains(", stopped at the limit:"));
+}
+
+#[test]
+fn check_findings() {
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let truncated = simple[..simple.find(" 8 [").unwrap()].replace(
+        " 5 [",
+        " 4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 99999999999999999999999: xxx\n 5 [",
+    );
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_findings(true);
+    let r = trace::parse(truncated.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    let mut findings = prof.take_findings();
+    let lines = findings.iter().map(|f| f.line()).collect::<Vec<_>>();
+    let malformed = truncated.lines().position(|l| l.contains("999")).unwrap() + 1;
+    assert_eq!(lines, [malformed, malformed + 3, malformed + 4]);
+
+    let r = check::add_context(&mut findings, truncated.as_bytes(), 1);
+    assert!(r.is_ok());
+    let mut output = Vec::<u8>::new();
+    let r = check::write_text(&mut output, &findings);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::CHECK_FINDINGS);
+}
+
+#[test]
+fn lint_findings() {
+    let line =
+        |pc: usize, text: &str| format!(" 1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] {}: {}\n", pc, text);
+    let mut input = String::from("[Z TRACE bpf] BPF Program Instruction Trace:\n");
+    input += &line(1, "mov64 r1, 1");
+    input += &line(2, "lddw r2, 0x1");
+    input += &line(4, "add64 r1, 1");
+    input += &line(7, "add64 r1, 2");
+    input += &line(8, "ja +1");
+    input += &line(10, "aaa");
+    for _ in 0..config::CALL_DEPTH_LIMIT {
+        input += &line(20, "call 0x100");
+    }
+    input += &line(21, "exit");
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_findings(true);
+    let r = trace::parse(input.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    let findings = prof.take_findings();
+    let lines = findings
+        .iter()
+        .map(|f| (f.line(), f.is_error()))
+        .collect::<Vec<_>>();
+    let last = input.lines().count();
+    assert_eq!(lines, [(5, true), (last, true), (last + 1, false)]);
+
+    let mut output = Vec::<u8>::new();
+    let r = check::write_lint(&mut output, &findings);
+    assert!(r.is_ok());
+    let output = String::from_utf8(output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.starts_with(
+        "line 5: error [pc-jump]: instruction at pc 7 instead of pc 5 after a non-branch instruction\n"
+    ));
+    assert!(output.contains(": error [depth-limit]: call depth exceeds the limit of 64 frames\n"));
+    assert!(output.ends_with("2 error(s), 1 warning(s)\n"));
+}
+
+#[test]
+fn generate_resolution() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_resolution.dump");
+    fs::write(&path, mock::SIMPLE_DUMP).unwrap();
+    let resv = resolver::read(Some(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.ends_with(
+        "\nResolved functions: 1 of 3, unresolved cost=16 share=64.00%\n    \
+         0x300 function_30 (0x300) first_pc=30 cost=10 share=40.00%\n    \
+         0x100 function_4 (0x100) first_pc=4 cost=6 share=24.00%\n"
+    ));
+}
+
+#[test]
+fn generate_badge() {
+    let resv = resolver::read_from(Cursor::new(mock::SIMPLE_DUMP)).unwrap();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_badge_json(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert_eq!(
+        output,
+        "{\n  \"exceeds_compute_limit\": false,\n  \"functions\": 3,\n  \"resolution\": 33.3,\n  \
+         \"resolved\": 1,\n  \"total_cost\": 25\n}\n"
+    );
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_badge_svg(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.contains("<title>bpf-profile: 25 | 33% resolved</title>"));
+    assert!(output.contains("fill=\"#e05d44\""));
+}
+
+#[test]
+fn generate_instructions() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_mnemonics(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_instructions(&mut output, true);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_INSTRUCTIONS);
+}
+
+#[test]
+fn generate_annotated() {
+    let resv = resolver::read_from(Cursor::new(mock::SIMPLE_DUMP)).unwrap();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_executions(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_annotated(&mut output, None);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_ANNOTATED);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_annotated(&mut output, Some("process"));
+    assert!(r.is_ok());
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("process: "));
+    assert!(!output.contains("function_0"));
+}
+
+#[test]
+fn generate_parts() {
+    let mut profiles = Vec::new();
+    for (i, input) in [mock::SIMPLE_INPUT, mock::SIMPLE_INPUT].iter().enumerate() {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        let r = trace::parse(Cursor::new(input), &mut prof);
+        assert!(r.is_ok());
+        prof.set_thread(i + 1);
+        profiles.push(prof);
+    }
+
+    let mut output = Vec::<u8>::new();
+    let r = super::write_parts(&mut output, &profiles, config::DEFAULT_ASM, None);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.starts_with("# callgrind format\nversion: 1\ncreator: bpf-profile\nthread: 1\n"));
+    assert!(output.contains("\n\npart: 2\nthread: 2\npositions: line\n"));
+    assert_eq!(trace::next_callgrind_part(output, "Instructions"), Some(3));
+}
+
+#[test]
+fn generate_only_function() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_only_function.dump");
+    fs::write(&path, mock::SIMPLE_DUMP).unwrap();
+    let resv = resolver::read(Some(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_only_function("process");
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_CALLGRIND_ONLY_FUNCTION);
+}
+
+#[test]
+fn generate_filtered() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_filter(filter::Filter::new(None, Some(r"\(0x300\)$")).unwrap());
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_CALLGRIND_FILTERED);
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_filter(filter::Filter::new(Some("0x200"), None).unwrap());
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 25);
+    assert!(filter::Filter::new(Some("("), None).is_err());
+}
+
+#[test]
+fn generate_folded() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_stacks(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_folded(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_FOLDED);
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_speedscope(&mut output);
+    assert!(r.is_ok());
+    let speedscope = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+    let frames = &speedscope["shared"]["frames"];
+    assert_eq!(frames[0]["name"], "GROUND_ZERO");
+    assert_eq!(frames[1]["name"], "function_4 (0x100)");
+    let profile = &speedscope["profiles"][0];
+    assert_eq!(profile["type"], "sampled");
+    assert_eq!(profile["endValue"], prof.total_cost());
+    assert_eq!(profile["samples"][0], serde_json::json!([0]));
+    assert_eq!(profile["samples"][1], serde_json::json!([0, 1]));
+    assert_eq!(profile["weights"][0], 5);
+
+    #[cfg(feature = "flamegraph")]
+    {
+        let mut svg = Vec::<u8>::new();
+        assert!(prof.write_flamegraph(&mut svg).is_ok());
+        assert!(std::str::from_utf8(&svg).unwrap().contains("<svg"));
+    }
+}
+
+#[test]
+fn generate_metadata() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_metadata.trace");
+    fs::write(&path, b"abc").unwrap();
+    let metadata = metadata::Metadata::new(&[&path], None);
+    fs::remove_file(&path).unwrap();
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_metadata(metadata.unwrap());
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(output.contains(&format!(
+        "\ndesc: Trace: {} sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n",
+        path.display()
+    )));
+    assert!(output.contains("\ndesc: Cost model: instructions\n"));
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgraph_json(&mut output);
+    assert!(r.is_ok());
+    let json = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+    assert_eq!(
+        json["metadata"]["traces"][0]["sha256"],
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(json["metadata"]["cost_model"], "instructions");
+}
+
+#[test]
+fn generate_header_missing() {
+    let input = b"1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: aaa\n";
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(input), &mut prof);
+    assert!(matches!(r, Err(crate::error::Error::TraceFormat)));
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(b"Lorem ipsum dolor sit amet"), &mut prof);
+    assert!(matches!(r, Err(crate::error::Error::TraceFormat)));
+}
+
+#[test]
+fn generate_cached() {
+    let dir = std::env::temp_dir().join("bpf-profile-generate_cached");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let trace_path = dir.join("simple.trace");
+    fs::write(&trace_path, mock::SIMPLE_INPUT).unwrap();
+    let mut options = super::Options {
+        output: Some(dir.join("first.out")),
+        cache_dir: Some(dir.join("cache")),
+        ..super::Options::default()
+    };
+    let r = super::run(&trace_path, None, &options);
+    assert!(r.is_ok());
+
+    // The second conversion copies the (tampered) cached profile
+    let entry = cache::entry(&dir.join("cache"), &trace_path, None, &options)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        fs::read(entry.join("profile")).unwrap(),
+        fs::read(dir.join("first.out")).unwrap()
+    );
+    fs::write(entry.join("profile"), b"cached").unwrap();
+    options.output = Some(dir.join("second.out"));
+    let r = super::run(&trace_path, None, &options);
+    assert!(r.is_ok());
+    assert_eq!(fs::read(dir.join("second.out")).unwrap(), b"cached");
+
+    // Other options make another entry
+    options.calls_only = true;
+    let other = cache::entry(&dir.join("cache"), &trace_path, None, &options).unwrap();
+    assert_ne!(other, Some(entry));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generate_bookmarks() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_bookmarks.jsonl");
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_bookmarks(bookmarks::Bookmarks::create(&path).unwrap());
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    assert!(prof.finish().is_ok());
+    let bookmarks = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let output = std::str::from_utf8(&bookmarks).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.lines().count(), 7);
+    assert!(output.starts_with(mock::SIMPLE_BOOKMARK));
+
+    let mut output = Vec::<u8>::new();
+    let r = bookmarks::show(
+        Cursor::new(mock::SIMPLE_INPUT),
+        Cursor::new(&bookmarks),
+        "function_20 (0x200)",
+        1,
+        &mut output,
+    );
+    assert!(r.is_ok());
+    let input = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let lines = input.lines().skip(10).take(8).collect::<Vec<_>>();
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        lines.join("\n") + "\n"
+    );
+
+    let r = bookmarks::show(
+        Cursor::new(mock::SIMPLE_INPUT),
+        Cursor::new(&bookmarks),
+        "function_20 (0x200)",
+        2,
+        &mut output,
+    );
+    assert!(r.is_err());
+}
+
+#[test]
+fn generate_heatmap() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_heatmap(10);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_heatmap(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_HEATMAP);
+}
+
+#[test]
+fn generate_counters() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_counters(true);
+    let r = trace::parse(Cursor::new(mock::MEMORY_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::MEMORY_CALLGRIND_COUNTERS);
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_counters(true);
+    let r = trace::parse(Cursor::new(mock::SYSCALL_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\nsummary: 3 0 0 2\n"));
+    assert!(output.contains("\ncalls=1 0\n10 0 0 0 1\n"));
+    assert!(output.contains("\ncalls=1 0\n11 0 0 0 1\n"));
+}
+
+#[test]
+fn generate_syscall_cost() {
+    let generate = || {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        prof.set_syscall_cost(Some(100));
+        let r = trace::parse(Cursor::new(mock::SYSCALL_INPUT), &mut prof);
+        assert!(r.is_ok());
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = generate();
+    assert!(output.contains("\nevents: Instructions Reads Writes Syscalls SyscallCost\n"));
+    assert!(output.contains("\nsummary: 3 0 0 2 200\n"));
+    assert!(output.contains("\ncalls=1 0\n10 0 0 0 1 100\n"));
+
+    let path = std::env::temp_dir().join("bpf-profile-generate_syscall_cost.toml");
+    std::fs::write(
+        &path,
+        "[[syscall]]\nname = \"sol_log_\"\ncost = 7\n\n\
+         [[syscall]]\nname = \"custom_helper\"\naddress = 0x1234\n",
+    )
+    .unwrap();
+    let r = crate::syscalls::read_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(r.is_ok());
+    assert_eq!(crate::syscalls::name(0x1234), Some("custom_helper"));
+
+    let output = generate();
+    assert!(output.contains("\nsummary: 3 0 0 2 14\n"));
+    assert!(output.contains("\ncalls=1 0\n11 0 0 0 1 7\n"));
+}
+
+#[test]
+fn generate_drop_calls_below() {
+    let generate = |drop_calls_below| {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        prof.set_drop_calls_below(drop_calls_below);
+        for _ in 0..2 {
+            let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+            assert!(r.is_ok());
+        }
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        String::from_utf8(output).unwrap()
+    };
+
+    let stored = generate(None);
+    assert!(stored.contains("\ncalls=2 "));
+    assert_eq!(generate(Some(1000)), stored);
+    assert_eq!(generate(Some(1)), stored);
+}
+
+#[test]
+fn generate_call_percentiles() {
+    // Ten calls of 0x200 in a loop costing 2 instructions, but the last one costing 20
+    let mut input = String::from("[Z TRACE bpf] BPF Program Instruction Trace:\n");
+    let mut line = 0;
+    let mut add = |pc: usize, text: &str| {
+        line += 1;
+        input += &format!(
+            "{} [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] {}: {}\n",
+            line, pc, text
+        );
+    };
+    add(1, "aaa");
+    for i in 0..10 {
+        add(2, "call 0x200");
+        let cost = if i == 9 { 20 } else { 2 };
+        for j in 1..cost {
+            add(20 + j, "yyy");
+        }
+        add(40, "exit");
+    }
+    add(3, "ccc");
+
+    let generate = |max_call_samples| {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        prof.set_max_call_samples(max_call_samples);
+        let r = trace::parse(Cursor::new(input.as_bytes()), &mut prof);
+        assert!(r.is_ok());
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgraph_json(&mut output);
+        assert!(r.is_ok());
+
+        //==== do not delete ====================================
+        //println!("{}", std::str::from_utf8(&output).unwrap());
+        //=======================================================
+
+        let json = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+        let edge = json["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["calls"] == 10)
+            .cloned()
+            .unwrap();
+        ["min", "avg", "p50", "p95", "max"].map(|key| edge[key].as_u64().unwrap())
+    };
+
+    assert_eq!(generate(None), [2, 3, 2, 20, 20]);
+    // The last 5 calls are aggregated into one record at their average cost
+    assert_eq!(generate(Some(5)), [2, 3, 2, 5, 5]);
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn view_profile() {
+    use ratatui::crossterm::event::KeyCode;
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let mut viewer = prof.viewer();
+    let names = |viewer: &super::view::Viewer| {
+        viewer
+            .rows()
+            .iter()
+            .map(|r| r.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        names(&viewer),
+        [
+            "function_30 (0x300)",
+            "function_4 (0x100)",
+            "function_20 (0x200)"
+        ]
+    );
+    assert_eq!(
+        (viewer.rows()[0].inclusive, viewer.rows()[0].calls),
+        (10, 5)
+    );
+    viewer.handle(KeyCode::Char('s'));
+    assert_eq!(
+        names(&viewer),
+        [
+            "function_4 (0x100)",
+            "function_30 (0x300)",
+            "function_20 (0x200)"
+        ]
+    );
+    for key in "/_2".chars().map(KeyCode::Char).chain([KeyCode::Enter]) {
+        viewer.handle(key);
+    }
+    assert_eq!(names(&viewer), ["function_20 (0x200)"]);
+
+    viewer.handle(KeyCode::Tab);
+    let lines = viewer.tree_lines();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        (lines[1].name.as_str(), lines[1].cost),
+        ("function_4 (0x100)", 20)
+    );
+    viewer.handle(KeyCode::Down);
+    viewer.handle(KeyCode::Right);
+    viewer.handle(KeyCode::Char('n'));
+    viewer.handle(KeyCode::Right);
+    let lines = viewer
+        .tree_lines()
+        .iter()
+        .map(|l| (l.path.len(), l.name.clone(), l.cost, l.calls))
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[2], (3, "function_20 (0x200)".into(), 8, 1));
+    assert_eq!(lines[3], (4, "function_30 (0x300)".into(), 4, 2));
+    assert_eq!(lines[4], (3, "function_30 (0x300)".into(), 6, 3));
+
+    viewer.handle(KeyCode::Left);
+    assert_eq!(viewer.tree_lines().len(), 4);
+    viewer.handle(KeyCode::Char('q'));
+    assert!(viewer.is_done());
+}
+
+#[test]
+fn query_profile() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    for expr in [
+        "top 2 by inclusive",
+        "top 1",
+        "callers of function_30",
+        "callees of function_20",
+        "sum cost where name ~ 'function_[23]0'",
+    ] {
+        let q = query::parse(expr).unwrap();
+        let r = prof.query(&mut output, &q);
+        assert!(r.is_ok());
+    }
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_QUERIES);
+    assert!(query::parse("top ten").is_err());
+    assert!(query::parse("sum cost where name ~ '('").is_err());
+}
+
+#[test]
+fn call_tree() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_stacks(true);
+    let r = trace::parse(Cursor::new(mock::NESTED_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_tree(&mut output, None, None);
+    assert!(r.is_ok());
+    let mut limited = Vec::<u8>::new();
+    let r = prof.write_tree(&mut limited, Some("function_10"), Some(1));
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //println!("{}", std::str::from_utf8(&limited).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::NESTED_TREE);
+    assert_eq!(limited, mock::NESTED_TREE_LIMITED);
+    let r = prof.write_tree(std::io::sink(), Some("no_such_function"), None);
+    assert!(matches!(r, Err(crate::error::Error::FunctionNotFound(_))));
+}
+
+#[test]
+fn summary_table() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_summary(&mut output, 2, query::By::SelfCost, false);
+    assert!(r.is_ok());
+    let r = prof.write_summary(&mut output, 10, query::By::Inclusive, true);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SUMMARY_TABLE);
+}
+
+#[test]
+fn generate_events() {
+    let path = std::env::temp_dir().join("bpf-profile-generate_events.jsonl");
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_events(events::Events::create(&path).unwrap());
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let r = prof.finish_events();
+    assert!(r.is_ok());
+    let output = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::SIMPLE_EVENTS);
+}
+
+#[test]
+fn generate_truncated() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let r = prof.truncate_callgrind(mock::SIMPLE_CALLGRIND_INTEGRAL.len(), config::DEFAULT_ASM);
+    assert_eq!(r.unwrap(), 0);
+
+    let max_size = mock::SIMPLE_CALLGRIND_INTEGRAL.len() - 1;
+    let r = prof.truncate_callgrind(max_size, config::DEFAULT_ASM);
+    assert_eq!(r.unwrap(), 1);
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert!(output.len() <= max_size);
+    assert_eq!(prof.total_cost(), 25);
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_TRUNCATED);
+}
+
+#[test]
+fn generate_call_targets() {
+    // Calls of all functions, syscalls and synthetic ones included, are followed
+    // by the number of calls and the numeric position of the callee
+    for profile in [
+        mock::SIMPLE_CALLGRIND_INTEGRAL,
+        mock::SIMPLE_CALLGRIND_COMPRESSED,
+        mock::SIMPLE_CALLGRIND_LINE_BY_LINE,
+        mock::NESTED_CALLGRIND,
+        mock::NESTED_CALLGRIND_PROGRAMS,
+        mock::NESTED_CALLGRIND_FLATTENED,
+        mock::SIMPLE_CALLGRIND_CALLS_ONLY,
+        mock::MIDDLE_CALLGRIND,
+        mock::MERGED_CALLGRIND,
+        mock::SIMPLE_CALLGRIND_ROOT_NAME,
+        mock::SIMPLE_DIFF_CALLGRIND,
+        mock::SIMPLE_CALLGRIND_WINDOW,
+        mock::SIMPLE_CALLGRIND_IGNORE_BEFORE,
+        mock::SIMPLE_CALLGRIND_ONLY_FUNCTION,
+        mock::SIMPLE_CALLGRIND_SOURCE_LINES,
+        mock::SIMPLE_CALLGRIND_PATHS,
+        mock::SIMPLE_CALLGRIND_PRUNED,
+        mock::SIMPLE_CALLGRIND_TRUNCATED,
+        mock::SIMPLE_CALLGRIND_FILTERED,
+    ] {
+        let profile = std::str::from_utf8(profile).unwrap();
+        let calls = profile
+            .lines()
+            .filter_map(|line| line.strip_prefix("calls="))
+            .collect::<Vec<_>>();
+        assert!(!calls.is_empty());
+        for call in calls {
+            let numbers = call
+                .split(' ')
+                .map(|n| n.parse::<u64>())
+                .collect::<Vec<_>>();
+            assert_eq!(numbers.len(), 2, "calls={}", call);
+            assert!(numbers.iter().all(|n| n.is_ok()), "calls={}", call);
+        }
+    }
+}
+
+#[test]
+fn generate_chrome_trace() {
+    let options = Options {
+        format: "chrome-trace".into(),
+        jobs: 3,
+        ..Options::default()
+    };
+    let write = |prof: &trace::Profile| {
+        let mut output = Vec::<u8>::new();
+        prof.write_chrome_trace(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    let r = trace::parse(mock::SIMPLE_INPUT, &mut prof);
+    assert!(r.is_ok());
+    let output = write(&prof);
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    let chrome_trace = serde_json::from_str::<serde_json::Value>(&output).unwrap();
+    let events = serde_json::from_slice::<serde_json::Value>(mock::SIMPLE_CHROME_TRACE).unwrap();
+    assert_eq!(chrome_trace["traceEvents"], events);
+    assert_eq!(chrome_trace["otherData"]["unit"], "instructions");
+
+    // Timestamps of chunks parsed in parallel follow the previous chunks
+    let second = std::str::from_utf8(mock::SECOND_INPUT).unwrap();
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let input = format!("{}{}{}", second, simple, second);
+    let mut expected = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    trace::parse(input.as_bytes(), &mut expected).unwrap();
+    let mut prof = trace::Profile::with_options(resv, &options).unwrap();
+    let r = parallel::parse_chunks(input.as_bytes(), &mut prof, &options, 1);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), write(&expected));
+}
+
+#[test]
+fn generate_text() {
+    let options = Options {
+        format: "text".into(),
+        ..Options::default()
+    };
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::with_options(resv, &options).unwrap();
+    let r = trace::parse(mock::SIMPLE_INPUT, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_text(&mut output);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_TEXT);
+}
+
+#[test]
+fn generate_pruned() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.prune_below(0.0), 0);
+
+    assert_eq!(prof.prune_below(35.0), 1);
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 25);
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_PRUNED);
+}
+
+#[test]
+fn generate_sorted() {
+    let resv = resolver::read_from(Cursor::new(mock::SIMPLE_DUMP)).unwrap();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let mut functions = |order| {
+        prof.set_order(order);
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("fn="))
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        functions(profile::Order::Address),
+        ["function_4 (0x100)", "process", "function_30 (0x300)"]
+    );
+    assert_eq!(
+        functions(profile::Order::Name),
+        ["function_30 (0x300)", "function_4 (0x100)", "process"]
+    );
+    assert_eq!(
+        functions(profile::Order::SelfCost),
+        ["function_30 (0x300)", "function_4 (0x100)", "process"]
+    );
+    assert_eq!(
+        functions(profile::Order::InclusiveCost),
+        ["function_4 (0x100)", "function_30 (0x300)", "process"]
+    );
+}
+
+#[test]
+fn generate_output_name() {
+    let name =
+        |trace: &str, format: &str| crate::gen::output_name(Path::new(trace), format).unwrap();
+    assert_eq!(
+        name("traces/trace.log", "callgrind"),
+        Path::new("trace.callgrind.out")
+    );
+    assert_eq!(name("trace.log.gz", "json"), Path::new("trace.json"));
+    assert_eq!(name("trace", "flamegraph"), Path::new("trace.svg"));
+    assert!(crate::gen::output_name(Path::new(config::STDIN), "callgrind").is_err());
+
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // Names which are not UTF-8 are kept as they are
+        let trace = Path::new(OsStr::from_bytes(b"tr\xffce.log"));
+        let output = crate::gen::output_name(trace, "callgrind").unwrap();
+        assert_eq!(output.as_os_str().as_bytes(), b"tr\xffce.callgrind.out");
+        let path = Path::new(OsStr::from_bytes(b"d\xffr/{label}.out"));
+        let labeled = crate::gen::labeled_path(path, &["tx 1".into()], 1);
+        assert_eq!(labeled.as_os_str().as_bytes(), b"d\xffr/tx_1.out");
+    }
+}
+
+#[test]
+fn generate_custom_format() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(mock::SIMPLE_INPUT, &mut prof);
+    assert!(r.is_ok());
+    let write = |name: &str| {
+        let mut output = Vec::<u8>::new();
+        let r = format::find(name).unwrap().write(&prof, &mut output);
+        assert!(r.is_ok());
+        output
+    };
+
+    assert_eq!(write("callgrind"), mock::SIMPLE_CALLGRIND_INTEGRAL);
+    assert!(format::find("total-cost").is_none());
+    format::register(
+        "total-cost",
+        |profile: &trace::Profile, output: &mut dyn std::io::Write| {
+            writeln!(output, "{}", profile.total_cost())?;
+            Ok(())
+        },
+    );
+    assert_eq!(write("total-cost"), b"25\n");
+}
+
+#[test]
+fn generate_path_granularity() {
+    let options = Options {
+        granularity: "path".into(),
+        jobs: 3,
+        ..Options::default()
+    };
+    let write = |prof: &trace::Profile| {
+        let mut output = Vec::<u8>::new();
+        prof.write_callgrind(&mut output, config::DEFAULT_ASM)
+            .unwrap();
+        output
+    };
+
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    let r = trace::parse(mock::SIMPLE_INPUT, &mut prof);
+    assert!(r.is_ok());
+    let output = write(&prof);
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_PATHS);
+
+    // Call paths of chunks parsed in parallel are merged
+    let second = std::str::from_utf8(mock::SECOND_INPUT).unwrap();
+    let simple = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let input = format!("{}{}{}", second, simple, second);
+    let mut expected = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    trace::parse(input.as_bytes(), &mut expected).unwrap();
+    let mut prof = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    let r = parallel::parse_chunks(input.as_bytes(), &mut prof, &options, 1);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), write(&expected));
+
+    let options = Options {
+        counters: true,
+        ..options
+    };
+    let r = trace::Profile::with_options(resv, &options);
+    assert!(
+        matches!(r, Err(crate::error::Error::GranularityOption(option)) if option == "--counters")
+    );
+}
+
+#[test]
+fn generate_compressed() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_compress_output(true);
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_COMPRESSED);
+}
+
+#[test]
+fn generate_watched() {
+    let input = std::str::from_utf8(mock::SIMPLE_INPUT).unwrap();
+    let split = input.find("5: call 0x200\n").unwrap() + "5: call 0x200\n".len();
+    let path = std::env::temp_dir().join("bpf-profile-generate_watched.trace");
+    fs::write(&path, &input[..split]).unwrap();
+
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let mut parser = trace::TraceParser::new(&mut prof);
+    let mut tail = tail::Tail::new(&path);
+    let lines = tail.read().unwrap();
+    // The call waits for the first instruction of the callee
+    assert!(lines.ends_with("4: xxx\n"));
+    assert!(parser.feed(lines.as_bytes(), &mut prof).is_ok());
+
+    fs::write(&path, input).unwrap();
+    assert!(!tail.restarted().unwrap());
+    let lines = tail.read().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(lines.contains("5: call 0x200\n"));
+    assert!(parser.feed(lines.as_bytes(), &mut prof).is_ok());
+    assert!(parser.finish(&mut prof).is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_INTEGRAL);
+}
+
+#[test]
+fn generate_call_depth() {
+    // The entrypoint calls 0x100 recursively down to the depth of 60 frames
+    let mut input = String::from("[Z TRACE bpf] BPF Program Instruction Trace:\n");
+    let mut line = 0;
+    let mut add = |pc: usize, ix: &str| {
+        line += 1;
+        input += &format!(
+            "{} [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] {}: {}\n",
+            line, pc, ix
+        );
+    };
+    add(1, "call 0x100");
+    for _ in 1..59 {
+        add(2, "call 0x100");
+    }
+    for _ in 0..59 {
+        add(3, "exit");
+    }
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(input.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains(
+        "\nCall depth: max 60 (limit 64 frames) at risk\n    \
+         function_2 (0x100) frames=59 max_depth=60 avg_depth=31.00\n"
+    ));
+}
+
+#[test]
+fn generate_recursion() {
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::RECURSION_INPUT), &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert!(
+        output.ends_with("\nRecursion:\n    function_10 (0x100) direct=1 mutual=1 max_depth=2\n")
+    );
+
+    // The mutually recursive call is a part of the outer call of 0x100
+    let mut output = Vec::<u8>::new();
+    let r = prof.query(&mut output, &query::parse("top 2 by inclusive").unwrap());
+    assert!(r.is_ok());
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        "          10  function_10 (0x100)\n           4  function_20 (0x200)\n"
+    );
+}
+
+#[test]
+fn generate_lcov() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SIMPLE_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_lcov(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+
+    //==== do not delete ====================================
+    //println!("{}", std::str::from_utf8(&output).unwrap());
+    //=======================================================
+
+    assert_eq!(output, mock::SIMPLE_LCOV);
+}
+
+#[test]
+fn generate_syscalls() {
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SYSCALL_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_stats(&mut output);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.ends_with(mock::SYSCALL_STATS));
+}
+
+#[test]
+fn generate_totals() {
+    // The summary and the totals of every part are the sums of the cost lines of the part
+    let parts = |profile: &[u8]| {
+        let profile = std::str::from_utf8(profile).unwrap();
+        let mut parts = Vec::<(i64, i64)>::new();
+        let mut summary = None;
+        let mut call = false;
+        for line in profile.lines() {
+            if let Some(costs) = line.strip_prefix("summary: ") {
+                summary = Some(costs);
+            } else if let Some(totals) = line.strip_prefix("totals: ") {
+                assert_eq!(summary.take(), Some(totals));
+                let totals = totals.split(' ').next().unwrap().parse().unwrap();
+                parts.push((totals, 0));
+            } else if line.starts_with("calls=") {
+                call = true;
+            } else if line.starts_with(|c: char| c.is_ascii_digit() || "+-*".contains(c)) {
+                if !call {
+                    let cost = line.split(' ').nth(1).unwrap();
+                    parts.last_mut().unwrap().1 += cost.parse::<i64>().unwrap();
+                }
+                call = false;
+            }
+        }
+        parts
+    };
+    for profile in [
+        mock::SIMPLE_CALLGRIND_INTEGRAL,
+        mock::SIMPLE_CALLGRIND_COMPRESSED,
+        mock::SIMPLE_CALLGRIND_LINE_BY_LINE,
+        mock::NESTED_CALLGRIND,
+        mock::NESTED_CALLGRIND_FLATTENED,
+        mock::MIDDLE_CALLGRIND,
+        mock::MERGED_CALLGRIND,
+        mock::SIMPLE_CALLGRIND_ROOT_NAME,
+        mock::MEMORY_CALLGRIND_COUNTERS,
+        mock::SIMPLE_DIFF_CALLGRIND,
+        mock::SIMPLE_CALLGRIND_SOURCE_LINES,
+        mock::SIMPLE_CALLGRIND_PATHS,
+        mock::SIMPLE_CALLGRIND_PRUNED,
+        mock::SIMPLE_CALLGRIND_TRUNCATED,
+        mock::SIMPLE_CALLGRIND_FILTERED,
+    ] {
+        let parts = parts(profile);
+        assert!(!parts.is_empty());
+        for (totals, sum) in parts {
+            assert_eq!(totals, sum);
+        }
+    }
+}
+
+#[test]
+fn generate_intermediate() {
+    let dir = std::env::temp_dir().join(format!("bpf-profile-intermediate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let trace_path = dir.join("simple.trace");
+    fs::write(&trace_path, mock::SIMPLE_INPUT).unwrap();
+    let intermediate_path = dir.join("simple.bpp");
+    let options = Options {
+        output: Some(dir.join("simple.out")),
+        save_intermediate: Some(intermediate_path.clone()),
+        ..Options::default()
+    };
+    assert!(super::run(&trace_path, None, &options).is_ok());
+
+    // Outputs rendered from the intermediate profile are those of the trace
+    for format in ["callgrind", "folded", "json", "text"] {
+        let generated_path = dir.join(format!("generated.{}", format));
+        let rendered_path = dir.join(format!("rendered.{}", format));
+        let options = Options {
+            format: format.into(),
+            output: Some(generated_path.clone()),
+            ..Options::default()
+        };
+        assert!(super::run(&trace_path, None, &options).is_ok());
+        let options = Options {
+            output: Some(rendered_path.clone()),
+            ..options
+        };
+        assert!(super::render(&intermediate_path, &options).is_ok());
+        assert_eq!(
+            fs::read(&generated_path).unwrap(),
+            fs::read(&rendered_path).unwrap()
+        );
+    }
+
+    let rendered_path = dir.join("rendered.out");
+    let options = Options {
+        output: Some(rendered_path.clone()),
+        root_name: Some("tx1".into()),
+        ..Options::default()
+    };
+    assert!(super::render(&intermediate_path, &options).is_ok());
+    let rendered = fs::read_to_string(&rendered_path).unwrap();
+    assert!(rendered.contains("\nsummary: 25\ntotals: 25\n"));
+    assert!(rendered.contains("\nfn=tx1\n"));
+
+    let options = Options {
+        format: "chrome-trace".into(),
+        ..Options::default()
+    };
+    assert!(super::render(&intermediate_path, &options).is_err());
+    assert!(super::render(&trace_path, &Options::default()).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generate_nested() {
+    for (flatten, expected) in [
+        (false, mock::NESTED_CALLGRIND),
+        (true, mock::NESTED_CALLGRIND_FLATTENED),
+    ] {
+        let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+        prof.set_flatten_nested(flatten);
+        let r = trace::parse(Cursor::new(mock::NESTED_INPUT), &mut prof);
+        assert!(r.is_ok());
+        assert_eq!(prof.total_cost(), 13);
+
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        let output = std::str::from_utf8(&output).unwrap();
+
+        //==== do not delete ====================================
+        //println!("{}", output);
+        //=======================================================
+
+        assert_eq!(output.as_bytes(), expected);
+    }
+}
+
+#[test]
+fn generate_program_dumps() {
+    let dump = |name: &str, pc: usize| {
+        format!(
+            "ELF Header:\n  Class: ELF64\n\nDisassembly of section .text:\n\n\
+             0000000000000100 <{}>:\n      \
+             {}       07 00 00 00 00 00 00 00 ddd\n      \
+             {}       95 00 00 00 00 00 00 00 exit\n",
+            name,
+            pc,
+            pc + 1
+        )
+    };
+    let dir = std::env::temp_dir().join("bpf-profile-generate_program_dumps");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("outer.dump"), dump("outer_helper", 200)).unwrap();
+    fs::write(dir.join("invoked.dump"), dump("invoked_helper", 200)).unwrap();
+    let manifest_path = dir.join("programs.toml");
+    fs::write(
+        &manifest_path,
+        "[[program]]\nid = \"9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin\"\ndump = \"invoked.dump\"\n",
+    )
+    .unwrap();
+    let options = Options {
+        dump_manifest: Some(manifest_path.clone()),
+        ..Options::default()
+    };
+    let resv = crate::gen::read_resolver(Some(&dir.join("outer.dump")), &options);
+    let manifest = resolver::read_manifest(&manifest_path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        manifest.unwrap(),
+        [(
+            "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+            dir.join("invoked.dump")
+        )]
+    );
+    let resv = resv.unwrap();
+
+    // Functions of the invoked program are resolved by its dump,
+    // if the invoked program is reported
+    let input = std::str::from_utf8(mock::NESTED_INPUT).unwrap();
+    let invoke = "11: call 0xd7449092\n";
+    let invoked = input.replace(
+        invoke,
+        &format!(
+            "{}Program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin invoke [2]\n",
+            invoke
+        ),
+    );
+    for (input, name) in [(input, "outer_helper"), (&invoked, "invoked_helper")] {
+        let mut prof = trace::Profile::new(resv.clone(), None).unwrap();
+        let r = trace::parse(input.as_bytes(), &mut prof);
+        assert!(r.is_ok());
+        assert_eq!(prof.total_cost(), 13);
+
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(&format!("\nfn={}\n200 2\n", name)));
+    }
+}
+
+#[test]
+fn generate_programs() {
+    // Functions are grouped into callgrind objects by the programs executing them
+    let input = std::str::from_utf8(mock::NESTED_INPUT).unwrap();
+    let invoke = "11: call 0xd7449092\n";
+    let input = format!(
+        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [1]\n{}",
+        input.replace(
+            invoke,
+            &format!(
+                "{}Program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin invoke [2]\n",
+                invoke
+            ),
+        )
+    );
+    let write = |options: &Options| {
+        let mut prof =
+            trace::Profile::with_options(resolver::Resolver::default(), options).unwrap();
+        let r = trace::parse(input.as_bytes(), &mut prof);
+        assert!(r.is_ok());
+        let mut output = Vec::<u8>::new();
+        let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+        assert!(r.is_ok());
+        String::from_utf8(output).unwrap()
+    };
+    let options = Options {
+        root_name: Some("entrypoint".into()),
+        ..Options::default()
+    };
+    let output = write(&options);
+
+    //==== do not delete ====================================
+    //println!("{}", output);
+    //=======================================================
+
+    assert_eq!(output.as_bytes(), mock::NESTED_CALLGRIND_PROGRAMS);
+    let options = Options {
+        compress_output: true,
+        ..options
+    };
+    assert!(write(&options).contains("\nob=(2) 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin\n"));
+
+    // A single program is not written as an object
+    let input = format!(
+        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [1]\n{}",
+        std::str::from_utf8(mock::NESTED_INPUT).unwrap()
+    );
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(input.as_bytes(), &mut prof);
+    assert!(r.is_ok());
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::NESTED_CALLGRIND);
+}
+
+#[test]
+fn generate_pc_offset() {
+    let options = Options {
+        jobs: 2,
+        ..Options::default()
+    };
+    let write = |prof: &trace::Profile| {
+        let mut output = Vec::<u8>::new();
+        prof.write_callgrind(&mut output, config::DEFAULT_ASM)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    let resv = resolver::read_from(Cursor::new(mock::SIMPLE_DUMP)).unwrap();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    let expected = write(&prof);
+    assert!(expected.contains("\nfn=process\n"));
+
+    // The program is relocated by 100 instructions in the dump
+    let dump = std::str::from_utf8(mock::SIMPLE_DUMP)
+        .unwrap()
+        .replace("\n      2", "\n      12");
+    let resv = resolver::read_from(Cursor::new(dump)).unwrap();
+    let mut prof = trace::Profile::new(resv.clone(), None).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    assert!(!write(&prof).contains("\nfn=process\n"));
+
+    let mut resv = resv;
+    resv.set_pc_offset(-100);
+    let mut prof = trace::Profile::with_options(resv.clone(), &options).unwrap();
+    let r = trace::parse(Cursor::new(mock::SIMPLE_INPUT), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), expected);
+
+    let mut prof = trace::Profile::with_options(resv, &options).unwrap();
+    let r = parallel::parse_chunks(mock::SIMPLE_INPUT, &mut prof, &options, 1);
+    assert!(r.is_ok());
+    assert_eq!(write(&prof), expected);
+}
+
+#[test]
+fn generate_cluster_costs() {
+    assert!(crate::cost::Model::cluster("0.1").is_err());
+
+    let resv = resolver::Resolver::default();
+    let reader = Cursor::new(mock::SYSCALL_INPUT);
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    prof.set_cost_model(crate::cost::Model::cluster("1.18").unwrap());
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    // 5 instructions and 2 calls of sol_log_ which cost 100 each
+    assert_eq!(prof.total_cost(), 205);
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\ncfn=sol_log_\ncalls=1 0\n10 100\n"));
+    assert!(output.contains("\ncfn=sol_log_\ncalls=1 0\n11 100\n"));
+}
+
+#[test]
+fn generate_runtime_version() {
+    let input = std::str::from_utf8(mock::SYSCALL_INPUT)
+        .unwrap()
+        .replace("Instruction Trace:", "Instruction Trace (agave 2.0.3):");
+    let resv = resolver::Resolver::default();
+    let mut prof = trace::Profile::new(resv, None).unwrap();
+    let r = trace::parse(Cursor::new(input), &mut prof);
+    assert!(r.is_ok());
+
+    // Costs follow the detected release
+    assert_eq!(prof.total_cost(), 205);
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    let output = std::str::from_utf8(&output).unwrap();
+    assert!(output.contains("\ncreator: bpf-profile\ndesc: Runtime: 2.0.3\n"));
+
+    // An explicit cost model wins over the detected release
+    let input = std::str::from_utf8(mock::SYSCALL_INPUT)
+        .unwrap()
+        .replace("Instruction Trace:", "Instruction Trace (solana 1.9.1):");
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    prof.set_cost_model(crate::cost::Model::Instructions);
+    let r = trace::parse(Cursor::new(input), &mut prof);
+    assert!(r.is_ok());
+    assert_eq!(prof.total_cost(), 5);
+}
+
+#[test]
+fn generate_from_compact() {
+    let mut compacted = Vec::<u8>::new();
+    let r = compact::compact_trace(Cursor::new(mock::SIMPLE_INPUT), &mut compacted);
+    assert!(r.is_ok());
+    assert!(compacted.len() < mock::SIMPLE_INPUT.len());
+
+    let expander = compact::Expander::new(Cursor::new(compacted)).unwrap();
+    let reader = std::io::BufReader::new(expander);
+    let mut prof = trace::Profile::new(resolver::Resolver::default(), None).unwrap();
+    let r = trace::parse(reader, &mut prof);
+    assert!(r.is_ok());
+
+    let mut output = Vec::<u8>::new();
+    let r = prof.write_callgrind(&mut output, config::DEFAULT_ASM);
+    assert!(r.is_ok());
+    assert_eq!(output, mock::SIMPLE_CALLGRIND_INTEGRAL);
+}
+
+#[test]
+fn progress_counts() {
+    let mut input = mock::SIMPLE_INPUT.to_vec();
+    input.extend_from_slice(mock::SECOND_INPUT);
+    let mut invocations = trace::invocations(Cursor::new(&input));
+    assert!(invocations.by_ref().all(|i| i.is_ok()));
+    assert_eq!(invocations.bytes_read(), input.len() as u64);
+    assert_eq!(
+        invocations.lines(),
+        input.iter().filter(|b| **b == b'\n').count()
+    );
+
+    let dir = std::env::temp_dir();
+    let plain = dir.join("bpf-profile-progress_counts.log");
+    fs::write(&plain, &input).unwrap();
+    let compacted = dir.join("bpf-profile-progress_counts.bin");
+    let mut output = Vec::<u8>::new();
+    compact::compact_trace(Cursor::new(&input), &mut output).unwrap();
+    fs::write(&compacted, &output).unwrap();
+
+    assert_eq!(crate::filebuf::plain_size(&plain), Some(input.len() as u64));
+    assert_eq!(crate::filebuf::plain_size(&compacted), None);
+    assert_eq!(crate::filebuf::plain_size(Path::new(config::STDIN)), None);
+    fs::remove_file(&plain).unwrap();
+    fs::remove_file(&compacted).unwrap();
+}
+
 #[test]
 fn subsequence() {
     let r = find_subsequence(b"lorem ipsum dolor sit amet", b"dolor");
@@ -74,6 +2838,99 @@ fn subsequence() {
     assert!(r.is_none());
 }
 
+#[test]
+fn write_junit() {
+    use crate::gen::junit::{self, Failure, TestCase};
+    let cases = [
+        TestCase {
+            name: "function_1".into(),
+            failures: vec![],
+        },
+        TestCase {
+            name: "function_<2>".into(),
+            failures: vec![Failure {
+                kind: "budget/self".into(),
+                message: "cost 'a' & \"b\"".into(),
+            }],
+        },
+    ];
+    let mut output = Vec::<u8>::new();
+    junit::write(&mut output, "budgets", &cases).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="bpf-profile" tests="2" failures="1">
+  <testsuite name="budgets" tests="2" failures="1">
+    <testcase classname="budgets" name="function_1"/>
+    <testcase classname="budgets" name="function_&lt;2&gt;">
+      <failure type="budget/self" message="cost &apos;a&apos; &amp; &quot;b&quot;"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+}
+
+#[test]
+fn notify_text() {
+    use crate::gen::notify;
+    let messages = (0..22)
+        .map(|i| format!("function_{}: cost exceeds budget", i))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notify::text("1 violation(s)", &messages[..1]),
+        "bpf-profile: 1 violation(s)\nfunction_0: cost exceeds budget"
+    );
+
+    // Only the first messages are listed
+    let text = notify::text("22 violation(s)", &messages);
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 22);
+    assert_eq!(lines[0], "bpf-profile: 22 violation(s)");
+    assert_eq!(lines[20], "function_19: cost exceeds budget");
+    assert_eq!(lines[21], "... and 2 more");
+}
+
+#[test]
+fn generate_batch() {
+    let dir = std::env::temp_dir().join(format!("bpf-profile-batch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.trace"), mock::SIMPLE_INPUT).unwrap();
+    fs::write(dir.join("b.trace"), b"Not a trace\n").unwrap();
+    fs::write(dir.join("c.log"), mock::SIMPLE_INPUT).unwrap();
+
+    let traces = batch::discover(&dir, config::DEFAULT_TRACE_PATTERN).unwrap();
+    assert_eq!(traces, [dir.join("a.trace"), dir.join("b.trace")]);
+    assert!(batch::discover(&dir.join("missing"), "*").is_err());
+
+    let batch = traces
+        .iter()
+        .map(|trace| (trace.as_path(), trace.with_extension("out")))
+        .collect::<Vec<_>>();
+    let convert = |trace_path: &Path, output_path: &Path| {
+        let options = Options {
+            output: Some(output_path.into()),
+            ..Options::default()
+        };
+        super::run(trace_path, None, &options)
+    };
+    let mut output = Vec::<u8>::new();
+    let failed = batch::run(&batch, 2, convert, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    //==== do not delete ====
+    println!("{}", output);
+    //==== do not delete ====
+    assert_eq!(failed, 1);
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("trace"));
+    assert!(lines[1].contains("a.trace") && lines[1].ends_with("  ok"));
+    assert!(lines[2].contains("b.trace") && !lines[2].ends_with("  ok"));
+    let profile = fs::read(dir.join("a.out")).unwrap();
+    assert_eq!(profile, mock::SIMPLE_CALLGRIND_INTEGRAL);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
 /// Searches (ineffectively) a subslice in another slice.
 fn find_subsequence<T>(haystack: &[T], needle: &[T]) -> Option<usize>
 where
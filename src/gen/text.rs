@@ -0,0 +1,140 @@
+//! bpf-profile-generate text module.
+//! Implements the text report in the manner of `callgrind_annotate --inclusive=yes
+//! --tree=both`: the table of functions by inclusive and self costs with percentages
+//! of the total cost, followed by callers and callees of every function with
+//! the distribution of costs of repeated calls, so the profile is read without
+//! any external tools.
+
+use super::budget;
+use super::callgraph;
+use super::profile::{CallStats, FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::Result;
+use crate::global;
+use std::io::Write;
+
+/// Line separating sections of the report.
+const SEPARATOR: &str =
+    "--------------------------------------------------------------------------------";
+
+/// Width of columns of costs with percentages.
+const COST_WIDTH: usize = 22;
+
+/// Writes the report of costs of functions, the most costly inclusively first,
+/// of the event of the cost, e.g. `Instructions`.
+pub fn write(
+    mut output: impl Write,
+    functions: &Functions,
+    total_cost: Cost,
+    event: &str,
+) -> Result<()> {
+    let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+    let mut ids = functions.keys().copied().collect::<Vec<_>>();
+    ids.sort_by(|a, b| {
+        inclusive_costs[b]
+            .cmp(&inclusive_costs[a])
+            .then_with(|| functions[a].name().cmp(&functions[b].name()))
+            .then(a.cmp(b))
+    });
+    let cost = |cost: Cost| {
+        format!(
+            "{} ({:.2}%)",
+            global::number(cost),
+            100.0 * cost as f64 / total_cost.max(1) as f64
+        )
+    };
+
+    writeln!(output, "{}", SEPARATOR)?;
+    writeln!(output, "Events shown: {}", event)?;
+    writeln!(output, "Total:        {}", global::number(total_cost))?;
+    writeln!(output, "{}", SEPARATOR)?;
+    writeln!(
+        output,
+        "{:>w$} {:>w$}  function",
+        "inclusive",
+        "self",
+        w = COST_WIDTH
+    )?;
+    writeln!(output, "{}", SEPARATOR)?;
+    for id in &ids {
+        writeln!(
+            output,
+            "{:>w$} {:>w$}  {}",
+            cost(inclusive_costs[id]),
+            cost(functions[id].cost()),
+            functions[id].name(),
+            w = COST_WIDTH
+        )?;
+    }
+
+    let mut callers = Map::<FrameId, Vec<(FrameId, CallStats)>>::new();
+    let mut callees = Map::<FrameId, Vec<(FrameId, CallStats)>>::new();
+    for ((caller, callee), stats) in callgraph::edges(functions) {
+        callers
+            .entry(callee)
+            .or_default()
+            .push((caller, stats.clone()));
+        callees.entry(caller).or_default().push((callee, stats));
+    }
+    let write_calls = |output: &mut dyn Write,
+                       calls: Option<&mut Vec<(FrameId, CallStats)>>,
+                       direction: &str|
+     -> Result<()> {
+        let Some(calls) = calls else {
+            return Ok(());
+        };
+        calls.sort_by(|(a, a_stats), (b, b_stats)| {
+            b_stats
+                .cost
+                .cmp(&a_stats.cost)
+                .then_with(|| functions[a].name().cmp(&functions[b].name()))
+        });
+        for (id, stats) in calls.iter() {
+            write!(
+                output,
+                "{:>w$}  {} {} ({}x",
+                cost(stats.cost),
+                direction,
+                functions[id].name(),
+                stats.calls,
+                w = COST_WIDTH
+            )?;
+            if stats.calls > 1 {
+                write!(
+                    output,
+                    ": min {} avg {} p50 {} p95 {} max {}",
+                    global::number(stats.min),
+                    global::number(stats.average()),
+                    global::number(stats.percentile(50.0)),
+                    global::number(stats.percentile(95.0)),
+                    global::number(stats.max)
+                )?;
+            }
+            writeln!(output, ")")?;
+        }
+        Ok(())
+    };
+
+    writeln!(output)?;
+    writeln!(output, "{}", SEPARATOR)?;
+    writeln!(
+        output,
+        "-- Callers (<) and callees (>) of functions, by inclusive costs of calls"
+    )?;
+    writeln!(output, "{}", SEPARATOR)?;
+    for id in &ids {
+        writeln!(output)?;
+        write_calls(&mut output, callers.get_mut(id), "<")?;
+        writeln!(
+            output,
+            "*{:>w$}  {}",
+            cost(inclusive_costs[id]),
+            functions[id].name(),
+            w = COST_WIDTH - 1
+        )?;
+        write_calls(&mut output, callees.get_mut(id), ">")?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
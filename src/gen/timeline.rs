@@ -0,0 +1,114 @@
+//! bpf-profile-generate timeline module.
+//! Implements the timeline of calls in the trace event format of Chrome, read by
+//! Perfetto and about://tracing: the begin (`B`) and the end (`E`) event of every call,
+//! timestamped by the cost executed before them instead of microseconds, e.g.
+//! `{"name":"function_0 (0x100)","ph":"B","ts":3,"pid":1,"tid":1}`.
+
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::Result;
+use serde::Serialize;
+use serde_json::json;
+use std::io::Write;
+
+/// Represents the entry into or the exit from a function.
+#[derive(Debug)]
+struct Event {
+    begin: bool,
+    id: FrameId,
+    ts: Cost,
+}
+
+/// Represents entries into and exits from functions in order of the trace.
+#[derive(Debug, Default)]
+pub struct Timeline {
+    events: Vec<Event>,
+}
+
+impl Timeline {
+    /// Adds the entry into the function after the cost executed so far.
+    pub fn begin(&mut self, id: FrameId, ts: Cost) {
+        self.events.push(Event {
+            begin: true,
+            id,
+            ts,
+        });
+    }
+
+    /// Adds the exit from the function after the cost executed so far.
+    pub fn end(&mut self, id: FrameId, ts: Cost) {
+        self.events.push(Event {
+            begin: false,
+            id,
+            ts,
+        });
+    }
+
+    /// Replaces identifiers of functions by the map.
+    pub fn remap(&mut self, map: impl Fn(FrameId) -> FrameId) {
+        for event in &mut self.events {
+            event.id = map(event.id);
+        }
+    }
+
+    /// Appends the timeline of the following chunk of the trace,
+    /// which starts after the cost executed before it, replacing identifiers
+    /// of functions by the map.
+    pub fn append(&mut self, other: Timeline, start: Cost, map: impl Fn(FrameId) -> FrameId) {
+        self.events.extend(other.events.into_iter().map(|e| Event {
+            id: map(e.id),
+            ts: e.ts + start,
+            ..e
+        }));
+    }
+}
+
+/// Represents an event of the trace event format.
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    ph: &'a str,
+    ts: Cost,
+    pid: u32,
+    tid: u32,
+}
+
+/// Writes the timeline as a JSON object of the trace event format,
+/// with the metadata, if any, among other data.
+pub fn write(
+    mut output: impl Write,
+    timeline: &Timeline,
+    functions: &Functions,
+    metadata: Option<serde_json::Value>,
+) -> Result<()> {
+    let names = functions
+        .iter()
+        .map(|(id, f)| (*id, f.name()))
+        .collect::<Map<_, _>>();
+    let events = timeline
+        .events
+        .iter()
+        .map(|e| TraceEvent {
+            name: &names[&e.id],
+            ph: if e.begin { "B" } else { "E" },
+            ts: e.ts,
+            pid: 1,
+            tid: 1,
+        })
+        .collect::<Vec<_>>();
+    let mut trace = json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ns",
+        "otherData": {
+            "unit": "instructions",
+            "exporter": format!("bpf-profile {}", env!("CARGO_PKG_VERSION")),
+        },
+    });
+    if let Some(metadata) = metadata {
+        trace["otherData"]["metadata"] = metadata;
+    }
+    serde_json::to_writer(&mut output, &trace).map_err(std::io::Error::from)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
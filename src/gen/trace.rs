@@ -1,12 +1,44 @@
 //! bpf-profile-generate trace module.
 //! Implements parsing of the trace file and generating the profile.
 
-use super::asm;
-use super::profile::{self, Call, Function, Functions};
-use crate::config::{Cost, Map, ProgramCounter, GROUND_ZERO};
+use super::annotate::{self, Executions};
+use super::bookmarks::Bookmarks;
+use super::callgraph;
+use super::check::Finding;
+use super::events::Events;
+use super::filter::Filter;
+use super::flamegraph::{self, Stacks};
+use super::heatmap::Heatmap;
+use super::instructions::{self, Mnemonics};
+use super::intermediate::Intermediate;
+use super::metadata::Metadata;
+use super::profile::{self, Call, CallStack, Counters, FrameId, Function, Functions, PathCalls};
+use super::progress::{self, Progress};
+use super::stats::{self, Depths, MemoryRegions, OpcodeClasses, Recursions, UnknownOpcodes};
+use super::text;
+use super::timeline::{self, Timeline};
+use super::tree;
+#[cfg(feature = "tui")]
+use super::view;
+use super::window::Window;
+use super::{
+    asm, badge, budget, diff, lcov, metrics, parallel, query, regression, summary, Options,
+};
+use crate::config::{
+    Address, Cost, Map, ProgramCounter, CALL_DEPTH_LIMIT, DEFAULT_ASM, DEFAULT_ERROR_LIMIT,
+    DEFAULT_FORMAT, EXPLICIT_ROOT_NAME, FLAMEGRAPH_FORMATS, STACK_FRAME_LIMIT, STACK_LIMIT,
+    TIMELINE_FORMAT, UNRESOLVED_REPORT_LIMIT,
+};
+use crate::cost;
+use crate::diagnostics::{Diagnostic, Diagnostics};
 use crate::error::{Error, Result};
 use crate::resolver::{self, Resolver};
+use crate::syscalls;
+use crate::trace::{Legacy, TraceFormat};
 use crate::{filebuf, global};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::io::{BufRead, Write};
 use std::path::Path;
 
@@ -14,10 +46,76 @@ use std::path::Path;
 #[derive(Debug)]
 pub struct Profile {
     total_cost: Cost,
-    ground: Call,
+    total_counters: Option<Counters>,
+    syscall_cost: Option<Cost>,
+    drop_calls_below: Option<Cost>,
+    max_call_samples: Option<usize>,
+    /// Numbers of calls stored separately by callers and callees.
+    call_samples: Map<(FrameId, FrameId), usize>,
+    ground: CallStack,
     functions: Functions,
     resolver: Resolver,
     asm: Option<asm::Source>,
+    opcode_classes: OpcodeClasses,
+    unknown_opcodes: UnknownOpcodes,
+    mnemonics: Option<Mnemonics>,
+    executions: Option<Executions>,
+    memory_regions: Option<MemoryRegions>,
+    heatmap: Option<Heatmap>,
+    events: Option<Events>,
+    bookmarks: Option<Bookmarks>,
+    progress: Option<Progress>,
+    trace_format: Box<dyn TraceFormat>,
+    stacks: Option<Stacks>,
+    stack_cost: Option<Cost>,
+    path_calls: Option<PathCalls>,
+    timeline: Option<Timeline>,
+    metadata: Option<Metadata>,
+    stack: Vec<FrameId>,
+    open_frames: Map<FrameId, usize>,
+    depths: Depths,
+    recursions: Recursions,
+    cost_model: cost::Model,
+    cost_model_set: bool,
+    runtime_version: Option<String>,
+    labels: Vec<String>,
+    calls_only: bool,
+    syscall_sizes: bool,
+    flatten_nested: bool,
+    nested: Vec<(usize, usize)>,
+    nested_ended: bool,
+    programs: Vec<(Address, String)>,
+    source_lines: bool,
+    call_lines: bool,
+    compress_output: bool,
+    order: profile::Order,
+    ignore_before: Option<String>,
+    ignoring: bool,
+    only_function: bool,
+    window: Option<Window>,
+    filter: Option<Filter>,
+    hidden: BTreeSet<FrameId>,
+    hidden_frames: Vec<bool>,
+    entry_depth: usize,
+    entries: usize,
+    ground_exited: bool,
+    in_invocation: bool,
+    invocations: usize,
+    lenient: bool,
+    diagnostics: Diagnostics,
+    malformed_lines: usize,
+    skipped_lines: usize,
+    dropped_calls: usize,
+    unwound_calls: usize,
+    line: usize,
+    line_start: u64,
+    line_end: u64,
+    previous_line_start: u64,
+    findings: Option<Vec<Finding>>,
+    next_pc: Option<ProgramCounter>,
+    trace_start: bool,
+    root_named: bool,
+    thread: Option<usize>,
 }
 
 use crate::bpf::Instruction;
@@ -26,154 +124,2659 @@ impl Profile {
     /// Creates the initial instance of profile.
     pub fn new(resv: Resolver, asm_path: Option<&Path>) -> Result<Self> {
         let mut functions = Map::new();
-        functions.insert(GROUND_ZERO, Function::ground_zero());
+        functions.insert(FrameId::Root, Function::ground_zero());
         Ok(Profile {
             total_cost: 0,
-            ground: Call::new(GROUND_ZERO, 0),
+            total_counters: None,
+            syscall_cost: None,
+            drop_calls_below: None,
+            max_call_samples: None,
+            call_samples: Map::new(),
+            ground: CallStack::new(),
             functions,
             resolver: resv,
             asm: asm_path.map(asm::Source::new),
+            opcode_classes: Map::new(),
+            unknown_opcodes: Map::new(),
+            mnemonics: None,
+            executions: None,
+            memory_regions: None,
+            heatmap: None,
+            events: None,
+            bookmarks: None,
+            progress: None,
+            trace_format: Box::new(Legacy),
+            stacks: None,
+            stack_cost: None,
+            path_calls: None,
+            timeline: None,
+            metadata: None,
+            stack: Vec::new(),
+            open_frames: Map::new(),
+            depths: Map::new(),
+            recursions: Map::new(),
+            cost_model: cost::Model::default(),
+            cost_model_set: false,
+            runtime_version: None,
+            labels: Vec::new(),
+            calls_only: false,
+            syscall_sizes: false,
+            flatten_nested: false,
+            nested: Vec::new(),
+            nested_ended: false,
+            programs: Vec::new(),
+            source_lines: false,
+            call_lines: false,
+            compress_output: false,
+            order: profile::Order::default(),
+            ignore_before: None,
+            ignoring: false,
+            only_function: false,
+            window: None,
+            filter: None,
+            hidden: BTreeSet::new(),
+            hidden_frames: Vec::new(),
+            entry_depth: 0,
+            entries: 0,
+            ground_exited: false,
+            in_invocation: false,
+            invocations: 0,
+            lenient: false,
+            diagnostics: Diagnostics::new(DEFAULT_ERROR_LIMIT),
+            malformed_lines: 0,
+            skipped_lines: 0,
+            dropped_calls: 0,
+            unwound_calls: 0,
+            line: 0,
+            line_start: 0,
+            line_end: 0,
+            previous_line_start: 0,
+            findings: None,
+            next_pc: None,
+            trace_start: false,
+            root_named: false,
+            thread: None,
         })
     }
 
-    /// Reads the trace and creates the profile data.
+    /// Sets the model of costs of instructions.
+    pub fn set_cost_model(&mut self, cost_model: cost::Model) {
+        self.cost_model = cost_model;
+        self.cost_model_set = true;
+    }
+
+    /// Takes the runtime version from the header of the first trace which has it.
+    /// Unless a cost model is set, costs follow the release of the runtime.
+    fn set_header(&mut self, header: crate::trace::Header) {
+        let Some(version) = header.runtime_version else {
+            return;
+        };
+        if self.runtime_version.is_some() {
+            return;
+        }
+        if global::verbose() {
+            tracing::info!("Detected runtime version {}", version);
+        }
+        if !self.cost_model_set {
+            if let Some(cost_model) = cost::Model::for_runtime(&version) {
+                self.cost_model = cost_model;
+            }
+        }
+        self.runtime_version = Some(version);
+    }
+
+    /// Switches to the format of lines of the trace starting with the standard header.
+    fn set_trace_format(&mut self, header: &str) {
+        let trace_format = crate::trace::detect_format(header);
+        tracing::debug!("Profile.set_trace_format {:?}", trace_format);
+        self.trace_format = trace_format;
+    }
+
+    /// Adds the identifier of the execution marked in the trace, unless added already.
+    fn add_label(&mut self, label: &str) {
+        if !self.labels.iter().any(|l| l == label) {
+            self.labels.push(label.into());
+        }
+    }
+
+    /// Returns identifiers of the executions marked in the trace, in order of the trace.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Enables the fast mode which records only calls of functions
+    /// and skips accounting of costs of instructions.
+    pub fn set_calls_only(&mut self, calls_only: bool) {
+        self.calls_only = calls_only;
+    }
+
+    /// Enables the lenient mode which skips malformed lines of the trace
+    /// and calls without their first instruction (e.g. in a truncated trace)
+    /// instead of failing, and reports a summary of the skipped input.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Sets the number of errors of parsing collected before failing, unlimited if zero;
+    /// parsing fails at the first error by default.
+    pub fn set_error_limit(&mut self, limit: usize) {
+        self.diagnostics = Diagnostics::new(limit);
+    }
+
+    /// Skips the malformed line of the text in the lenient mode. Otherwise collects
+    /// the error with its context, skipping the line until the limit of errors.
+    fn recover(&mut self, error: Error, line: usize, text: &str) -> Result<()> {
+        if !self.lenient {
+            let offset = if line == self.line {
+                self.line_start
+            } else {
+                self.previous_line_start
+            };
+            let diagnostic = Diagnostic::new(line, offset, text, &error);
+            if !self
+                .diagnostics
+                .add(diagnostic, self.trace_format.dialect())
+            {
+                return Err(self.diagnostics.take());
+            }
+            self.malformed_lines += 1;
+            self.next_pc = None;
+            return Ok(());
+        }
+        tracing::debug!("Skipped malformed line: {}", error);
+        self.malformed_lines += 1;
+        self.next_pc = None;
+        self.add_finding(line, "malformed", error.to_string());
+        Ok(())
+    }
+
+    /// Enables collecting structural issues of the trace, which implies the lenient mode.
+    pub(super) fn set_findings(&mut self, enabled: bool) {
+        self.findings = enabled.then(Vec::new);
+        self.lenient |= enabled;
+    }
+
+    /// Records the structural issue of the trace at the line, if enabled.
+    fn add_finding(&mut self, line: usize, kind: &'static str, message: String) {
+        if let Some(findings) = &mut self.findings {
+            findings.push(Finding::new(line, kind, message));
+        }
+    }
+
+    /// Records the instruction which does not follow the previous one, if structural
+    /// issues are collected: only jumps, calls and exits change the flow of the program,
+    /// other instructions are followed by the next ones (the two slots of `lddw` apart).
+    fn check_sequence(&mut self, ix: &Instruction) {
+        if self.findings.is_none() {
+            return;
+        }
+        if let Some(expected) = self.next_pc.take() {
+            if ix.pc() != expected {
+                self.add_finding(
+                    self.line,
+                    "pc-jump",
+                    format!(
+                        "instruction at pc {} instead of pc {} after a non-branch instruction",
+                        ix.pc(),
+                        expected
+                    ),
+                );
+            }
+        }
+        // Unknown opcodes of newer runtimes may be branches
+        self.next_pc = match ix.opcode_class() {
+            "jump" | "call" | "exit" | "unknown" => None,
+            "load imm64" => Some(ix.pc() + 2),
+            _ => Some(ix.pc() + 1),
+        };
+    }
+
+    /// Takes the structural issues of the trace found so far.
+    pub(super) fn take_findings(&mut self) -> Vec<Finding> {
+        self.findings
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Logs the summary of the input skipped in the lenient mode.
+    fn report_lenient(&self) {
+        let recovered = self.malformed_lines + self.dropped_calls + self.unwound_calls;
+        let summary = format!(
+            "{} malformed lines skipped, {} calls without their first instruction dropped, \
+             {} unfinished calls unwound, {} other lines skipped",
+            self.malformed_lines, self.dropped_calls, self.unwound_calls, self.skipped_lines
+        );
+        if recovered > 0 {
+            tracing::warn!("Recovered from a damaged trace: {}", summary);
+        } else {
+            tracing::info!("Trace is intact: {}", summary);
+        }
+    }
+
+    /// Splits calls of syscalls with a size argument (e.g. sol_memcpy_)
+    /// into separate nodes by buckets of sizes.
+    pub fn set_syscall_sizes(&mut self, syscall_sizes: bool) {
+        self.syscall_sizes = syscall_sizes;
+    }
+
+    /// Attributes costs of programs invoked by syscalls, e.g. cross-program invocations,
+    /// to the callers of the syscalls; by default they are costs of calls
+    /// of the syscalls, so invoked functions are callees of the syscalls.
+    pub fn set_flatten_nested(&mut self, enabled: bool) {
+        self.flatten_nested = enabled;
+    }
+
+    /// Enables counting memory reads and writes and calls of syscalls besides the cost.
+    pub fn set_counters(&mut self, enabled: bool) {
+        self.total_counters = enabled.then(Counters::default);
+    }
+
+    /// Enables counting the fixed cost of every invocation of a syscall
+    /// as a separate event, unless the file of syscalls gives another cost.
+    /// Enables the other counters as well.
+    pub fn set_syscall_cost(&mut self, cost: Option<Cost>) {
+        self.syscall_cost = cost;
+        if cost.is_some() && self.total_counters.is_none() {
+            self.set_counters(true);
+        }
+    }
+
+    /// Returns the number of the counted events written besides the cost.
+    fn counter_events(&self) -> usize {
+        match (&self.total_counters, self.syscall_cost) {
+            (None, _) => 0,
+            (Some(_), None) => profile::SYSCALL_COST,
+            (Some(_), Some(_)) => profile::COUNTER_EVENTS.len(),
+        }
+    }
+
+    /// Enables the stream of executed instructions.
+    pub fn set_events(&mut self, events: Events) {
+        self.events = Some(events);
+    }
+
+    /// Finishes writing the stream of executed instructions, if enabled.
+    pub fn finish_events(&mut self) -> Result<()> {
+        match self.events.take() {
+            Some(events) => events.commit(),
+            None => Ok(()),
+        }
+    }
+
+    /// Enables the index of calls of functions by byte offsets in the trace.
+    pub fn set_bookmarks(&mut self, bookmarks: Bookmarks) {
+        self.bookmarks = Some(bookmarks);
+    }
+
+    /// Sets the reports of progress of parsing the next trace.
+    pub(super) fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    /// Takes the reports of progress of parsing the trace.
+    pub(super) fn take_progress(&mut self) -> Option<Progress> {
+        self.progress.take()
+    }
+
+    /// Finishes writing the index of calls, if enabled.
+    fn finish_bookmarks(&mut self) -> Result<()> {
+        match self.bookmarks.take() {
+            Some(bookmarks) => bookmarks.commit(),
+            None => Ok(()),
+        }
+    }
+
+    /// Enables the heatmap of costs with windows of the number of instructions.
+    pub fn set_heatmap(&mut self, window: usize) {
+        self.heatmap = Some(Heatmap::new(window));
+    }
+
+    /// Enables collecting costs by call stacks for flame graphs.
+    pub fn set_stacks(&mut self, enabled: bool) {
+        self.stacks = enabled.then(Map::new);
+    }
+
+    /// Enables aggregating costs by call paths instead of functions in the callgrind profile.
+    pub fn set_granularity(&mut self, granularity: profile::Granularity) {
+        self.path_calls = (granularity == profile::Granularity::Path).then(Map::new);
+        if self.path_calls.is_some() && self.stacks.is_none() {
+            self.set_stacks(true);
+        }
+    }
+
+    /// Enables recording entries into and exits from functions in order of the trace.
+    pub fn set_timeline(&mut self, enabled: bool) {
+        self.timeline = enabled.then(Timeline::default);
+    }
+
+    /// Discards every trace before the first entry into the function of the name.
+    pub fn set_ignore_before(&mut self, function: &str) {
+        self.ignore_before = Some(function.into());
+    }
+
+    /// Profiles only the function of the name: every trace outside
+    /// of its calls is discarded, costs of all its calls are aggregated.
+    pub fn set_only_function(&mut self, function: &str) {
+        self.ignore_before = Some(function.into());
+        self.only_function = true;
+    }
+
+    /// Profiles only the window of every trace, discarding instructions before it
+    /// and the rest of the trace after it.
+    pub fn set_window(&mut self, window: Option<Window>) {
+        self.window = window;
+    }
+
+    /// Hides functions from the profile by the filter of their names:
+    /// calls of hidden functions are not recorded, their costs are attributed
+    /// to the callers, the innermost shown functions on the call stack.
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+    }
+
+    /// Checks if the function starting at the program counter ends
+    /// the discarded part of the trace.
+    fn ends_ignored(&mut self, first_pc: ProgramCounter) -> bool {
+        let name = self.resolver.resolve_by_first_pc(first_pc);
+        let entered = match (&name, &self.ignore_before) {
+            (Some(name), Some(function)) => resolver::is_named(name, function),
+            _ => false,
+        };
+        if entered {
+            self.ignoring = false;
+            self.entry_depth = self.ground.depth();
+            self.entries += 1;
+        }
+        !self.ignoring
+    }
+
+    /// Discards the trace again after the exit from the only profiled function.
+    fn check_only_function_exit(&mut self) {
+        if self.only_function && self.ground.depth() == self.entry_depth {
+            self.ignoring = true;
+        }
+    }
+
+    /// Positions costs at source lines of instructions taken from the dump file
+    /// instead of program counters.
+    pub fn set_source_lines(&mut self, enabled: bool) {
+        if enabled && !self.resolver.has_source_locations() {
+            tracing::warn!(
+                "The dump file has no source lines, costs are positioned at program counters"
+            );
+        }
+        self.source_lines = enabled;
+    }
+
+    /// Records the first and the last trace lines of every call,
+    /// written to the JSON profile.
+    pub fn set_call_lines(&mut self, enabled: bool) {
+        self.call_lines = enabled;
+    }
+
+    /// Compresses names of functions and files and positions of costs
+    /// in the callgrind file.
+    pub fn set_compress_output(&mut self, enabled: bool) {
+        self.compress_output = enabled;
+    }
+
+    /// Enables counting of executed instructions by functions and mnemonics.
+    pub(super) fn set_mnemonics(&mut self, enabled: bool) {
+        self.mnemonics = enabled.then(Map::new);
+    }
+
+    /// Sets the order of functions in the callgrind profile.
+    pub fn set_order(&mut self, order: profile::Order) {
+        self.order = order;
+    }
+
+    /// Enables counting of executions of instructions by program counters.
+    pub(super) fn set_executions(&mut self, enabled: bool) {
+        self.executions = enabled.then(Map::new);
+    }
+
+    /// Enables counting of memory accesses by regions for the statistics report.
+    pub fn set_memory_regions(&mut self, enabled: bool) {
+        self.memory_regions = enabled.then(Map::new);
+    }
+
+    /// Renames the root frame of the profile (GROUND_ZERO by default),
+    /// e.g. to the transaction signature or the name of the test case.
+    /// The named root frame is written to the callgrind profile as well.
+    pub fn set_root_name(&mut self, name: &str) {
+        self.root_named = true;
+        self.functions
+            .get_mut(&FrameId::Root)
+            .expect("Ground zero not found in registry of functions")
+            .set_name(name);
+    }
+
+    /// Writes the root frame to the callgrind profile as the function `<root>`
+    /// calling the top-level functions, unless it is named already, so viewers
+    /// render inclusive costs of the top level relative to the whole trace.
+    pub fn set_explicit_root(&mut self, enabled: bool) {
+        if enabled && !self.root_named {
+            self.set_root_name(EXPLICIT_ROOT_NAME);
+        }
+    }
+
+    /// Embeds the description of how the profile was generated into the callgrind file.
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = Some(metadata);
+    }
+
+    /// Marks the profile as a separate thread in the callgrind file,
+    /// so profiles of several traces can be told apart in one file.
+    pub fn set_thread(&mut self, thread: usize) {
+        self.thread = Some(thread);
+    }
+
+    /// Reads the traces and creates the profile data.
+    /// Functions of several traces of the same program are merged by resolved names,
+    /// or by first program counters if not resolved.
     pub fn create(
-        trace_path: &Path,
+        trace_paths: &[&Path],
         dump_path: Option<&Path>,
-        asm_path: Option<&Path>,
+        options: &Options,
     ) -> Result<Self> {
-        tracing::debug!("Profile.create {:?}", trace_path);
+        tracing::debug!("Profile.create {:?}", trace_paths);
 
-        let resv = resolver::read(dump_path)?;
-        let reader = filebuf::open(trace_path)?;
-        let mut prof = Profile::new(resv, asm_path)?;
-        parse(reader, &mut prof)?;
+        let resv = super::read_resolver(dump_path, options)?;
+        Self::create_with_resolver(trace_paths, resv, dump_path, options)
+    }
 
+    /// Reads the traces and creates the profile data with the resolver
+    /// of the dump file, which may be shared with other profiles.
+    pub(super) fn create_with_resolver(
+        trace_paths: &[&Path],
+        resv: Resolver,
+        dump_path: Option<&Path>,
+        options: &Options,
+    ) -> Result<Self> {
+        let base = resv.clone();
+        let mut prof = Profile::with_options(resv, options)?;
+        if options.metadata {
+            prof.set_metadata(Metadata::new(trace_paths, dump_path)?);
+        }
+        for (i, trace_path) in trace_paths.iter().enumerate() {
+            if global::interrupted() {
+                break;
+            }
+            // Following traces are parsed separately, since addresses of their functions
+            // may belong to other functions of the merged ones
+            let mut scratch = match i {
+                0 => None,
+                _ => Some(Profile::with_options(base.clone(), options)?),
+            };
+            let trace_prof = scratch.as_mut().unwrap_or(&mut prof);
+            let reader = filebuf::open(trace_path)?;
+            trace_prof.set_progress(Progress::new(trace_path));
+            if options.jobs > 1 {
+                parallel::parse(reader, trace_prof, options)?;
+            } else {
+                parse(reader, trace_prof)?;
+            }
+            if let Some(scratch) = scratch {
+                prof.merge_trace(scratch.into_part());
+            }
+        }
+        prof.finish()?;
         Ok(prof)
     }
 
+    /// Creates the profile with the resolver and the options besides the input files.
+    /// Metadata of the input files and aliases of the resolver are not applied here.
+    pub(crate) fn with_options(resv: Resolver, options: &Options) -> Result<Self> {
+        let mut prof = Profile::parser(resv, options)?;
+        prof.set_source_lines(options.source_lines);
+        prof.set_call_lines(options.call_lines);
+        prof.set_compress_output(options.compress_output);
+        prof.set_order(
+            profile::Order::from_name(&options.sort)
+                .ok_or_else(|| Error::UnknownSort(options.sort.clone()))?,
+        );
+        if prof.path_calls.is_some() {
+            // Call paths carry costs only, written as the callgrind profile
+            let unsupported = [
+                ("--counters", options.counters),
+                ("--syscall-cost", options.syscall_cost.is_some()),
+                ("--source-lines", options.source_lines),
+                ("--threshold", options.threshold.is_some()),
+                ("--max-output-size", options.max_output_size.is_some()),
+                (
+                    "--format",
+                    options.format != DEFAULT_FORMAT
+                        && !FLAMEGRAPH_FORMATS.contains(&options.format.as_str()),
+                ),
+            ];
+            if let Some((option, _)) = unsupported.iter().find(|(_, enabled)| *enabled) {
+                return Err(Error::GranularityOption(option.to_string()));
+            }
+        }
+        if let Some(events_path) = &options.events {
+            prof.set_events(Events::create(events_path)?);
+        }
+        if let Some(bookmarks_path) = &options.bookmarks {
+            prof.set_bookmarks(Bookmarks::create(bookmarks_path)?);
+        }
+        if let Some(function) = &options.ignore_before {
+            prof.set_ignore_before(function);
+        }
+        if let Some(function) = &options.only_function {
+            prof.set_only_function(function);
+        }
+        prof.set_window(Window::new(
+            options.skip_instructions,
+            options.max_instructions,
+            options.range.as_deref(),
+        )?);
+        if let Some(name) = &options.root_name {
+            prof.set_root_name(name);
+        }
+        prof.set_explicit_root(options.explicit_root);
+        Ok(prof)
+    }
+
+    /// Aggregates calls cheaper than the cost, once finished, into one record
+    /// per caller, position and callee instead of storing every call.
+    pub fn set_drop_calls_below(&mut self, cost: Option<Cost>) {
+        self.drop_calls_below = cost;
+    }
+
+    /// Stores at most the number of calls of every callee by every caller separately,
+    /// aggregating further calls as if cheap, so percentiles of costs become approximate.
+    pub fn set_max_call_samples(&mut self, samples: Option<usize>) {
+        self.max_call_samples = samples;
+    }
+
+    /// Creates the profile with the resolver and the options affecting
+    /// accounting of the trace, but not outputs, e.g. for a chunk of the trace.
+    fn parser(resv: Resolver, options: &Options) -> Result<Self> {
+        let mut prof = Profile::new(resv, options.asm.as_deref())?;
+        prof.set_calls_only(options.calls_only);
+        prof.set_memory_regions(options.stats.is_some());
+        prof.set_syscall_sizes(options.syscall_sizes);
+        prof.set_flatten_nested(options.flatten_nested);
+        prof.set_counters(options.counters);
+        prof.set_syscall_cost(options.syscall_cost);
+        prof.set_drop_calls_below(options.drop_calls_below);
+        prof.set_max_call_samples(options.max_call_samples);
+        prof.set_lenient(options.lenient);
+        prof.set_error_limit(options.error_limit);
+        prof.set_filter(Filter::new(
+            options.include.as_deref(),
+            options.exclude.as_deref(),
+        )?);
+        // The intermediate profile keeps the stacks to render flame graphs later
+        prof.set_stacks(
+            FLAMEGRAPH_FORMATS.contains(&options.format.as_str())
+                || options.save_intermediate.is_some(),
+        );
+        prof.set_granularity(
+            profile::Granularity::from_name(&options.granularity)
+                .ok_or_else(|| Error::UnknownGranularity(options.granularity.clone()))?,
+        );
+        prof.set_timeline(options.format == TIMELINE_FORMAT);
+        if options.heatmap.is_some() {
+            prof.set_heatmap(options.heatmap_window);
+        }
+        if let Some(version) = &options.cluster_version {
+            prof.set_cost_model(cost::Model::cluster(version)?);
+        }
+        if let Some(weights_path) = &options.cost_model {
+            prof.set_cost_model(cost::Model::Weights(Box::new(cost::Weights::read(
+                weights_path,
+            )?)));
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script_path) = &options.cost_script {
+            prof.set_cost_model(cost::Model::Script(Box::new(cost::Script::load(
+                script_path,
+            )?)));
+        }
+        Ok(prof)
+    }
+
+    /// Finishes the profile after all traces are parsed.
+    pub(super) fn finish(&mut self) -> Result<()> {
+        self.finish_events()?;
+        self.finish_bookmarks()?;
+        if self.lenient {
+            self.report_lenient();
+        }
+        if self.invocations > 1 {
+            tracing::info!("Aggregated {} invocations of programs", self.invocations);
+        }
+        if global::verbose() {
+            self.report_unknown_opcodes();
+        }
+        if self.resolver.has_aliases() {
+            self.merge_aliased();
+        }
+        if !self.resolver.is_default() {
+            self.report_resolution();
+            self.report_stack();
+        }
+        Ok(())
+    }
+
+    /// Logs unknown opcodes executed, e.g. instructions of a newer runtime,
+    /// which are costed as any other instruction. Listed by --stats as well.
+    fn report_unknown_opcodes(&self) {
+        if self.unknown_opcodes.is_empty() {
+            return;
+        }
+        let mut opcodes = self
+            .unknown_opcodes
+            .iter()
+            .map(|(mnemonic, count)| format!("{} ({})", mnemonic, count))
+            .collect::<Vec<_>>();
+        opcodes.sort();
+        tracing::info!(
+            "Executed unknown opcodes costed as generic instructions: {}",
+            opcodes.join(", ")
+        );
+    }
+
+    /// Warns about stack frames of functions and the stack of the hottest path
+    /// close to the limits of SBF programs.
+    fn report_stack(&self) {
+        for (name, size) in stats::stack_frames(&self.functions, &self.resolver) {
+            if stats::is_stack_at_risk(size, STACK_FRAME_LIMIT) {
+                tracing::warn!(
+                    "Stack frame of {} takes {} of {} bytes",
+                    name,
+                    size,
+                    STACK_FRAME_LIMIT
+                );
+            }
+        }
+        let path = stats::hottest_path(&self.functions, &self.resolver);
+        let total = path.iter().filter_map(|(_, size)| *size).sum::<usize>();
+        if stats::is_stack_at_risk(total, STACK_LIMIT) {
+            let names = path.into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+            tracing::warn!(
+                "Stack of the hottest path takes {} of {} bytes: {}",
+                total,
+                STACK_LIMIT,
+                names.join(" -> ")
+            );
+        }
+    }
+
+    /// Logs how many functions of the trace are found in the dump file, the share
+    /// of the total cost of the unresolved ones and the costliest of them with their
+    /// first program counters. Many unresolved functions mean the dump does not match
+    /// the traced program.
+    fn report_resolution(&self) {
+        let (resolved, unresolved) = stats::resolution(&self.functions, &self.resolver);
+        if global::verbose() {
+            tracing::info!(
+                "Resolved {} of {} functions from the dump file",
+                resolved,
+                resolved + unresolved.len()
+            );
+        }
+        if unresolved.is_empty() {
+            return;
+        }
+        let share = |cost: Cost| 100.0 * cost as f64 / self.total_cost.max(1) as f64;
+        let unresolved_cost = unresolved.iter().map(|u| u.cost).sum::<Cost>();
+        tracing::warn!(
+            "{} functions with total cost {} ({:.2}% of the total cost) are not found \
+             in the dump file; check the dump matches the traced program",
+            unresolved.len(),
+            global::number(unresolved_cost),
+            share(unresolved_cost)
+        );
+        for u in unresolved.iter().take(UNRESOLVED_REPORT_LIMIT) {
+            tracing::warn!(
+                "Unresolved function {} at pc {}: cost {} ({:.2}%)",
+                u.name,
+                u.first_pc,
+                global::number(u.cost),
+                share(u.cost)
+            );
+        }
+        if unresolved.len() > UNRESOLVED_REPORT_LIMIT {
+            tracing::warn!(
+                "{} more unresolved functions are listed by --stats",
+                unresolved.len() - UNRESOLVED_REPORT_LIMIT
+            );
+        }
+    }
+
+    /// Returns the total cost of the profile.
+    pub fn total_cost(&self) -> Cost {
+        self.total_cost
+    }
+
+    /// Returns identifiers of functions in the order of the callgrind profile.
+    fn callgrind_order(&self) -> Vec<FrameId> {
+        let inclusive_costs = match self.order {
+            profile::Order::InclusiveCost => {
+                budget::inclusive_costs(&self.functions, self.total_cost)
+            }
+            _ => Map::new(),
+        };
+        profile::sort(&self.functions, self.order, &inclusive_costs)
+    }
+
+    /// Returns the name of the assembly file the callgrind profile refers to,
+    /// with characters of a path which is not UTF-8 replaced.
+    pub fn asm_filename(&self) -> Cow<'_, str> {
+        match &self.asm {
+            Some(asm) => asm.output_path().to_string_lossy(),
+            None => DEFAULT_ASM.into(),
+        }
+    }
+
+    /// Returns names of the callgrind events of the profile.
+    pub fn callgrind_events(&self) -> String {
+        let mut events = vec!["Instructions"];
+        events.extend(&profile::COUNTER_EVENTS[..self.counter_events()]);
+        events.join(" ")
+    }
+
     /// Writes the profile data in the callgrind file format.
     /// See details of the format in the Valgrind documentation.
-    pub fn write_callgrind(&self, mut output: impl Write, asm_fl: &str) -> Result<()> {
-        if self.asm.is_some() {
-            let asm = self.asm.as_ref().unwrap();
+    pub fn write_callgrind(&self, output: impl Write, asm_fl: &str) -> Result<()> {
+        self.write_callgrind_part(output, asm_fl, None)
+    }
+
+    /// Writes the profile data as a numbered part of a callgrind file,
+    /// which follows the previous parts. Writes the header of the file if no part given.
+    pub fn write_callgrind_part(
+        &self,
+        output: impl Write,
+        asm_fl: &str,
+        part: Option<usize>,
+    ) -> Result<()> {
+        if let Some(asm) = &self.asm {
             asm.write(&self.resolver)?;
         }
+        self.write_callgrind_records(output, asm_fl, part)
+    }
 
-        writeln!(output, "# callgrind format")?;
-        writeln!(output, "version: 1")?;
-        writeln!(output, "creator: bpf-profile")?;
+    /// Writes the header and the records of functions of the callgrind file.
+    fn write_callgrind_records(
+        &self,
+        mut output: impl Write,
+        asm_fl: &str,
+        part: Option<usize>,
+    ) -> Result<()> {
+        match part {
+            None => {
+                writeln!(output, "# callgrind format")?;
+                writeln!(output, "version: 1")?;
+                writeln!(output, "creator: bpf-profile")?;
+            }
+            Some(part) => writeln!(output, "part: {}", part)?,
+        }
+        if let Some(thread) = self.thread {
+            writeln!(output, "thread: {}", thread)?;
+        }
+        if let Some(metadata) = &self.metadata {
+            metadata.write_callgrind(&mut output, &self.cost_model.to_string())?;
+        }
+        if let Some(version) = &self.runtime_version {
+            writeln!(output, "desc: Runtime: {}", version)?;
+        }
+        for label in &self.labels {
+            writeln!(output, "desc: Execution: {}", label)?;
+        }
         writeln!(output, "positions: line")?;
-        writeln!(output, "events: Instructions")?;
-        writeln!(output, "totals: {}", self.total_cost)?;
+        writeln!(output, "events: {}", self.callgrind_events())?;
+        // The summary and the totals are the sum of the cost lines below, as the format
+        // requires, without the immediate costs of the root unless it is named
+        let mut totals = (self.total_cost, self.total_counters.unwrap_or_default());
+        if !self.root_named {
+            let root = &self.functions[&FrameId::Root];
+            totals.0 -= root.cost();
+            for (total, c) in totals.1.iter_mut().zip(root.total_counters()) {
+                *total -= c;
+            }
+        }
+        let costs = |(cost, counters): (Cost, Counters)| {
+            let mut costs = cost.to_string();
+            for c in &counters[..self.counter_events()] {
+                costs += &format!(" {}", c);
+            }
+            costs
+        };
+        writeln!(output, "summary: {}", costs(totals))?;
+        writeln!(output, "totals: {}", costs(totals))?;
         writeln!(output, "fl={}", asm_fl)?;
-        profile::write_callgrind_functions(output, &self.functions, self.asm.is_some())?;
+        let order = self.callgrind_order();
+        if let (Some(stacks), Some(path_calls)) = (&self.stacks, &self.path_calls) {
+            profile::write_callgrind_paths(
+                output,
+                (stacks, path_calls),
+                &self.functions,
+                self.root_named,
+                self.compress_output,
+            )?;
+        } else if self.source_lines {
+            profile::write_callgrind_source_lines(
+                output,
+                (&self.functions, &order),
+                self.root_named,
+                self.counter_events(),
+                asm_fl,
+                |pc| self.resolver.source_location(pc).unwrap_or((asm_fl, pc)),
+                self.compress_output,
+            )?;
+        } else {
+            profile::write_callgrind_functions(
+                output,
+                (&self.functions, &order),
+                self.asm.is_some(),
+                self.root_named,
+                self.counter_events(),
+                self.compress_output,
+            )?;
+        }
 
         Ok(())
     }
 
-    /// Adds instruction to the generated assembly listing.
+    /// Divides costs and numbers of calls by the number of runs, e.g. of merged
+    /// traces of the same transaction, so the profile is of an average run.
+    pub fn average(&mut self, runs: usize) {
+        if runs <= 1 {
+            return;
+        }
+        self.total_cost = profile::divide(self.total_cost, runs);
+        if let Some(total_counters) = &mut self.total_counters {
+            for c in total_counters.iter_mut() {
+                *c = profile::divide(*c, runs);
+            }
+        }
+        profile::average(&mut self.functions, runs);
+        if let Some(stacks) = &mut self.stacks {
+            for cost in stacks.values_mut() {
+                *cost = profile::divide(*cost, runs);
+            }
+        }
+    }
+
+    /// Moves the functions of inclusive costs below the percentage of the total cost
+    /// into the function `<below threshold>`. Total costs are preserved.
+    /// Returns the number of pruned functions.
+    pub fn prune_below(&mut self, threshold: f64) -> usize {
+        let min_cost = self.total_cost as f64 * threshold / 100.0;
+        let inclusive_costs = budget::inclusive_costs(&self.functions, self.total_cost);
+        let ids = self
+            .functions
+            .keys()
+            .filter(|id| {
+                !matches!(
+                    id,
+                    FrameId::Root | FrameId::BelowThreshold | FrameId::Truncated
+                )
+            })
+            .filter(|id| (inclusive_costs[id] as f64) < min_cost)
+            .copied()
+            .collect::<BTreeSet<_>>();
+        if !ids.is_empty() {
+            profile::prune(&mut self.functions, &ids);
+            tracing::info!(
+                "Pruned {} function(s) below {}% of the total cost",
+                ids.len(),
+                threshold
+            );
+        }
+        ids.len()
+    }
+
+    /// Moves the least significant functions, the cheapest by inclusive cost first,
+    /// into the function `<truncated>` until the callgrind file fits the size in bytes.
+    /// Total costs are preserved. Returns the number of truncated functions.
+    pub fn truncate_callgrind(&mut self, max_size: usize, asm_fl: &str) -> Result<usize> {
+        let callgrind_size = |prof: &Profile| -> Result<usize> {
+            let mut counter = ByteCounter(0);
+            prof.write_callgrind_records(&mut counter, asm_fl, None)?;
+            Ok(counter.0)
+        };
+        let mut size = callgrind_size(self)?;
+        if size <= max_size {
+            return Ok(0);
+        }
+        let inclusive_costs = budget::inclusive_costs(&self.functions, self.total_cost);
+        let mut candidates = self
+            .functions
+            .keys()
+            .filter(|id| !matches!(id, FrameId::Root | FrameId::Truncated))
+            .map(|id| (inclusive_costs[id], *id))
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        // Records of functions are of similar sizes, so the share of the remaining
+        // functions dropped at once follows the share of the excess size
+        let mut truncated = 0;
+        while size > max_size && truncated < candidates.len() {
+            let remaining = candidates.len() - truncated;
+            let count = (remaining * (size - max_size) / size).max(1);
+            let ids = candidates[truncated..truncated + count]
+                .iter()
+                .map(|(_, id)| *id)
+                .collect();
+            profile::truncate(&mut self.functions, &ids);
+            truncated += count;
+            size = callgrind_size(self)?;
+        }
+        if size > max_size {
+            tracing::warn!(
+                "Profile of {} bytes exceeds the maximum size {} with all functions truncated",
+                size,
+                max_size
+            );
+        } else {
+            tracing::info!(
+                "Truncated {} function(s) to fit the profile into {} bytes",
+                truncated,
+                max_size
+            );
+        }
+        Ok(truncated)
+    }
+
+    /// Writes summary of the profile as Prometheus metrics.
+    pub fn write_metrics(&self, output: impl Write) -> Result<()> {
+        metrics::write(
+            output,
+            self.total_cost,
+            &self.functions,
+            self.resolver.unresolved_count(),
+        )
+    }
+
+    /// Writes the total cost and the resolution of functions as JSON.
+    pub fn write_badge_json(&self, output: impl Write) -> Result<()> {
+        badge::write_json(output, &self.functions, self.total_cost, &self.resolver)
+    }
+
+    /// Writes the total cost and the resolution of functions as an SVG badge.
+    pub fn write_badge_svg(&self, output: impl Write) -> Result<()> {
+        badge::write_svg(output, &self.functions, self.total_cost, &self.resolver)
+    }
+
+    /// Writes differences of costs of this profile against the old one
+    /// in the callgrind file format; costs may be negative.
+    pub fn write_callgrind_diff(
+        &self,
+        output: impl Write,
+        old: &Profile,
+        asm_fl: &str,
+    ) -> Result<()> {
+        diff::write(
+            output,
+            (&old.functions, old.total_cost),
+            (&self.functions, self.total_cost),
+            asm_fl,
+        )
+    }
+
+    /// Writes the table of deltas of self and inclusive costs of functions
+    /// of this profile against the old one, optionally normalized per call.
+    pub fn write_diff_table(
+        &self,
+        output: impl Write,
+        old: &Profile,
+        per_call: bool,
+    ) -> Result<()> {
+        diff::write_table(
+            output,
+            (&old.functions, old.total_cost),
+            (&self.functions, self.total_cost),
+            per_call,
+        )
+    }
+
+    /// Writes the result of the query over functions of the profile.
+    pub fn query(&self, output: impl Write, query: &query::Query) -> Result<()> {
+        query::run(output, query, &self.functions, self.total_cost)
+    }
+
+    /// Returns the interactive terminal viewer of the profile.
+    #[cfg(feature = "tui")]
+    pub fn viewer(&self) -> view::Viewer {
+        view::Viewer::new(&self.functions, self.total_cost)
+    }
+
+    /// Writes the table of the top functions by the kind of cost, as text or as CSV.
+    pub fn write_summary(
+        &self,
+        output: impl Write,
+        top: usize,
+        by: query::By,
+        csv: bool,
+    ) -> Result<()> {
+        summary::write(output, &self.functions, self.total_cost, top, by, csv)
+    }
+
+    /// Writes the histogram of executed instructions by mnemonics, if enabled,
+    /// optionally broken down by functions.
+    pub fn write_instructions(&self, output: impl Write, by_function: bool) -> Result<()> {
+        match &self.mnemonics {
+            Some(mnemonics) => instructions::write(output, mnemonics, &self.functions, by_function),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the disassembly of executed functions annotated with numbers of executions
+    /// and costs of instructions, if enabled.
+    pub fn write_annotated(&self, output: impl Write, function: Option<&str>) -> Result<()> {
+        match &self.executions {
+            Some(executions) => annotate::write(
+                output,
+                executions,
+                (&self.functions, self.total_cost),
+                &self.resolver,
+                function,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks costs of functions against the budgets and writes each violation.
+    /// Returns the number of violations.
+    pub fn check_budgets(&self, output: impl Write, budgets: &[budget::Budget]) -> Result<usize> {
+        let violations = self.budget_violations(budgets);
+        budget::write(output, &violations)?;
+        Ok(violations.len())
+    }
+
+    /// Returns costs of functions exceeding the budgets.
+    pub fn budget_violations<'a>(
+        &self,
+        budgets: &'a [budget::Budget],
+    ) -> Vec<budget::Violation<'a>> {
+        budget::violations(&self.functions, self.total_cost, budgets)
+    }
+
+    /// Compares costs with the baseline and writes the report, as JSON if enabled.
+    /// Returns the number of costs which grew by more than the percentage.
+    pub fn check_regressions(
+        &self,
+        output: impl Write,
+        baseline: &regression::Baseline,
+        max_percent: f64,
+        json: bool,
+    ) -> Result<usize> {
+        let regressions = self.regressions(baseline, max_percent);
+        if json {
+            regression::write_json(output, &regressions)?;
+        } else {
+            regression::write_text(output, baseline, self.total_cost, &regressions)?;
+        }
+        Ok(regressions.len())
+    }
+
+    /// Returns costs which grew by more than the percentage over the baseline.
+    pub fn regressions(
+        &self,
+        baseline: &regression::Baseline,
+        max_percent: f64,
+    ) -> Vec<regression::Regression> {
+        regression::regressions(baseline, &self.functions, self.total_cost, max_percent)
+    }
+
+    /// Checks costs of functions against the budgets and writes the violations
+    /// as a SARIF log. Returns the number of violations.
+    pub fn check_budgets_sarif(
+        &self,
+        output: impl Write,
+        budgets: &[budget::Budget],
+    ) -> Result<usize> {
+        let violations = budget::violations(&self.functions, self.total_cost, budgets);
+        budget::write_sarif(output, &violations, &self.resolver)?;
+        Ok(violations.len())
+    }
+
+    /// Checks costs of functions against the budgets and writes the checks
+    /// as a JUnit XML report. Returns the number of violations.
+    pub fn check_budgets_junit(
+        &self,
+        output: impl Write,
+        budgets: &[budget::Budget],
+    ) -> Result<usize> {
+        let violations = budget::violations(&self.functions, self.total_cost, budgets);
+        budget::write_junit(output, &self.functions, budgets, &violations)?;
+        Ok(violations.len())
+    }
+
+    /// Writes the statistics report of the profile.
+    pub fn write_stats(&self, output: impl Write) -> Result<()> {
+        stats::write(
+            output,
+            &self.functions,
+            &self.opcode_classes,
+            &self.unknown_opcodes,
+            self.memory_regions.as_ref(),
+            (&self.depths, &self.recursions),
+            &self.resolver,
+        )
+    }
+
+    /// Writes the profile as JSON: functions with their costs and origins of names,
+    /// and the call graph with statistics of calls between functions.
+    pub fn write_callgraph_json(&self, output: impl Write) -> Result<()> {
+        callgraph::write_json(
+            output,
+            &self.functions,
+            self.total_cost,
+            &self.resolver,
+            &self.labels,
+            self.json_metadata(),
+        )
+    }
+
+    /// Returns the description of the profile for JSON outputs, if enabled.
+    fn json_metadata(&self) -> Option<serde_json::Value> {
+        self.metadata
+            .as_ref()
+            .map(|m| m.to_json(&self.cost_model.to_string()))
+    }
+
+    /// Writes the report of inclusive and self costs of functions with their callers
+    /// and callees as text, like `callgrind_annotate`.
+    pub fn write_text(&self, output: impl Write) -> Result<()> {
+        let events = self.callgrind_events();
+        let event = events.split_whitespace().next().unwrap_or_default();
+        text::write(output, &self.functions, self.total_cost, event)
+    }
+
+    /// Writes the call graph with statistics of calls between functions as a DOT graph.
+    pub fn write_callgraph_dot(&self, output: impl Write) -> Result<()> {
+        callgraph::write_dot(output, &self.functions, self.total_cost)
+    }
+
+    /// Writes costs by call stacks in the collapsed format of flamegraph tools, if enabled.
+    pub fn write_folded(&self, output: impl Write) -> Result<()> {
+        match &self.stacks {
+            Some(stacks) => flamegraph::write_folded(output, stacks, &self.functions),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the call tree of costs by call stacks, if enabled, from the root or from
+    /// calls of functions with names containing the given one, down to the depth if given.
+    pub fn write_tree(
+        &self,
+        output: impl Write,
+        root: Option<&str>,
+        depth: Option<usize>,
+    ) -> Result<()> {
+        match &self.stacks {
+            Some(stacks) => tree::write(
+                output,
+                stacks,
+                &self.functions,
+                self.total_cost,
+                root,
+                depth,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the flame graph of costs by call stacks as an SVG image, if enabled.
+    #[cfg(feature = "flamegraph")]
+    pub fn write_flamegraph(&self, output: impl Write) -> Result<()> {
+        match &self.stacks {
+            Some(stacks) => flamegraph::write_svg(output, stacks, &self.functions),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes costs by call stacks as a speedscope profile, if enabled.
+    pub fn write_speedscope(&self, output: impl Write) -> Result<()> {
+        match &self.stacks {
+            Some(stacks) => {
+                flamegraph::write_speedscope(output, stacks, &self.functions, self.json_metadata())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the timeline of calls in the trace event format of Chrome, if enabled.
+    pub fn write_chrome_trace(&self, output: impl Write) -> Result<()> {
+        match &self.timeline {
+            Some(timeline) => {
+                timeline::write(output, timeline, &self.functions, self.json_metadata())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the heatmap of costs in the CSV format, if enabled.
+    pub fn write_heatmap(&self, output: impl Write) -> Result<()> {
+        match &self.heatmap {
+            Some(heatmap) => heatmap.write(output),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the coverage report in the LCOV format.
+    pub fn write_lcov(&self, output: impl Write, source_filename: &str) -> Result<()> {
+        lcov::write(output, source_filename, &self.functions, &self.resolver)
+    }
+
+    /// Adds instruction to the generated assembly listing
+    /// and remembers the class of its opcode.
+    /// Executions of unknown opcodes are counted by mnemonics.
     fn keep_asm(&mut self, ix: &Instruction) {
         let _ = self.asm.as_mut().map(|a| a.add_instruction(ix));
+        let class = *self
+            .opcode_classes
+            .entry(ix.pc())
+            .or_insert_with(|| ix.opcode_class());
+        if class == "unknown" {
+            *self
+                .unknown_opcodes
+                .entry(ix.mnemonic().into())
+                .or_insert(0) += 1;
+        }
+        if let Some(mnemonics) = &mut self.mnemonics {
+            let id = self.stack.last().copied().unwrap_or(FrameId::Root);
+            instructions::add(mnemonics, id, ix);
+        }
+        if let Some(executions) = &mut self.executions {
+            annotate::add(executions, ix);
+        }
+    }
+
+    /// Counts the memory access of the load or store instruction by the accessed region.
+    fn keep_memory_access(&mut self, ix: &Instruction) {
+        let Some(regions) = &mut self.memory_regions else {
+            return;
+        };
+        if let Some(region) = crate::bpf::memory_access_region(ix) {
+            *regions.entry((ix.pc(), region)).or_insert(0) += 1;
+        }
+    }
+
+    /// Increments the total cost and the cost of current call
+    /// by the cost of the instruction.
+    fn increment_cost(&mut self, ix: &Instruction) -> Result<()> {
+        if self.calls_only {
+            return Ok(());
+        }
+        tracing::trace!("Profile.increment_cost");
+        let cost = self.cost_model.cost(ix)?;
+        if let Some(total_counters) = &mut self.total_counters {
+            if ix.is_memory_access() {
+                let mut counters = Counters::default();
+                let event = if ix.is_memory_load() {
+                    profile::READS
+                } else {
+                    profile::WRITES
+                };
+                counters[event] = 1;
+                profile::add_counters(total_counters, &counters);
+                self.ground
+                    .increment_counters(ix.pc(), &counters, &mut self.functions);
+            }
+        }
+        if let Some(events) = &mut self.events {
+            let id = self.stack.last().copied().unwrap_or(FrameId::Root);
+            events.add(
+                ix.pc(),
+                ix.opcode_class(),
+                self.ground.depth(),
+                &self.functions[&id].name(),
+                cost,
+            )?;
+        }
+        self.add_cost(ix.pc(), cost);
+        Ok(())
+    }
+
+    /// Adds the cost of the instruction at the program counter to the current function.
+    fn add_cost(&mut self, pc: ProgramCounter, cost: Cost) {
+        self.total_cost += cost;
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.add(self.ground.depth(), cost);
+        }
+        // Costs of the call stack are added to the stacks when it changes
+        if self.stacks.is_some() {
+            *self.stack_cost.get_or_insert(0) += cost;
+        }
+        self.ground.increment_cost(pc, cost, &mut self.functions);
+    }
+
+    /// Adds the costs executed since the last change of the call stack to the stacks.
+    fn flush_stack_cost(&mut self) {
+        if let (Some(stacks), Some(cost)) = (&mut self.stacks, self.stack_cost.take()) {
+            *stacks.entry(self.stack.clone()).or_insert(0) += cost;
+        }
+    }
+
+    /// Adds the cost of the instruction at the program counter reported
+    /// by an embedder rather than read from the trace.
+    pub(super) fn record_cost(&mut self, pc: ProgramCounter, cost: Cost) {
+        self.in_invocation = true;
+        self.check_trace_start();
+        if !self.calls_only {
+            self.add_cost(pc, cost);
+        }
+    }
+
+    /// Adds the call of the function at the address reported by an embedder,
+    /// from the caller at the program counter, to the callee starting
+    /// at the first program counter. Calls of syscalls finish at once.
+    pub(super) fn record_call(
+        &mut self,
+        address: Address,
+        caller_pc: ProgramCounter,
+        first_pc: ProgramCounter,
+    ) {
+        self.in_invocation = true;
+        let call = Call::new(FrameId::Addr(address), caller_pc);
+        if call.id().is_syscall() {
+            self.add_syscall(call, false);
+        } else {
+            self.push_call(call, first_pc);
+        }
+    }
+
+    /// Returns from the current call reported by an embedder.
+    pub(super) fn record_return(&mut self) {
+        self.in_invocation = true;
+        self.exit();
+    }
+
+    /// Finishes the invocation reported by an embedder, like the end of the trace.
+    pub(super) fn finish_records(&mut self) {
+        self.end_invocation();
+        report_parsed(self);
+    }
+
+    /// Records the program invoked at the depth of invocations, whose functions
+    /// are resolved in the region of addresses of the program, if known.
+    /// The outermost program executes the root frame.
+    fn invoke_program(&mut self, program_id: &str, depth: usize) {
+        let region = self.resolver.program_region(program_id).unwrap_or(0);
+        let outer = depth.saturating_sub(1);
+        self.programs.resize(outer, (0, String::new()));
+        self.programs.push((region, program_id.into()));
+        if outer == 0 {
+            let root = self.functions.get_mut(&FrameId::Root);
+            if let Some(root) = root.filter(|root| root.program().is_none()) {
+                root.set_program(program_id);
+            }
+        }
     }
 
-    /// Increments the total cost and the cost of current call.
-    fn increment_cost(&mut self, pc: ProgramCounter) {
-        tracing::debug!("Profile.increment_cost");
-        self.total_cost += 1;
-        self.ground.increment_cost(pc, &mut self.functions);
+    /// Returns the identifier of the program of the current nested execution, if reported.
+    fn current_program(&self) -> Option<&str> {
+        self.programs
+            .get(self.nested.len())
+            .map(|(_, program_id)| program_id.as_str())
+            .filter(|program_id| !program_id.is_empty())
+    }
+
+    /// Moves the call into the region of addresses of the program
+    /// of the current nested execution.
+    fn in_program_region(&self, call: Call) -> Call {
+        let region = self
+            .programs
+            .get(self.nested.len())
+            .map_or(0, |(region, _)| *region);
+        match call.id() {
+            FrameId::Addr(address) if region != 0 => {
+                Call::new(FrameId::Addr(address | region), call.caller_pc())
+            }
+            _ => call,
+        }
     }
 
     /// Adds next call to the call stack.
     fn push_call(&mut self, call: Call, first_pc: ProgramCounter) {
-        let address = call.address();
-        tracing::debug!("Profile.push_call 0x{:x}", address);
+        let mut call = self.in_program_region(call);
+        let id = call.id();
+        tracing::debug!("Profile.push_call {}", id);
+        let hidden = self.register_function(id, |resolver| {
+            let FrameId::Addr(address) = id else {
+                panic!("Call of the synthetic frame {}", id);
+            };
+            Function::new(address, first_pc, resolver)
+        });
+        if self.filter.is_some() {
+            self.hidden_frames.push(hidden);
+        }
+        if hidden {
+            return;
+        }
+        if self.count_recursion(id) {
+            call.set_recursive();
+        }
+        if let Some(path_calls) = &mut self.path_calls {
+            *path_calls.entry(self.stack.clone()).or_insert(0) += 1;
+        }
+        self.count_depth(id);
+        if self.call_lines {
+            call.enter_line(self.line);
+        }
+        // The frame of the entrypoint is not on the call stack
+        if self.stack.len() == CALL_DEPTH_LIMIT {
+            self.add_finding(
+                self.line,
+                "depth-limit",
+                format!(
+                    "call depth exceeds the limit of {} frames",
+                    CALL_DEPTH_LIMIT
+                ),
+            );
+        }
         self.ground.push_call(call);
-        #[allow(clippy::map_entry)]
-        if !self.functions.contains_key(&address) {
-            tracing::debug!("Add function to the registry: 0x{:x}", address);
-            let func = Function::new(address, first_pc, &mut self.resolver);
-            self.functions.insert(address, func);
+        self.enter_bookmark(id);
+        if let Some(timeline) = &mut self.timeline {
+            timeline.begin(id, self.total_cost);
         }
     }
 
+    /// Begins the bookmark of the call at the current line, the first one of the callee.
+    fn enter_bookmark(&mut self, id: FrameId) {
+        if let Some(bookmarks) = &mut self.bookmarks {
+            bookmarks.enter(id, self.line_start, self.line);
+        }
+    }
+
+    /// Adds the function called first to the registry, unless the filter hides it.
+    /// Returns true if calls of the function are hidden.
+    fn register_function(
+        &mut self,
+        id: FrameId,
+        function: impl FnOnce(&mut Resolver) -> Function,
+    ) -> bool {
+        if self.hidden.contains(&id) {
+            return true;
+        }
+        if !self.functions.contains_key(&id) {
+            let mut func = function(&mut self.resolver);
+            if let Some(program_id) = self.current_program() {
+                func.set_program(program_id);
+            }
+            if let Some(filter) = &self.filter {
+                if !filter.shows(&func.name()) {
+                    tracing::debug!("Hide function {}", func.name());
+                    self.hidden.insert(id);
+                    return true;
+                }
+            }
+            tracing::debug!("Add function to the registry: {}", id);
+            self.functions.insert(id, func);
+        }
+        false
+    }
+
+    /// Parses the instruction of the trace line, capturing its registers
+    /// only if they are needed, for sizes of syscalls or memory accesses.
+    fn parse_instruction(&mut self, line: &str) -> Result<Instruction> {
+        let registers = self.syscall_sizes || self.memory_regions.is_some();
+        self.trace_format.parse_line(line, registers)
+    }
+
+    /// Replaces the call of a syscall with the call of the node of the bucket
+    /// of its size argument, taken from registers of the instruction of the call.
+    fn size_syscall(&self, call: Call, ix: &Instruction) -> Call {
+        if !self.syscall_sizes {
+            return call;
+        }
+        let FrameId::Addr(address) = call.id() else {
+            return call;
+        };
+        let size = syscalls::name(address)
+            .and_then(syscalls::size_register)
+            .and_then(|register| ix.register(register));
+        match size {
+            Some(size) => Call::new(
+                FrameId::SyscallSize(address, syscalls::size_bucket(size)),
+                call.caller_pc(),
+            ),
+            None => call,
+        }
+    }
+
+    /// Counts the call as recursive if the function is on the call stack already.
+    /// Returns true if so.
+    fn count_recursion(&mut self, id: FrameId) -> bool {
+        let open_frames = self.open_frames.entry(id).or_default();
+        let depth = *open_frames;
+        *open_frames += 1;
+        if depth > 0 {
+            let recursion = self.recursions.entry(id).or_default();
+            if self.stack.last() == Some(&id) {
+                recursion.direct += 1;
+            } else {
+                recursion.mutual += 1;
+            }
+            recursion.max_depth = recursion.max_depth.max(depth);
+        }
+        self.flush_stack_cost();
+        self.stack.push(id);
+        depth > 0
+    }
+
+    /// Counts the frame of the function entered at the depth of the call stack,
+    /// including the frame of the entrypoint.
+    fn count_depth(&mut self, id: FrameId) {
+        let depth = self.stack.len() + 1;
+        let entry = self.depths.entry(id).or_default();
+        entry.frames += 1;
+        entry.total += depth;
+        entry.max = entry.max.max(depth);
+    }
+
+    /// Adds finished call of a syscall to the current function.
+    /// If the syscall executes another program, the nested execution starts:
+    /// the call of the syscall stays on the call stack until the exit
+    /// from the invoked program, unless nested executions are flattened.
+    fn add_syscall(&mut self, mut call: Call, nested: bool) {
+        let id = call.id();
+        tracing::debug!("Profile.add_syscall {}", id);
+        let hidden = self.register_function(id, |_| match id {
+            FrameId::Addr(address) => Function::syscall(address),
+            FrameId::SyscallSize(address, bucket) => Function::syscall_size(address, bucket),
+            _ => panic!("Call of the synthetic frame {}", id),
+        });
+        let (FrameId::Addr(address) | FrameId::SyscallSize(address, _)) = id else {
+            panic!("Call of the synthetic frame {}", id);
+        };
+        let start = self.total_cost;
+        if self.call_lines {
+            call.enter_line(self.line);
+        }
+        if !self.calls_only {
+            let cost = self.cost_model.syscall_cost(address);
+            self.total_cost += cost;
+            // The cost of a hidden syscall is the immediate cost of the caller
+            let pc = call.caller_pc();
+            if hidden {
+                self.ground.increment_cost(pc, cost, &mut self.functions);
+            } else {
+                call.add_cost(cost);
+            }
+            if let Some(total_counters) = &mut self.total_counters {
+                let mut counters = Counters::default();
+                counters[profile::SYSCALLS] = 1;
+                if let Some(cost) = self.syscall_cost {
+                    counters[profile::SYSCALL_COST] = syscalls::cost(address).unwrap_or(cost);
+                }
+                profile::add_counters(total_counters, &counters);
+                if hidden {
+                    self.ground
+                        .increment_counters(pc, &counters, &mut self.functions);
+                } else {
+                    call.add_counters(&counters);
+                }
+            }
+            if let Some(stacks) = &mut self.stacks {
+                let mut stack = self.stack.clone();
+                if !hidden {
+                    stack.push(id);
+                    if let Some(path_calls) = &mut self.path_calls {
+                        *path_calls.entry(stack.clone()).or_insert(0) += 1;
+                    }
+                }
+                *stacks.entry(stack).or_insert(0) += cost;
+            }
+        }
+        if nested && !hidden && !self.flatten_nested {
+            if self.filter.is_some() {
+                self.hidden_frames.push(false);
+            }
+            if self.count_recursion(id) {
+                call.set_recursive();
+            }
+            self.ground.push_call(call);
+            self.enter_bookmark(id);
+            if let Some(timeline) = &mut self.timeline {
+                timeline.begin(id, start);
+            }
+        } else if !hidden {
+            self.ground.add_syscall(call, &mut self.functions);
+            if let Some(timeline) = &mut self.timeline {
+                timeline.begin(id, start);
+                timeline.end(id, self.total_cost);
+            }
+        }
+        if nested {
+            self.nested
+                .push((self.ground.depth(), self.hidden_frames.len()));
+        }
+    }
+
+    /// Returns from the current call at the exit instruction, or ends
+    /// the nested execution at the exit from the invoked program.
+    fn exit(&mut self) {
+        if self.nested.last() != Some(&(self.ground.depth(), self.hidden_frames.len())) {
+            self.pop_call();
+            return;
+        }
+        tracing::debug!("Profile.exit: end of the nested execution");
+        self.nested.pop();
+        self.programs.truncate(self.nested.len() + 1);
+        self.nested_ended = true;
+        if !self.flatten_nested {
+            self.pop_call();
+        }
+    }
+
+    /// Handles the end of an invocation reported by the trace within the nested
+    /// execution or right after it. The invoked program failed if the nested
+    /// execution did not end, so calls remaining within it are finished.
+    /// Returns false if the end is of the outermost invocation.
+    fn end_nested(&mut self) -> bool {
+        if std::mem::take(&mut self.nested_ended) {
+            return true;
+        }
+        let Some((depth, hidden_frames)) = self.nested.pop() else {
+            return false;
+        };
+        self.programs.truncate(self.nested.len() + 1);
+        while self.ground.depth() > depth || self.hidden_frames.len() > hidden_frames {
+            self.pop_call();
+        }
+        if !self.flatten_nested {
+            self.pop_call();
+        }
+        true
+    }
+
+    /// Moves costs executed so far outside of any call into the synthetic
+    /// `<trace-start>` function, if the trace continues after an exit from
+    /// the outermost frame. This means that tracing began after execution started,
+    /// in the middle of a function which never appears as called.
+    fn check_trace_start(&mut self) {
+        if !self.ground_exited {
+            return;
+        }
+        self.ground_exited = false;
+        tracing::debug!("Profile.check_trace_start: trace started in the middle of a function");
+        self.trace_start = true;
+        self.add_finding(
+            self.line,
+            "trace-start",
+            "instructions continue after the exit from the program".into(),
+        );
+        let mut ground = self
+            .functions
+            .remove(&FrameId::Root)
+            .expect("Ground zero not found in registry of functions");
+        self.functions
+            .entry(FrameId::TraceStart)
+            .or_insert_with(Function::trace_start)
+            .absorb(&mut ground);
+        self.functions.insert(FrameId::Root, ground);
+        self.flush_stack_cost();
+        if let Some(stacks) = &mut self.stacks {
+            for (mut stack, cost) in std::mem::take(stacks) {
+                if stack.first() != Some(&FrameId::TraceStart) {
+                    stack.insert(0, FrameId::TraceStart);
+                }
+                *stacks.entry(stack).or_insert(0) += cost;
+            }
+        }
+        if let Some(path_calls) = &mut self.path_calls {
+            for (mut stack, count) in std::mem::take(path_calls) {
+                if stack.first() != Some(&FrameId::TraceStart) {
+                    stack.insert(0, FrameId::TraceStart);
+                }
+                *path_calls.entry(stack).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Finishes the invocation of the program at its boundary in the trace:
+    /// calls remaining on the call stack are finished, so the next invocation
+    /// starts at the root rather than in the middle of a function.
+    fn end_invocation(&mut self) {
+        self.flush_stack_cost();
+        if !self.in_invocation {
+            return;
+        }
+        self.in_invocation = false;
+        self.invocations += 1;
+        self.next_pc = None;
+        // Only calls of shown functions are on the call stack
+        self.hidden_frames.clear();
+        self.nested.clear();
+        self.nested_ended = false;
+        self.programs.truncate(1);
+        if self.ground.depth() > 0 {
+            tracing::warn!("Unbalanced call/exit: {}", self.ground.depth());
+            self.unwound_calls += self.ground.depth();
+            self.add_finding(
+                self.line,
+                "unbalanced",
+                format!(
+                    "{} unfinished calls at the end of the invocation",
+                    self.ground.depth()
+                ),
+            );
+            for _ in 0..self.ground.depth() {
+                self.pop_call();
+            }
+        }
+        self.ground_exited = false;
+    }
+
     /// Removes finished call from the call stack and adds it to the caller.
     fn pop_call(&mut self) {
-        let call = self.ground.pop_call();
-        tracing::debug!("Profile.pop_call 0x{:x}", &call.address());
+        if self.hidden_frames.pop() == Some(true) {
+            tracing::debug!("Profile.pop_call: exit from the hidden function");
+            return;
+        }
+        if self.ground.depth() == 0 {
+            tracing::debug!("Profile.pop_call: exit from the program");
+            self.ground_exited = true;
+            return;
+        }
+        let mut call = self.ground.pop_call();
+        if self.call_lines {
+            call.exit_line(self.line);
+        }
+        self.flush_stack_cost();
+        if let Some(id) = self.stack.pop() {
+            if let Some(open_frames) = self.open_frames.get_mut(&id) {
+                *open_frames -= 1;
+                if *open_frames == 0 {
+                    self.open_frames.remove(&id);
+                }
+            }
+        }
+        if let Some(timeline) = &mut self.timeline {
+            timeline.end(call.id(), self.total_cost);
+        }
+        tracing::debug!("Profile.pop_call {}", call.id());
+        if let Some(bookmarks) = &mut self.bookmarks {
+            let name = self.functions[&call.id()].name();
+            bookmarks.exit(&name, self.line_end, self.line, call.cost());
+        }
         if !call.is_ground() {
+            let aggregate = match (self.drop_calls_below, self.max_call_samples) {
+                (Some(cost), _) if call.cost() < cost => true,
+                (_, Some(max)) => {
+                    let samples = self
+                        .call_samples
+                        .entry((call.caller(), call.id()))
+                        .or_default();
+                    *samples += 1;
+                    *samples > max
+                }
+                _ => false,
+            };
             let f = self
                 .functions
                 .get_mut(&call.caller())
                 .expect("Caller not found in registry of functions");
-            f.add_call(call);
+            if aggregate {
+                f.aggregate_call(call);
+            } else {
+                f.add_call(call);
+            }
+        }
+    }
+
+    /// Merges functions renamed to the same name by aliases into the one
+    /// with the least identifier, so reports list them once.
+    fn merge_aliased(&mut self) {
+        let mut ids = Map::<String, FrameId>::new();
+        for (id, f) in &self.functions {
+            let merged_id = ids.entry(f.name()).or_insert(*id);
+            *merged_id = (*merged_id).min(*id);
+        }
+        let ids = self
+            .functions
+            .iter()
+            .map(|(id, f)| (*id, ids[&f.name()]))
+            .filter(|(id, merged_id)| id != merged_id)
+            .collect::<Map<_, _>>();
+        if ids.is_empty() {
+            return;
+        }
+        let map = |id| ids.get(&id).copied().unwrap_or(id);
+
+        let mut functions = std::mem::take(&mut self.functions)
+            .into_iter()
+            .collect::<Vec<_>>();
+        // Merged functions keep the properties of the one with the least identifier
+        functions.sort_by_key(|(id, _)| *id);
+        for (id, mut f) in functions {
+            f.remap(map);
+            match self.functions.get_mut(&map(id)) {
+                Some(merged) => merged.absorb(&mut f),
+                None => {
+                    self.functions.insert(map(id), f);
+                }
+            }
+        }
+        // Calls between the merged functions are recursive calls of the merged one
+        for merged_id in ids.values().collect::<BTreeSet<_>>() {
+            let merged = self.functions.get_mut(merged_id).expect("Merged function");
+            merged.set_recursive_calls();
+        }
+        if let Some(stacks) = &mut self.stacks {
+            let mut merged = Stacks::new();
+            for (stack, cost) in std::mem::take(stacks) {
+                let stack = stack.into_iter().map(map).collect();
+                *merged.entry(stack).or_insert(0) += cost;
+            }
+            *stacks = merged;
+        }
+        if let Some(path_calls) = &mut self.path_calls {
+            let mut merged = PathCalls::new();
+            for (stack, count) in std::mem::take(path_calls) {
+                let stack = stack.into_iter().map(map).collect();
+                *merged.entry(stack).or_insert(0) += count;
+            }
+            *path_calls = merged;
+        }
+        if let Some(timeline) = &mut self.timeline {
+            timeline.remap(map);
+        }
+        for (id, depth) in std::mem::take(&mut self.depths) {
+            self.depths.entry(map(id)).or_default().add(&depth);
+        }
+        for (id, recursion) in std::mem::take(&mut self.recursions) {
+            self.recursions.entry(map(id)).or_default().add(&recursion);
+        }
+    }
+
+    /// Returns the resolver of names of functions.
+    pub(super) fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    /// Returns the accounting of the profile to save as the intermediate profile.
+    pub(super) fn intermediate(&self) -> Intermediate<'_> {
+        Intermediate {
+            total_cost: self.total_cost,
+            total_counters: self.total_counters,
+            syscall_cost: self.syscall_cost,
+            functions: Cow::Borrowed(&self.functions),
+            stacks: self.stacks.as_ref().map(Cow::Borrowed),
+            runtime_version: self.runtime_version.clone(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// Replaces the accounting of the profile with the intermediate profile.
+    /// The name of the root set by the options is kept.
+    pub(super) fn restore(&mut self, intermediate: Intermediate) -> Result<()> {
+        if self.stacks.is_some() && intermediate.stacks.is_none() {
+            return Err(Error::RenderOption("--format".into()));
+        }
+        let root_name = self.functions[&FrameId::Root].name();
+        self.total_cost = intermediate.total_cost;
+        self.total_counters = intermediate.total_counters;
+        self.syscall_cost = intermediate.syscall_cost;
+        self.functions = intermediate.functions.into_owned();
+        if self.root_named {
+            self.set_root_name(&root_name);
+        }
+        if self.stacks.is_some() {
+            self.stacks = intermediate.stacks.map(Cow::into_owned);
+        }
+        self.runtime_version = intermediate.runtime_version;
+        self.labels = intermediate.labels;
+        Ok(())
+    }
+
+    /// Takes the accounting of the parsed chunk of the trace.
+    fn into_part(self) -> Part {
+        Part {
+            total_cost: self.total_cost,
+            total_counters: self.total_counters,
+            functions: self.functions,
+            resolver: self.resolver,
+            asm: self.asm,
+            opcode_classes: self.opcode_classes,
+            unknown_opcodes: self.unknown_opcodes,
+            memory_regions: self.memory_regions,
+            stacks: self.stacks,
+            path_calls: self.path_calls,
+            timeline: self.timeline,
+            depths: self.depths,
+            recursions: self.recursions,
+            runtime_version: self.runtime_version,
+            labels: self.labels,
+            invocations: self.invocations,
+            malformed_lines: self.malformed_lines,
+            skipped_lines: self.skipped_lines,
+            dropped_calls: self.dropped_calls,
+            unwound_calls: self.unwound_calls,
+            trace_start: self.trace_start,
+        }
+    }
+
+    /// Merges the accounting of the chunk of the trace following the merged ones.
+    /// Functions first called in the chunk are named by the resolver of the profile
+    /// in order of their calls, so generated names are the same as of sequential parsing.
+    pub(super) fn merge(&mut self, mut part: Part) {
+        for (address, first_pc) in part.resolver.traced_functions() {
+            let id = FrameId::Addr(address);
+            if self.functions.contains_key(&id) {
+                continue;
+            }
+            let name = self.resolver.update(address, first_pc);
+            if let Some(f) = part.functions.get_mut(&id) {
+                f.set_name(&name);
+            }
+        }
+        self.absorb_part(part, |id| id);
+    }
+
+    /// Parses the following trace of the same program into a separate profile
+    /// and merges it only if parsed successfully, so a malformed trace leaves
+    /// the accounting of the profile intact.
+    pub fn parse_merged(&mut self, reader: impl BufRead, options: &Options) -> Result<()> {
+        let mut prof = Profile::with_options(self.resolver.clone(), options)?;
+        parse(reader, &mut prof)?;
+        self.merge(prof.into_part());
+        Ok(())
+    }
+
+    /// Merges the accounting of another trace of the same program. Functions are
+    /// matched by their resolved names, or by their first program counters if not
+    /// resolved, since addresses of functions differ between builds of the program.
+    pub(super) fn merge_trace(&mut self, mut part: Part) {
+        let mut merged_functions = Map::new();
+        for (id, identity) in identities(&self.functions, &self.resolver) {
+            let merged_id = merged_functions.entry(identity).or_insert(id);
+            *merged_id = (*merged_id).min(id);
+        }
+        let mut used = self.functions.keys().copied().collect::<BTreeSet<_>>();
+        let mut ids = Map::new();
+        let part_functions = identities(&part.functions, &part.resolver);
+        // Functions new to the profile are named by its resolver in order of their calls
+        for (address, first_pc) in part.resolver.traced_functions() {
+            let id = FrameId::Addr(address);
+            let Some(identity) = part_functions.get(&id) else {
+                continue;
+            };
+            let merged_id = match merged_functions.get(identity) {
+                Some(merged_id) => *merged_id,
+                None if used.contains(&id) => {
+                    // The address belongs to another function of the merged traces
+                    let name = self.resolver.update_unindexed(address, first_pc);
+                    if let Some(f) = part.functions.get_mut(&id) {
+                        f.set_name(&name);
+                    }
+                    FrameId::Merged(first_pc)
+                }
+                None => {
+                    let name = self.resolver.update(address, first_pc);
+                    if let Some(f) = part.functions.get_mut(&id) {
+                        f.set_name(&name);
+                    }
+                    id
+                }
+            };
+            used.insert(merged_id);
+            ids.insert(id, merged_id);
+        }
+        self.absorb_part(part, |id| ids.get(&id).copied().unwrap_or(id));
+    }
+
+    /// Adds the accounting of the part to the profile, replacing identifiers
+    /// of functions of the part by the map.
+    fn absorb_part(&mut self, part: Part, map: impl Fn(FrameId) -> FrameId) {
+        for (id, mut f) in part.functions {
+            f.remap(&map);
+            match self.functions.get_mut(&map(id)) {
+                Some(merged) => merged.absorb(&mut f),
+                None => {
+                    self.functions.insert(map(id), f);
+                }
+            }
+        }
+
+        self.total_cost += part.total_cost;
+        if let (Some(total), Some(counters)) = (&mut self.total_counters, &part.total_counters) {
+            profile::add_counters(total, counters);
+        }
+        if let (Some(asm), Some(source)) = (&mut self.asm, part.asm) {
+            asm.absorb(source);
         }
+        for (pc, class) in part.opcode_classes {
+            self.opcode_classes.entry(pc).or_insert(class);
+        }
+        for (mnemonic, count) in part.unknown_opcodes {
+            *self.unknown_opcodes.entry(mnemonic).or_insert(0) += count;
+        }
+        if let (Some(regions), Some(accesses)) = (&mut self.memory_regions, part.memory_regions) {
+            for (region, count) in accesses {
+                *regions.entry(region).or_insert(0) += count;
+            }
+        }
+        if let (Some(stacks), Some(costs)) = (&mut self.stacks, part.stacks) {
+            for (stack, cost) in costs {
+                let stack = stack.into_iter().map(&map).collect();
+                *stacks.entry(stack).or_insert(0) += cost;
+            }
+        }
+        if let (Some(path_calls), Some(counts)) = (&mut self.path_calls, part.path_calls) {
+            for (stack, count) in counts {
+                let stack = stack.into_iter().map(&map).collect();
+                *path_calls.entry(stack).or_insert(0) += count;
+            }
+        }
+        if let (Some(timeline), Some(events)) = (&mut self.timeline, part.timeline) {
+            timeline.append(events, self.total_cost - part.total_cost, &map);
+        }
+        for (id, depth) in part.depths {
+            self.depths.entry(map(id)).or_default().add(&depth);
+        }
+        for (id, recursion) in part.recursions {
+            self.recursions.entry(map(id)).or_default().add(&recursion);
+        }
+        self.set_header(crate::trace::Header {
+            runtime_version: part.runtime_version,
+        });
+        for label in &part.labels {
+            self.add_label(label);
+        }
+        self.invocations += part.invocations;
+        self.malformed_lines += part.malformed_lines;
+        self.skipped_lines += part.skipped_lines;
+        self.dropped_calls += part.dropped_calls;
+        self.unwound_calls += part.unwound_calls;
+        self.trace_start |= part.trace_start;
+    }
+}
+
+/// Identifies functions of the program across traces: by resolved names,
+/// or by first program counters if not resolved.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Identity {
+    Name(String),
+    FirstPc(ProgramCounter),
+}
+
+/// Returns identities of functions of the program, besides syscalls and synthetic frames.
+fn identities(functions: &Functions, resolver: &Resolver) -> Map<FrameId, Identity> {
+    let first_pcs = resolver
+        .traced_functions()
+        .into_iter()
+        .collect::<Map<_, _>>();
+    let mut identities = Map::new();
+    for (id, f) in functions {
+        let identity = match *id {
+            _ if id.is_syscall() => continue,
+            FrameId::Addr(address) if resolver.is_resolved(address) => Identity::Name(f.name()),
+            FrameId::Addr(address) => match first_pcs.get(&address) {
+                Some(first_pc) => Identity::FirstPc(*first_pc),
+                None => continue,
+            },
+            FrameId::Merged(first_pc) if resolver.is_resolved_pc(first_pc) => {
+                Identity::Name(f.name())
+            }
+            FrameId::Merged(first_pc) => Identity::FirstPc(first_pc),
+            _ => continue,
+        };
+        identities.insert(*id, identity);
+    }
+    identities
+}
+
+/// Represents the accounting of a chunk of the trace parsed by a separate thread,
+/// without the parts of the profile which are not sent between threads.
+#[derive(Debug)]
+pub(super) struct Part {
+    total_cost: Cost,
+    total_counters: Option<Counters>,
+    functions: Functions,
+    resolver: Resolver,
+    asm: Option<asm::Source>,
+    opcode_classes: OpcodeClasses,
+    unknown_opcodes: UnknownOpcodes,
+    memory_regions: Option<MemoryRegions>,
+    stacks: Option<Stacks>,
+    path_calls: Option<PathCalls>,
+    timeline: Option<Timeline>,
+    depths: Depths,
+    recursions: Recursions,
+    runtime_version: Option<String>,
+    labels: Vec<String>,
+    invocations: usize,
+    malformed_lines: usize,
+    skipped_lines: usize,
+    dropped_calls: usize,
+    unwound_calls: usize,
+    trace_start: bool,
+}
+
+/// Counts bytes written, to size outputs without keeping them.
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
+/// Checks the callgrind file was written by this tool with the same kind of positions
+/// and the events, and returns number of the next part for appending to the file.
+pub fn next_callgrind_part(callgrind: &str, events: &str) -> Option<usize> {
+    let mut lines = callgrind.lines();
+    if lines.next() != Some("# callgrind format") {
+        return None;
+    }
+    let mut parts = 1;
+    let mut creator = false;
+    for line in lines {
+        if line.starts_with("creator:") {
+            creator = line == "creator: bpf-profile";
+        } else if (line.starts_with("positions:") && line != "positions: line")
+            || (line.starts_with("events:") && line != format!("events: {}", events))
+        {
+            return None;
+        } else if line.starts_with("part:") {
+            parts += 1;
+        }
+    }
+    creator.then_some(parts + 1)
+}
+
 /// Parses the trace file line by line, building the Profile instance.
-pub fn parse(mut reader: impl BufRead, prof: &mut Profile) -> Result<()> {
+/// Calls remaining on the call stack are finished even if parsing fails,
+/// so the profile stays consistent for further traces.
+pub fn parse(reader: impl BufRead, prof: &mut Profile) -> Result<()> {
     if global::verbose() {
         tracing::info!("Parsing trace file, creating profile...")
     }
 
-    let mut line = String::with_capacity(512);
-    let mut bytes_read = usize::MAX;
-    let mut lc = 0_usize;
-    let mut ix: Instruction;
+    let result = parse_instructions(reader, prof, true);
+    prof.end_invocation();
+    if let Some(progress) = prof.take_progress() {
+        // The end of the whole trace is read as one more empty line
+        let lines = prof.line - usize::from(result.is_ok() && prof.line > 0);
+        progress.finish(
+            prof.line_end,
+            lines,
+            Some(prof.ground.depth()),
+            Some(prof.functions.len()),
+        );
+    }
+    report_parsed(prof);
+    result
+}
+
+/// Warns about parts of the parsed trace which the profile may miss.
+pub(super) fn report_parsed(prof: &Profile) {
+    if let (0, Some(function)) = (prof.entries, &prof.ignore_before) {
+        tracing::warn!("Function '{}' is never entered in the trace", function);
+    }
+
+    if prof.trace_start {
+        tracing::warn!(
+            "Trace starts in the middle of a function, its costs are attributed to <trace-start>"
+        );
+    }
+}
+
+/// Parses the chunk of whole invocations of programs of the trace
+/// into a separate profile with a copy of the resolver.
+/// Numbers of lines in errors count from the start of the chunk.
+pub(super) fn parse_part(chunk: &str, resv: Resolver, options: &Options) -> Result<Part> {
+    let mut prof = Profile::parser(resv, options)?;
+    let result = parse_instructions(chunk.as_bytes(), &mut prof, false);
+    prof.end_invocation();
+    result?;
+    Ok(prof.into_part())
+}
+
+/// Parses instructions of the trace file adding them to the profile,
+/// until the end of the trace or the interruption if enabled.
+fn parse_instructions(reader: impl BufRead, prof: &mut Profile, interruptible: bool) -> Result<()> {
+    let mut parser = TraceParser::new(prof);
+    parser.interruptible = interruptible;
+    parser.feed(reader, prof)?;
+    parser.finish(prof)
+}
+
+/// Represents the state of parsing the trace besides the profile, kept between
+/// parts of a growing trace fed one after another. A part must not end with a call,
+/// since the first instruction of the callee follows it.
+#[derive(Debug)]
+pub(super) struct TraceParser {
+    lc: usize,
+    offset: u64,
+    header_expected: bool,
+    header_found: bool,
+    interruptible: bool,
+    interrupted: bool,
+    instructions: usize,
+    windowed: usize,
+}
 
-    while bytes_read != 0 {
-        if line.is_empty() {
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
+impl TraceParser {
+    /// Starts parsing the trace into the profile.
+    pub(super) fn new(prof: &mut Profile) -> Self {
+        prof.ground_exited = false;
+        prof.ignoring = prof.ignore_before.is_some();
+        prof.entries = 0;
+        TraceParser {
+            lc: 0,
+            offset: 0,
+            header_expected: true,
+            header_found: false,
+            interruptible: false,
+            interrupted: false,
+            instructions: 0,
+            windowed: 0,
         }
+    }
 
-        let ixr = Instruction::parse(&line);
-        if let Err(Error::TraceSkipped) = &ixr {
-            /* warn!("Skip '{}'", &line.trim()); */
-            line.clear();
-            continue;
+    /// Reads the next line of the trace, keeping its number and byte offsets.
+    /// Lines of states, e.g. of registers, are consumed by the format of the trace.
+    /// Returns zero at the end of the trace.
+    fn read_line(
+        &mut self,
+        reader: &mut impl BufRead,
+        line: &mut String,
+        prof: &mut Profile,
+    ) -> Result<usize> {
+        let (bytes_read, lines) =
+            crate::trace::read_line(reader, line, prof.trace_format.as_mut())?;
+        self.lc += lines;
+        prof.line = self.lc;
+        prof.previous_line_start = prof.line_start;
+        prof.line_start = self.offset;
+        self.offset += bytes_read as u64;
+        prof.line_end = self.offset;
+        if self.lc / progress::CHECK_LINES != (self.lc - lines) / progress::CHECK_LINES {
+            if let Some(progress) = &mut prof.progress {
+                progress.update(
+                    self.offset,
+                    self.lc,
+                    Some(prof.ground.depth()),
+                    Some(prof.functions.len()),
+                );
+            }
         }
-        ix = ixr?;
+        Ok(if line.is_empty() { 0 } else { bytes_read })
+    }
+
+    /// Parses the next part of the trace adding its instructions to the profile.
+    pub(super) fn feed(&mut self, reader: impl BufRead, prof: &mut Profile) -> Result<()> {
+        let result = self.feed_lines(reader, prof);
+        // The profile is complete between parts, e.g. to be written
+        prof.flush_stack_cost();
+        result
+    }
+
+    /// Parses lines of the next part of the trace.
+    fn feed_lines(&mut self, mut reader: impl BufRead, prof: &mut Profile) -> Result<()> {
+        let mut line = String::with_capacity(512);
+        let mut bytes_read = usize::MAX;
+        let mut ix: Instruction;
+
+        while bytes_read != 0 {
+            if line.is_empty() {
+                // The rest of the trace is skipped, calls open are finished as at its end
+                if self.interruptible && global::interrupted() {
+                    self.interrupted = true;
+                    return Ok(());
+                }
+                bytes_read = self.read_line(&mut reader, &mut line, prof)?;
+            }
 
-        prof.keep_asm(&ix);
+            // Only calls and exits change the call stack, all instructions count in the window
+            if prof.calls_only && prof.window.is_none() && !may_be_call_or_exit(&line) {
+                parse_skipped(&line, prof, self.header_expected, &mut self.header_found);
+                line.clear();
+                continue;
+            }
 
-        if ix.is_exit() {
-            prof.increment_cost(ix.pc());
-            prof.pop_call();
-            line.clear();
-            continue;
+            let ixr = prof.parse_instruction(&line);
+            if let Err(Error::TraceSkipped) = &ixr {
+                /* warn!("Skip '{}'", &line.trim()); */
+                if !parse_skipped(&line, prof, self.header_expected, &mut self.header_found)
+                    && !self.header_expected
+                {
+                    prof.skipped_lines += 1;
+                }
+                line.clear();
+                continue;
+            }
+            ix = match ixr {
+                Ok(ix) => ix,
+                Err(e) => {
+                    prof.recover(e, self.lc, &line)?;
+                    line.clear();
+                    continue;
+                }
+            };
+            // The standard header precedes instructions, so the input is checked
+            // while streaming rather than read twice
+            if self.header_expected && !self.header_found {
+                prof.add_finding(
+                    self.lc,
+                    "header",
+                    "instructions precede the standard header".into(),
+                );
+                return Err(Error::TraceFormat);
+            }
+            self.header_expected = false;
+
+            // Discard instructions before the window, the rest of the trace after it
+            if let Some(window) = &prof.window {
+                self.instructions += 1;
+                match window.locate(self.instructions, self.lc) {
+                    Ordering::Less => {
+                        line.clear();
+                        continue;
+                    }
+                    Ordering::Greater => return Ok(()),
+                    Ordering::Equal => self.windowed += 1,
+                }
+            }
+            prof.in_invocation = true;
+            prof.nested_ended = false;
+
+            // Discard the trace until the first entry into the function
+            if prof.ignoring {
+                if !ix.is_call() {
+                    line.clear();
+                    continue;
+                }
+                // Read next line — the first instruction of the call
+                bytes_read = self.read_line(&mut reader, &mut line, prof)?;
+                if let Ok(next) = prof.parse_instruction(&line) {
+                    if prof.ends_ignored(next.pc()) {
+                        prof.keep_asm(&ix);
+                        prof.increment_cost(&ix)?;
+                        match Call::from(&ix, self.lc - 1) {
+                            Ok(call) => prof.push_call(call, next.pc()),
+                            Err(e) => prof.recover(e, self.lc - 1, &ix.text())?,
+                        }
+                    }
+                }
+                // Keep the next line to process further
+                continue;
+            }
+
+            prof.check_trace_start();
+            prof.check_sequence(&ix);
+            prof.keep_asm(&ix);
+
+            if ix.is_exit() {
+                prof.increment_cost(&ix)?;
+                prof.exit();
+                prof.check_only_function_exit();
+                line.clear();
+                continue;
+            }
+
+            if !ix.is_call() {
+                prof.keep_memory_access(&ix);
+                prof.increment_cost(&ix)?;
+                line.clear();
+                continue;
+            }
+
+            // Handle sequences of enclosed calls as well:
+            // 604: call 0xcb3fc071
+            // 588: call 0x8e0001f9
+            // 1024: call 0x8bf38212
+            // ...
+            while ix.is_call() {
+                prof.increment_cost(&ix)?;
+                let call = match Call::from(&ix, self.lc) {
+                    Ok(call) => prof.size_syscall(call, &ix),
+                    Err(e) => {
+                        prof.recover(e, self.lc, &ix.text())?;
+                        line.clear();
+                        break;
+                    }
+                };
+                // Read next line — the first instruction of the call
+                // or the next instruction of the caller if it was a syscall
+                bytes_read = self.read_line(&mut reader, &mut line, prof)?;
+                // The program invoked by the syscall may be reported before its first instruction
+                while call.id().is_syscall() {
+                    let Some((program_id, depth)) = crate::trace::parse_invocation_start(&line)
+                    else {
+                        break;
+                    };
+                    prof.invoke_program(program_id, depth);
+                    bytes_read = self.read_line(&mut reader, &mut line, prof)?;
+                }
+                let next = prof.parse_instruction(&line);
+                if call.id().is_syscall() {
+                    let nested = next.as_ref().is_ok_and(|next| is_nested(&call, next));
+                    prof.add_syscall(call, nested);
+                } else if let Ok(next) = &next {
+                    prof.push_call(call, next.pc());
+                } else {
+                    prof.dropped_calls += 1;
+                    prof.add_finding(
+                        self.lc - 1,
+                        "dropped-call",
+                        "call without its first instruction".into(),
+                    );
+                }
+                match next {
+                    // Keep the call to check it against the window
+                    Ok(next) if next.is_call() && prof.window.is_some() => break,
+                    Ok(next) => ix = next,
+                    // Keep the line to process further, it may be a boundary of invocations
+                    Err(Error::TraceSkipped) if prof.lenient => break,
+                    Err(e) => {
+                        prof.recover(e, self.lc, &line)?;
+                        line.clear();
+                        break;
+                    }
+                }
+            }
+            // Keep here the last non-call line to process further
         }
+        // The end of the part is not a line of the trace
+        self.lc -= 1;
+        Ok(())
+    }
 
-        if !ix.is_call() {
-            prof.increment_cost(ix.pc());
-            line.clear();
-            continue;
+    /// Checks that the standard header was found in the parsed trace.
+    pub(super) fn finish(&self, prof: &mut Profile) -> Result<()> {
+        if !self.header_found && !self.interrupted {
+            prof.add_finding(1, "header", "no standard header in the trace".into());
+            return Err(Error::TraceFormat);
+        }
+        // Lines skipped below the limit of errors still fail the trace
+        if !prof.diagnostics.is_empty() {
+            return Err(prof.diagnostics.take());
         }
+        if prof.window.is_some() {
+            if self.windowed == 0 {
+                tracing::warn!("No instructions of the trace are within the window");
+            } else if global::verbose() {
+                tracing::info!(
+                    "Profiled {} instructions of the window of the trace",
+                    global::number(self.windowed)
+                );
+            }
+        }
+        Ok(())
+    }
+}
 
-        // Handle sequences of enclosed calls as well:
-        // 604: call 0xcb3fc071
-        // 588: call 0x8e0001f9
-        // 1024: call 0x8bf38212
-        // ...
-        while ix.is_call() {
-            prof.increment_cost(ix.pc());
-            let call = Call::from(&ix, lc)?;
-            // Read next line — the first instruction of the call
-            bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-            lc += 1;
-            ix = Instruction::parse(&line)?;
-            prof.push_call(call, ix.pc());
+/// Handles the line which is not an instruction: the standard header before
+/// instructions, a boundary of invocations of programs after them,
+/// or the identifier of the execution. Returns false if the line is none of them.
+fn parse_skipped(
+    line: &str,
+    prof: &mut Profile,
+    header_expected: bool,
+    header_found: &mut bool,
+) -> bool {
+    if let Some(label) = crate::trace::parse_label(line) {
+        prof.add_label(label);
+        return true;
+    }
+    if let Some((program_id, depth)) = crate::trace::parse_invocation_start(line) {
+        prof.invoke_program(program_id, depth);
+        return true;
+    }
+    let header = crate::trace::parse_header(line);
+    if header.is_some() {
+        prof.set_trace_format(line);
+    }
+    if header_expected {
+        if let Some(header) = header {
+            *header_found = true;
+            prof.set_header(header);
+        }
+    } else if crate::trace::is_invocation_end(line) {
+        if !prof.end_nested() {
+            prof.end_invocation();
         }
-        // Keep here the last non-call line to process further
+    } else if header.is_some() {
+        prof.end_invocation();
+    } else {
+        return false;
     }
+    true
+}
 
-    if prof.ground.depth() > 0 {
-        tracing::warn!("Unbalanced call/exit: {}", &prof.ground.depth());
-        for _ in 0..prof.ground.depth() {
-            prof.pop_call();
+/// Returns the iterator over invocations of programs of the trace,
+/// which reads the trace while iterating.
+pub fn invocations<R: BufRead>(reader: R) -> Invocations<R> {
+    Invocations {
+        reader,
+        header: String::new(),
+        runtime_header: None,
+        label: None,
+        line: String::with_capacity(512),
+        bytes_read: 0,
+        lines: 0,
+        trace_format: Box::new(Legacy),
+        found: false,
+        done: false,
+    }
+}
+
+/// Iterates over invocations of programs of the trace split at their boundaries,
+/// which include lines identifying executions. Every invocation starts with
+/// the last standard header seen, so it can be parsed as a separate trace,
+/// preceded by the identifier of its execution, if any.
+#[derive(Debug)]
+pub struct Invocations<R> {
+    reader: R,
+    header: String,
+    runtime_header: Option<String>,
+    label: Option<String>,
+    line: String,
+    bytes_read: u64,
+    lines: usize,
+    trace_format: Box<dyn TraceFormat>,
+    found: bool,
+    done: bool,
+}
+
+impl<R> Invocations<R> {
+    /// Returns the number of bytes read from the trace so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the number of lines read from the trace so far.
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Returns the first standard header with the runtime version read so far.
+    pub fn runtime_header(&self) -> Option<&str> {
+        self.runtime_header.as_deref()
+    }
+}
+
+impl<R: BufRead> Iterator for Invocations<R> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut invocation = self.header.clone();
+        let mut label = self.label.take();
+        let mut instructions = false;
+        // The identifier of the execution leads the invocation
+        let labeled = |label: Option<String>, invocation: String| match label {
+            Some(label) => label + invocation.as_str(),
+            None => invocation,
+        };
+        loop {
+            match filebuf::read_line(&mut self.reader, &mut self.line) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.bytes_read += n as u64;
+                    self.lines += 1;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            let line = &self.line;
+            if let Some(header) = crate::trace::parse_header(line) {
+                if self.runtime_header.is_none() && header.runtime_version.is_some() {
+                    self.runtime_header = Some(line.clone());
+                }
+                self.header = line.clone();
+                self.trace_format = crate::trace::detect_format(line);
+                if instructions {
+                    self.found = true;
+                    return Some(Ok(labeled(label, invocation)));
+                }
+                invocation = line.clone();
+                continue;
+            }
+            if crate::trace::parse_label(line).is_some() {
+                if instructions {
+                    self.label = Some(line.clone());
+                    self.found = true;
+                    return Some(Ok(labeled(label, invocation)));
+                }
+                label = Some(line.clone());
+                continue;
+            }
+            invocation.push_str(line);
+            if crate::trace::is_invocation_end(line) {
+                if instructions {
+                    self.found = true;
+                    return Some(Ok(labeled(label, invocation)));
+                }
+            } else if !instructions && self.trace_format.parse_line(line, false).is_ok() {
+                instructions = true;
+            }
         }
+        self.done = true;
+        (instructions || !self.found).then_some(Ok(labeled(label, invocation)))
     }
+}
+
+/// Checks if the call of the syscall executes another program: the instruction
+/// following it is not the next instruction of the caller.
+fn is_nested(call: &Call, next: &Instruction) -> bool {
+    let FrameId::Addr(address) = call.id() else {
+        return false;
+    };
+    syscalls::name(address).is_some_and(syscalls::is_invoke) && next.pc() != call.caller_pc() + 1
+}
 
-    Ok(())
+/// Quickly checks if the trace line may contain a call or an exit instruction,
+/// without parsing the line.
+fn may_be_call_or_exit(line: &str) -> bool {
+    line.contains(": call") || line.trim_end().ends_with(": exit")
 }
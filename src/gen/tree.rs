@@ -0,0 +1,146 @@
+//! bpf-profile-generate tree module.
+//! Implements the call tree in plain text: call paths of the profile merged into
+//! a tree indented by depth, every node with its inclusive cost and percentage
+//! of the total cost, the most costly callees first:
+//! ```text
+//!           13  100.00%  GROUND_ZERO
+//!            9   69.23%    function_0 (0x100)
+//!            5   38.46%      sol_invoke_signed_rust
+//!            2   15.38%        function_1 (0x500)
+//! ```
+//! Unlike the call graph, calls of a function are not aggregated across callers,
+//! so every node is the cost of the function called by the same path.
+
+use super::flamegraph::Stacks;
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::{Error, Result};
+use crate::global;
+use std::io::Write;
+
+/// Width of the column of costs.
+const COST_WIDTH: usize = 12;
+
+/// Represents calls of a function by the same call path with the calls below them.
+#[derive(Debug, Default)]
+struct Node {
+    cost: Cost,
+    callees: Map<FrameId, Node>,
+}
+
+impl Node {
+    /// Adds the cost of the call path below the node.
+    fn add(&mut self, path: &[FrameId], cost: Cost) {
+        let mut node = self;
+        node.cost += cost;
+        for id in path {
+            node = node.callees.entry(*id).or_default();
+            node.cost += cost;
+        }
+    }
+
+    /// Adds the costs of the other node and of its callees.
+    fn merge(&mut self, other: Node) {
+        self.cost += other.cost;
+        for (id, callee) in other.callees {
+            self.callees.entry(id).or_default().merge(callee);
+        }
+    }
+
+    /// Moves the outermost calls of the matching functions below the node
+    /// to the roots, merging calls of the same function.
+    fn take_calls(self, matches: &dyn Fn(FrameId) -> bool, roots: &mut Map<FrameId, Node>) {
+        for (id, callee) in self.callees {
+            if matches(id) {
+                roots.entry(id).or_default().merge(callee);
+            } else {
+                callee.take_calls(matches, roots);
+            }
+        }
+    }
+}
+
+/// Writes the call tree of the costs by call stacks, from the root or from the
+/// outermost calls of functions with names containing the given one, down to the
+/// depth below them if given. Calls without cost are skipped.
+pub fn write(
+    mut output: impl Write,
+    stacks: &Stacks,
+    functions: &Functions,
+    total_cost: Cost,
+    root: Option<&str>,
+    depth: Option<usize>,
+) -> Result<()> {
+    let mut tree = Node::default();
+    for (stack, cost) in stacks {
+        tree.add(stack, *cost);
+    }
+    let roots = match root {
+        None => Map::from([(FrameId::Root, tree)]),
+        Some(name) => {
+            let mut roots = Map::new();
+            tree.take_calls(&|id| functions[&id].name().contains(name), &mut roots);
+            if roots.is_empty() {
+                return Err(Error::FunctionNotFound(name.into()));
+            }
+            roots
+        }
+    };
+
+    let mut writer = TreeWriter {
+        output: &mut output,
+        functions,
+        total_cost,
+        depth: depth.unwrap_or(usize::MAX),
+    };
+    for (id, node) in writer.sorted(&roots) {
+        writer.write(id, node, 0)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes lines of nodes of the call tree.
+struct TreeWriter<'a> {
+    output: &'a mut dyn Write,
+    functions: &'a Functions,
+    total_cost: Cost,
+    depth: usize,
+}
+
+impl TreeWriter<'_> {
+    /// Writes the line of the node of the function at the level and the lines of its callees.
+    fn write(&mut self, id: FrameId, node: &Node, level: usize) -> Result<()> {
+        writeln!(
+            self.output,
+            "{:>w$} {:>7.2}%  {:indent$}{}",
+            global::number(node.cost),
+            100.0 * node.cost as f64 / self.total_cost.max(1) as f64,
+            "",
+            self.functions[&id].name(),
+            w = COST_WIDTH,
+            indent = 2 * level
+        )?;
+        if level < self.depth {
+            for (id, callee) in self.sorted(&node.callees) {
+                if callee.cost > 0 {
+                    self.write(id, callee, level + 1)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the nodes, the most costly first, then by names of functions.
+    fn sorted<'n>(&self, nodes: &'n Map<FrameId, Node>) -> Vec<(FrameId, &'n Node)> {
+        let mut nodes = nodes.iter().map(|(id, n)| (*id, n)).collect::<Vec<_>>();
+        nodes.sort_by(|(a, a_node), (b, b_node)| {
+            b_node
+                .cost
+                .cmp(&a_node.cost)
+                .then_with(|| self.functions[a].name().cmp(&self.functions[b].name()))
+                .then(a.cmp(b))
+        });
+        nodes
+    }
+}
@@ -0,0 +1,454 @@
+//! bpf-profile-generate view module.
+//! Implements the interactive terminal viewer of a loaded profile: the table
+//! of functions sortable by costs, calls and names, and the call tree expanded
+//! from the root. The table is filtered by the search of names of functions,
+//! the tree moves to the next shown function matching it.
+
+use super::budget;
+use super::profile::{FrameId, Functions};
+use crate::config::{Cost, Map};
+use crate::error::Result;
+use crate::global;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table};
+use ratatui::widgets::{TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+
+/// Text of the help line.
+const HELP: &str =
+    "Tab table/tree  s sort  / search  n next match  Enter/Right expand  Left collapse  q quit";
+
+/// Number of lines moved by PageUp and PageDown.
+const PAGE: usize = 20;
+
+/// Represents the column the table of functions is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Immediate cost, the most expensive first.
+    SelfCost,
+    /// Cost including callees, the most expensive first.
+    Inclusive,
+    /// Number of calls, the most called first.
+    Calls,
+    /// Name in alphabetical order.
+    Name,
+}
+
+impl SortBy {
+    /// Returns the column following this one.
+    fn next(self) -> Self {
+        match self {
+            SortBy::SelfCost => SortBy::Inclusive,
+            SortBy::Inclusive => SortBy::Calls,
+            SortBy::Calls => SortBy::Name,
+            SortBy::Name => SortBy::SelfCost,
+        }
+    }
+
+    /// Returns the name of the column.
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::SelfCost => "self",
+            SortBy::Inclusive => "inclusive",
+            SortBy::Calls => "calls",
+            SortBy::Name => "name",
+        }
+    }
+}
+
+/// Represents a function in the table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionRow {
+    /// Name of the function.
+    pub name: String,
+    /// Immediate cost.
+    pub self_cost: Cost,
+    /// Cost including callees.
+    pub inclusive: Cost,
+    /// Number of calls.
+    pub calls: usize,
+}
+
+/// Represents calls of a callee from a caller.
+#[derive(Clone, Debug)]
+struct Callee {
+    id: FrameId,
+    cost: Cost,
+    calls: usize,
+    recursive: bool,
+}
+
+/// Represents a shown line of the call tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeLine {
+    /// Functions from the root to the function of the line.
+    pub path: Vec<FrameId>,
+    /// Name of the function.
+    pub name: String,
+    /// Cost of the calls including callees.
+    pub cost: Cost,
+    /// Number of the calls.
+    pub calls: usize,
+    /// The function has callees which can be shown.
+    pub expandable: bool,
+}
+
+/// Represents the state of the viewer.
+#[derive(Debug)]
+pub struct Viewer {
+    rows: Vec<FunctionRow>,
+    names: Map<FrameId, String>,
+    callees: Map<FrameId, Vec<Callee>>,
+    total_cost: Cost,
+    sort_by: SortBy,
+    search: String,
+    searching: bool,
+    tree: bool,
+    expanded: HashSet<Vec<FrameId>>,
+    table_state: TableState,
+    tree_state: ListState,
+    done: bool,
+}
+
+impl Viewer {
+    /// Creates the viewer of the functions, showing the table sorted by immediate costs
+    /// and the tree with callees of the root.
+    pub fn new(functions: &Functions, total_cost: Cost) -> Self {
+        let inclusive_costs = budget::inclusive_costs(functions, total_cost);
+        let mut calls = Map::<FrameId, usize>::new();
+        let mut callees = Map::<FrameId, Vec<Callee>>::new();
+        for (caller, f) in functions {
+            let mut by_callee = Map::<FrameId, Callee>::new();
+            for c in f.calls() {
+                *calls.entry(c.id()).or_insert(0) += c.count();
+                let callee = by_callee.entry(c.id()).or_insert(Callee {
+                    id: c.id(),
+                    cost: 0,
+                    calls: 0,
+                    recursive: false,
+                });
+                callee.cost += c.cost();
+                callee.calls += c.count();
+                callee.recursive |= c.is_recursive();
+            }
+            let mut by_callee = by_callee.into_values().collect::<Vec<_>>();
+            by_callee.sort_by(|a, b| b.cost.cmp(&a.cost).then_with(|| a.id.cmp(&b.id)));
+            callees.insert(*caller, by_callee);
+        }
+        let names = functions
+            .iter()
+            .map(|(id, f)| (*id, f.name()))
+            .collect::<Map<_, _>>();
+        let rows = functions
+            .iter()
+            .filter(|(id, _)| **id != FrameId::Root)
+            .map(|(id, f)| FunctionRow {
+                name: f.name(),
+                self_cost: f.cost(),
+                inclusive: inclusive_costs[id],
+                calls: calls.get(id).copied().unwrap_or(0),
+            })
+            .collect();
+
+        let mut viewer = Viewer {
+            rows,
+            names,
+            callees,
+            total_cost,
+            sort_by: SortBy::SelfCost,
+            search: String::new(),
+            searching: false,
+            tree: false,
+            expanded: HashSet::from([vec![FrameId::Root]]),
+            table_state: TableState::default().with_selected(Some(0)),
+            tree_state: ListState::default().with_selected(Some(0)),
+            done: false,
+        };
+        viewer.sort();
+        viewer
+    }
+
+    /// Returns the rows of the table matching the search, in order of the sorting.
+    pub fn rows(&self) -> Vec<&FunctionRow> {
+        self.rows
+            .iter()
+            .filter(|r| r.name.contains(self.search.as_str()))
+            .collect()
+    }
+
+    /// Returns the shown lines of the call tree, the root first.
+    /// Callees of recursive calls are not expanded.
+    pub fn tree_lines(&self) -> Vec<TreeLine> {
+        let mut lines = Vec::new();
+        let root = Callee {
+            id: FrameId::Root,
+            cost: self.total_cost,
+            calls: 1,
+            recursive: false,
+        };
+        self.add_tree_lines(&mut lines, Vec::new(), &root);
+        lines
+    }
+
+    /// Adds the line of the callee and, if expanded, the lines of its callees.
+    fn add_tree_lines(&self, lines: &mut Vec<TreeLine>, mut path: Vec<FrameId>, callee: &Callee) {
+        let expandable = !callee.recursive
+            && !path.contains(&callee.id)
+            && self.callees.get(&callee.id).is_some_and(|c| !c.is_empty());
+        path.push(callee.id);
+        let expanded = expandable && self.expanded.contains(&path);
+        lines.push(TreeLine {
+            path: path.clone(),
+            name: self.names[&callee.id].clone(),
+            cost: callee.cost,
+            calls: callee.calls,
+            expandable,
+        });
+        if expanded {
+            for c in &self.callees[&callee.id] {
+                self.add_tree_lines(lines, path.clone(), c);
+            }
+        }
+    }
+
+    /// Checks if the viewer is closed.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Handles the pressed key.
+    pub fn handle(&mut self, key: KeyCode) {
+        if self.searching {
+            match key {
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Enter => {
+                    self.searching = false;
+                    if self.tree {
+                        self.next_match();
+                    }
+                }
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                }
+                _ => {}
+            }
+            self.table_state.select(Some(0));
+            return;
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.done = true,
+            KeyCode::Tab => self.tree = !self.tree,
+            KeyCode::Char('/') => {
+                self.searching = true;
+                self.search.clear();
+            }
+            KeyCode::Char('n') if self.tree => self.next_match(),
+            KeyCode::Char('s') if !self.tree => {
+                self.sort_by = self.sort_by.next();
+                self.sort();
+                self.table_state.select(Some(0));
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-(PAGE as isize)),
+            KeyCode::PageDown => self.move_by(PAGE as isize),
+            KeyCode::Home => self.move_by(isize::MIN),
+            KeyCode::End => self.move_by(isize::MAX),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') if self.tree => {
+                if let Some(line) = self.selected_line() {
+                    if line.expandable && !self.expanded.remove(&line.path) {
+                        self.expanded.insert(line.path);
+                    }
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') if self.tree => self.collapse(),
+            _ => {}
+        }
+    }
+
+    /// Sorts the rows of the table by the column, then by names.
+    fn sort(&mut self) {
+        let sort_by = self.sort_by;
+        self.rows.sort_by(|a, b| {
+            match sort_by {
+                SortBy::SelfCost => b.self_cost.cmp(&a.self_cost),
+                SortBy::Inclusive => b.inclusive.cmp(&a.inclusive),
+                SortBy::Calls => b.calls.cmp(&a.calls),
+                SortBy::Name => std::cmp::Ordering::Equal,
+            }
+            .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+
+    /// Moves the selection in the table or the tree by the number of lines.
+    fn move_by(&mut self, delta: isize) {
+        let len = if self.tree {
+            self.tree_lines().len()
+        } else {
+            self.rows().len()
+        };
+        let state = if self.tree {
+            self.tree_state.selected_mut()
+        } else {
+            self.table_state.selected_mut()
+        };
+        let selected = state.unwrap_or(0).saturating_add_signed(delta);
+        *state = Some(selected.min(len.saturating_sub(1)));
+    }
+
+    /// Returns the selected line of the tree.
+    fn selected_line(&self) -> Option<TreeLine> {
+        let selected = self.tree_state.selected()?;
+        self.tree_lines().into_iter().nth(selected)
+    }
+
+    /// Collapses the selected line of the tree if expanded,
+    /// otherwise selects the line of its caller.
+    fn collapse(&mut self) {
+        let Some(line) = self.selected_line() else {
+            return;
+        };
+        if self.expanded.remove(&line.path) {
+            return;
+        }
+        let caller = &line.path[..line.path.len() - 1];
+        if let Some(i) = self.tree_lines().iter().position(|l| l.path == caller) {
+            self.tree_state.select(Some(i));
+        }
+    }
+
+    /// Selects the next shown line of the tree matching the search, wrapping around.
+    fn next_match(&mut self) {
+        if self.search.is_empty() {
+            return;
+        }
+        let lines = self.tree_lines();
+        let start = self.tree_state.selected().map_or(0, |i| i + 1);
+        let found = (0..lines.len())
+            .map(|i| (start + i) % lines.len())
+            .find(|i| lines[*i].name.contains(self.search.as_str()));
+        if found.is_some() {
+            self.tree_state.select(found);
+        }
+    }
+
+    /// Returns the share of the cost in the total cost as text.
+    fn percent(&self, cost: Cost) -> String {
+        format!(
+            "{:5.1}%",
+            100.0 * cost as f64 / self.total_cost.max(1) as f64
+        )
+    }
+
+    /// Draws the table or the tree over the status line.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        if self.tree {
+            let lines = self.tree_lines();
+            let items = lines
+                .iter()
+                .map(|l| {
+                    let marker = match (l.expandable, self.expanded.contains(&l.path)) {
+                        (false, _) => ' ',
+                        (true, false) => '+',
+                        (true, true) => '-',
+                    };
+                    ListItem::new(format!(
+                        "{:>14} {}  {:indent$}{} {} (calls={})",
+                        global::number(l.cost),
+                        self.percent(l.cost),
+                        "",
+                        marker,
+                        l.name,
+                        global::number(l.calls),
+                        indent = 2 * (l.path.len() - 1)
+                    ))
+                })
+                .collect::<Vec<_>>();
+            let title = format!("Call tree (total cost {})", global::number(self.total_cost));
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_style(highlight);
+            frame.render_stateful_widget(list, main, &mut self.tree_state);
+        } else {
+            let rows = self
+                .rows()
+                .into_iter()
+                .map(|r| {
+                    Row::new([
+                        global::number(r.self_cost),
+                        self.percent(r.self_cost),
+                        global::number(r.inclusive),
+                        self.percent(r.inclusive),
+                        global::number(r.calls),
+                        r.name.clone(),
+                    ])
+                })
+                .collect::<Vec<_>>();
+            let widths = [
+                Constraint::Length(14),
+                Constraint::Length(6),
+                Constraint::Length(14),
+                Constraint::Length(6),
+                Constraint::Length(10),
+                Constraint::Min(10),
+            ];
+            let header = Row::new(["Self", "", "Inclusive", "", "Calls", "Function"])
+                .style(Style::default().add_modifier(Modifier::BOLD));
+            let title = format!(
+                "Functions by {} (total cost {})",
+                self.sort_by.name(),
+                global::number(self.total_cost)
+            );
+            let table = Table::new(rows, widths)
+                .header(header)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .row_highlight_style(highlight);
+            frame.render_stateful_widget(table, main, &mut self.table_state);
+        }
+
+        let status_text = if self.searching {
+            format!("/{}", self.search)
+        } else if !self.search.is_empty() {
+            format!("search: {}  {}", self.search, HELP)
+        } else {
+            HELP.into()
+        };
+        frame.render_widget(
+            Paragraph::new(status_text).wrap(Wrap { trim: true }),
+            status,
+        );
+    }
+
+    /// Runs the viewer in the terminal until closed.
+    pub fn run(mut self) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal);
+        ratatui::restore();
+        result
+    }
+
+    /// Draws the viewer and handles pressed keys until closed.
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.done {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle(key.code);
+                }
+            }
+        }
+        Ok(())
+    }
+}
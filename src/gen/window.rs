@@ -0,0 +1,79 @@
+//! bpf-profile-generate window module.
+//! Implements slicing the profiled part of the trace: instructions after the skipped
+//! ones up to the maximum number, within the range of numbers of lines of the trace.
+//! Instructions before the window are discarded, the rest of the trace after it is not read.
+
+use crate::error::{Error, Result};
+use std::cmp::Ordering;
+
+/// Represents the window of the trace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Window {
+    skip_instructions: usize,
+    max_instructions: Option<usize>,
+    first_line: usize,
+    last_line: Option<usize>,
+}
+
+impl Window {
+    /// Creates the window skipping the number of instructions and profiling
+    /// at most the maximum number of instructions after them, within the range
+    /// of lines `start:end`, e.g. `1000:2000`, either of them optional.
+    /// Returns None if the whole trace is profiled.
+    pub fn new(
+        skip_instructions: usize,
+        max_instructions: Option<usize>,
+        range: Option<&str>,
+    ) -> Result<Option<Self>> {
+        let (first_line, last_line) = match range {
+            Some(range) => parse_range(range)?,
+            None => (1, None),
+        };
+        let window = Window {
+            skip_instructions,
+            max_instructions,
+            first_line,
+            last_line,
+        };
+        let whole = skip_instructions == 0
+            && max_instructions.is_none()
+            && first_line <= 1
+            && last_line.is_none();
+        Ok((!whole).then_some(window))
+    }
+
+    /// Returns the position of the window relative to the instruction of the number
+    /// at the line of the trace, both counting from one: `Less` if the instruction
+    /// precedes the window, `Greater` if it follows the window.
+    pub fn locate(&self, instruction: usize, line: usize) -> Ordering {
+        let last_instruction = self
+            .max_instructions
+            .map_or(usize::MAX, |max| self.skip_instructions.saturating_add(max));
+        if instruction > last_instruction || self.last_line.is_some_and(|last| line > last) {
+            Ordering::Greater
+        } else if instruction <= self.skip_instructions || line < self.first_line {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+/// Parses the range of numbers of lines `start:end`, both inclusive.
+fn parse_range(range: &str) -> Result<(usize, Option<usize>)> {
+    let error = || Error::LineRange(range.into());
+    let (start, end) = range.split_once(':').ok_or_else(error)?;
+    let bound = |text: &str| {
+        let text = text.trim();
+        match text {
+            "" => Ok(None),
+            _ => text.parse::<usize>().map(Some).map_err(|_| error()),
+        }
+    };
+    let first_line = bound(start)?.unwrap_or(1);
+    let last_line = bound(end)?;
+    if first_line == 0 || last_line.is_some_and(|last| last < first_line) {
+        return Err(error());
+    }
+    Ok((first_line, last_line))
+}
@@ -1,16 +1,134 @@
 //! bpf-profile global module.
 
+use crate::config::{DEFAULT_BUFFER_SIZE, PROGRESS_FORMATS};
 use lazy_static::lazy_static;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 
 lazy_static! {
     static ref VERBOSE: AtomicBool = AtomicBool::default();
+    static ref NUMBER_FORMAT: AtomicU8 = AtomicU8::default();
+    static ref PROGRESS_FORMAT: AtomicU8 = AtomicU8::default();
+    static ref DEMANGLE: AtomicBool = AtomicBool::new(true);
+    static ref INTERRUPTED: AtomicBool = AtomicBool::default();
+    static ref READ_BUFFER: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
+    static ref WRITE_BUFFER: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
 }
 
+/// Enables or disables verbose messages.
 pub fn set_verbose(v: bool) {
     VERBOSE.store(v, Ordering::Relaxed);
 }
 
+/// Returns true if verbose messages are enabled.
 pub fn verbose() -> bool {
     VERBOSE.load(Ordering::Relaxed)
 }
+
+/// Enables or disables demangling of names of functions.
+pub fn set_demangle(v: bool) {
+    DEMANGLE.store(v, Ordering::Relaxed);
+}
+
+/// Returns true if names of functions are demangled.
+pub fn demangle() -> bool {
+    DEMANGLE.load(Ordering::Relaxed)
+}
+
+/// Sets the sizes in bytes of buffers of reading and writing files.
+pub fn set_buffer_sizes(read: usize, write: usize) {
+    READ_BUFFER.store(read, Ordering::Relaxed);
+    WRITE_BUFFER.store(write, Ordering::Relaxed);
+}
+
+/// Returns the size in bytes of buffers of reading files.
+pub fn read_buffer() -> usize {
+    READ_BUFFER.load(Ordering::Relaxed)
+}
+
+/// Returns the size in bytes of buffers of writing files.
+pub fn write_buffer() -> usize {
+    WRITE_BUFFER.load(Ordering::Relaxed)
+}
+
+/// Stops parsing of traces at the next check, so the profile gathered so far is written.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if parsing of traces is interrupted, e.g. by Ctrl-C.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Sets the format of reports of progress of parsing traces, "text", "json" or "bar";
+/// none disables the reports.
+pub fn set_progress_format(format: Option<&str>) {
+    let format = format
+        .and_then(|f| PROGRESS_FORMATS.iter().position(|p| *p == f))
+        .map_or(0, |i| i as u8 + 1);
+    PROGRESS_FORMAT.store(format, Ordering::Relaxed);
+}
+
+/// Returns the format of reports of progress of parsing traces, if enabled.
+pub fn progress_format() -> Option<&'static str> {
+    match PROGRESS_FORMAT.load(Ordering::Relaxed) {
+        0 => None,
+        i => PROGRESS_FORMATS.get(i as usize - 1).copied(),
+    }
+}
+
+const PLAIN: u8 = 0;
+const SEPARATED: u8 = 1;
+const SHORT: u8 = 2;
+
+/// Sets the format of numbers in human-readable reports:
+/// "plain", "separated" (thousands separators) or "short" (k/M suffixes).
+pub fn set_number_format(format: &str) {
+    let format = match format {
+        "separated" => SEPARATED,
+        "short" => SHORT,
+        _ => PLAIN,
+    };
+    NUMBER_FORMAT.store(format, Ordering::Relaxed);
+}
+
+/// Formats the number for human-readable reports; machine formats keep raw numbers.
+pub fn number(n: usize) -> String {
+    match NUMBER_FORMAT.load(Ordering::Relaxed) {
+        SEPARATED => separated(n),
+        SHORT => short(n),
+        _ => n.to_string(),
+    }
+}
+
+/// Formats the number with thousands separators, e.g. 12,345,678.
+pub fn separated(n: usize) -> String {
+    let digits = n.to_string();
+    let mut s = String::with_capacity(digits.len() * 4 / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            s.push(',');
+        }
+        s.push(digit);
+    }
+    s
+}
+
+/// Formats the number with a k/M/G suffix and one decimal, e.g. 12.3M.
+pub fn short(n: usize) -> String {
+    let mut value = n as f64;
+    let mut suffix = "";
+    for next in ["k", "M", "G"] {
+        // Values rounding to 1000.0 take the next suffix
+        if value < 999.95 {
+            break;
+        }
+        value /= 1000.0;
+        suffix = next;
+    }
+    if suffix.is_empty() {
+        n.to_string()
+    } else {
+        format!("{:.1}{}", value, suffix)
+    }
+}
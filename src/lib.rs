@@ -0,0 +1,35 @@
+//! bpf-profile library.
+//!
+//! Converts traces of the BPF VM into profiles for tools like `callgrind_annotate`
+//! or `qcachegrind`; the `bpf-profile` program is built on top of it.
+
+#![deny(warnings)]
+#![deny(unsafe_code)]
+#![deny(missing_docs)]
+
+pub mod anonymize;
+pub mod bench;
+mod bpf;
+pub mod calls;
+pub mod compact;
+pub mod config;
+pub mod cost;
+#[cfg(unix)]
+pub mod daemon;
+pub mod diagnostics;
+pub mod error;
+mod filebuf;
+pub mod gen;
+pub mod global;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod remote;
+pub mod resolver;
+pub mod syscalls;
+pub mod trace;
+#[cfg(feature = "run")]
+pub mod vm;
+pub mod watch;
+
+#[cfg(test)]
+mod tests;
@@ -17,67 +17,720 @@
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 
-mod bpf;
-mod calls;
 mod cli;
-mod config;
-mod error;
-mod filebuf;
-mod gen;
-mod global;
-mod resolver;
-mod trace;
 
-#[cfg(test)]
-mod tests;
+#[cfg(unix)]
+use bpf_profile::daemon;
+#[cfg(feature = "plugins")]
+use bpf_profile::plugin;
+use bpf_profile::{
+    anonymize, bench, calls, compact, config, error, gen, global, remote, resolver, syscalls, watch,
+};
 
 fn main() {
-    init_logger();
-    if let Err(err) = execute(cli::application()) {
+    let app = cli::application();
+    if let Err(err) = init_logger(&app).and_then(|_| execute(app)) {
         eprintln!("Error: {:#}", err);
         std::process::exit(config::FAILURE);
     }
 }
 
-/// Initializes the logger.
-fn init_logger() {
-    if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "info");
+/// Initializes the logger by the format of messages and the filters by modules
+/// taken from the command line, or RUST_LOG unless the level of verbosity is given.
+fn init_logger(app: &cli::Application) -> Result<()> {
+    use std::io::IsTerminal;
+    use tracing_subscriber::EnvFilter;
+
+    let filter = match (&app.log_filter, app.quiet, app.verbose) {
+        (Some(directives), _, _) => EnvFilter::try_new(directives),
+        (None, true, _) => EnvFilter::try_new("error"),
+        (None, false, 2) => EnvFilter::try_new("debug"),
+        (None, false, 3..) => EnvFilter::try_new("trace"),
+        (None, false, _) => {
+            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))
+        }
     }
-    tracing_subscriber::fmt::init();
+    .map_err(|e| error::Error::LogFilter(e.to_string()))?;
+
+    // Logs go to stderr not to mix with profiles written to stdout
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    if app.log_format == "json" {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+    Ok(())
 }
 
-use crate::error::Result;
+use error::Result;
+use std::path::{Path, PathBuf};
 
 /// Dispatches CLI commands.
 fn execute(app: cli::Application) -> Result<()> {
-    global::set_verbose(app.verbose);
+    global::set_verbose(app.verbose > 0);
+    global::set_number_format(&app.number_format);
+    global::set_progress_format(
+        app.progress_format
+            .as_deref()
+            .or_else(|| default_progress_format(&app)),
+    );
+    global::set_demangle(!app.no_demangle);
+    #[cfg(feature = "plugins")]
+    if let Some(plugins_dir) = &app.plugins_dir {
+        plugin::load_dir(plugins_dir)?;
+    }
+    let preset = config::read_preset(&app.config, app.preset.as_deref())?;
+    if preset.dump_function_header.is_some() || preset.dump_instruction.is_some() {
+        resolver::set_dump_patterns(resolver::DumpPatterns::new(
+            preset
+                .dump_function_header
+                .as_deref()
+                .unwrap_or(config::DEFAULT_DUMP_FUNCTION_HEADER),
+            preset
+                .dump_instruction
+                .as_deref()
+                .unwrap_or(config::DEFAULT_DUMP_INSTRUCTION),
+        )?);
+    }
+    global::set_buffer_sizes(
+        buffer_size("--read-buffer", app.read_buffer.or(preset.read_buffer))?,
+        buffer_size("--write-buffer", app.write_buffer.or(preset.write_buffer))?,
+    );
+    if let Some(path) = app.syscalls.or(preset.syscalls) {
+        syscalls::read_file(&path)?;
+    }
+    if let Some(template) = app.unresolved_name.or(preset.unresolved_name) {
+        resolver::set_unresolved_name(resolver::UnresolvedName::new(&template)?);
+    }
 
     match app.cmd {
-        cli::Command::Calls { trace, dump, tab } => {
-            calls::run(
-                &trace,
-                dump.as_ref().map(|p| p.as_ref()), // Option<T> -> Option<&T>,
-                tab,
-            )?;
+        cli::Command::Calls {
+            trace,
+            dump,
+            tab,
+            lines,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            calls::run(trace.path(), dump.as_ref().map(|i| i.path()), tab, lines)?;
         }
 
         cli::Command::Generate {
-            trace,
+            traces,
+            asm,
+            dump,
+            dump_manifest,
+            symbols,
+            pc_offset,
+            format,
+            output,
+            auto_name,
+            output_dir,
+            input_dir,
+            pattern,
+            metrics,
+            badge,
+            stats,
+            lcov,
+            heatmap,
+            heatmap_window,
+            events,
+            bookmarks,
+            save_intermediate,
+            calls_only,
+            root_name,
+            explicit_root,
+            append,
+            cluster_version,
+            cost_model,
+            #[cfg(feature = "scripting")]
+            cost_script,
+            syscall_sizes,
+            flatten_nested,
+            lenient,
+            error_limit,
+            source_lines,
+            lines,
+            counters,
+            syscall_cost,
+            drop_calls_below,
+            max_call_samples,
+            ignore_before,
+            only_function,
+            skip_instructions,
+            max_instructions,
+            range,
+            include,
+            exclude,
+            aliases,
+            cache_dir,
+            metadata,
+            split_invocations,
+            jobs,
+            threshold,
+            max_output_size,
+            compress_output,
+            sort,
+            granularity,
+            watch,
+            watch_interval,
+            upload,
+        } => {
+            let (asm, metrics) = if calls_only {
+                (None, None)
+            } else {
+                (asm.or(preset.asm), metrics.or(preset.metrics))
+            };
+            let format = format
+                .or(preset.format)
+                .unwrap_or_else(|| config::DEFAULT_FORMAT.into());
+            let traces = if let Some(input_dir) = &input_dir {
+                let traces = gen::batch::discover(input_dir, &pattern)?;
+                if traces.is_empty() {
+                    return Err(error::Error::NoTraces(input_dir.clone(), pattern));
+                }
+                traces
+            } else if traces.is_empty() {
+                vec![config::STDIN.into()]
+            } else {
+                traces
+            };
+            let inputs = traces
+                .iter()
+                .map(|t| remote::fetch(t))
+                .collect::<Result<Vec<_>>>()?;
+            let paths = inputs.iter().map(|i| i.path()).collect::<Vec<_>>();
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            let outputs = output_names(&traces, &format, auto_name, output_dir.as_deref())?;
+            let output = match outputs.as_slice() {
+                [named] => Some(named.clone()),
+                _ => output,
+            };
+            let options = gen::Options {
+                format,
+                output,
+                asm,
+                metrics,
+                badge,
+                stats,
+                lcov,
+                heatmap,
+                heatmap_window,
+                events,
+                bookmarks,
+                save_intermediate,
+                calls_only,
+                root_name: root_name.or(preset.root_name),
+                explicit_root,
+                append,
+                cluster_version: cluster_version.or(preset.cluster_version),
+                cost_model: cost_model.or(preset.cost_model),
+                #[cfg(feature = "scripting")]
+                cost_script,
+                syscall_sizes,
+                flatten_nested,
+                lenient,
+                error_limit,
+                source_lines,
+                call_lines: lines,
+                counters,
+                syscall_cost,
+                drop_calls_below,
+                max_call_samples,
+                ignore_before,
+                only_function,
+                skip_instructions,
+                max_instructions,
+                range,
+                include: include.or(preset.include),
+                exclude: exclude.or(preset.exclude),
+                aliases: aliases.or(preset.aliases),
+                dump_manifest,
+                symbols: symbols.or(preset.symbols),
+                pc_offset,
+                metadata,
+                cache_dir,
+                split_invocations,
+                jobs: jobs.or(preset.jobs).unwrap_or(1),
+                threshold,
+                max_output_size,
+                compress_output,
+                sort,
+                granularity,
+                average: false,
+            };
+            let dump_path = dump.as_ref().map(|i| i.path());
+            match paths.as_slice() {
+                [trace_path] if watch => {
+                    gen::watch(trace_path, dump_path, &options, watch_interval)?
+                }
+                // Traces of the input directory are converted as a batch with the summary
+                [trace_path] if input_dir.is_none() => {
+                    catch_interrupts();
+                    gen::run(trace_path, dump_path, &options)?
+                }
+                _ if watch => return Err(error::Error::SingleTrace("--watch".into())),
+                _ if !outputs.is_empty() => {
+                    catch_interrupts();
+                    let batch = paths
+                        .iter()
+                        .copied()
+                        .zip(outputs.clone())
+                        .collect::<Vec<_>>();
+                    gen::run_batch(&batch, dump_path, &options)?
+                }
+                _ => {
+                    catch_interrupts();
+                    gen::run_parts(&paths, dump_path, &options)?
+                }
+            }
+            if global::interrupted() {
+                return Err(error::Error::Interrupted);
+            }
+            if let Some(url) = upload {
+                // Profiles of a batch are uploaded each with its own trace
+                let uploads = match &options.output {
+                    Some(output) => vec![(output, traces.as_slice())],
+                    None => outputs.iter().zip(traces.chunks(1)).collect(),
+                };
+                for (output, traces) in uploads {
+                    let trace_names = traces
+                        .iter()
+                        .map(|t| t.file_name().unwrap_or_default().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let metadata = [
+                        ("version", env!("CARGO_PKG_VERSION")),
+                        ("format", &options.format),
+                        ("trace", &trace_names),
+                    ];
+                    remote::upload(&url, output, &metadata)?;
+                }
+            }
+        }
+
+        cli::Command::Merge {
+            traces,
             asm,
             dump,
+            output,
+            average,
+            metadata,
+        } => {
+            let inputs = traces
+                .iter()
+                .map(|t| remote::fetch(t))
+                .collect::<Result<Vec<_>>>()?;
+            let paths = inputs.iter().map(|i| i.path()).collect::<Vec<_>>();
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            let options = gen::Options {
+                format: preset
+                    .format
+                    .unwrap_or_else(|| config::DEFAULT_FORMAT.into()),
+                output,
+                asm: asm.or(preset.asm),
+                aliases: preset.aliases,
+                symbols: preset.symbols,
+                metadata,
+                average,
+                ..gen::Options::default()
+            };
+            gen::merge(&paths, dump.as_ref().map(|i| i.path()), &options)?;
+        }
+
+        cli::Command::Render {
+            intermediate,
+            format,
+            output,
+            root_name,
+            explicit_root,
+            threshold,
+            max_output_size,
+            compress_output,
+            sort,
+        } => {
+            let intermediate = remote::fetch(&intermediate)?;
+            let options = gen::Options {
+                format: format
+                    .or(preset.format)
+                    .unwrap_or_else(|| config::DEFAULT_FORMAT.into()),
+                output,
+                root_name: root_name.or(preset.root_name),
+                explicit_root,
+                threshold,
+                max_output_size,
+                compress_output,
+                sort,
+                ..gen::Options::default()
+            };
+            gen::render(intermediate.path(), &options)?;
+        }
+
+        cli::Command::Diff {
+            old,
+            new,
+            dump,
+            old_dump,
+            table,
+            per_call,
+            output,
+        } => {
+            let old = remote::fetch(&old)?;
+            let new = remote::fetch(&new)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            let old_dump = match old_dump {
+                Some(old_dump) => Some(remote::fetch(&old_dump)?),
+                None => None,
+            };
+            let dump_path = dump.as_ref().map(|i| i.path());
+            gen::diff(
+                old.path(),
+                old_dump.as_ref().map(|i| i.path()).or(dump_path),
+                new.path(),
+                dump_path,
+                output.as_deref(),
+                table.as_deref(),
+                per_call,
+            )?;
+        }
+
+        cli::Command::DumpDiff { old, new, output } => {
+            let old = remote::fetch(&old)?;
+            let new = remote::fetch(&new)?;
+            gen::dump_diff(old.path(), new.path(), output.as_deref())?;
+        }
+
+        cli::Command::Summary {
+            trace,
+            dump,
+            top,
+            by,
+            csv,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            let by = gen::query::By::from_name(&by).expect("Unsupported kind of cost");
+            gen::summary(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                top,
+                by,
+                csv,
+                output.as_deref(),
+            )?;
+        }
+
+        cli::Command::Instructions {
+            trace,
+            dump,
+            by_function,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::instructions(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                by_function,
+                output.as_deref(),
+            )?;
+        }
+
+        cli::Command::Annotate {
+            trace,
+            dump,
+            function,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::annotate(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                function.as_deref(),
+                output.as_deref(),
+            )?;
+        }
+
+        cli::Command::Show {
+            trace,
+            bookmarks,
+            function,
+            occurrence,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            gen::show(
+                trace.path(),
+                &bookmarks,
+                &function,
+                occurrence,
+                output.as_deref(),
+            )?;
+        }
+
+        cli::Command::Query { trace, dump, expr } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::query(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                expr.as_deref(),
+            )?;
+        }
+
+        cli::Command::Tree {
+            trace,
+            dump,
+            root,
+            depth,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::tree(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                root.as_deref(),
+                depth,
+                output.as_deref(),
+            )?;
+        }
+
+        #[cfg(feature = "run")]
+        cli::Command::Run {
+            program,
+            input,
+            compute_limit,
             format,
             output,
+            root_name,
+        } => {
+            let program = remote::fetch(&program)?;
+            let input = remote::fetch_opt(input.as_deref())?;
+            let options = gen::Options {
+                format: format
+                    .or(preset.format)
+                    .unwrap_or_else(|| config::DEFAULT_FORMAT.into()),
+                output,
+                root_name: root_name.or(preset.root_name),
+                ..gen::Options::default()
+            };
+            gen::run_program(
+                program.path(),
+                input.as_ref().map(|i| i.path()),
+                compute_limit,
+                &options,
+            )?;
+        }
+
+        #[cfg(feature = "tui")]
+        cli::Command::View { trace, dump } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::view(trace.path(), dump.as_ref().map(|i| i.path()))?;
+        }
+
+        cli::Command::Check {
+            trace,
+            dump,
+            baseline,
+            max_regression,
+            context,
+            json,
+            output,
+            notify_url,
         } => {
-            gen::run(
-                &trace,
-                asm.as_ref().map(|p| p.as_ref()), // Option<T> -> Option<&T>
-                dump.as_ref().map(|p| p.as_ref()), // Option<T> -> Option<&T>
+            let trace = remote::fetch(&trace)?;
+            match baseline {
+                Some(baseline) => {
+                    let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+                    gen::check_regression(
+                        trace.path(),
+                        dump.as_ref().map(|i| i.path()),
+                        &baseline,
+                        &max_regression,
+                        json,
+                        output.as_deref(),
+                        notify_url.as_deref(),
+                    )?;
+                }
+                None => gen::check(trace.path(), context, json, output.as_deref())?,
+            }
+        }
+
+        cli::Command::Lint {
+            trace,
+            context,
+            json,
+            output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            gen::lint(trace.path(), context, json, output.as_deref())?;
+        }
+
+        cli::Command::CheckBudget {
+            trace,
+            dump,
+            budgets,
+            sarif,
+            junit,
+            notify_url,
+        } => {
+            let budgets = match budgets {
+                Some(path) => gen::budget::read(&path)?,
+                None if !preset.budget.is_empty() => preset.budget,
+                None => gen::budget::read(Path::new(config::DEFAULT_BUDGETS))?,
+            };
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            gen::check_budget(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                &budgets,
+                sarif.as_deref(),
+                junit.as_deref(),
+                notify_url.as_deref(),
+            )?;
+        }
+
+        cli::Command::WatchDir {
+            input_dir,
+            dump,
+            format,
+            output_dir,
+            interval,
+        } => {
+            let format = format
+                .or(preset.format)
+                .unwrap_or_else(|| config::DEFAULT_FORMAT.into());
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            watch::run(
+                &input_dir,
+                dump.as_ref().map(|i| i.path()),
                 &format,
-                output.as_ref().map(|p| p.as_ref()), // Option<T> -> Option<&T>
+                &output_dir,
+                interval,
             )?;
         }
+
+        #[cfg(unix)]
+        cli::Command::Daemon { socket, dump_dir } => {
+            let options = gen::Options {
+                root_name: preset.root_name,
+                cluster_version: preset.cluster_version,
+                cost_model: preset.cost_model,
+                aliases: preset.aliases,
+                symbols: preset.symbols,
+                ..gen::Options::default()
+            };
+            daemon::run(&socket, dump_dir.as_deref(), &options)?;
+        }
+
+        cli::Command::Bench {
+            trace,
+            dump,
+            iterations,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.or(preset.dump).as_deref())?;
+            bench::run(trace.path(), dump.as_ref().map(|i| i.path()), iterations)?;
+        }
+
+        cli::Command::Anonymize {
+            trace,
+            dump,
+            output,
+            dump_output,
+        } => {
+            let trace = remote::fetch(&trace)?;
+            let dump = remote::fetch_opt(dump.as_deref())?;
+            anonymize::run(
+                trace.path(),
+                dump.as_ref().map(|i| i.path()),
+                &output,
+                dump_output.as_deref(),
+            )?;
+        }
+
+        cli::Command::Compact { trace, output } => {
+            let trace = remote::fetch(&trace)?;
+            compact::compact(trace.path(), &output)?;
+        }
+
+        cli::Command::Expand { input, output } => {
+            let input = remote::fetch(&input)?;
+            compact::expand(input.path(), &output)?;
+        }
+
+        cli::Command::Completions { shell } => {
+            cli::write_completions(shell);
+        }
     }
 
     Ok(())
 }
+
+/// Returns the size of buffers given by the option or the default one.
+fn buffer_size(option: &str, size: Option<usize>) -> Result<usize> {
+    match size {
+        Some(size) if size < config::MIN_BUFFER_SIZE => Err(error::Error::BufferSize(
+            option.into(),
+            config::MIN_BUFFER_SIZE,
+        )),
+        size => Ok(size.unwrap_or(config::DEFAULT_BUFFER_SIZE)),
+    }
+}
+
+/// Returns the progress bar if the standard error is a terminal and not quiet.
+fn default_progress_format(app: &cli::Application) -> Option<&'static str> {
+    use std::io::IsTerminal;
+
+    (cfg!(feature = "progress-bar") && !app.quiet && std::io::stderr().is_terminal())
+        .then_some("bar")
+}
+
+/// Makes the first Ctrl-C stop parsing traces, so the profile gathered so far is
+/// written before the command fails, and the second one exit at once.
+fn catch_interrupts() {
+    let result = ctrlc::set_handler(|| {
+        if global::interrupted() {
+            std::process::exit(config::FAILURE);
+        }
+        tracing::warn!("Interrupted, writing the profile gathered so far (Ctrl-C again to exit)");
+        global::interrupt();
+    });
+    if let Err(e) = result {
+        tracing::warn!("Cannot handle Ctrl-C: {}", e);
+    }
+}
+
+/// Returns paths of the profiles named after the traces in the directory if given,
+/// otherwise in the current directory, or none if the profiles are not named so.
+fn output_names(
+    traces: &[PathBuf],
+    format: &str,
+    auto_name: bool,
+    output_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
+    if !auto_name && output_dir.is_none() {
+        return Ok(Vec::new());
+    }
+    if let Some(output_dir) = output_dir {
+        std::fs::create_dir_all(output_dir)?;
+    }
+    traces
+        .iter()
+        .map(|trace| {
+            let name = gen::output_name(trace, format)?;
+            Ok(match output_dir {
+                Some(output_dir) => output_dir.join(name),
+                None => name,
+            })
+        })
+        .collect()
+}
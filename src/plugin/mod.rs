@@ -0,0 +1,167 @@
+//! bpf-profile plugin module.
+//! Loads dynamic libraries providing output formats and symbol sources.
+//!
+//! A plugin is a dynamic library (cdylib) which exports C functions:
+//! - `const char *bpf_profile_plugin_kind(void)` returns "format" or "resolver";
+//! - `const char *bpf_profile_plugin_name(void)` returns name of the plugin,
+//!   which is the name of the format for format plugins;
+//! - `int32_t bpf_profile_format_write(const char *callgrind, const char *output_path)`
+//!   (format plugins) converts the profile given in the callgrind format and writes it
+//!   to the output path (standard output if the path is empty), returns 0 on success;
+//! - `const char *bpf_profile_resolve(uint64_t address, uint64_t first_pc)`
+//!   (resolver plugins) returns name of the function or NULL if unknown;
+//!   the string must stay valid until the next call.
+
+#![allow(unsafe_code)]
+
+use crate::config::{Address, ProgramCounter};
+use crate::error::{Error, Result};
+use crate::global;
+use lazy_static::lazy_static;
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, CStr, CString};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type WriteFn = unsafe extern "C" fn(*const c_char, *const c_char) -> i32;
+type ResolveFn = unsafe extern "C" fn(u64, u64) -> *const c_char;
+
+/// Represents a loaded plugin.
+struct Plugin {
+    kind: String,
+    name: String,
+    library: Library,
+}
+
+lazy_static! {
+    static ref PLUGINS: Mutex<Vec<Plugin>> = Mutex::new(Vec::new());
+}
+
+/// Loads all dynamic libraries of the directory as plugins.
+pub fn load_dir(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(Error::NotDirectory(dir.into()));
+    }
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let is_library = path
+            .extension()
+            .is_some_and(|e| e == std::env::consts::DLL_EXTENSION);
+        if !is_library {
+            continue;
+        }
+        let plugin = load(&path)?;
+        if global::verbose() {
+            tracing::info!(
+                "Loaded {} plugin '{}' from {:?}",
+                plugin.kind,
+                plugin.name,
+                path
+            );
+        }
+        PLUGINS.lock().expect("Plugins lock poisoned").push(plugin);
+    }
+
+    Ok(())
+}
+
+/// Loads one plugin and checks it exports the functions of its kind.
+fn load(path: &Path) -> Result<Plugin> {
+    let error = |message: String| Error::Plugin(path.display().to_string(), message);
+
+    // SAFETY: the library is trusted to be a plugin of bpf-profile
+    // which exports the functions with the documented signatures.
+    unsafe {
+        let library = Library::new(path).map_err(|e| error(e.to_string()))?;
+        let kind = call_name(&library, b"bpf_profile_plugin_kind").map_err(error)?;
+        let name = call_name(&library, b"bpf_profile_plugin_name").map_err(error)?;
+        let required: &[u8] = match kind.as_str() {
+            "format" => b"bpf_profile_format_write",
+            "resolver" => b"bpf_profile_resolve",
+            _ => return Err(error(format!("unsupported kind of plugin '{}'", kind))),
+        };
+        library
+            .get::<unsafe extern "C" fn()>(required)
+            .map_err(|e| error(e.to_string()))?;
+        Ok(Plugin {
+            kind,
+            name,
+            library,
+        })
+    }
+}
+
+/// Calls a function of the library which returns a static string.
+unsafe fn call_name(library: &Library, symbol: &[u8]) -> std::result::Result<String, String> {
+    let f: Symbol<NameFn> = library.get(symbol).map_err(|e| e.to_string())?;
+    let s = f();
+    if s.is_null() {
+        return Err(format!("{} returned NULL", String::from_utf8_lossy(symbol)));
+    }
+    Ok(CStr::from_ptr(s).to_string_lossy().into_owned())
+}
+
+/// Checks if a format plugin of the name is loaded.
+pub fn has_format(format: &str) -> bool {
+    let plugins = PLUGINS.lock().expect("Plugins lock poisoned");
+    plugins
+        .iter()
+        .any(|p| p.kind == "format" && p.name == format)
+}
+
+/// Converts the callgrind profile by the format plugin and writes it to the output path
+/// (standard output if none).
+pub fn write_format(format: &str, callgrind: Vec<u8>, output_path: Option<&Path>) -> Result<()> {
+    let plugins = PLUGINS.lock().expect("Plugins lock poisoned");
+    let plugin = plugins
+        .iter()
+        .find(|p| p.kind == "format" && p.name == format)
+        .ok_or_else(|| Error::UnknownFormat(format.into()))?;
+    let error = |message: String| Error::Plugin(format.into(), message);
+
+    let callgrind = CString::new(callgrind).map_err(|e| error(e.to_string()))?;
+    let output_path = output_path
+        .map(|p| p.to_str().ok_or_else(|| Error::Filename(p.into())))
+        .transpose()?
+        .unwrap_or_default();
+    let output_path = CString::new(output_path).map_err(|e| error(e.to_string()))?;
+
+    // SAFETY: the symbol was checked on loading; the strings outlive the call.
+    let status = unsafe {
+        let f: Symbol<WriteFn> = plugin
+            .library
+            .get(b"bpf_profile_format_write")
+            .map_err(|e| error(e.to_string()))?;
+        f(callgrind.as_ptr(), output_path.as_ptr())
+    };
+    if status != 0 {
+        return Err(error(format!("failed with status {}", status)));
+    }
+    Ok(())
+}
+
+/// Asks the resolver plugins for the name of a function in order of loading.
+pub fn resolve(address: Address, first_pc: ProgramCounter) -> Option<String> {
+    let plugins = PLUGINS.lock().expect("Plugins lock poisoned");
+    plugins
+        .iter()
+        .filter(|p| p.kind == "resolver")
+        .find_map(|p| {
+            // SAFETY: the symbol was checked on loading; the returned string
+            // is copied before the next call.
+            unsafe {
+                let f: Symbol<ResolveFn> = p.library.get(b"bpf_profile_resolve").ok()?;
+                let s = f(address, first_pc as u64);
+                if s.is_null() {
+                    return None;
+                }
+                Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+            }
+        })
+}
@@ -0,0 +1,185 @@
+//! bpf-profile remote files module.
+//! Downloads input files given as URLs into temporary local files
+//! and uploads generated files to remote storage or notifies webhooks.
+
+use crate::error::{Error, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Represents an input file, either local or a downloaded copy of remote one.
+#[derive(Debug)]
+pub struct Input {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Input {
+    /// Returns path of the local file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Downloads the input if it is given by URL, otherwise uses the local file.
+pub fn fetch(path: &Path) -> Result<Input> {
+    let url = match path.to_str() {
+        Some(s) if is_url(s) => s,
+        _ => {
+            return Ok(Input {
+                path: path.into(),
+                temporary: false,
+            })
+        }
+    };
+
+    let local = temporary_path(url);
+    download(url, &local)?;
+    Ok(Input {
+        path: local,
+        temporary: true,
+    })
+}
+
+/// Downloads the input if it is given by URL (optional variant).
+pub fn fetch_opt(path: Option<&Path>) -> Result<Option<Input>> {
+    path.map(fetch).transpose()
+}
+
+const SCHEMES: &[&str] = &["http://", "https://", "s3://"];
+
+/// Checks if the string looks like a URL of supported scheme.
+fn is_url(s: &str) -> bool {
+    SCHEMES.iter().any(|scheme| s.starts_with(scheme))
+}
+
+/// Generates unique name of a temporary file for the URL.
+fn temporary_path(url: &str) -> PathBuf {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let name = url.rsplit('/').next().unwrap_or_default();
+    std::env::temp_dir().join(format!(
+        "bpf-profile-{}-{:x}-{}",
+        std::process::id(),
+        hasher.finish(),
+        name
+    ))
+}
+
+#[cfg(feature = "remote")]
+fn download(url: &str, local: &Path) -> Result<()> {
+    use crate::{filebuf, global};
+    use std::io;
+
+    let url = http_url(url)?;
+    if global::verbose() {
+        tracing::info!("Downloading {}...", &url);
+    }
+    let response = ureq::get(&url)
+        .call()
+        .map_err(|e| Error::Download(url.clone(), e.to_string()))?;
+    let mut output = filebuf::open_w(local)?;
+    io::copy(&mut response.into_reader(), &mut output)
+        .map_err(|e| Error::Download(url.clone(), e.to_string()))?;
+    output.commit()
+}
+
+#[cfg(not(feature = "remote"))]
+fn download(url: &str, _: &Path) -> Result<()> {
+    Err(Error::RemoteDisabled(url.into()))
+}
+
+/// Uploads the local file by HTTP PUT request attaching the metadata as headers.
+#[cfg(feature = "remote")]
+pub fn upload(url: &str, local: &Path, metadata: &[(&str, &str)]) -> Result<()> {
+    use crate::{filebuf, global};
+
+    let is_s3 = url.starts_with("s3://");
+    let url = http_url(url)?;
+    if global::verbose() {
+        tracing::info!("Uploading {:?} to {}...", local, &url);
+    }
+    // Storages like S3 do not accept chunked transfer encoding
+    let size = fs::metadata(local)?.len();
+    let mut request = ureq::put(&url).set("Content-Length", &size.to_string());
+    for (key, value) in metadata {
+        let header = if is_s3 {
+            format!("x-amz-meta-bpf-profile-{}", key)
+        } else {
+            format!("x-bpf-profile-{}", key)
+        };
+        request = request.set(&header, value);
+    }
+    request
+        .send(filebuf::open(local)?)
+        .map_err(|e| Error::Upload(url.clone(), e.to_string()))?;
+    Ok(())
+}
+
+/// Fails as uploads are not supported without the `remote` feature.
+#[cfg(not(feature = "remote"))]
+pub fn upload(url: &str, _: &Path, _: &[(&str, &str)]) -> Result<()> {
+    Err(Error::RemoteDisabled(url.into()))
+}
+
+/// Posts the JSON payload to the webhook.
+#[cfg(feature = "remote")]
+pub fn notify(url: &str, payload: &serde_json::Value) -> Result<()> {
+    use crate::global;
+
+    if global::verbose() {
+        tracing::info!("Notifying {}...", url);
+    }
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&payload.to_string())
+        .map_err(|e| Error::Notify(url.into(), e.to_string()))?;
+    Ok(())
+}
+
+/// Fails as notifications are not supported without the `remote` feature.
+#[cfg(not(feature = "remote"))]
+pub fn notify(url: &str, _: &serde_json::Value) -> Result<()> {
+    Err(Error::RemoteDisabled(url.into()))
+}
+
+/// Converts the URL into the HTTP(S) one.
+#[cfg(feature = "remote")]
+fn http_url(url: &str) -> Result<String> {
+    match url.strip_prefix("s3://") {
+        None => Ok(url.into()),
+        Some(location) => s3_url(url, location),
+    }
+}
+
+/// Converts `s3://bucket/key` into the public HTTPS endpoint of the object.
+/// Only anonymous access is supported: the object must be accessible without credentials.
+#[cfg(all(feature = "remote", feature = "s3"))]
+fn s3_url(url: &str, location: &str) -> Result<String> {
+    let (bucket, key) = location
+        .split_once('/')
+        .ok_or_else(|| Error::Download(url.into(), "missing object key".into()))?;
+    let region = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".into());
+    Ok(format!(
+        "https://{}.s3.{}.amazonaws.com/{}",
+        bucket, region, key
+    ))
+}
+
+#[cfg(all(feature = "remote", not(feature = "s3")))]
+fn s3_url(url: &str, _: &str) -> Result<String> {
+    Err(Error::Download(
+        url.into(),
+        "support of s3:// is not enabled in this build".into(),
+    ))
+}
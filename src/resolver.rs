@@ -1,44 +1,491 @@
 //! bpf-profile resolver module.
 
-use crate::config::{Address, Index, Map, ProgramCounter, GROUND_ZERO, PADDING};
+use crate::bpf;
+use crate::config::{
+    Address, Index, Map, ProgramCounter, DEFAULT_DUMP_FUNCTION_HEADER, DEFAULT_DUMP_INSTRUCTION,
+    DEFAULT_UNRESOLVED_NAME, PADDING, PROGRAM_REGION_SHIFT,
+};
 use crate::error::{Error, Result};
 use crate::{filebuf, global};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
 use std::io::{BufRead, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 /// Reads the dump file (if any) and returns a dump representation.
 pub fn read(filepath: Option<&Path>) -> Result<Resolver> {
     match filepath {
         None => Ok(Resolver::default()),
-        Some(path) => Resolver::read(path),
+        Some(path) => {
+            let patterns = DUMP_PATTERNS
+                .read()
+                .expect("Dump patterns lock poisoned")
+                .clone();
+            read_with_patterns(path, &patterns)
+        }
+    }
+}
+
+/// Reads the dump file with the patterns of its lines.
+pub fn read_with_patterns(filepath: &Path, patterns: &DumpPatterns) -> Result<Resolver> {
+    Resolver::read(filepath, patterns)
+}
+
+/// Reads the dump or the ELF file of the program from the reader,
+/// e.g. a dump kept in memory.
+pub fn read_from(reader: impl BufRead) -> Result<Resolver> {
+    let patterns = DUMP_PATTERNS
+        .read()
+        .expect("Dump patterns lock poisoned")
+        .clone();
+    Resolver::read_from(reader, &patterns)
+}
+
+/// Represents regular expressions of lines of the dump file: headers of functions
+/// with the named group `name` and instructions with the named groups `pc` and `text`.
+#[derive(Debug, Clone)]
+pub struct DumpPatterns {
+    function_header: Regex,
+    instruction: Regex,
+}
+
+impl DumpPatterns {
+    /// Compiles the patterns and checks they contain the required named groups.
+    pub fn new(function_header: &str, instruction: &str) -> Result<Self> {
+        let compile = |pattern: &str, groups: &[&str]| {
+            let regex = Regex::new(pattern)
+                .map_err(|e| Error::DumpPattern(pattern.into(), e.to_string()))?;
+            match groups
+                .iter()
+                .find(|g| !regex.capture_names().any(|n| n == Some(**g)))
+            {
+                Some(group) => Err(Error::DumpPattern(
+                    pattern.into(),
+                    format!("missing group '{}'", group),
+                )),
+                None => Ok(regex),
+            }
+        };
+        Ok(DumpPatterns {
+            function_header: compile(function_header, &["name"])?,
+            instruction: compile(instruction, &["pc", "text"])?,
+        })
+    }
+}
+
+impl Default for DumpPatterns {
+    fn default() -> Self {
+        DumpPatterns::new(DEFAULT_DUMP_FUNCTION_HEADER, DEFAULT_DUMP_INSTRUCTION)
+            .expect("Invalid regex")
+    }
+}
+
+lazy_static! {
+    static ref DUMP_PATTERNS: RwLock<DumpPatterns> = RwLock::new(DumpPatterns::default());
+}
+
+/// Sets the patterns of lines of dump files read afterwards.
+pub fn set_dump_patterns(patterns: DumpPatterns) {
+    *DUMP_PATTERNS.write().expect("Dump patterns lock poisoned") = patterns;
+}
+
+/// Represents the template of names of functions not found in the dump file
+/// with the placeholders `{index}` (number of the unresolved function),
+/// `{address}` (hexadecimal, without `0x`) and `{pc}` (first program counter).
+#[derive(Debug, Clone)]
+pub struct UnresolvedName {
+    template: String,
+}
+
+impl UnresolvedName {
+    /// Checks the placeholders of the template; names must be unique,
+    /// so the template must contain the index or the address.
+    pub fn new(template: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::UnresolvedName(template.into(), reason);
+        let placeholders = PLACEHOLDER
+            .captures_iter(template)
+            .map(|caps| caps[1].to_string())
+            .collect::<Vec<_>>();
+        if let Some(unknown) = placeholders
+            .iter()
+            .find(|p| !["index", "address", "pc"].contains(&p.as_str()))
+        {
+            return Err(invalid(format!("unknown placeholder '{{{}}}'", unknown)));
+        }
+        if !placeholders.iter().any(|p| p == "index" || p == "address") {
+            return Err(invalid(
+                "missing placeholder '{index}' or '{address}'".into(),
+            ));
+        }
+        Ok(UnresolvedName {
+            template: template.into(),
+        })
+    }
+
+    /// Returns the name of the unresolved function.
+    pub fn format(&self, index: usize, address: Address, pc: ProgramCounter) -> String {
+        self.template
+            .replace("{index}", &index.to_string())
+            .replace("{address}", &format!("{:x}", address))
+            .replace("{pc}", &pc.to_string())
+    }
+}
+
+/// The template set by `set_unresolved_name`.
+impl Default for UnresolvedName {
+    fn default() -> Self {
+        UNRESOLVED_NAME
+            .read()
+            .expect("Unresolved name lock poisoned")
+            .clone()
     }
 }
 
-/// Represents the dump file contents.
-#[derive(Default, Debug)]
+lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{(\w*)\}").expect("Invalid regex");
+    static ref UNRESOLVED_NAME: RwLock<UnresolvedName> =
+        RwLock::new(UnresolvedName::new(DEFAULT_UNRESOLVED_NAME).expect("Invalid template"));
+}
+
+/// Sets the template of names of unresolved functions of dump files read afterwards.
+pub fn set_unresolved_name(name: UnresolvedName) {
+    *UNRESOLVED_NAME
+        .write()
+        .expect("Unresolved name lock poisoned") = name;
+}
+
+/// Rules renaming functions: the first pattern matching the name is replaced.
+pub type Aliases = Vec<(Regex, String)>;
+
+/// Represents a rule of the file of aliases.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Alias {
+    pattern: String,
+    name: String,
+}
+
+/// Represents contents of the file of aliases.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AliasFile {
+    #[serde(default)]
+    alias: Vec<Alias>,
+}
+
+/// Reads the file of aliases: regular expressions matching names of functions
+/// and their replacements, which may refer to groups, e.g. `$1`.
+pub fn read_aliases(filepath: &Path) -> Result<Aliases> {
+    let error = |message: String| Error::ConfigParsing(filepath.into(), message);
+    let text = fs::read_to_string(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    let file: AliasFile = toml::from_str(&text).map_err(|e| error(e.to_string()))?;
+    file.alias
+        .into_iter()
+        .map(|a| {
+            let pattern = Regex::new(&a.pattern).map_err(|e| error(e.to_string()))?;
+            Ok((pattern, a.name))
+        })
+        .collect()
+}
+
+/// Checks if the name of a function is the name, or one of the names of symbols
+/// sharing its first instruction, which are joined into `name1|name2`.
+pub fn is_named(function: &str, name: &str) -> bool {
+    function == name || function.split('|').any(|symbol| symbol == name)
+}
+
+/// Represents a program of the manifest of dump files.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestProgram {
+    id: String,
+    dump: PathBuf,
+}
+
+/// Represents contents of the manifest of dump files.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    program: Vec<ManifestProgram>,
+}
+
+/// Reads the manifest of dump files of programs invoked by the trace, e.g. by
+/// cross-program invocations. Returns ids of the programs with paths of their dumps;
+/// relative paths are taken from the directory of the manifest.
+pub fn read_manifest(filepath: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let text = fs::read_to_string(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    let file: ManifestFile =
+        toml::from_str(&text).map_err(|e| Error::ConfigParsing(filepath.into(), e.to_string()))?;
+    let dir = filepath.parent().unwrap_or(Path::new(""));
+    Ok(file
+        .program
+        .into_iter()
+        .map(|p| (p.id, dir.join(p.dump)))
+        .collect())
+}
+
+/// Represents a function of the symbol map exported from the build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// Id of the program of the function, if given.
+    pub program: Option<String>,
+    /// First program counter of the function, as in the dump file.
+    pub pc: ProgramCounter,
+    /// Name of the function.
+    pub name: String,
+}
+
+/// Represents a function of the symbol map in the JSON format.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SymbolEntry {
+    #[serde(default)]
+    program: Option<String>,
+    address: SymbolAddress,
+    name: String,
+}
+
+/// Represents an address of the symbol map in the JSON format, a number or a string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SymbolAddress {
+    Number(ProgramCounter),
+    Text(String),
+}
+
+/// Reads the symbol map of first program counters of functions with their names,
+/// e.g. exported from the build when the dump file is not at hand. The map is either
+/// a JSON array of objects `{"program": "<id>", "address": "0x10", "name": "<name>"}`
+/// or lines `<program-id>:<address>:<name>` (or separated by commas), where
+/// the program is optional and addresses are decimal or hexadecimal with `0x`.
+pub fn read_symbols(filepath: &Path) -> Result<Vec<Symbol>> {
+    let error = |message: String| Error::ConfigParsing(filepath.into(), message);
+    let text = fs::read_to_string(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    let parse_pc = |address: &str| {
+        match address.strip_prefix("0x") {
+            Some(hex) => ProgramCounter::from_str_radix(hex, 16),
+            None => address.parse(),
+        }
+        .map_err(|_| error(format!("invalid address '{}'", address)))
+    };
+    let program = |id: &str| (!id.is_empty()).then(|| id.to_string());
+
+    if text.trim_start().starts_with('[') {
+        let entries: Vec<SymbolEntry> =
+            serde_json::from_str(&text).map_err(|e| error(e.to_string()))?;
+        return entries
+            .into_iter()
+            .map(|e| {
+                let pc = match e.address {
+                    SymbolAddress::Number(pc) => pc,
+                    SymbolAddress::Text(address) => parse_pc(&address)?,
+                };
+                Ok(Symbol {
+                    program: e.program.as_deref().and_then(program),
+                    pc,
+                    name: e.name,
+                })
+            })
+            .collect();
+    }
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            // Names may contain separators, e.g. paths of Rust functions
+            let mut fields = line.splitn(3, [':', ',']);
+            let (Some(id), Some(address), Some(name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(error(format!(
+                    "invalid line '{}', expected <program-id>:<address>:<name>",
+                    line
+                )));
+            };
+            Ok(Symbol {
+                program: program(id.trim()),
+                pc: parse_pc(address.trim())?,
+                name: name.trim().into(),
+            })
+        })
+        .collect()
+}
+
+/// Represents contents of the dump file, shared read-only by clones of the resolver.
+#[derive(Clone, Default, Debug)]
+struct Dump {
+    functions: Vec<String>,
+    index_function_by_first_pc: Map<ProgramCounter, Index>,
+    pretty_source: Vec<String>,
+    source_locations: BTreeMap<ProgramCounter, String>,
+    size_by_first_pc: Map<ProgramCounter, usize>,
+    frame_sizes: Map<Index, usize>,
+}
+
+/// Represents the dump file contents and functions resolved while parsing traces.
+/// Clones share the contents of the dump, so one parsed dump serves profiles
+/// of many traces and threads; functions found in traces belong to each clone.
+#[derive(Clone, Default, Debug)]
 pub struct Resolver {
     not_default: bool,
+    dump: Arc<Dump>,
+    // Functions missing in the dump, indexed after the functions of the dump
     functions: Vec<String>,
     index_function_by_address: Map<Address, Index>,
     index_function_by_first_pc: Map<ProgramCounter, Index>,
     unresolved_counter: usize,
-    pretty_source: Vec<String>,
+    unresolved_name: UnresolvedName,
+    unresolved_functions: BTreeSet<Index>,
+    aliases: Aliases,
+    // Offset of program counters of the trace from those of the dump
+    pc_offset: isize,
+    // Resolvers of other programs by their ids, in order of their regions
+    programs: Vec<(String, Resolver)>,
 }
 
-const PREFIX_OF_UNRESOLVED: &str = "function_";
-
 impl Resolver {
     /// Reads the dump file to collect function names and pretty assembly.
+    /// The ELF file of the program itself is accepted instead of its dump.
     /// Returns non-trivial (with real function names) instance of the Resolver.
-    fn read(filepath: &Path) -> Result<Self> {
+    fn read(filepath: &Path, patterns: &DumpPatterns) -> Result<Self> {
         if global::verbose() {
             tracing::info!("Reading dump file, creating resolver...")
         }
-        let mut resv = Resolver::default();
-        let reader = filebuf::open(filepath)?;
-        parse_dump_file(reader, &mut resv)?;
-        resv.not_default = true;
-        Ok(resv)
+        let mut reader = filebuf::open(filepath)?;
+        reader
+            .fill_buf()
+            .map_err(|e| Error::OpenFile(e, filepath.into()))?;
+        Self::read_from(reader, patterns)
+    }
+
+    /// Reads the dump or the ELF file from the reader.
+    fn read_from(mut reader: impl BufRead, patterns: &DumpPatterns) -> Result<Self> {
+        let mut dump = Dump::default();
+        let is_elf = reader.fill_buf()?.starts_with(ELF_MAGIC);
+        if is_elf {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            parse_elf_file(&data, &mut dump)?;
+        } else {
+            parse_dump_file(reader, &mut dump, patterns)?;
+        }
+        Ok(Resolver {
+            not_default: true,
+            dump: Arc::new(dump),
+            ..Resolver::default()
+        })
+    }
+
+    /// Creates the resolver naming functions by the symbol map instead of the dump file.
+    pub fn from_symbols(symbols: &[Symbol]) -> Self {
+        let mut resolver = Resolver::default();
+        resolver.add_symbols(symbols);
+        resolver
+    }
+
+    /// Names functions by the symbol map over the dump file, if any: names of the map
+    /// replace those of the dump at the same program counters. Symbols of programs
+    /// added before name their functions, the others those of the traced program.
+    /// Symbols are added before parsing traces and setting aliases.
+    pub fn add_symbols(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            let program = symbol
+                .program
+                .as_deref()
+                .and_then(|id| self.programs.iter_mut().find(|(p, _)| p == id));
+            match program {
+                Some((_, resolver)) => resolver.add_symbol(symbol),
+                None => self.add_symbol(symbol),
+            }
+        }
+    }
+
+    /// Names the function of the dump starting at the program counter of the symbol.
+    fn add_symbol(&mut self, symbol: &Symbol) {
+        self.not_default = true;
+        let dump = Arc::make_mut(&mut self.dump);
+        let mut name = if global::demangle() {
+            demangle(&symbol.name)
+        } else {
+            symbol.name.clone()
+        };
+        match dump.index_function_by_first_pc.get(&symbol.pc) {
+            Some(i) => dump.functions[*i] = name,
+            None => {
+                while dump.contains_function(&name) {
+                    name += "@";
+                }
+                dump.index_function_by_first_pc
+                    .insert(symbol.pc, dump.functions.len());
+                dump.functions.push(name);
+            }
+        }
+    }
+
+    /// Renames known and further functions by the aliases.
+    /// Functions renamed to the same name are merged in the profile.
+    /// The dump is copied if shared with other clones, so aliases are better set
+    /// before cloning.
+    pub fn set_aliases(&mut self, aliases: Aliases) {
+        self.aliases = aliases;
+        let dump_names = std::mem::take(&mut Arc::make_mut(&mut self.dump).functions);
+        let dump_names = dump_names.iter().map(|n| self.alias(n)).collect();
+        Arc::make_mut(&mut self.dump).functions = dump_names;
+        let names = std::mem::take(&mut self.functions);
+        self.functions = names.iter().map(|n| self.alias(n)).collect();
+        for (_, program) in &mut self.programs {
+            program.set_aliases(self.aliases.clone());
+        }
+    }
+
+    /// Sets the offset of program counters of the trace from those of the dump,
+    /// e.g. if the loader relocated the program, so functions of the trace are found
+    /// in the dump by their first program counters less the offset.
+    pub fn set_pc_offset(&mut self, offset: isize) {
+        self.pc_offset = offset;
+    }
+
+    /// Adds the resolver of another program of the trace, which resolves functions
+    /// in the region of addresses of the program.
+    pub fn add_program(&mut self, id: &str, resolver: Resolver) {
+        self.programs.push((id.into(), resolver));
+    }
+
+    /// Returns the tag of addresses of functions of the program, if its resolver is added.
+    pub fn program_region(&self, id: &str) -> Option<Address> {
+        let index = self.programs.iter().position(|(p, _)| p == id)?;
+        Some(((index + 1) as Address) << PROGRAM_REGION_SHIFT)
+    }
+
+    /// Returns the resolver of the program of the region of the address, if not this one.
+    fn program(&self, address: Address) -> Option<&Resolver> {
+        let index = ((address >> PROGRAM_REGION_SHIFT) as usize).checked_sub(1)?;
+        self.programs.get(index).map(|(_, r)| r)
+    }
+
+    /// Returns the mutable resolver of the program of the region of the address.
+    fn program_mut(&mut self, address: Address) -> Option<&mut Resolver> {
+        let index = ((address >> PROGRAM_REGION_SHIFT) as usize).checked_sub(1)?;
+        self.programs.get_mut(index).map(|(_, r)| r)
+    }
+
+    /// Returns the name replaced by the first matching alias, if any.
+    fn alias(&self, name: &str) -> String {
+        self.aliases
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(name))
+            .map_or_else(
+                || name.into(),
+                |(pattern, replacement)| pattern.replace(name, replacement.as_str()).into(),
+            )
+    }
+
+    /// Checks if functions are renamed by aliases.
+    pub fn has_aliases(&self) -> bool {
+        !self.aliases.is_empty()
     }
 
     /// Checks if resolver was generated from nothing (default) or from the dump file.
@@ -46,90 +493,342 @@ impl Resolver {
         !self.not_default
     }
 
+    /// Checks if instructions are known from the dump file, unlike names
+    /// of the symbol map alone.
+    pub fn has_disassembly(&self) -> bool {
+        !self.dump.pretty_source.is_empty()
+    }
+
+    /// Returns the number of functions which got generated names.
+    pub fn unresolved_count(&self) -> usize {
+        self.unresolved_counter
+            + self
+                .programs
+                .iter()
+                .map(|(_, r)| r.unresolved_count())
+                .sum::<usize>()
+    }
+
+    /// Checks if the function at the address got a real name rather than a generated one.
+    pub fn is_resolved(&self, address: Address) -> bool {
+        if let Some(program) = self.program(address) {
+            return program.is_resolved(address);
+        }
+        self.index_function_by_address
+            .get(&address)
+            .is_some_and(|i| !self.unresolved_functions.contains(i))
+    }
+
     /// Takes an address and returns name of corresponding function.
     pub fn resolve_by_address(&self, address: Address) -> String {
         tracing::debug!("Resolver.resolve(0x{:x})", &address);
-        assert_ne!(address, GROUND_ZERO);
+        if let Some(program) = self.program(address) {
+            return program.resolve_by_address(address);
+        }
         let func_index = self.index_function_by_address[&address];
-        let func_name = self.functions[func_index].clone();
+        let func_name = self.name(func_index).to_string();
         tracing::debug!("Resolver.resolve returns {})", &func_name);
         func_name
     }
 
     /// Takes a program counter and returns name of function which begins with it (if any).
     pub fn resolve_by_first_pc(&self, pc: ProgramCounter) -> Option<String> {
-        let func_index = self.index_function_by_first_pc.get(&pc);
-        func_index.map(|i| self.functions[*i].clone())
+        self.index_by_first_pc(pc).map(|i| self.name(i).into())
     }
 
     /// Takes an address and returns name of corresponding function,
     /// otherwise returns a generated string if can not resolve properly.
     pub fn update(&mut self, address: Address, first_pc: ProgramCounter) -> String {
         tracing::debug!("Resolver.update(0x{:x}, {})", &address, &first_pc);
-        assert_ne!(address, GROUND_ZERO);
+        if let Some(program) = self.program_mut(address) {
+            return program.update(address, first_pc);
+        }
 
         let found = self.index_function_by_address.contains_key(&address);
         if !found {
-            if self.contains_function_with_first_pc(first_pc) {
-                // There can be multiple copies of one function with different addresses
-                let func_index = self.index_function_by_first_pc[&first_pc];
-                self.index_function_by_address.insert(address, func_index);
-            } else {
-                let unresolved_func_name = format!(
-                    "{}{} (0x{:x})",
-                    PREFIX_OF_UNRESOLVED, self.unresolved_counter, address
-                );
-                self.unresolved_counter += 1;
-                let func_index = self.update_first_pc_index(&unresolved_func_name, first_pc);
-                self.index_function_by_address.insert(address, func_index);
-            }
+            let func_index = self.index_function(address, first_pc);
+            self.index_function_by_address.insert(address, func_index);
         }
 
         let func_index = self.index_function_by_address[&address];
-        let func_name = self.functions[func_index].clone();
+        let func_name = self.name(func_index).to_string();
         tracing::debug!("Resolver.update returns {})", &func_name);
         func_name
     }
 
+    /// Returns the name of the function of another trace at the address taken
+    /// by another function, without indexing the address.
+    pub fn update_unindexed(&mut self, address: Address, first_pc: ProgramCounter) -> String {
+        tracing::debug!("Resolver.update_unindexed(0x{:x}, {})", &address, &first_pc);
+        if let Some(program) = self.program_mut(address) {
+            return program.update_unindexed(address, first_pc);
+        }
+        let func_index = self.index_function(address, first_pc);
+        self.name(func_index).to_string()
+    }
+
+    /// Checks if the function starting at the program counter of the trace got
+    /// a real name rather than a generated one.
+    pub fn is_resolved_pc(&self, first_pc: ProgramCounter) -> bool {
+        self.index_by_traced_pc(first_pc)
+            .is_some_and(|i| !self.unresolved_functions.contains(&i))
+    }
+
+    /// Returns addresses of the functions of the trace with their first program counters,
+    /// in order of their resolution.
+    pub fn traced_functions(&self) -> Vec<(Address, ProgramCounter)> {
+        let first_pcs = self
+            .dump
+            .index_function_by_first_pc
+            .iter()
+            .map(|(pc, i)| (*i, pc.wrapping_add_signed(self.pc_offset)))
+            .chain(
+                self.index_function_by_first_pc
+                    .iter()
+                    .map(|(pc, i)| (*i, *pc)),
+            )
+            .collect::<Map<_, _>>();
+        let mut addresses = self
+            .index_function_by_address
+            .iter()
+            .map(|(address, i)| (*i, *address))
+            .collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses
+            .into_iter()
+            .map(|(i, address)| (address, first_pcs[&i]))
+            .chain(self.programs.iter().flat_map(|(_, r)| r.traced_functions()))
+            .collect()
+    }
+
+    /// Returns the first program counter of the function at the address of the trace.
+    pub fn first_pc(&self, address: Address) -> Option<ProgramCounter> {
+        if let Some(program) = self.program(address) {
+            return program.first_pc(address);
+        }
+        let index = self.index_function_by_address.get(&address)?;
+        self.dump
+            .index_function_by_first_pc
+            .iter()
+            .find(|(_, i)| *i == index)
+            .map(|(pc, _)| pc.wrapping_add_signed(self.pc_offset))
+            .or_else(|| {
+                self.index_function_by_first_pc
+                    .iter()
+                    .find(|(_, i)| *i == index)
+                    .map(|(pc, _)| *pc)
+            })
+    }
+
+    /// Returns the size in bytes of the stack frame of the function at the address,
+    /// if the function is found in the dump file.
+    pub fn frame_size(&self, address: Address) -> Option<usize> {
+        if let Some(program) = self.program(address) {
+            return program.frame_size(address);
+        }
+        let index = self.index_function_by_address.get(&address)?;
+        if self.is_default() || self.unresolved_functions.contains(index) {
+            return None;
+        }
+        Some(self.dump.frame_sizes.get(index).copied().unwrap_or(0))
+    }
+
+    /// Asks the resolver plugins for a name of the function, unique in the resolver.
+    #[cfg(feature = "plugins")]
+    fn resolve_by_plugins(&self, address: Address, first_pc: ProgramCounter) -> Option<String> {
+        let mut name = crate::plugin::resolve(address, first_pc)?;
+        while self.contains_function(&name) {
+            name += "@";
+        }
+        Some(name)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn resolve_by_plugins(&self, _: Address, _: ProgramCounter) -> Option<String> {
+        None
+    }
+
+    /// Returns functions of the dump file with their first program counters
+    /// and sizes in instructions (sorted by first program counter).
+    pub fn dump_functions(&self) -> Vec<(ProgramCounter, String, usize)> {
+        let mut functions = self
+            .dump
+            .size_by_first_pc
+            .iter()
+            .map(|(pc, size)| {
+                let name = self.resolve_by_first_pc(*pc).expect("Function not indexed");
+                (*pc, name, *size)
+            })
+            .collect::<Vec<_>>();
+        functions.sort();
+        functions
+    }
+
+    /// Returns program counters of all instructions of the dump file.
+    pub fn instruction_pcs(&self) -> Vec<ProgramCounter> {
+        self.dump
+            .pretty_source
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(pc, _)| pc)
+            .collect()
+    }
+
+    /// Returns the line of the instruction of the dump file as written in the generated
+    /// assembly, if the dump has the instruction.
+    pub fn pretty_instruction(&self, pc: ProgramCounter) -> Option<&str> {
+        self.dump
+            .pretty_source
+            .get(pc)
+            .filter(|s| !s.is_empty())
+            .map(String::as_str)
+    }
+
+    /// Checks if source lines of instructions are known.
+    pub fn has_source_locations(&self) -> bool {
+        !self.dump.source_locations.is_empty()
+    }
+
+    /// Returns the source file and line of the instruction of the dump, taken from
+    /// the location of the instruction or the nearest preceding one (`llvm-objdump -l`).
+    pub fn source_location(&self, pc: ProgramCounter) -> Option<(&str, usize)> {
+        if self.dump.pretty_source.get(pc).is_none_or(String::is_empty) {
+            return None;
+        }
+        let (_, location) = self.dump.source_locations.range(..=pc).next_back()?;
+        let (file, line) = location.rsplit_once(':')?;
+        Some((file, line.parse().ok()?))
+    }
+
     /// Writes source lines from dump file (if any) into the output.
     pub fn write_pretty_source(&self, mut output: impl Write) -> Result<()> {
         writeln!(
             output,
             ";; Generated BPF pretty assembly code for QCacheGrind"
         )?;
-        for i in 2..self.pretty_source.len() {
-            if !self.contains_function_with_first_pc(i) {
-                writeln!(output, "{}", &self.pretty_source[i])?;
-            } else {
-                let function = self.resolve_by_first_pc(i).unwrap();
-                writeln!(
+        for i in 2..self.dump.pretty_source.len() {
+            match self.resolve_by_first_pc(i) {
+                None => writeln!(output, "{}", &self.dump.pretty_source[i])?,
+                Some(function) => writeln!(
                     output,
                     "{}{}; {}",
-                    &self.pretty_source[i], PADDING, function
-                )?;
+                    &self.dump.pretty_source[i], PADDING, function
+                )?,
             }
         }
         output.flush()?;
         Ok(())
     }
 
+    /// Returns the name of the function by its index.
+    fn name(&self, index: Index) -> &str {
+        match index.checked_sub(self.dump.functions.len()) {
+            None => &self.dump.functions[index],
+            Some(i) => &self.functions[i],
+        }
+    }
+
+    /// Returns the index of the function starting at the program counter, if indexed.
+    fn index_by_first_pc(&self, first_pc: ProgramCounter) -> Option<Index> {
+        self.dump
+            .index_function_by_first_pc
+            .get(&first_pc)
+            .or_else(|| self.index_function_by_first_pc.get(&first_pc))
+            .copied()
+    }
+
+    /// Searches a function by the program counter of the trace, found in the dump
+    /// at the program counter less the offset.
+    fn index_by_traced_pc(&self, first_pc: ProgramCounter) -> Option<Index> {
+        first_pc
+            .checked_add_signed(-self.pc_offset)
+            .and_then(|pc| self.dump.index_function_by_first_pc.get(&pc))
+            .or_else(|| self.index_function_by_first_pc.get(&first_pc))
+            .copied()
+    }
+
     /// Searches a function by name.
+    #[cfg(feature = "plugins")]
     fn contains_function(&self, name: &str) -> bool {
-        self.functions.iter().any(|f| f == name)
+        self.dump.contains_function(name) || self.functions.iter().any(|f| f == name)
     }
 
-    /// Checks if a function has been indexed already.
-    fn contains_function_with_first_pc(&self, first_pc: ProgramCounter) -> bool {
-        self.index_function_by_first_pc.contains_key(&first_pc)
+    /// Finds the function starting at the program counter of the trace, otherwise
+    /// names it by plugins or generates its name.
+    fn index_function(&mut self, address: Address, first_pc: ProgramCounter) -> Index {
+        if let Some(func_index) = self.index_by_traced_pc(first_pc) {
+            // There can be multiple copies of one function with different addresses
+            func_index
+        } else if let Some(name) = self.resolve_by_plugins(address, first_pc) {
+            self.update_first_pc_index(&name, first_pc)
+        } else {
+            let unresolved_func_name =
+                self.unresolved_name
+                    .format(self.unresolved_counter, address, first_pc);
+            self.unresolved_counter += 1;
+            let func_index = self.update_first_pc_index(&unresolved_func_name, first_pc);
+            self.unresolved_functions.insert(func_index);
+            func_index
+        }
     }
 
     /// Creates new entry in the index of functions by their first instruction's pc.
     fn update_first_pc_index(&mut self, name: &str, first_pc: ProgramCounter) -> Index {
-        let func_index = self.functions.len();
-        self.functions.push(name.into());
+        let func_index = self.dump.functions.len() + self.functions.len();
+        self.functions.push(self.alias(name));
         self.index_function_by_first_pc.insert(first_pc, func_index);
         func_index
     }
+}
+
+impl Dump {
+    /// Searches a function by name.
+    fn contains_function(&self, name: &str) -> bool {
+        self.functions.iter().any(|f| f == name)
+    }
+
+    /// Indexes the function of the dump starting at the program counter.
+    /// Symbols sharing the first instruction (e.g. aliases) are not dropped
+    /// but joined into one name `name1|name2`. Mangled symbols are demangled
+    /// unless disabled.
+    fn index_symbols(&mut self, symbols: Vec<String>, first_pc: ProgramCounter) {
+        let symbols = symbols
+            .into_iter()
+            .map(|s| if global::demangle() { demangle(&s) } else { s });
+        let index = self.index_function_by_first_pc.get(&first_pc).copied();
+        let mut names = index.map_or_else(Vec::new, |i| {
+            self.functions[i].split('|').map(String::from).collect()
+        });
+        for symbol in symbols {
+            if !names.contains(&symbol) {
+                names.push(symbol);
+            }
+        }
+        if names.len() > 1 {
+            tracing::warn!(
+                "Functions {} share the first instruction {}, naming it {}",
+                names.join(", "),
+                first_pc,
+                names.join("|")
+            );
+        }
+
+        let mut name = names.join("|");
+        match index {
+            Some(i) => self.functions[i] = name,
+            None => {
+                // There can be several copies of identical function,
+                // so we generate unique name for each copy
+                while self.contains_function(&name) {
+                    name += "@";
+                }
+                self.index_function_by_first_pc
+                    .insert(first_pc, self.functions.len());
+                self.functions.push(name);
+            }
+        }
+    }
 
     /// Adds new line to the pretty source listing.
     fn add_pretty_source(&mut self, i: usize, s: String) {
@@ -139,6 +838,40 @@ impl Resolver {
         self.pretty_source[i] = s;
     }
 
+    /// Calculates sizes of the dump's functions as distances between their first
+    /// program counters; the last function ends with the last instruction.
+    fn update_sizes(&mut self) {
+        let mut first_pcs = self
+            .index_function_by_first_pc
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        first_pcs.sort_unstable();
+        let end = self.pretty_source.len();
+        for (i, pc) in first_pcs.iter().enumerate() {
+            let next = first_pcs.get(i + 1).copied().unwrap_or(end);
+            self.size_by_first_pc.insert(*pc, next - pc);
+        }
+    }
+
+    /// Calculates sizes of stack frames of the dump's functions as the largest
+    /// of the sizes implied by their instructions.
+    fn update_frame_sizes(&mut self, frames: Vec<(ProgramCounter, usize)>) {
+        let mut first_pcs = self
+            .index_function_by_first_pc
+            .iter()
+            .map(|(pc, i)| (*pc, *i))
+            .collect::<Vec<_>>();
+        first_pcs.sort_unstable();
+        for (pc, size) in frames {
+            let i = first_pcs.partition_point(|(first_pc, _)| *first_pc <= pc);
+            if let Some((_, index)) = i.checked_sub(1).map(|i| first_pcs[i]) {
+                let frame_size = self.frame_sizes.entry(index).or_insert(0);
+                *frame_size = (*frame_size).max(size);
+            }
+        }
+    }
+
     fn compress(&mut self) {
         self.functions.shrink_to_fit();
         self.pretty_source.shrink_to_fit();
@@ -148,11 +881,178 @@ impl Resolver {
 use lazy_static::lazy_static;
 use regex::Regex;
 
-const HEADER: &str = "ELF Header";
-const DISASM_HEADER: &str = "Disassembly of section .text";
+/// Header of dump files.
+pub const HEADER: &str = "ELF Header";
+/// Header of a disassembled section; there may be several executable sections,
+/// e.g. `.text`, `.text.unlikely` or custom ones.
+pub const DISASM_HEADER: &str = "Disassembly of section ";
+/// Headers of listings which some objdump options append to the disassembly,
+/// skipped up to the next disassembled section.
+const LISTING_HEADERS: &[&str] = &[
+    "RELOCATION RECORDS FOR ",
+    "DYNAMIC RELOCATION RECORDS",
+    "SYMBOL TABLE:",
+    "DYNAMIC SYMBOL TABLE:",
+    "Contents of section ",
+    "Sections:",
+    "Program Header:",
+    "Dynamic Section:",
+];
+/// Prefixes of names of data sections, which are disassembled by `objdump -D`
+/// but contain no instructions.
+const DATA_SECTIONS: &[&str] = &[
+    ".rodata",
+    ".data",
+    ".bss",
+    ".dyn",
+    ".rel",
+    ".got",
+    ".eh_frame",
+    ".symtab",
+    ".strtab",
+    ".shstrtab",
+    ".comment",
+    ".debug",
+];
+
+/// Checks if the header of the disassembled section is of a data section.
+fn is_data_section(header: &str) -> bool {
+    let name = header[DISASM_HEADER.len()..].trim().trim_end_matches(':');
+    DATA_SECTIONS.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// Returns the readable name of the Rust or C++ symbol without the hash,
+/// or the symbol itself if it is not mangled.
+pub fn demangle(symbol: &str) -> String {
+    if let Ok(name) = rustc_demangle::try_demangle(symbol) {
+        return format!("{:#}", name);
+    }
+    cpp_demangle::Symbol::new(symbol)
+        .ok()
+        .and_then(|name| name.demangle(&Default::default()).ok())
+        .unwrap_or_else(|| symbol.into())
+}
+
+/// Magic number starting ELF files.
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// Size of a BPF instruction in bytes.
+const INSTRUCTION_SIZE: usize = 8;
+
+/// Parses the symbol table of the program's ELF file building the contents of the dump.
+/// Program counters are numbers of instructions from the start of the `.text` section.
+/// Instructions are not disassembled, so the listing shows their raw bytes.
+/// Source lines of instructions are taken from the DWARF line tables, if any.
+fn parse_elf_file(data: &[u8], dump: &mut Dump) -> Result<()> {
+    use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+    let elf_error = |e: object::Error| Error::ElfParsing(e.to_string());
+    let elf = object::File::parse(data).map_err(elf_error)?;
+    let text = elf
+        .section_by_name(".text")
+        .ok_or_else(|| Error::ElfParsing("no .text section".into()))?;
+
+    let mut frames = Vec::new();
+    for (pc, ix) in text
+        .data()
+        .map_err(elf_error)?
+        .chunks(INSTRUCTION_SIZE)
+        .enumerate()
+    {
+        if let Some(size) = bpf::stack_frame_size_of_bytes(ix) {
+            frames.push((pc, size));
+        }
+        let bytes = ix
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        dump.add_pretty_source(pc, format!("{}:{}{}", pc, PADDING, bytes));
+    }
+
+    // Stripped programs keep the dynamic symbols only
+    let mut symbols = elf.symbols().collect::<Vec<_>>();
+    if symbols.is_empty() {
+        symbols = elf.dynamic_symbols().collect();
+    }
+    let mut symbols_by_pc = BTreeMap::<ProgramCounter, Vec<String>>::new();
+    for symbol in symbols {
+        if symbol.kind() != SymbolKind::Text || symbol.section_index() != Some(text.index()) {
+            continue;
+        }
+        let name = symbol.name().map_err(elf_error)?;
+        let Some(offset) = symbol.address().checked_sub(text.address()) else {
+            continue;
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let pc = offset as usize / INSTRUCTION_SIZE;
+        symbols_by_pc.entry(pc).or_default().push(name.into());
+    }
+    for (pc, symbols) in symbols_by_pc {
+        dump.index_symbols(symbols, pc);
+    }
+
+    if let Some(context) = dwarf_context(&elf)? {
+        let dwarf_error = |e: addr2line::gimli::Error| Error::ElfParsing(e.to_string());
+        let end = text.address() + text.size();
+        for range in context
+            .find_location_range(text.address(), end)
+            .map_err(dwarf_error)?
+        {
+            let (address, _, location) = range;
+            if let (Some(file), Some(line)) = (location.file, location.line) {
+                let pc = (address - text.address()) as usize / INSTRUCTION_SIZE;
+                dump.source_locations
+                    .insert(pc, format!("{}:{}", file, line));
+            }
+        }
+    }
+
+    dump.update_sizes();
+    dump.update_frame_sizes(frames);
+    dump.compress();
+    Ok(())
+}
 
-/// Parses the dump file building the Resolver instance.
-fn parse_dump_file(mut reader: impl BufRead, resv: &mut Resolver) -> Result<()> {
+/// DWARF sections of the ELF file.
+type Dwarf<'data> = addr2line::gimli::EndianSlice<'data, addr2line::gimli::RunTimeEndian>;
+
+/// Loads the DWARF debugging information of the ELF file, if any.
+fn dwarf_context<'data>(
+    elf: &object::File<'data>,
+) -> Result<Option<addr2line::Context<Dwarf<'data>>>> {
+    use addr2line::gimli::{self, RunTimeEndian};
+    use object::{Object, ObjectSection};
+
+    if elf.section_by_name(".debug_line").is_none() {
+        return Ok(None);
+    }
+    let endian = if elf.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<Dwarf<'data>> {
+        let data = match elf.section_by_name(id.name()) {
+            Some(section) => section
+                .data()
+                .map_err(|e| Error::ElfParsing(e.to_string()))?,
+            None => &[],
+        };
+        Ok(gimli::EndianSlice::new(data, endian))
+    })?;
+    addr2line::Context::from_dwarf(dwarf)
+        .map(Some)
+        .map_err(|e| Error::ElfParsing(e.to_string()))
+}
+
+/// Parses the dump file building its contents.
+fn parse_dump_file(
+    mut reader: impl BufRead,
+    dump: &mut Dump,
+    patterns: &DumpPatterns,
+) -> Result<()> {
     let mut line = String::with_capacity(512);
     let mut bytes_read = usize::MAX;
     let mut lc = 0_usize;
@@ -178,18 +1078,19 @@ fn parse_dump_file(mut reader: impl BufRead, resv: &mut Resolver) -> Result<()>
     if !was_disasm {
         return Err(Error::DumpFormatNoDisasm);
     }
+    let mut skipping = is_data_section(&line);
 
     lazy_static! {
-        static ref LBB: Regex = Regex::new(r"^[[:xdigit:]]+\s+<(LBB.+)>").expect("Invalid regex");
-        static ref FUNC_HEADER: Regex =
-            Regex::new(r"^[[:xdigit:]]+\s+<(.+)>").expect("Invalid regex");
-        static ref INSTRUCTION: Regex =
-            Regex::new(r"^\s+(\d+)(\s+[[:xdigit:]]{2})+\s+(.+)").expect("Invalid regex");
+        static ref SOURCE_LOCATION: Regex =
+            Regex::new(r"^;\s*(\S+:\d+)\s*$").expect("Invalid regex");
     }
 
     // Read functions and their instructions
     let mut label = String::new();
-    let mut function = String::new();
+    let mut symbols = Vec::new();
+    let mut location = String::new();
+    let mut source = String::new();
+    let mut frames = Vec::new();
     while bytes_read != 0 {
         bytes_read = filebuf::read_line(&mut reader, &mut line)?;
         lc += 1;
@@ -198,42 +1099,82 @@ fn parse_dump_file(mut reader: impl BufRead, resv: &mut Resolver) -> Result<()>
             continue;
         }
 
-        if let Some(caps) = LBB.captures(&line) {
-            assert!(label.is_empty());
-            label = caps[1].to_string();
-        } else if let Some(caps) = FUNC_HEADER.captures(&line) {
-            assert!(function.is_empty());
-            function = caps[1].to_string();
-        } else if let Some(caps) = INSTRUCTION.captures(&line) {
-            let pc = caps[1]
-                .parse::<ProgramCounter>()
-                .expect("Cannot parse program counter");
-            let text = caps[3].to_string();
-            if !function.is_empty() {
-                if !resv.contains_function_with_first_pc(pc) {
-                    // There can be several copies of identical function,
-                    // so we generate unique name for each copy
-                    while resv.contains_function(&function) {
-                        function += "@";
-                    }
-                    resv.update_first_pc_index(&function, pc);
-                }
-                function.clear();
-            }
-            resv.add_pretty_source(
-                pc,
-                if label.is_empty() {
-                    format!("{}:{}{}", pc, PADDING, &text)
-                } else {
-                    format!("{}:{}{}{}; {}", pc, PADDING, &text, PADDING, &label)
-                },
-            );
+        // Functions of all disassembled sections are indexed, besides data sections
+        if line.starts_with(DISASM_HEADER) {
+            symbols.clear();
+            label.clear();
+            location.clear();
+            source.clear();
+            skipping = is_data_section(&line);
+            continue;
+        }
+
+        // Relocations, symbol tables and contents of sections are not instructions
+        if LISTING_HEADERS
+            .iter()
+            .any(|header| line.starts_with(header))
+        {
+            skipping = true;
+            continue;
+        }
+        if skipping {
+            continue;
+        }
+
+        // Source lines of `llvm-objdump -S` and their locations (with `-l`)
+        // precede the instructions compiled from them
+        if let Some(text) = line.strip_prefix(';') {
+            if let Some(caps) = SOURCE_LOCATION.captures(&line) {
+                location = caps[1].to_string();
+            } else if !text.trim().is_empty() {
+                source = text.trim().to_string();
+            }
+            continue;
+        }
+
+        if let Some(caps) = patterns.function_header.captures(&line) {
+            // Basic blocks are labels rather than functions
+            if caps["name"].starts_with("LBB") {
+                assert!(label.is_empty());
+                label = caps["name"].to_string();
+            } else {
+                symbols.push(caps["name"].to_string());
+            }
+        } else if let Some(caps) = patterns.instruction.captures(&line) {
+            let pc = bpf::str_to_program_counter(&caps["pc"])?;
+            // Sections are indexed by the same program counters, so they must not overlap
+            if dump.pretty_source.get(pc).is_some_and(|s| !s.is_empty()) {
+                return Err(Error::DumpOverlap(pc, lc));
+            }
+            let text = caps["text"].to_string();
+            if let Some(size) = bpf::stack_frame_size(&text) {
+                frames.push((pc, size));
+            }
+            if !symbols.is_empty() {
+                dump.index_symbols(std::mem::take(&mut symbols), pc);
+            }
+            let mut pretty = format!("{}:{}{}", pc, PADDING, &text);
+            if !label.is_empty() {
+                pretty += &format!("{}; {}", PADDING, &label);
+            }
+            // Attribute the instruction to its source line, preferring the location
+            if !location.is_empty() {
+                pretty += &format!("{}; {}", PADDING, &location);
+                dump.source_locations.insert(pc, location.clone());
+            } else if !source.is_empty() {
+                pretty += &format!("{}; {}", PADDING, &source);
+            }
+            dump.add_pretty_source(pc, pretty);
             label.clear();
+            location.clear();
+            source.clear();
         } else {
             return Err(Error::DumpParsing(line, lc));
         }
     }
 
-    resv.compress();
+    dump.update_sizes();
+    dump.update_frame_sizes(frames);
+    dump.compress();
     Ok(())
 }
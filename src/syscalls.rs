@@ -0,0 +1,198 @@
+//! bpf-profile syscalls module.
+//! Recognizes calls of the runtime's syscalls (helpers) by hashes of their names.
+
+use crate::config::{Address, Cost, Map};
+use crate::error::{Error, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Names of known Solana syscalls.
+const NAMES: &[&str] = &[
+    "abort",
+    "sol_panic_",
+    "sol_log_",
+    "sol_log_64_",
+    "sol_log_compute_units_",
+    "sol_log_pubkey",
+    "sol_log_data",
+    "sol_create_program_address",
+    "sol_try_find_program_address",
+    "sol_sha256",
+    "sol_keccak256",
+    "sol_blake3",
+    "sol_secp256k1_recover",
+    "sol_poseidon",
+    "sol_get_clock_sysvar",
+    "sol_get_epoch_schedule_sysvar",
+    "sol_get_fees_sysvar",
+    "sol_get_rent_sysvar",
+    "sol_get_epoch_rewards_sysvar",
+    "sol_get_last_restart_slot_sysvar",
+    "sol_get_sysvar",
+    "sol_get_epoch_stake",
+    "sol_memcpy_",
+    "sol_memmove_",
+    "sol_memcmp_",
+    "sol_memset_",
+    "sol_invoke_signed_c",
+    "sol_invoke_signed_rust",
+    "sol_alloc_free_",
+    "sol_set_return_data",
+    "sol_get_return_data",
+    "sol_get_processed_sibling_instruction",
+    "sol_get_stack_height",
+    "sol_remaining_compute_units",
+    "sol_curve_validate_point",
+    "sol_curve_group_op",
+    "sol_curve_multiscalar_mul",
+    "sol_alt_bn128_group_op",
+    "sol_alt_bn128_compression",
+    "sol_big_mod_exp",
+];
+
+lazy_static! {
+    static ref SYSCALLS: RwLock<Map<Address, &'static str>> = RwLock::new(
+        NAMES
+            .iter()
+            .map(|name| (Address::from(hash(name)), *name))
+            .collect()
+    );
+    static ref COSTS: RwLock<Map<Address, Cost>> = RwLock::new(Map::new());
+}
+
+/// Returns name of the syscall with the address (if any).
+pub fn name(address: Address) -> Option<&'static str> {
+    SYSCALLS
+        .read()
+        .expect("Syscalls lock poisoned")
+        .get(&address)
+        .copied()
+}
+
+/// Checks if the address is the address of a known syscall.
+pub fn is_syscall(address: Address) -> bool {
+    SYSCALLS
+        .read()
+        .expect("Syscalls lock poisoned")
+        .contains_key(&address)
+}
+
+/// Returns the cost of an invocation of the syscall given in the file of syscalls (if any).
+pub fn cost(address: Address) -> Option<Cost> {
+    COSTS
+        .read()
+        .expect("Syscalls lock poisoned")
+        .get(&address)
+        .copied()
+}
+
+/// Represents a syscall of the file of syscalls.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Syscall {
+    name: String,
+    address: Option<Address>,
+    cost: Option<Cost>,
+}
+
+/// Represents contents of the file of syscalls.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SyscallFile {
+    #[serde(default)]
+    syscall: Vec<Syscall>,
+}
+
+/// Reads the file of syscalls, which adds syscalls to the built-in ones
+/// or renames them, and sets costs of their invocations.
+/// The address of a syscall is the hash of its name unless given.
+pub fn read_file(filepath: &Path) -> Result<()> {
+    let text = fs::read_to_string(filepath).map_err(|e| Error::OpenFile(e, filepath.into()))?;
+    let file: SyscallFile =
+        toml::from_str(&text).map_err(|e| Error::ConfigParsing(filepath.into(), e.to_string()))?;
+    let mut syscalls = SYSCALLS.write().expect("Syscalls lock poisoned");
+    let mut costs = COSTS.write().expect("Syscalls lock poisoned");
+    for syscall in file.syscall {
+        let address = syscall
+            .address
+            .unwrap_or_else(|| Address::from(hash(&syscall.name)));
+        // Names live as long as the built-in ones
+        syscalls.insert(address, Box::leak(syscall.name.into_boxed_str()));
+        if let Some(cost) = syscall.cost {
+            costs.insert(address, cost);
+        }
+    }
+    Ok(())
+}
+
+/// Checks if the syscall invokes another program (cross-program invocation),
+/// which is executed within the call of the syscall.
+pub fn is_invoke(name: &str) -> bool {
+    matches!(name, "sol_invoke_signed_c" | "sol_invoke_signed_rust")
+}
+
+/// Upper bounds of buckets of sizes passed to syscalls.
+const SIZE_BUCKETS: &[u64] = &[64, 1024, 16384];
+
+/// Returns the register holding the size argument of the syscall (if any),
+/// e.g. the number of bytes copied by sol_memcpy_.
+pub fn size_register(name: &str) -> Option<usize> {
+    match name {
+        "sol_memcpy_" | "sol_memmove_" | "sol_memcmp_" | "sol_memset_" => Some(3),
+        "sol_log_" => Some(2),
+        _ => None,
+    }
+}
+
+/// Returns index of the bucket of the size.
+pub fn size_bucket(size: u64) -> usize {
+    SIZE_BUCKETS
+        .iter()
+        .position(|b| size <= *b)
+        .unwrap_or(SIZE_BUCKETS.len())
+}
+
+/// Returns label of the bucket of sizes, e.g. "<=64".
+pub fn size_bucket_label(bucket: usize) -> String {
+    match SIZE_BUCKETS.get(bucket) {
+        Some(bound) => format!("<={}", bound),
+        None => format!(">{}", SIZE_BUCKETS[SIZE_BUCKETS.len() - 1]),
+    }
+}
+
+/// Computes the address of a syscall as the runtime does:
+/// 32-bit Murmur3 hash of the name with zero seed.
+pub fn hash(name: &str) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let data = name.as_bytes();
+    let mut h = 0_u32;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        h ^= mix(k);
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0_u32, |k, (i, b)| k | (u32::from(*b) << (8 * i)));
+        h ^= mix(k);
+    }
+
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
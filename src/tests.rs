@@ -17,3 +17,820 @@ fn header_ok() {
     assert!(r.is_ok());
     assert!(r.unwrap());
 }
+
+#[test]
+fn header_runtime_version() {
+    let reader = Cursor::new(b"[Z TRACE bpf] BPF Program Instruction Trace (solana v1.18.2):");
+    let header = crate::trace::read_header(reader).unwrap().unwrap();
+    assert_eq!(header.runtime_version.as_deref(), Some("1.18.2"));
+
+    let header = crate::trace::parse_header("[Z TRACE bpf] BPF Program Instruction Trace:");
+    assert_eq!(header, Some(crate::trace::Header::default()));
+}
+
+#[test]
+fn preset_selected() {
+    let path = std::env::temp_dir().join("bpf-profile-preset_selected.conf");
+    std::fs::write(
+        &path,
+        "[preset.ci]\nformat = \"callgrind\"\ndump = \"ci.dump\"\njobs = 4\nread_buffer = 1048576\n\n\
+         [[preset.ci.budget]]\nfunction = \"process*\"\nself = 100\n\n[preset.local]\n",
+    )
+    .unwrap();
+
+    let preset = crate::config::read_preset(&path, Some("ci")).unwrap();
+    assert_eq!(preset.format.as_deref(), Some("callgrind"));
+    assert_eq!(preset.dump, Some("ci.dump".into()));
+    assert_eq!(preset.jobs, Some(4));
+    assert_eq!(preset.read_buffer, Some(1 << 20));
+    assert!(preset.write_buffer.is_none());
+    assert_eq!(preset.budget.len(), 1);
+    assert_eq!(preset.budget[0].function, "process*");
+    assert_eq!(preset.budget[0].self_cost, Some(100));
+    let preset = crate::config::read_preset(&path, Some("local")).unwrap();
+    assert!(preset.dump.is_none());
+    assert!(preset.budget.is_empty());
+    assert!(crate::config::read_preset(&path, Some("missing")).is_err());
+}
+
+#[test]
+fn anonymize_trace() {
+    let input = Cursor::new(
+        b"Log line with secret\n[Z TRACE bpf] BPF Program Instruction Trace:\n\
+         1 [00000000000000FF, 0000000000000001] 3: call 0xcafe\n\
+         2 [0000000000000000, 0000000000000000] 4: call 0xbeef\n\
+         3 [0000000000000000, 0000000000000000] 5: call 0xcafe\n",
+    );
+    let mut output = Vec::<u8>::new();
+    let r = crate::anonymize::anonymize_trace(input, &mut output);
+    assert!(r.is_ok());
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        "[Z TRACE bpf] BPF Program Instruction Trace:\n\
+         1 [0000000000000000, 0000000000000000] 3: call 0x1\n\
+         2 [0000000000000000, 0000000000000000] 4: call 0x2\n\
+         3 [0000000000000000, 0000000000000000] 5: call 0x1\n"
+    );
+}
+
+#[test]
+fn syscall_hash() {
+    assert_eq!(crate::syscalls::hash("sol_log_"), 0x207559bd);
+    assert_eq!(crate::syscalls::name(0x207559bd), Some("sol_log_"));
+    assert!(!crate::syscalls::is_syscall(0x100));
+}
+
+#[test]
+fn call_target_64_bit() {
+    use crate::bpf::Instruction;
+    use crate::error::Error;
+
+    let ix = Instruction::parse("1 [0] 5: call 0xfedcba9876543210").unwrap();
+    assert_eq!(ix.extract_call_target(1).unwrap(), 0xfedcba9876543210);
+
+    let ix = Instruction::parse("1 [0] 5: call 0x1fedcba9876543210").unwrap();
+    let r = ix.extract_call_target(1);
+    assert!(matches!(r, Err(Error::OutOfRange(_))));
+
+    let r = Instruction::parse("1 [0] 99999999999999999999: exit");
+    assert!(matches!(r, Err(Error::OutOfRange(_))));
+}
+
+#[test]
+fn opcode_classes_unknown() {
+    use crate::bpf::Instruction;
+
+    let class = |text: &str| Instruction::new(1, text.into()).opcode_class();
+    assert_eq!(class("add64 r1, 2"), "alu64");
+    assert_eq!(class("udiv32 r1, r2"), "alu32");
+    assert_eq!(class("be16 r1"), "alu64");
+    assert_eq!(class("jsle r1, r2, +3"), "jump");
+    assert_eq!(class("ldxdw r1, [r2+8]"), "load 8B");
+    assert_eq!(class("jmp32 r1"), "unknown");
+    assert_eq!(class("syscall sol_log_"), "unknown");
+
+    let ix = Instruction::new(1, "syscall sol_log_".into());
+    assert_eq!(ix.mnemonic(), "syscall");
+}
+
+#[test]
+fn instruction_registers() {
+    use crate::bpf::Instruction;
+
+    let line = "1 [0, 10, 400000008] 5: ldxdw r1, [r2+0x8]";
+    let ix = Instruction::parse(line).unwrap();
+    assert_eq!(ix.register(2), None);
+    let ix = Instruction::parse_with_registers(line).unwrap();
+    assert_eq!(ix.register(1), Some(0x10));
+    assert_eq!(ix.register(2), Some(0x400000008));
+    assert_eq!(
+        crate::bpf::memory_access_region(&ix),
+        Some(crate::bpf::Region::Input)
+    );
+
+    // Registers appended by some tracers are not part of the instruction
+    let line = "2 [0, 0] 6: exit ; r0=0x2a r1=7";
+    let ix = Instruction::parse_with_registers(line).unwrap();
+    assert!(ix.is_exit());
+    assert_eq!(ix.register(0), Some(42));
+    assert_eq!(ix.register(1), Some(7));
+    let ix = Instruction::parse("3 [0] 7: call 0x100 r1=0x10, r2=0x20").unwrap();
+    assert_eq!(ix.extract_call_target(3).unwrap(), 0x100);
+    assert_eq!(ix.text(), "call 0x100");
+}
+
+#[test]
+fn stack_frame_sizes() {
+    use crate::bpf::{stack_frame_size, stack_frame_size_of_bytes};
+
+    assert_eq!(stack_frame_size("stxdw [r10 - 0x18], r1"), Some(0x18));
+    assert_eq!(stack_frame_size("ldxw r2, [r10-8]"), Some(8));
+    assert_eq!(stack_frame_size("add64 r11, -0x40"), Some(0x40));
+    assert_eq!(stack_frame_size("sub64 r11, 128"), Some(128));
+    assert_eq!(stack_frame_size("stxdw [r1 - 0x18], r10"), None);
+    assert_eq!(stack_frame_size("add64 r11, 0x40"), None);
+
+    // stxdw [r10-0x18], r1 and ldxdw r1, [r10-0x20]
+    assert_eq!(
+        stack_frame_size_of_bytes(&[0x7b, 0x1a, 0xe8, 0xff, 0, 0, 0, 0]),
+        Some(0x18)
+    );
+    assert_eq!(
+        stack_frame_size_of_bytes(&[0x79, 0xa1, 0xe0, 0xff, 0, 0, 0, 0]),
+        Some(0x20)
+    );
+    // add64 r11, -0x40
+    assert_eq!(
+        stack_frame_size_of_bytes(&[0x07, 0x0b, 0, 0, 0xc0, 0xff, 0xff, 0xff]),
+        Some(0x40)
+    );
+    assert_eq!(
+        stack_frame_size_of_bytes(&[0x95, 0, 0, 0, 0, 0, 0, 0]),
+        None
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_aggregates() {
+    use crate::daemon;
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    let trace = "[Z TRACE bpf] BPF Program Instruction Trace:
+1 [0] 1: aaa
+2 [0] 2: call 0x100
+3 [0] 4: bbb
+4 [0] 5: exit
+5 [0] 3: exit
+";
+    let mut profiles = daemon::Profiles::new();
+    let mut request = |text: &str| {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client.write_all(text.as_bytes()).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        daemon::serve(server, &mut profiles, None, &crate::gen::Options::default()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    assert_eq!(request(&format!("trace prog\n{}", trace)), "ok\n");
+    assert_eq!(request(&format!("trace prog\n{}", trace)), "ok\n");
+    assert_eq!(request("list\n"), "prog 10\n");
+    // Malformed traces are not added, even partially
+    let malformed = format!("{}garbage\n", &trace[..trace.find("3 [0]").unwrap()]);
+    assert!(request(&format!("trace prog\n{}", malformed)).starts_with("error:"));
+    assert!(request(&format!("trace new\n{}", malformed)).starts_with("error:"));
+    assert_eq!(request("list\n"), "prog 10\n");
+    let report = request("report prog\n");
+    assert!(report.contains("summary: 4\ntotals: 4\n"));
+    assert!(report.contains("\nfn=function_4 (0x100)\n4 4\n"));
+    assert!(request("report other\n").starts_with("error:"));
+    assert!(request("trace ../prog\n").starts_with("error:"));
+}
+
+#[test]
+fn output_atomic() {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join(format!("bpf-profile-output-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("profile.out");
+    std::fs::write(&path, "good").unwrap();
+
+    // Interrupted writing keeps the previous output
+    let mut output = crate::filebuf::open_w(&path).unwrap();
+    write!(output, "partial").unwrap();
+    drop(output);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "good");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut output = crate::filebuf::open_w(&path).unwrap();
+    write!(output, "new").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "good");
+    output.commit().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn input_compressed() {
+    use std::io::{Read, Write};
+
+    let text = "[Z TRACE bpf] BPF Program Instruction Trace:\n";
+    let mut gzip = Vec::new();
+    for _ in 0..2 {
+        let mut encoder = flate2::write::GzEncoder::new(&mut gzip, Default::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+    }
+    let level = ruzstd::encoding::CompressionLevel::Fastest;
+    let zstd = ruzstd::encoding::compress_to_vec(text.as_bytes(), level);
+
+    let path = std::env::temp_dir().join(format!("bpf-profile-input-{}", std::process::id()));
+    for (data, expected) in [(gzip, text.repeat(2)), (zstd, text.into())] {
+        std::fs::write(&path, data).unwrap();
+        let mut input = String::new();
+        let r = crate::filebuf::open(&path)
+            .unwrap()
+            .read_to_string(&mut input);
+        assert!(r.is_ok());
+        assert_eq!(input, expected);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn calls_lines() {
+    let trace = b"[Z TRACE bpf] BPF Program Instruction Trace:
+1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 1: call 0x100
+2 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: call 0x200
+3 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: exit
+4 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 11: call 0x200
+5 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 20: exit
+6 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 12: exit
+7 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 2: call 0x100
+8 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 10: ja +1
+";
+    let mut resv = crate::resolver::Resolver::default();
+    crate::calls::update_resolver(Cursor::new(trace), &mut resv).unwrap();
+    let layout = crate::calls::Layout {
+        depth_width: 1,
+        tab: 2,
+        lines: true,
+    };
+    let mut output = Vec::new();
+    crate::calls::trace_calls(Cursor::new(trace), &mut output, &resv, &layout).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&output).unwrap(),
+        "[0] function_10 (0x100) lines=2-7
+[1]   function_20 (0x200) lines=3-4
+[1]   function_20 (0x200) lines=5-6
+[0] function_10 (0x100) lines=8-9
+"
+    );
+}
+
+#[test]
+fn dump_intermixed_source() {
+    let dump = b"ELF Header:
+  Class: ELF64
+
+Disassembly of section .text:
+
+0000000000000100 <process>:
+; fn process(x: u64) -> u64 {
+; /src/lib.rs:3
+      20       07 00 00 00 00 00 00 00 add64 r1, 1
+;     x + 1
+      21       95 00 00 00 00 00 00 00 exit
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_intermixed_source.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(resv.resolve_by_first_pc(20), Some("process".into()));
+
+    let mut output = Vec::new();
+    resv.write_pretty_source(&mut output).unwrap();
+    let output = std::str::from_utf8(&output).unwrap();
+    let p = crate::config::PADDING;
+    assert!(output.contains(&format!(
+        "20:{p}add64 r1, 1{p}; /src/lib.rs:3{p}; process\n"
+    )));
+    assert!(output.contains(&format!("21:{p}exit{p}; x + 1\n")));
+}
+
+#[test]
+fn dump_several_sections() {
+    let dump = b"ELF Header:
+  Class: ELF64
+
+Disassembly of section .text:
+
+0000000000000100 <entrypoint>:
+      20       85 10 00 00 00 00 00 00 call 0x300
+      21       95 00 00 00 00 00 00 00 exit
+
+Disassembly of section .text.unlikely:
+
+0000000000000300 <panic>:
+      30       95 00 00 00 00 00 00 00 exit
+
+Disassembly of section custom:
+
+0000000000000400 <helper>:
+      40       95 00 00 00 00 00 00 00 exit
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_several_sections.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(resv.resolve_by_first_pc(20), Some("entrypoint".into()));
+    assert_eq!(resv.resolve_by_first_pc(30), Some("panic".into()));
+    assert_eq!(resv.resolve_by_first_pc(40), Some("helper".into()));
+}
+
+#[test]
+fn dump_overlapping_sections() {
+    let dump = b"ELF Header:
+Disassembly of section .text:
+0000000000000100 <entrypoint>:
+      20       85 10 00 00 00 00 00 00 call 0x300
+      21       95 00 00 00 00 00 00 00 exit
+Disassembly of section .text.unlikely:
+0000000000000108 <panic>:
+      21       95 00 00 00 00 00 00 00 exit
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_overlapping_sections.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(resv, Err(crate::error::Error::DumpOverlap(21, 8))));
+}
+
+#[test]
+fn dump_data_sections() {
+    let dump = b"ELF Header:
+  Class: ELF64
+
+Disassembly of section .text:
+
+0000000000000100 <entrypoint>:
+      20       85 10 00 00 00 00 00 00 call 0x300
+      21       95 00 00 00 00 00 00 00 exit
+
+RELOCATION RECORDS FOR [.text]:
+OFFSET           TYPE                     VALUE
+0000000000000020 R_BPF_64_32              sol_log_
+
+Disassembly of section .rodata:
+
+0000000000000200 <message>:
+      40       68 65 6c 6c 6f 00 00 00 <unknown>
+
+Disassembly of section .text.unlikely:
+
+0000000000000300 <panic>:
+      30       95 00 00 00 00 00 00 00 exit
+
+SYMBOL TABLE:
+0000000000000100 g     F .text  0000000000000010 entrypoint
+0000000000000300 l     F .text.unlikely  0000000000000008 panic
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_data_sections.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(resv.resolve_by_first_pc(20), Some("entrypoint".into()));
+    assert_eq!(resv.resolve_by_first_pc(30), Some("panic".into()));
+    assert_eq!(resv.resolve_by_first_pc(40), None);
+}
+
+#[test]
+fn dump_patterns() {
+    let r = crate::resolver::DumpPatterns::new(r"^<(.+)>", r"^(?P<pc>\d+) (?P<text>.+)");
+    assert!(matches!(r, Err(crate::error::Error::DumpPattern(..))));
+
+    let dump = b"ELF Header:
+Disassembly of section .text:
+0000000000000100 <process>:
+ 20: 0x0000a0 07 00 00 00 00 00 00 00 add64 r1, 1
+ 21: 0x0000a8 95 00 00 00 00 00 00 00 exit
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_patterns.dump");
+    std::fs::write(&path, dump).unwrap();
+    let patterns = crate::resolver::DumpPatterns::new(
+        crate::config::DEFAULT_DUMP_FUNCTION_HEADER,
+        r"^\s*(?P<pc>\d+): 0x[[:xdigit:]]+(\s+[[:xdigit:]]{2})+\s+(?P<text>.+)",
+    )
+    .unwrap();
+    let resv = crate::resolver::read_with_patterns(&path, &patterns);
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(resv.resolve_by_first_pc(20), Some("process".into()));
+    assert_eq!(resv.instruction_pcs(), vec![20, 21]);
+}
+
+#[test]
+fn dump_shared_first_pc() {
+    let dump = b"ELF Header:
+Disassembly of section .text:
+0000000000000100 <entrypoint>:
+0000000000000100 <process_instruction>:
+0000000000000100 <alias>:
+      20       95 00 00 00 00 00 00 00 exit
+0000000000000108 <helper>:
+      21       95 00 00 00 00 00 00 00 exit
+";
+    let path = std::env::temp_dir().join("bpf-profile-dump_shared_first_pc.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(
+        resv.resolve_by_first_pc(20),
+        Some("entrypoint|process_instruction|alias".into())
+    );
+    assert!(crate::resolver::is_named(
+        "entrypoint|process_instruction|alias",
+        "process_instruction"
+    ));
+    assert!(!crate::resolver::is_named(
+        "entrypoint|process_instruction|alias",
+        "process"
+    ));
+    assert_eq!(resv.resolve_by_first_pc(21), Some("helper".into()));
+}
+
+#[test]
+fn resolver_shared() {
+    let dump = b"ELF Header:
+Disassembly of section .text:
+0000000000000100 <process_instruction>:
+      20       95 00 00 00 00 00 00 00 exit
+";
+    let resv = crate::resolver::read_from(Cursor::new(&dump[..])).unwrap();
+    std::thread::scope(|scope| {
+        for address in [0x100, 0x200] {
+            let mut resv = resv.clone();
+            scope.spawn(move || {
+                assert_eq!(resv.update(address, 20), "process_instruction");
+                assert_eq!(resv.update(0x300, 99), "function_99 (0x300)");
+                assert!(resv.is_resolved(address));
+            });
+        }
+        scope.spawn(|| assert_eq!(resv.dump_functions().len(), 1));
+    });
+    // Functions of traces are kept by the clones
+    assert!(!resv.is_resolved(0x100));
+    assert_eq!(resv.resolve_by_first_pc(99), None);
+}
+
+#[test]
+fn symbol_map() {
+    use crate::resolver::{read_symbols, Resolver, Symbol};
+    let dir = std::env::temp_dir();
+    let lines_path = dir.join("bpf-profile-symbol_map.csv");
+    std::fs::write(
+        &lines_path,
+        "# program-id:address:name
+:0x14:process_instruction
+Other111:21:other::helper
+,22,helper<A, B>
+",
+    )
+    .unwrap();
+    let json_path = dir.join("bpf-profile-symbol_map.json");
+    std::fs::write(
+        &json_path,
+        r#"[{"address": "0x14", "name": "process_instruction"},
+            {"program": "Other111", "address": 21, "name": "other::helper"},
+            {"program": "", "address": 22, "name": "helper<A, B>"}]"#,
+    )
+    .unwrap();
+    let invalid_path = dir.join("bpf-profile-symbol_map.txt");
+    std::fs::write(&invalid_path, "0x14:process_instruction\n").unwrap();
+    let lines = read_symbols(&lines_path);
+    let json = read_symbols(&json_path);
+    let invalid = read_symbols(&invalid_path);
+    for path in [lines_path, json_path, invalid_path] {
+        std::fs::remove_file(&path).unwrap();
+    }
+    let symbols = lines.unwrap();
+    assert_eq!(symbols, json.unwrap());
+    assert_eq!(
+        symbols[1],
+        Symbol {
+            program: Some("Other111".into()),
+            pc: 21,
+            name: "other::helper".into()
+        }
+    );
+    assert!(invalid.is_err());
+
+    // Alone, symbols of unknown programs name functions of the traced program
+    let mut resv = Resolver::from_symbols(&symbols);
+    assert!(!resv.is_default());
+    assert!(!resv.has_disassembly());
+    assert_eq!(resv.update(0x100, 20), "process_instruction");
+    assert_eq!(resv.update(0x200, 22), "helper<A, B>");
+    assert_eq!(resv.update(0x300, 23), "function_23 (0x300)");
+
+    // Over the dump, symbols replace names of the dump and add missing functions
+    let dump = b"ELF Header:
+Disassembly of section .text:
+0000000000000100 <entrypoint>:
+      20       95 00 00 00 00 00 00 00 exit
+0000000000000108 <helper>:
+      22       95 00 00 00 00 00 00 00 exit
+";
+    let mut resv = crate::resolver::read_from(Cursor::new(&dump[..])).unwrap();
+    resv.add_program("Other111", Resolver::default());
+    resv.add_symbols(&symbols);
+    assert!(resv.has_disassembly());
+    assert_eq!(
+        resv.resolve_by_first_pc(20),
+        Some("process_instruction".into())
+    );
+    assert_eq!(resv.resolve_by_first_pc(21), None);
+    assert_eq!(resv.resolve_by_first_pc(22), Some("helper<A, B>".into()));
+    let region = resv.program_region("Other111").unwrap();
+    assert_eq!(resv.update(region | 0x100, 21), "other::helper");
+}
+
+/// Builds a minimal ELF file of a BPF program with the `.text` section at 0x120
+/// and the symbols of the section, given by names, types and addresses.
+fn elf_file(text: &[u8], symbols: &[(&str, u8, u64)]) -> Vec<u8> {
+    let mut strtab = vec![0_u8];
+    let mut symtab = vec![0_u8; 24];
+    for (name, kind, address) in symbols {
+        symtab.extend((strtab.len() as u32).to_le_bytes());
+        symtab.extend([0x10 | kind, 0]);
+        symtab.extend(1_u16.to_le_bytes());
+        symtab.extend(address.to_le_bytes());
+        symtab.extend(8_u64.to_le_bytes());
+        strtab.extend(name.as_bytes());
+        strtab.push(0);
+    }
+    let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+
+    let mut data = Vec::new();
+    let mut sections = Vec::new();
+    // Name, type, flags, address, link, info, entry size, contents
+    let contents = [
+        (1_u32, 1_u32, 0x6_u64, 0x120_u64, 0_u32, 0_u32, 0_u64, text),
+        (7, 2, 0, 0, 3, 1, 24, &symtab[..]),
+        (15, 3, 0, 0, 0, 0, 0, &strtab[..]),
+        (23, 3, 0, 0, 0, 0, 0, &shstrtab[..]),
+    ];
+    for (name, kind, flags, address, link, info, entry_size, bytes) in contents {
+        while data.len() % 8 != 0 {
+            data.push(0);
+        }
+        let offset = 64 + data.len() as u64;
+        data.extend(bytes);
+        let mut header = Vec::new();
+        header.extend(name.to_le_bytes());
+        header.extend(kind.to_le_bytes());
+        header.extend(flags.to_le_bytes());
+        header.extend(address.to_le_bytes());
+        header.extend(offset.to_le_bytes());
+        header.extend((bytes.len() as u64).to_le_bytes());
+        header.extend(link.to_le_bytes());
+        header.extend(info.to_le_bytes());
+        header.extend(8_u64.to_le_bytes());
+        header.extend(entry_size.to_le_bytes());
+        sections.push(header);
+    }
+    while data.len() % 8 != 0 {
+        data.push(0);
+    }
+
+    let mut elf = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+    elf.extend(3_u16.to_le_bytes()); // shared object
+    elf.extend(247_u16.to_le_bytes()); // BPF
+    elf.extend(1_u32.to_le_bytes());
+    elf.extend(0_u64.to_le_bytes());
+    elf.extend(0_u64.to_le_bytes());
+    elf.extend((64 + data.len() as u64).to_le_bytes());
+    elf.extend(0_u32.to_le_bytes());
+    for field in [64_u16, 56, 0, 64, 5, 4] {
+        elf.extend(field.to_le_bytes());
+    }
+    elf.extend(data);
+    elf.extend([0_u8; 64]);
+    for header in sections {
+        elf.extend(header);
+    }
+    elf
+}
+
+#[test]
+fn dump_elf_symbols() {
+    let exit = [0x95, 0, 0, 0, 0, 0, 0, 0];
+    let text = exit.repeat(4);
+    let elf = elf_file(
+        &text,
+        &[
+            ("entrypoint", 2, 0x120),
+            ("process_instruction", 2, 0x120),
+            ("helper", 2, 0x130),
+            ("CONSTANT", 1, 0x138),
+        ],
+    );
+    let path = std::env::temp_dir().join("bpf-profile-dump_elf_symbols.so");
+    std::fs::write(&path, elf).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert!(!resv.is_default());
+    assert_eq!(
+        resv.dump_functions(),
+        vec![
+            (0, "entrypoint|process_instruction".into(), 2),
+            (2, "helper".into(), 2)
+        ]
+    );
+    assert_eq!(resv.instruction_pcs(), vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn demangle_symbols() {
+    use crate::resolver::demangle;
+    assert_eq!(
+        demangle("_ZN7example8function17h0123456789abcdefE"),
+        "example::function"
+    );
+    assert_eq!(
+        demangle("_RNvCs1234_7example8function"),
+        "example::function"
+    );
+    assert_eq!(demangle("_ZN2ns6helperEi"), "ns::helper(int)");
+    assert_eq!(demangle("entrypoint"), "entrypoint");
+    assert_eq!(demangle("function_0 (0x100)"), "function_0 (0x100)");
+}
+
+#[test]
+fn unresolved_names() {
+    use crate::resolver::UnresolvedName;
+    let name = UnresolvedName::new("fn_{index}_{pc}@{address}").unwrap();
+    assert_eq!(name.format(3, 0x100, 42), "fn_3_42@100");
+    assert_eq!(
+        UnresolvedName::default().format(0, 0x100, 4),
+        "function_4 (0x100)"
+    );
+    assert!(UnresolvedName::new("fn_{pc}").is_err());
+    assert!(UnresolvedName::new("fn_{index}_{name}").is_err());
+}
+
+#[test]
+fn number_formats() {
+    use crate::global::{separated, short};
+    assert_eq!(separated(0), "0");
+    assert_eq!(separated(999), "999");
+    assert_eq!(separated(1000), "1,000");
+    assert_eq!(separated(12_345_678), "12,345,678");
+    assert_eq!(short(999), "999");
+    assert_eq!(short(1000), "1.0k");
+    assert_eq!(short(12_345_678), "12.3M");
+    assert_eq!(short(999_999), "1.0M");
+    assert_eq!(short(1_400_000_000), "1.4G");
+}
+
+#[test]
+fn line_endings() {
+    use crate::filebuf::read_line;
+    use std::io::BufReader;
+    // The small buffer splits CRLF between chunks
+    let input: &[u8] = b"\xef\xbb\xbfone\r\ntwo\rthree\nfour\r\r\nfive";
+    let mut reader = BufReader::with_capacity(4, input);
+    let mut line = String::new();
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let n = read_line(&mut reader, &mut line).unwrap();
+        if n == 0 {
+            break;
+        }
+        total += n;
+        lines.push(line.clone());
+    }
+    assert_eq!(lines, ["one\n", "two\n", "three\n", "four\n", "\n", "five"]);
+    assert_eq!(total, input.len());
+
+    let reader = Cursor::new(b"\xef\xbb\xbf[Z TRACE bpf] BPF Program Instruction Trace:\r\n");
+    assert!(crate::trace::contains_standard_header(reader).unwrap());
+
+    let mut reader = Cursor::new(b"\xff\xfe\n");
+    assert!(read_line(&mut reader, &mut line).is_err());
+}
+
+#[test]
+fn dump_windows_line_endings() {
+    let dump = "\u{feff}ELF Header:
+  Class: ELF64
+
+Disassembly of section .text:
+
+0000000000000100 <process>:
+      20       07 00 00 00 00 00 00 00 add64 r1, 1
+      21       95 00 00 00 00 00 00 00 exit
+"
+    .replace('\n', "\r\n");
+    let path = std::env::temp_dir().join("bpf-profile-dump_windows_line_endings.dump");
+    std::fs::write(&path, dump).unwrap();
+    let resv = crate::resolver::read(Some(&path));
+    std::fs::remove_file(&path).unwrap();
+    let resv = resv.unwrap();
+    assert_eq!(resv.resolve_by_first_pc(20), Some("process".into()));
+}
+
+#[cfg(feature = "run")]
+#[test]
+fn run_program() {
+    let text = [
+        [0x85, 0, 0, 0, 2, 0, 0, 0], // call helper
+        [0xb7, 0, 0, 0, 0, 0, 0, 0], // mov64 r0, 0
+        [0x95, 0, 0, 0, 0, 0, 0, 0], // exit
+        [0xb7, 0, 0, 0, 1, 0, 0, 0], // mov64 r0, 1
+        [0x95, 0, 0, 0, 0, 0, 0, 0], // exit
+    ]
+    .concat();
+    let mut elf = elf_file(&text, &[("entrypoint", 2, 0x120), ("helper", 2, 0x138)]);
+    elf[24..32].copy_from_slice(&0x120_u64.to_le_bytes());
+
+    let execution = crate::vm::execute(&elf, &[], 100).unwrap();
+    assert_eq!(execution.result(), Ok(0));
+    assert_eq!(execution.instructions(), 5);
+    let mut trace = String::new();
+    std::io::Read::read_to_string(&mut execution.trace().unwrap(), &mut trace).unwrap();
+    let mut lines = trace.lines();
+    assert_eq!(
+        lines.next(),
+        Some("[Z TRACE bpf] BPF Program Instruction Trace:")
+    );
+    let lines = lines
+        .map(|l| l.split_once("]").map_or(l, |(_, text)| text.trim()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "0: call 0xa33b57b3",
+            "3: mov64 r0, 1",
+            "4: exit",
+            "1: mov64 r0, 0",
+            "2: exit",
+        ]
+    );
+
+    let program_path = std::env::temp_dir().join("bpf-profile-run_program.so");
+    let output_path = std::env::temp_dir().join("bpf-profile-run_program.callgrind");
+    std::fs::write(&program_path, elf).unwrap();
+    let options = crate::gen::Options {
+        output: Some(output_path.clone()),
+        ..crate::gen::Options::default()
+    };
+    let result = crate::gen::run_program(&program_path, None, 100, &options);
+    std::fs::remove_file(&program_path).unwrap();
+    result.unwrap();
+    let profile = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+    //==== do not delete ====
+    println!("{}", profile);
+    //==== do not delete ====
+    assert_eq!(
+        profile,
+        "# callgrind format
+version: 1
+creator: bpf-profile
+positions: line
+events: Instructions
+summary: 2
+totals: 2
+fl=<none>
+
+fn=helper
+3 2
+"
+    );
+
+    // Calls of syscalls not provided fail the program, the trace up to the call is kept
+    let text = [
+        [0x85, 0, 0, 0, 0xff, 0xff, 0xff, 0xff], // call sol_invoke_signed_rust
+        [0x95, 0, 0, 0, 0, 0, 0, 0],             // exit
+    ]
+    .concat();
+    let mut elf = elf_file(&text, &[("entrypoint", 2, 0x120)]);
+    elf[24..32].copy_from_slice(&0x120_u64.to_le_bytes());
+    let execution = crate::vm::execute(&elf, &[], 100).unwrap();
+    assert!(execution.result().is_err());
+}
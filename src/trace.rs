@@ -1,21 +1,257 @@
 //! bpf-profile trace module.
+//! Recognizes lines of traces besides instructions and the formats of instructions:
+//! the legacy format with registers in brackets on every line of an instruction,
+//! and the format of newer validators, announced by `format 2` in the standard header,
+//! with states of registers and markers of frames on separate lines:
+//! ```text
+//! [Z TRACE bpf] BPF Program Instruction Trace (agave 2.2.1, format 2):
+//! frame 0 enter
+//!     r1=0x400000000 r10=0x200001000
+//! 0 29: call 0x100
+//! frame 1 enter 0x100
+//!     r1=0x400000060
+//! 1 40: exit
+//! frame 1 exit
+//! ```
 
-use crate::{error::Result, filebuf};
+use crate::bpf::Instruction;
+use crate::config::Address;
+use crate::error::{Error, Result};
+use crate::filebuf;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt;
 use std::io::BufRead;
 
-const HEADER: &str = "BPF Program Instruction Trace";
+/// Standard header line of trace files.
+pub const HEADER: &str = "BPF Program Instruction Trace";
 
-/// Checks the trace file contains expected header line.
-pub fn contains_standard_header(mut reader: impl BufRead) -> Result<bool> {
+/// Represents the standard header line of the trace.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    /// Version of the runtime which produced the trace, e.g. "1.18.2", if present.
+    pub runtime_version: Option<String>,
+}
+
+/// Parses the line if it is the standard header line of the trace.
+/// The runtime version is recognized after "solana", "agave" or "runtime",
+/// e.g. "[Z TRACE bpf] BPF Program Instruction Trace (agave 2.0.3):".
+pub fn parse_header(line: &str) -> Option<Header> {
+    lazy_static! {
+        static ref VERSION: Regex =
+            Regex::new(r"(?i)\b(?:solana|agave|runtime)[\s:/v-]*(\d+\.\d+(?:\.\d+)?)\b")
+                .expect("Invalid regex");
+    }
+
+    if !line.contains(HEADER) {
+        return None;
+    }
+    Some(Header {
+        runtime_version: VERSION.captures(line).map(|caps| caps[1].to_string()),
+    })
+}
+
+/// Parses the line if it reports the start of an invocation of a program
+/// and returns the program id with the depth of the invocation,
+/// e.g. "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]".
+pub fn parse_invocation_start(line: &str) -> Option<(&str, usize)> {
+    lazy_static! {
+        static ref INVOCATION_START: Regex =
+            Regex::new(r"(?i)\bprogram (\w+) invoke \[(\d+)\]").expect("Invalid regex");
+    }
+
+    let caps = INVOCATION_START.captures(line)?;
+    let depth = caps[2].parse().ok()?;
+    Some((caps.get(1)?.as_str(), depth))
+}
+
+/// Checks if the line reports the end of an invocation of a program,
+/// e.g. "Program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin success".
+pub fn is_invocation_end(line: &str) -> bool {
+    lazy_static! {
+        static ref INVOCATION_END: Regex =
+            Regex::new(r"(?i)\bprogram \w+ (?:success|failed)\b").expect("Invalid regex");
+    }
+
+    INVOCATION_END.is_match(line)
+}
+
+/// Parses the line if it marks the execution of a transaction and returns
+/// its identifier, e.g. "Transaction signature: 5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb"
+/// or "[Z DEBUG] execution id = test_swap". Log prefixes in brackets are allowed.
+pub fn parse_label(line: &str) -> Option<&str> {
+    lazy_static! {
+        static ref LABEL: Regex = Regex::new(
+            r"(?i)^\s*(?:\[[^\]]*\]\s*)*(?:transaction|tx|execution)(?:[ _-]?(?:id|signature))?\s*[:=]\s*([\w.:-]+)\s*$"
+        )
+        .expect("Invalid regex");
+    }
+
+    LABEL
+        .captures(line)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str())
+}
+
+/// Reads the trace file until the standard header line and parses it.
+pub fn read_header(mut reader: impl BufRead) -> Result<Option<Header>> {
     let mut line = String::with_capacity(512);
     let mut bytes_read = usize::MAX;
 
     while bytes_read != 0 {
         bytes_read = filebuf::read_line(&mut reader, &mut line)?;
-        if line.contains(HEADER) {
-            return Ok(true);
+        if let Some(header) = parse_header(&line) {
+            return Ok(Some(header));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Checks the trace file contains expected header line.
+pub fn contains_standard_header(reader: impl BufRead) -> Result<bool> {
+    Ok(read_header(reader)?.is_some())
+}
+
+/// Represents the format of lines of a trace.
+pub(crate) trait TraceFormat: fmt::Debug + Send {
+    /// Checks if the standard header line starts a trace of the format.
+    fn detect(&self, header: &str) -> bool;
+
+    /// Consumes the line if it carries a state besides instructions,
+    /// e.g. values of registers or a marker of a frame, returning true.
+    fn consume_state(&mut self, line: &str) -> bool;
+
+    /// Parses the line of an instruction, with values of registers before
+    /// its execution if captured; other lines are skipped by `Error::TraceSkipped`.
+    fn parse_line(&mut self, line: &str, registers: bool) -> Result<Instruction>;
+
+    /// Describes the format with an example of the line of an instruction,
+    /// a hint for lines which cannot be parsed.
+    fn dialect(&self) -> &'static str;
+}
+
+/// Represents the legacy format: registers in brackets on the line of every instruction,
+/// `1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 29: mov64 r2, r1`.
+#[derive(Debug, Default)]
+pub(crate) struct Legacy;
+
+impl TraceFormat for Legacy {
+    fn detect(&self, _header: &str) -> bool {
+        true
+    }
+
+    fn dialect(&self) -> &'static str {
+        "the legacy format, e.g. `1 [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] 29: mov64 r2, r1`"
+    }
+
+    fn consume_state(&mut self, _line: &str) -> bool {
+        false
+    }
+
+    fn parse_line(&mut self, line: &str, registers: bool) -> Result<Instruction> {
+        if registers {
+            Instruction::parse_with_registers(line)
+        } else {
+            Instruction::parse(line)
+        }
+    }
+}
+
+/// Represents the format of newer validators: registers and frames on separate lines,
+/// `0 29: mov64 r2, r1`. A line of registers lists values changed before
+/// the following instruction; the other registers keep their values.
+#[derive(Debug, Default)]
+pub(crate) struct RegisterLines {
+    registers: Vec<Option<Address>>,
+}
+
+lazy_static! {
+    static ref FORMAT_2: Regex = Regex::new(r"(?i)\bformat\s*2\b").expect("Invalid regex");
+    static ref STATE: Regex =
+        Regex::new(r"^\s*(?:frame\s+\d+\b|r\d{1,2}=)").expect("Invalid regex");
+    static ref REGISTER: Regex =
+        Regex::new(r"\br(\d{1,2})=(?:0x([[:xdigit:]]+)|(\d+))").expect("Invalid regex");
+    static ref INSTRUCTION: Regex = Regex::new(r"^\s*\d+\s+(\d+):\s+(.+)").expect("Invalid regex");
+}
+
+impl TraceFormat for RegisterLines {
+    fn detect(&self, header: &str) -> bool {
+        FORMAT_2.is_match(header)
+    }
+
+    fn dialect(&self) -> &'static str {
+        "format 2, e.g. `0 29: mov64 r2, r1` after lines of registers `r1=0x400000000`"
+    }
+
+    fn consume_state(&mut self, line: &str) -> bool {
+        if !is_state_line(line) {
+            return false;
         }
+        for caps in REGISTER.captures_iter(line) {
+            let index = caps[1].parse::<usize>().unwrap_or_default();
+            let value = match (caps.get(2), caps.get(3)) {
+                (Some(hex), _) => Address::from_str_radix(hex.as_str(), 16).ok(),
+                (_, Some(decimal)) => decimal.as_str().parse().ok(),
+                _ => None,
+            };
+            if self.registers.len() <= index {
+                self.registers.resize(index + 1, None);
+            }
+            self.registers[index] = value;
+        }
+        true
+    }
+
+    fn parse_line(&mut self, line: &str, registers: bool) -> Result<Instruction> {
+        let Some(caps) = INSTRUCTION.captures(line) else {
+            return Err(Error::TraceSkipped);
+        };
+        let pc = crate::bpf::str_to_program_counter(&caps[1])?;
+        let ix = Instruction::new(pc, caps[2].trim().to_string());
+        Ok(match registers {
+            true => ix.with_registers(self.registers.clone()),
+            false => ix,
+        })
     }
+}
 
-    Ok(false)
+/// Returns the format of the trace starting with the standard header line.
+pub(crate) fn detect_format(header: &str) -> Box<dyn TraceFormat> {
+    let formats: [Box<dyn TraceFormat>; 2] = [Box::<RegisterLines>::default(), Box::new(Legacy)];
+    formats
+        .into_iter()
+        .find(|f| f.detect(header))
+        .expect("Legacy format not detected")
+}
+
+/// Parses the line of an instruction in any format.
+pub(crate) fn parse_instruction(line: &str) -> Option<Instruction> {
+    Legacy
+        .parse_line(line, false)
+        .or_else(|_| RegisterLines::default().parse_line(line, false))
+        .ok()
+}
+
+/// Checks if the line carries a state besides instructions in any format.
+pub(crate) fn is_state_line(line: &str) -> bool {
+    STATE.is_match(line)
+}
+
+/// Reads the next line of the trace which is not consumed as a state by the format.
+/// Returns the numbers of bytes and of lines read.
+pub(crate) fn read_line(
+    reader: &mut impl BufRead,
+    line: &mut String,
+    format: &mut dyn TraceFormat,
+) -> Result<(usize, usize)> {
+    let (mut bytes, mut lines) = (0, 0);
+    loop {
+        let bytes_read = filebuf::read_line(reader, line)?;
+        bytes += bytes_read;
+        lines += 1;
+        if bytes_read == 0 || !format.consume_state(line) {
+            return Ok((bytes, lines));
+        }
+    }
 }
@@ -0,0 +1,440 @@
+//! bpf-profile vm module.
+//! Implements running programs under the embedded SBF virtual machine (solana-rbpf)
+//! with tracing enabled, capturing the trace in the format of the validator:
+//! ```text
+//! [Z TRACE bpf] BPF Program Instruction Trace:
+//!     0 [0000000400000000, 0000000000000000, ...]    29: call 0x5c2a3178
+//!     1 [0000000400000000, 0000000000000000, ...]    40: exit
+//! ```
+//! The program gets the input as the serialized parameters of its entrypoint.
+//! Only syscalls of logging and memory are provided: a program calling another syscall
+//! fails at the call, and the trace of its execution up to the call is kept.
+
+use crate::config::{Cost, VM_HEAP_SIZE};
+use crate::error::{Error, Result};
+use crate::trace::HEADER;
+use solana_rbpf::aligned_memory::AlignedMemory;
+use solana_rbpf::declare_builtin_function;
+use solana_rbpf::ebpf;
+use solana_rbpf::elf::Executable;
+use solana_rbpf::error::EbpfError;
+use solana_rbpf::memory_region::{MemoryMapping, MemoryRegion};
+use solana_rbpf::program::{BuiltinFunction, BuiltinProgram, FunctionRegistry};
+use solana_rbpf::static_analysis::Analysis;
+use solana_rbpf::verifier::RequisiteVerifier;
+use solana_rbpf::vm::{Config, ContextObject, EbpfVm, TestContextObject};
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::sync::Arc;
+
+/// Represents the traced execution of the program.
+pub struct Execution {
+    executable: Executable<TestContextObject>,
+    trace_log: Vec<[u64; 12]>,
+    instructions: Cost,
+    result: std::result::Result<u64, String>,
+}
+
+/// Runs the program of the ELF file with the input, executing at most the number
+/// of instructions, and captures the trace of the execution.
+pub fn execute(elf: &[u8], input: &[u8], compute_limit: Cost) -> Result<Execution> {
+    let config = Config {
+        enable_instruction_tracing: true,
+        ..Config::default()
+    };
+    let loader = Arc::new(BuiltinProgram::new_loader(config, syscalls()?));
+    let executable = Executable::<TestContextObject>::from_elf(elf, Arc::clone(&loader))
+        .map_err(|e| Error::Program(e.to_string()))?;
+    executable
+        .verify::<RequisiteVerifier>()
+        .map_err(|e| Error::Program(e.to_string()))?;
+
+    let config = executable.get_config();
+    let sbpf_version = executable.get_sbpf_version();
+    let mut stack = AlignedMemory::<{ ebpf::HOST_ALIGN }>::zero_filled(config.stack_size());
+    let stack_len = stack.len();
+    // Frames of the stack are separated by gaps unless they are of dynamic sizes
+    let stack_gap = if !sbpf_version.dynamic_stack_frames() && config.enable_stack_frame_gaps {
+        config.stack_frame_size as u64
+    } else {
+        0
+    };
+    let mut heap = AlignedMemory::<{ ebpf::HOST_ALIGN }>::zero_filled(VM_HEAP_SIZE);
+    let mut input = AlignedMemory::<{ ebpf::HOST_ALIGN }>::from_slice(input);
+    let regions = vec![
+        executable.get_ro_region(),
+        MemoryRegion::new_writable_gapped(stack.as_slice_mut(), ebpf::MM_STACK_START, stack_gap),
+        MemoryRegion::new_writable(heap.as_slice_mut(), ebpf::MM_HEAP_START),
+        MemoryRegion::new_writable(input.as_slice_mut(), ebpf::MM_INPUT_START),
+    ];
+    let memory_mapping = MemoryMapping::new(regions, config, sbpf_version)
+        .map_err(|e| Error::Program(e.to_string()))?;
+
+    let mut context = TestContextObject::new(compute_limit as u64);
+    let mut vm = EbpfVm::new(
+        loader,
+        sbpf_version,
+        &mut context,
+        memory_mapping,
+        stack_len,
+    );
+    let (instructions, result) = vm.execute_program(&executable, true);
+    let result = std::result::Result::from(result).map_err(|e| e.to_string());
+    Ok(Execution {
+        trace_log: context.trace_log,
+        executable,
+        instructions: instructions as Cost,
+        result,
+    })
+}
+
+impl Execution {
+    /// Returns the number of executed instructions.
+    pub fn instructions(&self) -> Cost {
+        self.instructions
+    }
+
+    /// Returns the value returned by the program or the error which stopped it.
+    pub fn result(&self) -> std::result::Result<u64, &str> {
+        self.result.as_ref().copied().map_err(String::as_str)
+    }
+
+    /// Returns the reader of the trace of the execution, with calls of functions
+    /// by their keys and of syscalls by hashes of their names.
+    pub fn trace(&self) -> Result<Trace<'_>> {
+        let analysis = Analysis::from_executable(&self.executable)
+            .map_err(|e| Error::Program(e.to_string()))?;
+        let mut instructions = BTreeMap::new();
+        for (i, insn) in analysis.instructions.iter().enumerate() {
+            instructions.insert(insn.ptr, i);
+        }
+        let keys = self
+            .executable
+            .get_function_registry()
+            .iter()
+            .map(|(key, (_, pc))| (pc, key))
+            .collect();
+        Ok(Trace {
+            execution: self,
+            analysis,
+            instructions,
+            keys,
+            next: 0,
+            line: format!("[Z TRACE bpf] {}:\n", HEADER).into_bytes(),
+            position: 0,
+        })
+    }
+}
+
+/// Reads the trace of the execution line by line, disassembling the executed instructions.
+pub struct Trace<'a> {
+    execution: &'a Execution,
+    analysis: Analysis<'a>,
+    instructions: BTreeMap<usize, usize>,
+    keys: BTreeMap<usize, u32>,
+    next: usize,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl Trace<'_> {
+    /// Writes the line of the executed instruction of the number.
+    fn write_line(&mut self, index: usize) -> io::Result<()> {
+        let entry = &self.execution.trace_log[index];
+        let pc = entry[11] as usize;
+        let insn = self
+            .instructions
+            .get(&pc)
+            .map(|i| &self.analysis.instructions[*i])
+            .ok_or_else(|| io::Error::other(format!("No instruction at pc {}", pc)))?;
+        let text = match insn.opc {
+            ebpf::CALL_IMM | ebpf::CALL_REG => self.call_text(insn, entry),
+            _ => self.analysis.disassemble_instruction(insn),
+        };
+        self.line.clear();
+        self.position = 0;
+        use std::io::Write;
+        writeln!(
+            self.line,
+            "{:5?} {:016X?} {:5?}: {}",
+            index,
+            &entry[0..11],
+            pc,
+            text
+        )
+    }
+
+    /// Returns the text of the call instruction with the key of the called function
+    /// as the target, the hash of the name of the syscall, or the program counter
+    /// of the function if it has no key.
+    fn call_text(&self, insn: &ebpf::Insn, registers: &[u64; 12]) -> String {
+        let executable = &self.execution.executable;
+        let sbpf_version = executable.get_sbpf_version();
+        let target_pc = if insn.opc == ebpf::CALL_REG {
+            let register = if sbpf_version.callx_uses_src_reg() {
+                insn.src
+            } else {
+                insn.imm as u8
+            };
+            let (text_address, _) = executable.get_text_bytes();
+            let offset = registers[usize::from(register)].wrapping_sub(text_address);
+            Some(offset as usize / ebpf::INSN_SIZE)
+        } else if sbpf_version.static_syscalls() {
+            (insn.src != 0).then(|| (insn.ptr as i64 + insn.imm + 1) as usize)
+        } else {
+            let key = insn.imm as u32;
+            match executable.get_function_registry().lookup_by_key(key) {
+                Some((_, pc)) => Some(pc),
+                None => return format!("call 0x{:x}", key),
+            }
+        };
+        let operation = if insn.opc == ebpf::CALL_REG {
+            "callx"
+        } else {
+            "call"
+        };
+        match target_pc {
+            Some(pc) => match self.keys.get(&pc) {
+                Some(key) => format!("{} 0x{:x}", operation, key),
+                None => format!("{} 0x{:x}", operation, pc),
+            },
+            None => format!("{} 0x{:x}", operation, insn.imm as u32),
+        }
+    }
+}
+
+impl Read for Trace<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.line.len() {
+            if self.next == self.execution.trace_log.len() {
+                return Ok(0);
+            }
+            self.write_line(self.next)?;
+            self.next += 1;
+        }
+        let count = buf.len().min(self.line.len() - self.position);
+        buf[..count].copy_from_slice(&self.line[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Returns the registry of the syscalls provided to programs.
+fn syscalls() -> Result<FunctionRegistry<BuiltinFunction<TestContextObject>>> {
+    let mut registry = FunctionRegistry::<BuiltinFunction<TestContextObject>>::default();
+    let syscalls: [(&str, BuiltinFunction<TestContextObject>); 10] = [
+        ("abort", SyscallAbort::vm),
+        ("sol_panic_", SyscallPanic::vm),
+        ("sol_log_", SyscallLog::vm),
+        ("sol_log_64_", SyscallLogU64::vm),
+        ("sol_log_compute_units_", SyscallLogComputeUnits::vm),
+        ("sol_log_pubkey", SyscallLogPubkey::vm),
+        ("sol_memcpy_", SyscallMemcpy::vm),
+        ("sol_memmove_", SyscallMemcpy::vm),
+        ("sol_memset_", SyscallMemset::vm),
+        ("sol_memcmp_", SyscallMemcmp::vm),
+    ];
+    for (name, syscall) in syscalls {
+        registry
+            .register_function_hashed(name, syscall)
+            .map_err(|e| Error::Program(e.to_string()))?;
+    }
+    Ok(registry)
+}
+
+/// Reads the bytes of the memory of the program at the address.
+fn read(memory: &MemoryMapping, address: u64, len: u64) -> std::result::Result<Vec<u8>, EbpfError> {
+    (0..len)
+        .map(|i| {
+            std::result::Result::from(memory.load::<u8>(address.wrapping_add(i))).map(|b| b as u8)
+        })
+        .collect()
+}
+
+/// Writes the bytes to the memory of the program at the address.
+fn write(memory: &MemoryMapping, address: u64, bytes: &[u8]) -> std::result::Result<(), EbpfError> {
+    for (i, byte) in bytes.iter().enumerate() {
+        std::result::Result::from(memory.store(*byte, address.wrapping_add(i as u64)))?;
+    }
+    Ok(())
+}
+
+declare_builtin_function!(
+    /// Stops the program.
+    SyscallAbort,
+    fn rust(
+        _context: &mut TestContextObject,
+        _arg1: u64,
+        _arg2: u64,
+        _arg3: u64,
+        _arg4: u64,
+        _arg5: u64,
+        _memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        Err("Program aborted".into())
+    }
+);
+
+declare_builtin_function!(
+    /// Stops the program with the location of the panic.
+    SyscallPanic,
+    fn rust(
+        _context: &mut TestContextObject,
+        file: u64,
+        len: u64,
+        line: u64,
+        column: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let file = read(memory, file, len)?;
+        Err(format!(
+            "Program panicked at {}:{}:{}",
+            String::from_utf8_lossy(&file),
+            line,
+            column
+        )
+        .into())
+    }
+);
+
+declare_builtin_function!(
+    /// Logs the message of the program.
+    SyscallLog,
+    fn rust(
+        _context: &mut TestContextObject,
+        message: u64,
+        len: u64,
+        _arg3: u64,
+        _arg4: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let message = read(memory, message, len)?;
+        tracing::info!("Program log: {}", String::from_utf8_lossy(&message));
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Logs the arguments in hexadecimal.
+    SyscallLogU64,
+    fn rust(
+        _context: &mut TestContextObject,
+        arg1: u64,
+        arg2: u64,
+        arg3: u64,
+        arg4: u64,
+        arg5: u64,
+        _memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        tracing::info!(
+            "Program log: {:#x}, {:#x}, {:#x}, {:#x}, {:#x}",
+            arg1,
+            arg2,
+            arg3,
+            arg4,
+            arg5
+        );
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Logs the number of instructions the program may still execute.
+    SyscallLogComputeUnits,
+    fn rust(
+        context: &mut TestContextObject,
+        _arg1: u64,
+        _arg2: u64,
+        _arg3: u64,
+        _arg4: u64,
+        _arg5: u64,
+        _memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        tracing::info!(
+            "Program consumption: {} units remaining",
+            context.get_remaining()
+        );
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Logs the public key in hexadecimal.
+    SyscallLogPubkey,
+    fn rust(
+        _context: &mut TestContextObject,
+        pubkey: u64,
+        _arg2: u64,
+        _arg3: u64,
+        _arg4: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let pubkey = read(memory, pubkey, 32)?;
+        let hex = pubkey.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        tracing::info!("Program log: {}", hex);
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Copies the bytes, the source and the destination may overlap.
+    SyscallMemcpy,
+    fn rust(
+        _context: &mut TestContextObject,
+        destination: u64,
+        source: u64,
+        len: u64,
+        _arg4: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let bytes = read(memory, source, len)?;
+        write(memory, destination, &bytes)?;
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Fills the bytes with the value.
+    SyscallMemset,
+    fn rust(
+        _context: &mut TestContextObject,
+        destination: u64,
+        value: u64,
+        len: u64,
+        _arg4: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        write(memory, destination, &vec![value as u8; len as usize])?;
+        Ok(0)
+    }
+);
+
+declare_builtin_function!(
+    /// Compares the bytes, writing the difference of the first unequal ones
+    /// as a 32-bit integer to the result.
+    SyscallMemcmp,
+    fn rust(
+        _context: &mut TestContextObject,
+        left: u64,
+        right: u64,
+        len: u64,
+        result: u64,
+        _arg5: u64,
+        memory: &mut MemoryMapping,
+    ) -> std::result::Result<u64, Box<dyn std::error::Error>> {
+        let left = read(memory, left, len)?;
+        let right = read(memory, right, len)?;
+        let difference = left
+            .iter()
+            .zip(&right)
+            .find(|(l, r)| l != r)
+            .map_or(0, |(l, r)| i32::from(*l) - i32::from(*r));
+        std::result::Result::from(memory.store(difference as u32, result))?;
+        Ok(0)
+    }
+);
@@ -0,0 +1,108 @@
+//! bpf-profile watch-dir command implementation.
+
+use crate::config::TRACE_EXTENSION;
+use crate::error::{Error, Result};
+use crate::{gen, global};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Monitors the input directory and converts every new trace file
+/// into a profile in the output directory. Never returns on success.
+pub fn run(
+    input_dir: &Path,
+    dump_path: Option<&Path>,
+    format: &str,
+    output_dir: &Path,
+    interval: u64,
+) -> Result<()> {
+    if !input_dir.is_dir() {
+        return Err(Error::NotDirectory(input_dir.into()));
+    }
+    fs::create_dir_all(output_dir)?;
+
+    let interval = Duration::from_millis(interval);
+    tracing::info!("Watching {:?} for new trace files...", input_dir);
+
+    // Trace files seen on previous scan with their sizes and modification times
+    let mut pending = HashMap::<PathBuf, (u64, SystemTime)>::new();
+    // Trace files failed to convert, retried only once they change
+    let mut failed = HashMap::<PathBuf, (u64, SystemTime)>::new();
+    loop {
+        for (trace_path, state) in scan(input_dir)? {
+            let output_path = output_path(&trace_path, format, output_dir)?;
+            if is_up_to_date(&output_path, state.1) || failed.get(&trace_path) == Some(&state) {
+                pending.remove(&trace_path);
+                continue;
+            }
+            // Convert the file only when it has stopped growing
+            if pending.get(&trace_path) != Some(&state) {
+                pending.insert(trace_path, state);
+                continue;
+            }
+            pending.remove(&trace_path);
+            if convert(&trace_path, dump_path, format, &output_path) {
+                failed.remove(&trace_path);
+            } else {
+                failed.insert(trace_path, state);
+            }
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+/// Lists trace files of the directory with their sizes and modification times.
+fn scan(dir: &Path) -> Result<Vec<(PathBuf, (u64, SystemTime))>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|e| e != TRACE_EXTENSION) {
+            continue;
+        }
+        let meta = fs::metadata(&path)?;
+        files.push((path, (meta.len(), meta.modified()?)));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns path of the profile generated for the trace file.
+fn output_path(trace_path: &Path, format: &str, output_dir: &Path) -> Result<PathBuf> {
+    let stem = trace_path
+        .file_stem()
+        .ok_or_else(|| Error::Filename(trace_path.into()))?;
+    let mut name = stem.to_os_string();
+    name.push(format!(".{}.out", format));
+    Ok(output_dir.join(name))
+}
+
+/// Checks if the output exists and is newer than the trace.
+fn is_up_to_date(output_path: &Path, trace_modified: SystemTime) -> bool {
+    fs::metadata(output_path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|m| m >= trace_modified)
+}
+
+/// Converts one trace file reporting (but not propagating) errors.
+/// Returns false if the conversion failed.
+fn convert(trace_path: &Path, dump_path: Option<&Path>, format: &str, output_path: &Path) -> bool {
+    if global::verbose() {
+        tracing::info!("Converting {:?} to {:?}...", trace_path, output_path);
+    }
+    let options = gen::Options {
+        format: format.into(),
+        output: Some(output_path.into()),
+        ..gen::Options::default()
+    };
+    match gen::run(trace_path, dump_path, &options) {
+        Ok(()) => {
+            tracing::info!("Generated {:?}", output_path);
+            true
+        }
+        Err(err) => {
+            tracing::error!("Cannot convert {:?}: {:#}", trace_path, err);
+            false
+        }
+    }
+}
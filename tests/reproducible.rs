@@ -0,0 +1,25 @@
+//! Outputs are checked here, outside of the unit tests of the crate, since only
+//! builds without `cfg(test)` use hash maps in the order of which nothing is written.
+
+use bpf_profile::gen::ProfileBuilder;
+use std::io::Cursor;
+
+#[test]
+fn callgrind_reproducible() {
+    let trace = std::fs::read("examples/simple.trace").unwrap();
+    let generate = || {
+        let profile = ProfileBuilder::new()
+            .trace_reader(Cursor::new(&trace))
+            .build()
+            .unwrap();
+        let mut output = Vec::<u8>::new();
+        profile.write_callgrind(&mut output, "<none>").unwrap();
+        output
+    };
+
+    let output = generate();
+    assert!(output.windows(4).filter(|w| w == b"cfn=").count() > 1);
+    for _ in 0..8 {
+        assert!(generate() == output);
+    }
+}